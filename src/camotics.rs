@@ -33,10 +33,11 @@ pub struct Workpiece {
 }
 
 /// Tool shape, will be derived from the [tools](../tools/index.html) used in the program.
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum CamoticsToolShape {
     /// Cylindrical tool
+    #[default]
     Cylindrical,
     /// Ballnose tool
     Ballnose,
@@ -44,12 +45,6 @@ pub enum CamoticsToolShape {
     Conical,
 }
 
-impl Default for CamoticsToolShape {
-    fn default() -> Self {
-        Self::Cylindrical
-    }
-}
-
 /// Tool definition in the format required by Camotics, will be derived from the
 /// [tools](../tools/index.html) used in the program.
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
@@ -98,6 +93,15 @@ impl CamoticsTool {
                 number,
                 shape: CamoticsToolShape::Conical,
             },
+            // Camotics has no flat tipped conical shape, the small tip flat is left out
+            Tool::Engraving(t) => CamoticsTool {
+                units: t.units,
+                angle: Some(t.angle),
+                length: t.length,
+                diameter: t.diameter,
+                number,
+                shape: CamoticsToolShape::Conical,
+            },
         }
     }
 }
@@ -141,7 +145,7 @@ impl Camotics {
     #[must_use]
     pub fn new(name: &str, tools: &HashMap<Tool, u8>, workpiece: Bounds, resolution: f64) -> Self {
         let mut tools_map = HashMap::new();
-        for (_, (tool, number)) in tools.iter().enumerate() {
+        for (tool, number) in tools.iter() {
             tools_map.insert(*number, CamoticsTool::from_tool(*tool, *number));
         }
 
@@ -166,13 +170,33 @@ impl Camotics {
         let mut tools = HashMap::new();
 
         for tool in program.tools() {
-            tools.insert(tool, program.tool_ordering(&tool).unwrap());
+            tools.insert(tool, program.tool_number(&tool).unwrap());
         }
 
         let workpiece = program.bounds();
         Self::new(name, &tools, workpiece, resolution)
     }
 
+    /// Creates a new `Camotics` struct from a name and program, with the resolution derived
+    /// from the size of the program bounds so that the simulation uses about
+    /// `target_triangles` cells. A larger program gets a coarser resolution, which keeps the
+    /// memory use of the simulation in check.
+    ///
+    /// Sides of the bounds without any size are left out, and a resolution of 1.0 is used if
+    /// the program has no size at all.
+    #[must_use]
+    pub fn from_program_auto_resolution(
+        name: &str,
+        program: &Program,
+        target_triangles: u64,
+    ) -> Self {
+        Self::from_program(
+            name,
+            program,
+            auto_resolution(&program.bounds(), target_triangles),
+        )
+    }
+
     /// Serializes the Camotics struct to the JSON format used by the Camotics
     /// application when loading a project.
     #[must_use]
@@ -181,6 +205,21 @@ impl Camotics {
     }
 }
 
+fn auto_resolution(bounds: &Bounds, target_triangles: u64) -> f64 {
+    let size = bounds.size();
+    let sides = [size.x, size.y, size.z]
+        .into_iter()
+        .filter(|side| side.is_finite() && *side > 0.0)
+        .collect::<Vec<_>>();
+
+    if sides.is_empty() || target_triangles == 0 {
+        return 1.0;
+    }
+
+    let volume = sides.iter().product::<f64>();
+    (volume / target_triangles as f64).powf(1.0 / sides.len() as f64)
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::Value;
@@ -323,4 +362,44 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_camotics_auto_resolution() {
+        let tool = Tool::default();
+
+        let mut small = Program::new(Units::Metric, 10.0, 50.0);
+        small.context(tool).append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+        ));
+
+        let mut large = Program::new(Units::Metric, 10.0, 50.0);
+        large.context(tool).append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(200.0, 200.0),
+            -20.0,
+            1.0,
+        ));
+
+        let small = Camotics::from_program_auto_resolution("small", &small, 1_000_000);
+        let large = Camotics::from_program_auto_resolution("large", &large, 1_000_000);
+
+        assert!(large.resolution > small.resolution);
+
+        // 20 * 20 * 2 mm split into a million cells
+        let resolution = auto_resolution(&Bounds::new(20.0, 20.0, 2.0), 1_000_000);
+        assert!((resolution - 0.0928).abs() < 0.0001);
+        let resolution = auto_resolution(&Bounds::new(200.0, 200.0, 20.0), 1_000_000);
+        assert!((resolution - 0.928).abs() < 0.001);
+        let resolution = auto_resolution(&Bounds::new(200.0, 200.0, 0.0), 10_000);
+        assert!((resolution - 2.0).abs() < 1e-9);
+
+        assert_eq!(
+            Camotics::from_program_auto_resolution("empty", &Program::default(), 1_000_000)
+                .resolution,
+            1.0
+        );
+    }
 }
@@ -0,0 +1,175 @@
+//! Builds programs from job descriptions in configuration files, so that programs can be
+//! created without writing Rust, for example by a command line tool.
+//!
+//! The configuration is written as [TOML](https://toml.io), which is parsed with the `toml`
+//! crate that is only pulled in by the `config` feature. A configuration sets the program
+//! settings, names the tools to use, and lists the cuts to make in order:
+//!
+//! ```toml
+//! name = "coaster"
+//! description = ["A round coaster with a recess for the cup"]
+//! units = "metric"
+//! z_safe = 10.0
+//! z_tool_change = 50.0
+//! flavor = "grbl"
+//!
+//! [tools.flat]
+//! kind = "cylindrical"
+//! units = "metric"
+//! length = 20.0
+//! diameter = 6.0
+//! direction = "clockwise"
+//! spindle_speed = 18000.0
+//! feed_rate = 1200.0
+//!
+//! [[cuts]]
+//! name = "Cup recess"
+//! tool = "flat"
+//!
+//! [cuts.cut]
+//! kind = "circularpocket"
+//! center = [50.0, 50.0, 0.0]
+//! radius = 40.0
+//! end_z = -2.0
+//! max_step_z = 1.0
+//! step_over = 2.5
+//! ```
+//!
+//! Program settings other than the name, description, and flavor keep their defaults:
+//!
+//! | Field           | Required | Description                                             |
+//! |-----------------|----------|---------------------------------------------------------|
+//! | `name`          | no       | Name of the program.                                    |
+//! | `description`   | no       | Description lines of the program.                       |
+//! | `units`         | yes      | `"metric"` or `"imperial"`.                             |
+//! | `z_safe`        | yes      | Height for rapid moves between cuts.                    |
+//! | `z_tool_change` | yes      | Height for tool changes.                                |
+//! | `flavor`        | no       | G-code flavor, `"grbl"` by default.                     |
+//! | `tools`         | yes      | Tools by name, in the same format as the project files. |
+//! | `cuts`          | yes      | Cuts in order, each with a `tool` name and a `cut`.     |
+//!
+//! The cuts use the same format as the cuts in the project files, with the cut type given
+//! by `kind` and the vectors written as arrays. A cut with a `name` gets a comment with the
+//! name before it in the G-code.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::cuts::*;
+use crate::program::*;
+use crate::tools::*;
+use crate::types::*;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProgramConfig {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Vec<String>,
+    units: Units,
+    z_safe: f64,
+    z_tool_change: f64,
+    #[serde(default)]
+    flavor: Flavor,
+    tools: BTreeMap<String, Tool>,
+    cuts: Vec<CutConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CutConfig {
+    #[serde(default)]
+    name: Option<String>,
+    tool: String,
+    cut: Cut,
+}
+
+/// Builds a program from a TOML job description, see the [module documentation](index.html)
+/// for the format.
+///
+/// Returns error if the configuration can not be parsed, or if a cut refers to a tool that
+/// is not in the tool list.
+pub fn program_from_config(config: &str) -> Result<Program> {
+    let config: ProgramConfig = toml::from_str(config)
+        .map_err(|error| anyhow!("Unable to parse program config: {}", error))?;
+
+    let mut program = Program::builder()
+        .units(config.units)
+        .z_safe(config.z_safe)
+        .z_tool_change(config.z_tool_change)
+        .flavor(config.flavor);
+
+    if let Some(name) = &config.name {
+        program = program.name(name);
+    }
+
+    for description in &config.description {
+        program = program.description(description);
+    }
+
+    let mut program = program.build();
+
+    for (index, cut) in config.cuts.into_iter().enumerate() {
+        let tool = config.tools.get(&cut.tool).ok_or_else(|| {
+            anyhow!(
+                "Unable to find tool \"{}\" used by cut {} in program config",
+                cut.tool,
+                index
+            )
+        })?;
+
+        let mut context = program.context(*tool);
+
+        if let Some(name) = &cut.name {
+            context.append_comment(name);
+        }
+
+        context.append_cut(cut.cut);
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_from_config() -> Result<()> {
+        let program = program_from_config(include_str!("../examples/config.toml"))?;
+
+        assert_eq!(program.name(), "coaster");
+        assert_eq!(program.z_safe(), 10.0);
+        assert_eq!(program.z_tool_change(), 50.0);
+        assert_eq!(program.tools().len(), 2);
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.contains(";(Cup recess)"));
+        assert!(gcode.contains(";(Outline)"));
+
+        let error = program_from_config(
+            r#"
+                units = "metric"
+                z_safe = 10.0
+                z_tool_change = 50.0
+                tools = {}
+
+                [[cuts]]
+                tool = "missing"
+                cut = { kind = "line", from = [0.0, 0.0, 0.0], to = [1.0, 0.0, 0.0] }
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("\"missing\""));
+
+        assert!(program_from_config("units = \"metric\"").is_err());
+
+        // JSON is not accepted
+        assert!(program_from_config("{ \"units\": \"metric\" }").is_err());
+
+        Ok(())
+    }
+}
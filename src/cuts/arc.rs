@@ -1,5 +1,7 @@
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 
+use crate::error::CncError;
 use crate::instructions::*;
 use crate::program::*;
 use crate::types::*;
@@ -10,7 +12,7 @@ use crate::utils::*;
 ///
 /// It can be used to cut in a arc/circle or helix. It will be converted to
 /// G2 and G3 G-code instructions.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Arc {
     /// Starting point in 3D space.
     pub from: Vector3,
@@ -24,6 +26,10 @@ pub struct Arc {
     pub axis: Axis,
     /// The direction to cut the arc with.
     pub direction: Direction,
+    /// The number of full turns to cut for a helix around the z axis that ends right below or
+    /// above where it starts, emitted as the P word of the arc. 0 and 1 both cut a single turn.
+    #[serde(default)]
+    pub turns: u32,
 }
 
 impl Arc {
@@ -42,9 +48,17 @@ impl Arc {
             center,
             axis,
             direction,
+            turns: 0,
         }
     }
 
+    /// Returns the `Arc` with the number of full helix turns changed.
+    #[must_use]
+    pub fn with_turns(mut self, turns: u32) -> Self {
+        self.turns = turns;
+        self
+    }
+
     /// Returns the radius of the arc.
     #[must_use]
     pub fn radius(&self) -> f64 {
@@ -53,6 +67,43 @@ impl Arc {
             .max(self.to.distance_to(self.center))
     }
 
+    /// Returns a new `Arc` with the transform applied to its coordinates. Mirroring flips the
+    /// arc direction when the arc plane is mirrored.
+    ///
+    /// Returns error when rotating an arc that is not cut around the z axis, as the resulting
+    /// arc would not be in any of the XY, XZ or YZ planes.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        if matches!(transform, Transform::RotateZ { .. }) && !matches!(self.axis, Axis::Z) {
+            return Err(anyhow!(
+                "Unable to rotate arc around axis {}, only arcs around axis Z can be rotated",
+                self.axis
+            ));
+        }
+
+        let direction = self.direction.transformed(transform, &self.axis);
+
+        Ok(Self {
+            from: transform.apply(self.from),
+            to: transform.apply(self.to),
+            center: transform.apply(self.center),
+            axis: self.axis.clone(),
+            direction,
+            turns: self.turns,
+        })
+    }
+
+    /// Returns the point where the cut starts.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.from
+    }
+
+    /// Returns the point where the cut ends.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.to
+    }
+
     /// Bounds in 3D space for the arc move, currently this is not yet properly calculated.
     #[must_use]
     pub fn bounds(&self) -> Bounds {
@@ -80,20 +131,31 @@ impl Arc {
 
     /// Converts arc to G-code instructions, will return error if the distance between
     /// center -> from does not equal center -> to.
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
         let distance_from = self.from.distance_to(self.center);
         let distance_to = self.to.distance_to(self.center);
 
-        if (distance_from - distance_to).abs() > 0.0001 {
-            return Err(anyhow!(
-                "Arc distances from/center ({:.2} {}) and to/center ({:.2} {}) must be equal",
+        if (distance_from - distance_to).abs() > DEFAULT_EPSILON {
+            return Err(CncError::ArcRadiusMismatch {
+                segment: None,
                 distance_from,
-                context.units(),
                 distance_to,
-                context.units(),
-            ));
+            });
         }
 
+        let p = if self.turns > 1 {
+            if !matches!(self.axis, Axis::Z) || self.from.xy() != self.to.xy() {
+                return Err(anyhow!(
+                    "Unable to cut arc with {} turns, turns can only be used for full turns around axis Z",
+                    self.turns
+                ).into());
+            }
+
+            Some(self.turns)
+        } else {
+            None
+        };
+
         let mut instructions = vec![];
 
         instructions.append(&mut vec![
@@ -151,7 +213,7 @@ impl Arc {
                     j: Some(self.center.y - self.from.y),
                     k: Some(self.center.z - self.from.z),
                     r: None,
-                    p: None,
+                    p,
                     f: Some(context.tool().feed_rate()),
                 }));
             }
@@ -164,7 +226,7 @@ impl Arc {
                     j: Some(self.center.y - self.from.y),
                     k: Some(self.center.z - self.from.z),
                     r: None,
-                    p: None,
+                    p,
                     f: Some(context.tool().feed_rate()),
                 }));
             }
@@ -182,3 +244,54 @@ impl Arc {
         Ok(instructions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    #[test]
+    fn test_arc_turns() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 5.0, 50.0);
+        let mut context = program.context(Tool::default());
+        let arc = Arc::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -6.0),
+            Vector3::new(5.0, 0.0, -3.0),
+            Axis::Z,
+            Direction::Counterclockwise,
+        )
+        .with_turns(3);
+        context.append_cut(crate::cuts::Cut::Arc(arc.clone()));
+
+        let inner_context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let helix = arc
+            .to_instructions(&inner_context)?
+            .into_iter()
+            .find_map(|instruction| match instruction {
+                Instruction::G3(g3) => Some(g3),
+                _ => None,
+            })
+            .expect("a G3 instruction");
+        assert_eq!(helix.p, Some(3));
+        assert_eq!(helix.z, Some(-6.0));
+
+        // Flavors without support for the P word get one arc per turn instead
+        assert!(!program.to_gcode()?.contains(" P3"));
+        program.set_flavor(Flavor::LinuxCnc);
+        assert!(program.to_gcode()?.contains(" P3"));
+
+        assert!(Arc::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, -6.0),
+            Vector3::new(5.0, 0.0, -3.0),
+            Axis::Z,
+            Direction::Counterclockwise,
+        )
+        .with_turns(3)
+        .to_instructions(&inner_context)
+        .is_err());
+
+        Ok(())
+    }
+}
@@ -1,12 +1,17 @@
+use std::time::Duration;
+
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 
+use crate::cuts::{chip_clear_instructions, is_chip_clear_layer, step_z, transform_rectangle};
+use crate::error::CncError;
 use crate::instructions::*;
 use crate::program::*;
 use crate::types::*;
 use crate::utils::*;
 
 /// Surface cut an area, can be used for both planing and rectangular pockets.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Area {
     /// Start point in 3D space.
     pub start: Vector3,
@@ -31,6 +36,31 @@ pub struct Area {
     /// `ToolPathCompensation::Outer` is useful for cutting out rectangle
     /// pieces.
     pub compensation: ToolPathCompensation,
+    /// The diameter of a previous larger tool that already cleared the pocket. When set only
+    /// the corners that the previous tool could not reach are cut, see
+    /// [Area::with_rest_from](struct.Area.html#method.with_rest_from).
+    #[serde(default)]
+    pub rest_from: Option<f64>,
+    /// The axis that the passes are spaced along, `Axis::Y` (the default) cuts passes along
+    /// the x axis stepping along y, and `Axis::X` cuts passes along the y axis stepping along x.
+    #[serde(default = "default_raster_axis")]
+    pub raster_axis: Axis,
+    /// Fully retract to `z_safe` after every given number of layers to let the chips clear,
+    /// instead of only lifting the tool slightly between the layers. Off by default.
+    #[serde(default)]
+    pub chip_clear_every: Option<u32>,
+    /// The time to wait at `z_safe` for the chips to clear, see `chip_clear_every`.
+    #[serde(default)]
+    pub chip_clear_dwell: Option<Duration>,
+    /// Point inside the area to plunge at before clearing the area, such as a pre-drilled
+    /// hole for tools that can not cut at the center. The area is entered at the start corner
+    /// when not set. Not used for rest machining.
+    #[serde(default)]
+    pub plunge_point: Option<Vector2>,
+}
+
+fn default_raster_axis() -> Axis {
+    Axis::Y
 }
 
 #[allow(deprecated)]
@@ -51,6 +81,11 @@ impl Area {
             end_z_stop: end_z,
             max_step_z,
             compensation,
+            rest_from: None,
+            raster_axis: default_raster_axis(),
+            chip_clear_every: None,
+            chip_clear_dwell: None,
+            plunge_point: None,
         }
     }
 
@@ -78,6 +113,108 @@ impl Area {
             end_z_stop,
             max_step_z,
             compensation,
+            rest_from: None,
+            raster_axis: default_raster_axis(),
+            chip_clear_every: None,
+            chip_clear_dwell: None,
+            plunge_point: None,
+        }
+    }
+
+    /// Returns the `Area` changed to only clear the material left in the corners by a previous
+    /// tool with the given diameter, also known as rest machining. This requires
+    /// `ToolPathCompensation::Inner` and a previous tool that is larger than the current tool.
+    #[must_use]
+    pub fn with_rest_from(mut self, previous_tool_diameter: f64) -> Self {
+        self.rest_from = Some(previous_tool_diameter);
+        self
+    }
+
+    /// Returns the `Area` with the axis that the passes are spaced along changed, see
+    /// [Area::raster_axis](struct.Area.html#structfield.raster_axis). Stepping along the short
+    /// side of the area gives fewer and longer passes. `Axis::Z` is treated as `Axis::Y`.
+    #[must_use]
+    pub fn with_raster_axis(mut self, raster_axis: Axis) -> Self {
+        self.raster_axis = raster_axis;
+        self
+    }
+
+    /// Returns the `Area` changed to fully retract to `z_safe` after every `every` layers, and
+    /// wait for the dwell if any, to let the chips clear before plunging back down.
+    #[must_use]
+    pub fn with_chip_clearing(mut self, every: u32, dwell: Option<Duration>) -> Self {
+        self.chip_clear_every = Some(every);
+        self.chip_clear_dwell = dwell;
+        self
+    }
+
+    /// Returns the `Area` changed to plunge down to the first layer at the given point before
+    /// clearing the area, see
+    /// [Area::plunge_point](struct.Area.html#structfield.plunge_point).
+    #[must_use]
+    pub fn with_plunge_point(mut self, plunge_point: Vector2) -> Self {
+        self.plunge_point = Some(plunge_point);
+        self
+    }
+
+    /// Returns a new `Area` with the transform applied to its coordinates and size.
+    ///
+    /// Returns error for rotations that are not a multiple of 90° as the area is always
+    /// aligned with the x and y axis.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        if !transform.is_axis_aligned() {
+            return Err(anyhow!(
+                "Unable to transform area, only rotations in multiples of 90° are supported"
+            ));
+        }
+
+        let (start, size) = transform_rectangle(self.start, self.size, transform);
+
+        // Quarter turns swaps the x and y axis
+        let swap_axis = transform.apply_offset(Vector2::new(1.0, 0.0)).x.abs() < 0.5;
+        let raster_axis = match (swap_axis, &self.raster_axis) {
+            (true, Axis::X) => Axis::Y,
+            (true, _) => Axis::X,
+            (false, raster_axis) => raster_axis.clone(),
+        };
+
+        Ok(Self {
+            start,
+            size,
+            end_z: transform.apply_z(self.end_z),
+            end_z_stop: transform.apply_z(self.end_z_stop),
+            max_step_z: self.max_step_z,
+            compensation: self.compensation.clone(),
+            rest_from: self
+                .rest_from
+                .map(|diameter| transform.apply_length(diameter)),
+            raster_axis,
+            chip_clear_every: self.chip_clear_every,
+            chip_clear_dwell: self.chip_clear_dwell,
+            plunge_point: self
+                .plunge_point
+                .map(|point| transform.apply(Vector3::new(point.x, point.y, 0.0)).xy()),
+        })
+    }
+
+    /// Returns the point where the cut starts, at the start corner or the plunge point. The
+    /// tool compensation is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        match self.plunge_point {
+            Some(point) if self.rest_from.is_none() => Vector3::new(point.x, point.y, self.start.z),
+            _ => self.start,
+        }
+    }
+
+    /// Returns the point where the cut ends, the last layer returns to the start corner. For
+    /// rest machining the cut ends in the last corner cleared, at the start x and end y.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        if self.rest_from.is_some() {
+            self.start.add_y(self.size.y).with_z(self.end_z)
+        } else {
+            self.start.with_z(self.end_z)
         }
     }
 
@@ -94,20 +231,74 @@ impl Area {
         }
     }
 
+    /// Returns the bounds of the area swept by a tool of the given radius.
+    ///
+    /// With `Inner` compensation, as for pockets, the tool center stays a tool radius inside of
+    /// the edges, so the tool sweeps the area itself. Without compensation the tool center
+    /// follows the edges and sweeps a tool radius past them. With `Outer` compensation, as for
+    /// planing, the tool center follows a path a tool radius outside of the edges, and the
+    /// other side of the tool reaches another tool radius further out, so the tool sweeps a
+    /// full tool diameter past each edge.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        let bounds = self.bounds();
+        let offset = match self.compensation {
+            ToolPathCompensation::None => tool_radius,
+            ToolPathCompensation::Inner => return bounds,
+            ToolPathCompensation::Outer => tool_radius * 2.0,
+        };
+
+        Bounds {
+            min: bounds.min.add_x(-offset).add_y(-offset),
+            max: bounds.max.add_x(offset).add_y(offset),
+        }
+    }
+
+    /// Returns the z depths of the passes over the area, for sloped areas the depth at the
+    /// start edge.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self) -> Result<Vec<f64>> {
+        let max_step_z = step_z(self.max_step_z)?;
+
+        // The start edge of a sloped area is raised so that the deeper stop edge starts at the
+        // top of the area
+        let delta_z = self.end_z_stop - self.end_z;
+        let start_z = if delta_z < 0.0 && self.rest_from.is_none() {
+            self.start.z - delta_z
+        } else {
+            self.start.z
+        };
+
+        Ok(depth_layers(start_z, self.end_z, max_step_z))
+    }
+
     /// Converts the struct to G-code instructions.
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
         let tool_radius = context.tool().radius();
         let tool_diameter = context.tool().diameter();
         let tool_units = context.tool().units();
 
         if self.size.x < tool_diameter {
-            // TODO: handle calculation for the case when tool and program units are different.
-            return Err(anyhow!("Unable to plane area, tool is {:.2} {} wider than x dimension (tool diameter is {:.2} {})", tool_diameter - self.size.x, tool_units, tool_diameter, tool_units));
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "area",
+                feature_size: self.size.x,
+                tool_diameter,
+                units: tool_units,
+            });
         }
 
         if self.size.y < tool_diameter {
-            // TODO: handle calculation for the case when tool and program units are different.
-            return Err(anyhow!("Unable to plane area, tool is {:.2} {} wider than y dimension (tool diameter is {:.2} {})", tool_diameter - self.size.y, tool_units, tool_diameter, tool_units));
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "area",
+                feature_size: self.size.y,
+                tool_diameter,
+                units: tool_units,
+            });
+        }
+
+        if let Some(previous_tool_diameter) = self.rest_from {
+            return Ok(self.rest_instructions(context, previous_tool_diameter)?);
         }
 
         let start = match self.compensation {
@@ -127,6 +318,31 @@ impl Area {
             }
         };
 
+        let delta_z = self.end_z_stop - self.end_z;
+        let depths = self.layers()?;
+        let layers = depths.len() as u32;
+
+        let entry = match self.plunge_point {
+            Some(point) => {
+                // The tool center has to stay inside the compensated area
+                if point.x < start.x - DEFAULT_EPSILON
+                    || point.x > start.x + size.x + DEFAULT_EPSILON
+                    || point.y < start.y - DEFAULT_EPSILON
+                    || point.y > start.y + size.y + DEFAULT_EPSILON
+                {
+                    return Err(anyhow!(
+                        "Unable to cut area, plunge point x = {}, y = {} is outside of the area",
+                        round_precision(point.x),
+                        round_precision(point.y)
+                    )
+                    .into());
+                }
+
+                point
+            }
+            None => start.xy(),
+        };
+
         let mut instructions = Vec::new();
 
         instructions.append(&mut vec![
@@ -145,8 +361,8 @@ impl Area {
                 z: Some(context.z_safe()),
             }),
             Instruction::G0(G0 {
-                x: Some(start.x),
-                y: Some(start.y),
+                x: Some(entry.x),
+                y: Some(entry.y),
                 z: None,
             }),
             Instruction::G1(G1 {
@@ -157,31 +373,44 @@ impl Area {
             }),
         ]);
 
-        let delta_z = self.end_z_stop - self.end_z;
-        let max_step_z = self.max_step_z.abs();
-        let layers = if (self.end_z - self.end_z_stop).abs() < 0.01 {
-            ((self.end_z - start.z).abs() / max_step_z).ceil() as u32
-        } else {
-            (delta_z.abs() / max_step_z).ceil() as u32
-        };
-        let start_z = if delta_z < 0.0 {
-            start.z - delta_z
-        } else {
-            start.z
-        };
-        let mut end_z = start_z;
-        let mut end_z_stop = start_z + delta_z;
+        // Plunge straight down to the first layer at the plunge point, and move over to the
+        // start corner at that depth
+        if self.plunge_point.is_some() {
+            let z = depths.first().copied().unwrap_or(self.end_z);
+
+            instructions.append(&mut vec![
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(z.min(context.z_safe())),
+                    f: None,
+                }),
+                Instruction::G1(G1 {
+                    x: Some(start.x),
+                    y: Some(start.y),
+                    z: None,
+                    f: None,
+                }),
+            ]);
+        }
 
-        for _layer in 1..layers {
-            end_z -= max_step_z;
-            end_z_stop -= max_step_z;
+        // The last layer is cut at the exact end depths after the loop
+        for (layer, end_z) in (1..layers).zip(depths) {
             instructions.append(&mut self.generate_layer_instructions(
                 start,
                 size,
                 end_z.min(context.z_safe()),
-                end_z_stop.min(context.z_safe()),
+                (end_z + delta_z).min(context.z_safe()),
                 tool_radius,
             ));
+
+            if is_chip_clear_layer(self.chip_clear_every, layer, layers) {
+                instructions.append(&mut chip_clear_instructions(
+                    context,
+                    end_z.min(context.z_safe()),
+                    self.chip_clear_dwell,
+                ));
+            }
         }
 
         instructions.append(&mut self.generate_layer_instructions(
@@ -201,6 +430,172 @@ impl Area {
         Ok(instructions)
     }
 
+    // Clears the corners left by a previous tool. The previous tool center turned around a
+    // point at its radius from both walls, so the material left is between that arc and the
+    // corner. It is cleared with arcs around the same point, growing from the arc of the previous
+    // tool until the current tool reaches the corner, each arc running from wall to wall.
+    fn rest_instructions(
+        &self,
+        context: &InnerContext,
+        previous_tool_diameter: f64,
+    ) -> Result<Vec<Instruction>> {
+        let tool_radius = context.tool().radius();
+        let tool_units = context.tool().units();
+        let previous_radius = previous_tool_diameter / 2.0;
+
+        if !matches!(self.compensation, ToolPathCompensation::Inner) {
+            return Err(anyhow!(
+                "Unable to rest machine area, it requires inner tool path compensation"
+            ));
+        }
+
+        if previous_radius <= tool_radius {
+            return Err(anyhow!(
+                "Unable to rest machine area, previous tool diameter {:.2} {} is not larger than tool diameter {:.2} {}",
+                previous_tool_diameter,
+                tool_units,
+                context.tool().diameter(),
+                tool_units
+            ));
+        }
+
+        if self.size.x < previous_tool_diameter || self.size.y < previous_tool_diameter {
+            return Err(anyhow!(
+                "Unable to rest machine area, previous tool diameter {:.2} {} is wider than the area",
+                previous_tool_diameter,
+                tool_units
+            ));
+        }
+
+        // Distance from the previous tool arc center to the walls, as seen by the current tool
+        let offset = previous_radius - tool_radius;
+        let max_distance = offset * 2.0_f64.sqrt();
+        let arcs = ((max_distance - offset) / (tool_radius * 1.8))
+            .ceil()
+            .max(1.0) as u32;
+        let max_step_z = step_z(self.max_step_z)?;
+        let depths = self.layers()?;
+
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Do rest machining at: x = {}, y = {}, size = {}, previous tool diameter = {}",
+                    round_precision(self.start.x),
+                    round_precision(self.start.y),
+                    self.size,
+                    round_precision(previous_tool_diameter)
+                ),
+            }),
+        ];
+
+        let corners = [
+            (self.start.xy(), 1.0, 1.0),
+            (self.start.xy().add_x(self.size.x), -1.0, 1.0),
+            (
+                self.start.xy().add_x(self.size.x).add_y(self.size.y),
+                -1.0,
+                -1.0,
+            ),
+            (self.start.xy().add_y(self.size.y), 1.0, -1.0),
+        ];
+
+        for (corner, sign_x, sign_y) in corners {
+            let center = corner
+                .add_x(sign_x * previous_radius)
+                .add_y(sign_y * previous_radius);
+            let point = |distance: f64, angle: f64| {
+                Vector2::new(
+                    center.x - sign_x * distance * angle.cos(),
+                    center.y - sign_y * distance * angle.sin(),
+                )
+            };
+
+            // Each arc ends on the walls, the angles are limited by the distance to them
+            let passes = (0..=arcs)
+                .map(|index| {
+                    let distance =
+                        offset + (max_distance - offset) * f64::from(index) / f64::from(arcs);
+                    let ratio = (offset / distance).min(1.0);
+                    (point(distance, ratio.acos()), point(distance, ratio.asin()))
+                })
+                .collect::<Vec<_>>();
+
+            instructions.append(&mut vec![
+                Instruction::G0(G0 {
+                    x: None,
+                    y: None,
+                    z: Some(context.z_safe()),
+                }),
+                Instruction::G0(G0 {
+                    x: Some(passes[0].0.x),
+                    y: Some(passes[0].0.y),
+                    z: None,
+                }),
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(self.start.z.min(context.z_safe())),
+                    f: Some(context.tool().feed_rate()),
+                }),
+            ]);
+
+            for (index, z) in depths.iter().enumerate() {
+                let z = z.min(context.z_safe());
+
+                if index > 0 {
+                    instructions.append(&mut vec![
+                        Instruction::G0(G0 {
+                            x: None,
+                            y: None,
+                            z: Some(z + max_step_z + 0.5),
+                        }),
+                        Instruction::G0(G0 {
+                            x: Some(passes[0].0.x),
+                            y: Some(passes[0].0.y),
+                            z: None,
+                        }),
+                    ]);
+                }
+
+                instructions.push(Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(z),
+                    f: None,
+                }));
+
+                for (index, (from, to)) in passes.iter().enumerate() {
+                    // Alternate the direction of the arcs, moving along the wall between them
+                    let (from, to) = if index % 2 == 0 {
+                        (*from, *to)
+                    } else {
+                        (*to, *from)
+                    };
+
+                    instructions.push(Instruction::G1(G1 {
+                        x: Some(from.x),
+                        y: Some(from.y),
+                        z: None,
+                        f: None,
+                    }));
+
+                    if from.distance_to(to) > 1e-9 {
+                        instructions.push(arc_instruction(center, from, to, z));
+                    }
+                }
+            }
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
+        Ok(instructions)
+    }
+
     fn generate_layer_instructions(
         &self,
         start: Vector3,
@@ -243,6 +638,17 @@ impl Area {
             f: None,
         }));
 
+        if matches!(self.raster_axis, Axis::X) {
+            return self.generate_x_raster_instructions(
+                instructions,
+                start,
+                size,
+                end_z,
+                end_z_stop,
+                tool_radius,
+            );
+        }
+
         let mut end_at_start = true;
 
         if size.x > tool_radius * 2.0 {
@@ -297,4 +703,362 @@ impl Area {
 
         instructions
     }
+
+    // Continues a layer after the outline with passes along the y axis, stepping along the x
+    // axis. For sloped areas the depth of each pass is given by its x position.
+    fn generate_x_raster_instructions(
+        &self,
+        mut instructions: Vec<Instruction>,
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        end_z_stop: f64,
+        tool_radius: f64,
+    ) -> Vec<Instruction> {
+        let passes = (size.x / (tool_radius * 1.8)).ceil() as i32;
+        let pass_x = size.x / passes as f64;
+        let mut last_z = end_z;
+
+        if size.y > tool_radius * 2.0 {
+            for index in 0..passes {
+                let x = start.x + index as f64 * pass_x;
+                last_z = end_z + (end_z_stop - end_z) * (x - start.x) / size.x;
+
+                instructions.push(Instruction::G1(G1 {
+                    x: Some(x),
+                    y: None,
+                    z: Some(last_z),
+                    f: None,
+                }));
+
+                instructions.push(Instruction::G1(G1 {
+                    x: None,
+                    y: Some(if index % 2 == 0 {
+                        start.y + size.y
+                    } else {
+                        start.y
+                    }),
+                    z: None,
+                    f: None,
+                }));
+            }
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(last_z + 0.5),
+        }));
+
+        instructions.push(Instruction::G0(G0 {
+            x: Some(start.x),
+            y: Some(start.y),
+            z: Some(end_z + 0.5),
+        }));
+
+        instructions.push(Instruction::G1(G1 {
+            x: None,
+            y: None,
+            z: Some(end_z),
+            f: None,
+        }));
+
+        instructions
+    }
+}
+
+// Returns the shortest arc instruction around the center between two points at the same
+// distance from it.
+fn arc_instruction(center: Vector2, from: Vector2, to: Vector2, z: f64) -> Instruction {
+    let cross = (from.x - center.x) * (to.y - center.y) - (from.y - center.y) * (to.x - center.x);
+    let i = Some(center.x - from.x);
+    let j = Some(center.y - from.y);
+
+    if cross > 0.0 {
+        Instruction::G3(G3 {
+            x: Some(to.x),
+            y: Some(to.y),
+            z: Some(z),
+            i,
+            j,
+            k: None,
+            r: None,
+            p: None,
+            f: None,
+        })
+    } else {
+        Instruction::G2(G2 {
+            x: Some(to.x),
+            y: Some(to.y),
+            z: Some(z),
+            i,
+            j,
+            k: None,
+            r: None,
+            p: None,
+            f: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    #[test]
+    fn test_area_rest_machining() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            3.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+        let area = Area::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(40.0, 30.0),
+            -2.0,
+            1.0,
+            ToolPathCompensation::Inner,
+        )
+        .with_rest_from(10.0);
+
+        let instructions = area.to_instructions(&context)?;
+        let points = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G1(G1 {
+                    x: Some(x),
+                    y: Some(y),
+                    ..
+                })
+                | Instruction::G2(G2 {
+                    x: Some(x),
+                    y: Some(y),
+                    ..
+                })
+                | Instruction::G3(G3 {
+                    x: Some(x),
+                    y: Some(y),
+                    ..
+                }) => Some(Vector2::new(*x, *y)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let corners = [
+            Vector2::new(1.5, 1.5),
+            Vector2::new(38.5, 1.5),
+            Vector2::new(38.5, 28.5),
+            Vector2::new(1.5, 28.5),
+        ];
+
+        // Only the corners out of reach for the 10 mm tool are cut, all the way into the corner
+        for point in &points {
+            assert!(
+                corners
+                    .iter()
+                    .any(|corner| (point.x - corner.x).abs() <= 3.5 + 1e-9
+                        && (point.y - corner.y).abs() <= 3.5 + 1e-9),
+                "{point:?}"
+            );
+        }
+        for corner in corners {
+            assert!(points.iter().any(|point| point.distance_to(corner) < 1e-9));
+        }
+
+        // One arc along the edge left by the previous tool per corner and layer
+        let arcs = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::G2(_) | Instruction::G3(_)))
+            .count();
+        assert_eq!(arcs, 4 * 2);
+
+        assert!(area
+            .clone()
+            .with_rest_from(2.0)
+            .to_instructions(&context)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_area_x_raster() -> Result<()> {
+        let context = InnerContext::new(
+            Units::Metric,
+            &Tool::cylindrical(
+                Units::Metric,
+                20.0,
+                4.0,
+                Direction::Clockwise,
+                5000.0,
+                400.0,
+            ),
+            5.0,
+            50.0,
+        );
+        let area = Area::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(100.0, 20.0),
+            -1.0,
+            1.0,
+            ToolPathCompensation::Inner,
+        );
+
+        // Returns the passes as the x and y of each move after the outline
+        let passes = |area: &Area| -> Result<Vec<(Option<f64>, Option<f64>)>> {
+            Ok(area
+                .to_instructions(&context)?
+                .into_iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::G1(g1) if g1.x.is_some() || g1.y.is_some() => Some((g1.x, g1.y)),
+                    _ => None,
+                })
+                .skip(4)
+                .collect())
+        };
+
+        // The default raster steps along y with passes along x
+        let y_passes = passes(&area)?;
+        assert_eq!(y_passes.len(), 2 * 5);
+        assert!(y_passes.iter().step_by(2).all(|(x, _)| x.is_none()));
+
+        let x_area = area.clone().with_raster_axis(Axis::X);
+        let x_passes = passes(&x_area)?;
+        assert_eq!(x_passes.len(), 2 * 27);
+
+        // The passes are spaced along x over the 96 mm the tool center can travel
+        let pass_x = 96.0 / 27.0;
+        for (index, pass) in x_passes.chunks(2).enumerate() {
+            let x = pass[0].0.expect("pass x position");
+            assert!((x - (2.0 + index as f64 * pass_x)).abs() < 1e-9);
+            assert_eq!(pass[1].0, None);
+            assert_eq!(pass[1].1, Some(if index % 2 == 0 { 18.0 } else { 2.0 }));
+        }
+
+        // A quarter turn swaps the raster axis, so the passes keep their direction in the area
+        let rotated = x_area.transform(&Transform::RotateZ {
+            angle: std::f64::consts::FRAC_PI_2,
+            pivot: Vector2::new(0.0, 0.0),
+        })?;
+        assert!(matches!(rotated.raster_axis, Axis::Y));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_area_chip_clearing() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let area = Area::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -10.0,
+            1.0,
+            ToolPathCompensation::Inner,
+        );
+
+        let full_retracts = |instructions: &[Instruction]| {
+            instructions
+                .iter()
+                .filter(|instruction| {
+                    matches!(instruction, Instruction::G0(G0 { z: Some(z), .. }) if *z == 5.0)
+                })
+                .count()
+        };
+
+        // Without chip clearing only the approach and the final retract go to z_safe
+        assert_eq!(full_retracts(&area.to_instructions(&context)?), 2);
+
+        // The 10 layers are cleared after layer 3, 6 and 9, but not after the last layer
+        let instructions = area
+            .with_chip_clearing(3, Some(Duration::from_secs(2)))
+            .to_instructions(&context)?;
+        assert_eq!(full_retracts(&instructions), 2 + 3);
+
+        let dwells = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::G4(_)))
+            .count();
+        assert_eq!(dwells, 3);
+
+        // The tool plunges back down to the depth of the cleared layer
+        let retract = instructions
+            .iter()
+            .position(|instruction| matches!(instruction, Instruction::G4(_)))
+            .expect("chip clearing dwell");
+        assert_eq!(
+            instructions[retract + 2],
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(-3.0),
+                f: Some(context.tool().feed_rate()),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_area_plunge_point() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let area = Area::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+            ToolPathCompensation::Inner,
+        )
+        .with_plunge_point(Vector2::new(10.0, 10.0));
+
+        assert_eq!(area.entry_point(), Vector3::new(10.0, 10.0, 0.0));
+
+        let instructions = area.to_instructions(&context)?;
+        let radius = context.tool().radius();
+
+        assert_eq!(
+            instructions[3..7],
+            [
+                Instruction::G0(G0 {
+                    x: Some(10.0),
+                    y: Some(10.0),
+                    z: None,
+                }),
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(0.0),
+                    f: Some(context.tool().feed_rate()),
+                }),
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(-1.0),
+                    f: None,
+                }),
+                Instruction::G1(G1 {
+                    x: Some(radius),
+                    y: Some(radius),
+                    z: None,
+                    f: None,
+                }),
+            ]
+        );
+
+        // The tool center can not reach the edge of an inner compensated area
+        let error = area
+            .with_plunge_point(Vector2::new(0.0, 10.0))
+            .to_instructions(&context)
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("outside of the area"),
+            "{}",
+            error
+        );
+
+        Ok(())
+    }
 }
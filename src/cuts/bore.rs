@@ -0,0 +1,448 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::cuts::step_z;
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// Bore a round hole by following a helix down to `end_z` with a single arc command, using the
+/// P word for the number of revolutions, followed by a flat finishing circle at the bottom.
+///
+/// The tool path is compensated so that the hole gets the given radius. For
+/// [flavors](../types/enum.Flavor.html) where the controller does not support the P word the
+/// arc is expanded to one arc per revolution when the G-code is generated.
+///
+/// With a [step over](struct.Bore.html#method.with_step_over) the whole hole is cleared
+/// instead, for holes that are much larger than the tool. Each layer is entered with a helix
+/// revolution around the center and cleared by spiraling outward to the wall, followed by a
+/// finishing circle along the wall.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bore {
+    /// Center point in 3D space, the z value is the start depth of the cut.
+    pub center: Vector3,
+    /// The radius of the hole.
+    pub radius: f64,
+    /// The end depth of the cut on the z axis.
+    pub end_z: f64,
+    /// The maximum depth to cut on the z axis for each revolution.
+    pub max_step_z: f64,
+    /// The distance between the turns when clearing the whole hole, only the wall is cut
+    /// when not set.
+    #[serde(default)]
+    pub step_over: Option<f64>,
+}
+
+impl Bore {
+    /// Creates a new `Bore` struct.
+    #[must_use]
+    pub fn new(center: Vector3, radius: f64, end_z: f64, max_step_z: f64) -> Self {
+        Self {
+            center,
+            radius,
+            end_z,
+            max_step_z,
+            step_over: None,
+        }
+    }
+
+    /// Returns the `Bore` clearing the whole hole with `step_over` between the turns of the
+    /// outward spiral.
+    #[must_use]
+    pub fn with_step_over(mut self, step_over: f64) -> Self {
+        self.step_over = Some(step_over);
+        self
+    }
+
+    /// Returns a new `Bore` with the transform applied to its coordinates and radius.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        Self {
+            center: transform.apply(self.center),
+            radius: transform.apply_length(self.radius),
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+            step_over: self
+                .step_over
+                .map(|step_over| transform.apply_length(step_over)),
+        }
+    }
+
+    /// Returns the point where the cut starts, at the left side of the hole. The tool
+    /// compensation is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.center.add_x(-self.radius)
+    }
+
+    /// Returns the point where the cut ends, below the entry point on the end depth.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.entry_point().with_z(self.end_z)
+    }
+
+    /// Returns the bounds of the cut.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        Bounds {
+            min: Vector3::new(
+                self.center.x - self.radius,
+                self.center.y - self.radius,
+                self.end_z,
+            ),
+            max: Vector3::new(
+                self.center.x + self.radius,
+                self.center.y + self.radius,
+                self.center.z,
+            ),
+        }
+    }
+
+    /// Returns the bounds of the area swept by a tool of the given radius, the whole hole, or
+    /// the tool itself when it is wider than the hole.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        Self {
+            radius: self.radius.max(tool_radius),
+            ..self.clone()
+        }
+        .bounds()
+    }
+
+    /// Returns the number of revolutions needed to reach `end_z` without going deeper than
+    /// `max_step_z` for each revolution.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn revolutions(&self) -> Result<u32> {
+        let max_step_z = step_z(self.max_step_z)?;
+
+        Ok((((self.center.z - self.end_z) / max_step_z).ceil() as u32).max(1))
+    }
+
+    /// Returns the z depths reached by each revolution, the depth is divided evenly between
+    /// the revolutions.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self) -> Result<Vec<f64>> {
+        let revolutions = self.revolutions()?;
+
+        Ok((1..=revolutions)
+            .map(|layer| {
+                self.center.z
+                    - (self.center.z - self.end_z) * f64::from(layer) / f64::from(revolutions)
+            })
+            .collect())
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let tool = context.tool();
+        let cut_radius = self.radius - tool.radius();
+
+        if cut_radius <= 0.0 {
+            let units = context.units();
+
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "bore",
+                feature_size: self.radius * 2.0,
+                tool_diameter: tool.diameter(),
+                units,
+            });
+        }
+
+        if let Some(step_over) = self.step_over {
+            return Ok(self.generate_clearing_instructions(context, cut_radius, step_over)?);
+        }
+
+        let start = Vector2::new(self.center.x - cut_radius, self.center.y);
+        let circle = |z: f64, p: Option<u32>| {
+            Instruction::G2(G2 {
+                x: Some(start.x),
+                y: Some(start.y),
+                z: Some(z),
+                i: Some(cut_radius),
+                j: Some(0.0),
+                k: None,
+                r: None,
+                p,
+                f: None,
+            })
+        };
+
+        Ok(vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Bore hole at: x = {}, y = {}",
+                    round_precision(self.center.x),
+                    round_precision(self.center.y)
+                ),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(start.x),
+                y: Some(start.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(self.center.z),
+                f: Some(tool.feed_rate()),
+            }),
+            circle(self.end_z, Some(self.revolutions()?)),
+            // Flat circle at the bottom
+            circle(self.end_z, None),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+        ])
+    }
+
+    // Clears the whole hole layer by layer, descending each layer with a helix revolution
+    // around the center before spiraling out to the wall.
+    fn generate_clearing_instructions(
+        &self,
+        context: &InnerContext,
+        cut_radius: f64,
+        step_over: f64,
+    ) -> Result<Vec<Instruction>> {
+        let tool = context.tool();
+
+        if !(step_over > 0.0 && step_over.is_finite()) {
+            return Err(CncError::InvalidStepOver {
+                feature: "bore",
+                step_over,
+            }
+            .into());
+        }
+
+        // Half circles that alternate between the sides of the center grow the radius by half
+        // the step over for each half turn
+        let half_step = step_over.min(tool.diameter()) / 2.0;
+        let helix_radius = half_step.min(cut_radius);
+        let start = Vector2::new(self.center.x - helix_radius, self.center.y);
+
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Bore and clear hole at: x = {}, y = {}, radius = {}",
+                    round_precision(self.center.x),
+                    round_precision(self.center.y),
+                    round_precision(self.radius)
+                ),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(start.x),
+                y: Some(start.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(self.center.z),
+                f: Some(tool.feed_rate()),
+            }),
+        ];
+
+        let revolutions = self.revolutions()?;
+        for layer in 1..=revolutions {
+            let z = self.center.z
+                - (self.center.z - self.end_z) * f64::from(layer) / f64::from(revolutions);
+
+            if layer > 1 {
+                instructions.push(Instruction::G1(G1 {
+                    x: Some(start.x),
+                    y: Some(start.y),
+                    z: None,
+                    f: None,
+                }));
+            }
+
+            // Helical descent around the center
+            instructions.push(Instruction::G2(G2 {
+                x: Some(start.x),
+                y: Some(start.y),
+                z: Some(z),
+                i: Some(helix_radius),
+                j: Some(0.0),
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }));
+
+            let mut from = -helix_radius;
+            let mut index = 2.0;
+            while from.abs() < cut_radius {
+                let side = if from > 0.0 { -1.0 } else { 1.0 };
+                let to = side * (index * half_step).min(cut_radius);
+
+                instructions.push(Instruction::G2(G2 {
+                    x: Some(self.center.x + to),
+                    y: Some(self.center.y),
+                    z: None,
+                    i: Some((to - from) / 2.0),
+                    j: Some(0.0),
+                    k: None,
+                    r: None,
+                    p: None,
+                    f: None,
+                }));
+
+                from = to;
+                index += 1.0;
+            }
+
+            // Finishing circle along the wall
+            instructions.push(Instruction::G2(G2 {
+                x: Some(self.center.x + from),
+                y: Some(self.center.y),
+                z: None,
+                i: Some(-from),
+                j: Some(0.0),
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }));
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
+        Ok(instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motion::MachineState;
+    use crate::tools::Tool;
+
+    #[test]
+    fn test_bore_revolutions() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+
+        let bore = Bore::new(Vector3::new(10.0, 10.0, 0.0), 5.0, -7.0, 2.0);
+        assert_eq!(bore.revolutions()?, 4);
+
+        let instructions = bore.to_instructions(&context)?;
+        assert_eq!(
+            instructions[5],
+            Instruction::G2(G2 {
+                x: Some(7.0),
+                y: Some(10.0),
+                z: Some(-7.0),
+                i: Some(3.0),
+                j: Some(0.0),
+                k: None,
+                r: None,
+                p: Some(4),
+                f: None,
+            })
+        );
+        assert!(instructions[5].to_gcode().ends_with(" P4"));
+
+        let error = Bore::new(Vector3::new(0.0, 0.0, 0.0), 2.0, -1.0, 1.0)
+            .to_instructions(&context)
+            .unwrap_err();
+        assert!(matches!(error, CncError::ToolWiderThanFeature { .. }));
+        assert!(Bore::new(Vector3::new(0.0, 0.0, 0.0), 5.0, -1.0, 0.0)
+            .revolutions()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bore_hole() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            6.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+
+        let bore = Bore::new(Vector3::new(50.0, 50.0, 0.0), 20.0, -4.0, 2.0).with_step_over(4.0);
+        let instructions = bore.to_instructions(&context)?;
+
+        let mut state = MachineState::default();
+        let mut layer_radii = vec![];
+        let mut depths = vec![];
+        for instruction in &instructions {
+            state.apply(instruction);
+            if let Instruction::G2(g2) = instruction {
+                let radius = state.position.xy().distance_to(Vector2::new(50.0, 50.0));
+                if g2.z.is_some() {
+                    // Each layer starts with a helix revolution around the center
+                    assert_eq!(radius, 2.0);
+                    depths.push(state.position.z);
+                    layer_radii.push(vec![]);
+                } else {
+                    layer_radii.last_mut().unwrap().push(radius);
+                }
+            }
+        }
+
+        assert_eq!(depths, vec![-2.0, -4.0]);
+
+        // The spiral grows 2 mm per half turn until it reaches the wall of the tool path,
+        // where the finishing circle is cut
+        for radii in layer_radii {
+            assert_eq!(
+                radii,
+                vec![4.0, 6.0, 8.0, 10.0, 12.0, 14.0, 16.0, 17.0, 17.0]
+            );
+        }
+
+        let error = Bore::new(Vector3::new(0.0, 0.0, 0.0), 3.0, -1.0, 1.0)
+            .with_step_over(2.0)
+            .to_instructions(&context)
+            .unwrap_err();
+        assert!(matches!(error, CncError::ToolWiderThanFeature { .. }));
+
+        let error = Bore::new(Vector3::new(0.0, 0.0, 0.0), 6.0, -1.0, 1.0)
+            .with_step_over(0.0)
+            .to_instructions(&context)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::InvalidStepOver {
+                feature: "bore",
+                ..
+            }
+        ));
+
+        Ok(())
+    }
+}
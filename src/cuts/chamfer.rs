@@ -0,0 +1,479 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cuts::{Arc2D, DepthMode, Line2D, Path, Segment};
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::tools::Tool;
+use crate::types::*;
+use crate::utils::*;
+
+/// Chamfer the top edge of a part along a path with a
+/// [conical](../tools/struct.Conical.html) or [engraving](../tools/struct.Engraving.html) tool.
+///
+/// The segments describe the edge of the part relative to the start point, with the part on
+/// the left side of the path, as for a counterclockwise outline. The tool tip is lowered
+/// `depth` below the top of the edge and offset away from the part, so that the flank of the
+/// tool cuts `width` into the part at the surface. The chamfer angle is given by the tool
+/// angle, and any depth beyond what is needed for the width keeps the tip clear of the edge.
+///
+/// Outside corners are rounded by the offset, inside corners between line segments are
+/// trimmed to where the offset lines meet, and other inside corners are joined with a line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Chamfer {
+    /// Start point in 3D space, the z value is the top of the edge.
+    pub start: Vector3,
+    /// Segments that makes up the edge, relative to the start point.
+    pub segments: Vec<Segment>,
+    /// The depth of the tool tip below the top of the edge.
+    pub depth: f64,
+    /// The width of the chamfer at the top of the edge.
+    pub width: f64,
+}
+
+impl Chamfer {
+    /// Creates a new `Chamfer` struct.
+    #[must_use]
+    pub fn new(start: Vector3, segments: Vec<Segment>, depth: f64, width: f64) -> Self {
+        Self {
+            start,
+            segments,
+            depth,
+            width,
+        }
+    }
+
+    // Returns the edge as a path, used for the geometry that is shared with paths.
+    fn path(&self, segments: Vec<Segment>) -> Path {
+        Path::new(self.start, segments, self.start.z - self.depth, self.depth)
+            .with_depth_mode(DepthMode::Constant)
+    }
+
+    /// Returns a new `Chamfer` with the transform applied to its coordinates and sizes.
+    /// Mirroring reverses the path so that the part stays on the left side of it.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let path = self.path(self.segments.clone()).transform(transform);
+        let segments = if transform.is_mirror() {
+            path.segments
+                .into_iter()
+                .rev()
+                .map(|segment| match segment {
+                    Segment::Line(line) => Segment::Line(Line2D {
+                        from: line.to,
+                        to: line.from,
+                        ..line
+                    }),
+                    Segment::Arc(arc) => Segment::Arc(Arc2D {
+                        from: arc.to,
+                        to: arc.from,
+                        direction: arc.direction.opposite(),
+                        ..arc
+                    }),
+                    point => point,
+                })
+                .collect()
+        } else {
+            path.segments
+        };
+
+        Self {
+            start: path.start,
+            segments,
+            depth: transform.apply_length(self.depth),
+            width: transform.apply_length(self.width),
+        }
+    }
+
+    /// Returns the point where the cut starts, at the start of the edge. The offset from the
+    /// edge is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.path(self.segments.clone()).entry_point()
+    }
+
+    /// Returns the point where the cut ends, at the end of the edge on the depth of the tip.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.path(self.segments.clone()).exit_point()
+    }
+
+    /// Returns the bounds of the cut, the edge expanded by the chamfer width.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        self.expanded_bounds(self.width)
+    }
+
+    /// Returns the bounds of the tool path for a tool of the given radius. The tip of the tool
+    /// is offset from the edge by less than the tool radius.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        self.expanded_bounds(tool_radius.max(self.width))
+    }
+
+    fn expanded_bounds(&self, offset: f64) -> Bounds {
+        let bounds = self.path(self.segments.clone()).bounds();
+
+        Bounds {
+            min: bounds.min.add_x(-offset).add_y(-offset),
+            max: bounds.max.add_x(offset).add_y(offset),
+        }
+    }
+
+    /// Returns the distance from the edge to the tool tip for the tool, so that the flank of
+    /// the tool cuts the chamfer width at the top of the edge.
+    ///
+    /// Returns error if the tool is not conical, if the tip is not deep enough for the width,
+    /// or if the tool is too narrow for the depth.
+    pub fn tip_offset(&self, tool: &Tool) -> Result<f64> {
+        let flank = match tool {
+            Tool::Conical(tool) => tool.width_at_depth(self.depth) / 2.0,
+            Tool::Engraving(tool) => tool.width_at_depth(self.depth) / 2.0,
+            _ => {
+                return Err(CncError::UnsupportedTool {
+                    feature: "chamfer",
+                    tool: *tool,
+                    required: "a conical or engraving tool",
+                }
+                .into())
+            }
+        };
+        let units = tool.units();
+
+        if flank < self.width {
+            return Err(anyhow!(
+                "Unable to chamfer edge {:.2} {} wide, the tool only reaches {:.2} {} at the depth {:.2} {}",
+                self.width,
+                units,
+                flank,
+                units,
+                self.depth,
+                units
+            ));
+        }
+
+        if flank > tool.radius() {
+            return Err(anyhow!(
+                "Unable to chamfer edge at the depth {:.2} {}, the tool diameter {:.2} {} is too small",
+                self.depth,
+                units,
+                tool.diameter(),
+                units
+            ));
+        }
+
+        Ok(flank - self.width)
+    }
+
+    // Returns the segments offset to the right side of the edge, with the corners joined.
+    fn offset_segments(&self, offset: f64) -> Result<Vec<Segment>> {
+        let mut edges = vec![];
+        let mut previous: Option<Vector2> = None;
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Point(point) => {
+                    if let Some(from) = previous {
+                        if !from.approx_eq(*point, DEFAULT_EPSILON) {
+                            edges.push(Segment::line(from, *point));
+                        }
+                    }
+                    previous = Some(*point);
+                }
+                Segment::Line(line) => {
+                    edges.push(segment.clone());
+                    previous = Some(line.to);
+                }
+                Segment::Arc(arc) => {
+                    if !matches!(arc.axis, Axis::Z) {
+                        return Err(anyhow!(
+                            "Unable to chamfer edge, only arcs around the z axis are supported"
+                        ));
+                    }
+
+                    edges.push(segment.clone());
+                    previous = Some(arc.to);
+                }
+            }
+        }
+
+        let mut offset_edges = edges
+            .iter()
+            .map(|edge| offset_segment(edge, offset))
+            .collect::<Result<Vec<_>>>()?;
+
+        let closed = match (edges.first(), edges.last()) {
+            (Some(first), Some(last)) => endpoints(first)
+                .0
+                .approx_eq(endpoints(last).1, DEFAULT_EPSILON),
+            _ => false,
+        };
+        let junctions = if closed {
+            edges.len()
+        } else {
+            edges.len().saturating_sub(1)
+        };
+
+        let mut segments = vec![];
+        let mut closing = None;
+
+        for index in 0..offset_edges.len() {
+            let next = (index + 1) % offset_edges.len();
+
+            if index < junctions {
+                let vertex = endpoints(&edges[index]).1;
+                let turn = cross(tangents(&edges[index]).1, tangents(&edges[next]).0);
+                let from = endpoints(&offset_edges[index]).1;
+                let to = endpoints(&offset_edges[next]).0;
+
+                let joint = if from.approx_eq(to, DEFAULT_EPSILON) {
+                    None
+                } else if turn > 0.0 {
+                    Some(Segment::arc(from, to, vertex, Direction::Counterclockwise))
+                } else if let (Segment::Line(a), Segment::Line(b)) =
+                    (&offset_edges[index], &offset_edges[next])
+                {
+                    let direction_a = a.to - a.from;
+                    let direction_b = b.to - b.from;
+                    let scale =
+                        cross(b.from - a.from, direction_b) / cross(direction_a, direction_b);
+                    let point = a.from + direction_a * Vector2::splat(scale);
+
+                    set_endpoints(&mut offset_edges[index], None, Some(point));
+                    set_endpoints(&mut offset_edges[next], Some(point), None);
+                    None
+                } else {
+                    Some(Segment::line(from, to))
+                };
+
+                segments.push(offset_edges[index].clone());
+
+                if next == 0 {
+                    closing = joint;
+                } else if let Some(joint) = joint {
+                    segments.push(joint);
+                }
+            } else {
+                segments.push(offset_edges[index].clone());
+            }
+        }
+
+        // The start of the first segment can have been trimmed by the closing corner
+        if closed {
+            segments[0] = offset_edges[0].clone();
+        }
+
+        if let Some(closing) = closing {
+            segments.push(closing);
+        }
+
+        Ok(segments)
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let offset = self.tip_offset(&context.tool())?;
+        let mut instructions = self
+            .path(self.offset_segments(offset)?)
+            .to_instructions(context)?;
+
+        if let Some(Instruction::Comment(comment)) = instructions.get_mut(1) {
+            comment.text = format!(
+                "Chamfer edge at: x = {}, y = {}, width = {}",
+                round_precision(self.start.x),
+                round_precision(self.start.y),
+                round_precision(self.width)
+            );
+        }
+
+        Ok(instructions)
+    }
+}
+
+fn cross(a: Vector2, b: Vector2) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+// Returns the start and end points of a line or arc segment.
+fn endpoints(segment: &Segment) -> (Vector2, Vector2) {
+    match segment {
+        Segment::Line(line) => (line.from, line.to),
+        Segment::Arc(arc) => (arc.from, arc.to),
+        Segment::Point(point) => (*point, *point),
+    }
+}
+
+fn set_endpoints(segment: &mut Segment, from: Option<Vector2>, to: Option<Vector2>) {
+    if let Segment::Line(line) = segment {
+        line.from = from.unwrap_or(line.from);
+        line.to = to.unwrap_or(line.to);
+    }
+}
+
+// Returns the directions of travel at the start and end of a line or arc segment.
+fn tangents(segment: &Segment) -> (Vector2, Vector2) {
+    match segment {
+        Segment::Arc(arc) => {
+            let tangent = |point: Vector2| {
+                let radial = point - arc.center;
+                match arc.direction {
+                    Direction::Clockwise => Vector2::new(radial.y, -radial.x),
+                    Direction::Counterclockwise => Vector2::new(-radial.y, radial.x),
+                }
+                .normalized()
+            };
+
+            (tangent(arc.from), tangent(arc.to))
+        }
+        _ => {
+            let (from, to) = endpoints(segment);
+            let direction = (to - from).normalized();
+            (direction, direction)
+        }
+    }
+}
+
+// Moves a line or arc segment the distance to the right side of its direction of travel.
+fn offset_segment(segment: &Segment, distance: f64) -> Result<Segment> {
+    let (start_tangent, end_tangent) = tangents(segment);
+    let right = |point: Vector2, tangent: Vector2| {
+        point + Vector2::new(tangent.y, -tangent.x) * Vector2::splat(distance)
+    };
+    let (from, to) = endpoints(segment);
+
+    Ok(match segment {
+        Segment::Arc(arc) => {
+            if arc.radius() - distance <= 0.0 && arc.direction == Direction::Clockwise {
+                return Err(anyhow!(
+                    "Unable to chamfer edge, the arc radius {:.2} is smaller than the tool offset {:.2}",
+                    arc.radius(),
+                    distance
+                ));
+            }
+
+            Segment::Arc(Arc2D {
+                from: right(from, start_tangent),
+                to: right(to, end_tangent),
+                ..arc.clone()
+            })
+        }
+        Segment::Line(line) => Segment::Line(Line2D {
+            from: right(from, start_tangent),
+            to: right(to, end_tangent),
+            ..line.clone()
+        }),
+        Segment::Point(point) => Segment::Point(*point),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chamfer_straight_edge() -> Result<()> {
+        let tool = Tool::conical(
+            Units::Metric,
+            90.0,
+            16.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+        let chamfer = Chamfer::new(
+            Vector3::new(10.0, 10.0, 0.0),
+            vec![Segment::line(Vector2::ZERO, Vector2::new(50.0, 0.0))],
+            3.0,
+            1.0,
+        );
+
+        // A 90° tool reaches 3 mm out at 3 mm depth, so the tip is 2 mm from the edge
+        assert!((chamfer.tip_offset(&tool)? - 2.0).abs() < 1e-9);
+
+        let instructions = chamfer.to_instructions(&context)?;
+        let cut = instructions
+            .iter()
+            .rev()
+            .find_map(|instruction| match instruction {
+                Instruction::G1(G1 {
+                    x: Some(x),
+                    y: Some(y),
+                    z: Some(z),
+                    ..
+                }) => Some(Vector3::new(*x, *y, *z)),
+                _ => None,
+            })
+            .unwrap();
+        assert!(cut.distance_to(Vector3::new(60.0, 8.0, -3.0)) < 1e-9);
+
+        let bounds = chamfer.bounds();
+        assert_eq!(bounds.min, Vector3::new(9.0, 9.0, -3.0));
+        assert_eq!(bounds.max, Vector3::new(61.0, 11.0, 0.0));
+
+        // Too shallow for the width, and too deep for the tool
+        assert!(Chamfer {
+            depth: 0.5,
+            ..chamfer.clone()
+        }
+        .to_instructions(&context)
+        .is_err());
+        assert!(Chamfer {
+            depth: 10.0,
+            ..chamfer.clone()
+        }
+        .to_instructions(&context)
+        .is_err());
+        assert!(matches!(
+            chamfer.to_instructions(&InnerContext::new(
+                Units::Metric,
+                &Tool::default(),
+                5.0,
+                50.0
+            )),
+            Err(CncError::UnsupportedTool {
+                feature: "chamfer",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chamfer_corners() -> Result<()> {
+        let chamfer = Chamfer::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Segment::points(vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(20.0, 0.0),
+                Vector2::new(20.0, 20.0),
+                Vector2::new(10.0, 10.0),
+                Vector2::new(0.0, 20.0),
+                Vector2::new(0.0, 0.0),
+            ]),
+            3.0,
+            1.0,
+        );
+
+        let segments = chamfer.offset_segments(2.0)?;
+        let points = segments.iter().map(endpoints).collect::<Vec<_>>();
+
+        // The outside corners are rounded around the corner and the path stays connected
+        assert!(
+            matches!(segments[1], Segment::Arc(ref arc) if arc.center == Vector2::new(20.0, 0.0))
+        );
+        for (from, to) in points.iter().zip(points.iter().cycle().skip(1)) {
+            assert!(from.1.distance_to(to.0) < 1e-9, "{from:?} {to:?}");
+        }
+
+        // The inside corner is trimmed to where the offset lines meet
+        let inside = Vector2::new(10.0, 10.0 + 2.0 * 2.0_f64.sqrt());
+        assert!(points
+            .iter()
+            .any(|(from, _)| from.distance_to(inside) < 1e-9));
+
+        Ok(())
+    }
+}
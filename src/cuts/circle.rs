@@ -1,5 +1,8 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
+use crate::cuts::step_z;
+use crate::error::CncError;
 use crate::instructions::*;
 use crate::program::*;
 use crate::types::*;
@@ -10,7 +13,7 @@ use crate::utils::*;
 /// If the circle radius equals the tool radius with `ToolPathCompensation::None` the cut will
 /// instead be a drilling top/down cut. Unlike [Area](struct.Area.html), the circle cut will
 /// only cut at the edge of the circle, and not cut inside the circle.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Circle {
     /// Start point in 3D space.
     pub start: Vector3,
@@ -25,6 +28,15 @@ pub struct Circle {
     /// `ToolPathCompensation::Outer` is useful for cutting out round pieces, and
     /// `ToolPathCompensation::None` is useful when drilling.
     pub compensation: ToolPathCompensation,
+    /// Spiral inward to the center at the end depth, leaving a flat bottom in blind holes. Not
+    /// supported with `ToolPathCompensation::Outer`, as the spiral would remove the round piece
+    /// that the cut leaves.
+    #[serde(default)]
+    pub clear_bottom: bool,
+    /// The maximum depth the helix may descend in one revolution, for tools that can not ramp
+    /// down steeply. The depth is not limited when not set.
+    #[serde(default)]
+    pub max_ramp_per_revolution: Option<f64>,
 }
 
 impl Circle {
@@ -43,9 +55,28 @@ impl Circle {
             radius,
             max_step_z,
             compensation,
+            clear_bottom: false,
+            max_ramp_per_revolution: None,
         }
     }
 
+    /// Returns the `Circle` with the option to clear the bottom changed, when enabled the tool
+    /// spirals inward to the center after reaching `end_z` so that the whole bottom is flat.
+    /// Cutting the circle returns error if it also has outer compensation.
+    #[must_use]
+    pub fn with_clear_bottom(mut self, clear_bottom: bool) -> Self {
+        self.clear_bottom = clear_bottom;
+        self
+    }
+
+    /// Returns the `Circle` with the maximum depth to descend in one revolution of the helix
+    /// changed, cutting the circle returns error if `max_step_z` is deeper than that.
+    #[must_use]
+    pub fn with_max_ramp_per_revolution(mut self, depth: f64) -> Self {
+        self.max_ramp_per_revolution = Some(depth);
+        self
+    }
+
     /// Drill cut from start coordinate to end z depth.
     #[must_use]
     pub fn drill(start: Vector3, end_z: f64) -> Self {
@@ -55,6 +86,42 @@ impl Circle {
             end_z,
             max_step_z: 0.0,
             compensation: ToolPathCompensation::None,
+            clear_bottom: false,
+            max_ramp_per_revolution: None,
+        }
+    }
+
+    /// Returns a new `Circle` with the transform applied to its coordinates and radius.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        Self {
+            start: transform.apply(self.start),
+            radius: transform.apply_length(self.radius),
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+            compensation: self.compensation.clone(),
+            clear_bottom: self.clear_bottom,
+            max_ramp_per_revolution: self
+                .max_ramp_per_revolution
+                .map(|depth| transform.apply_length(depth)),
+        }
+    }
+
+    /// Returns the point where the cut starts, at the left side of the circle or at the
+    /// center for drilling. The tool compensation is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.start.add_x(-self.radius)
+    }
+
+    /// Returns the point where the cut ends, at the end depth below the entry point, or at the
+    /// center when the bottom is cleared.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        if self.clear_bottom {
+            self.start.with_z(self.end_z)
+        } else {
+            self.entry_point().with_z(self.end_z)
         }
     }
 
@@ -75,16 +142,51 @@ impl Circle {
         }
     }
 
-    /// Converts the struct to G-code instructions.
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
-        let mut instructions = vec![];
+    /// Returns the bounds of the area swept by a tool of the given radius, the circle that the
+    /// tool center follows grown by the tool radius.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        Self {
+            radius: self.cut_radius(tool_radius).max(0.0) + tool_radius,
+            ..self.clone()
+        }
+        .bounds()
+    }
+
+    /// Returns the z depths of the revolutions around the circle for a tool of the given radius,
+    /// followed by the flat revolution at `end_z`. A circle that is drilled, when the tool is
+    /// as wide as the circle, has a single depth.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self, tool_radius: f64) -> Result<Vec<f64>> {
+        if (0.0..0.001).contains(&self.cut_radius(tool_radius)) {
+            return Ok(vec![self.end_z]);
+        }
 
-        let tool_radius = context.tool().radius();
-        let cut_radius = match self.compensation {
+        let mut depths = depth_layers(self.start.z, self.end_z, step_z(self.max_step_z)?);
+        depths.push(self.end_z);
+
+        Ok(depths)
+    }
+
+    // Returns the radius of the tool center path, with the tool compensation applied.
+    fn cut_radius(&self, tool_radius: f64) -> f64 {
+        match self.compensation {
             ToolPathCompensation::None => self.radius,
             ToolPathCompensation::Inner => self.radius - tool_radius,
             ToolPathCompensation::Outer => self.radius + tool_radius,
-        };
+        }
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        if self.clear_bottom && matches!(self.compensation, ToolPathCompensation::Outer) {
+            return Err(CncError::ClearBottomWithOuterCompensation);
+        }
+
+        let mut instructions = vec![];
+
+        let cut_radius = self.cut_radius(context.tool().radius());
 
         if (0.0..0.001).contains(&cut_radius) {
             instructions.append(&mut vec![
@@ -146,17 +248,25 @@ impl Circle {
                 }),
             ]);
 
-            let max_step_z = self.max_step_z.abs();
+            let max_step_z = step_z(self.max_step_z)?;
 
-            // TODO: add check that layer steps does not exceed cutting height if the bit
-            let layers = ((self.start.z - self.end_z) / max_step_z).floor() as u32;
+            if let Some(max_ramp) = self.max_ramp_per_revolution {
+                if max_step_z > max_ramp.abs() {
+                    return Err(CncError::RampTooSteep {
+                        center: Vector2::new(self.start.x, self.start.y),
+                        radius: self.radius,
+                        step_z: max_step_z,
+                        max_ramp: max_ramp.abs(),
+                    });
+                }
+            }
 
             // Cut spiraling down in steps
-            for index in 0..layers {
+            for z in depth_layers(self.start.z, self.end_z, max_step_z) {
                 instructions.push(Instruction::G2(G2 {
                     x: Some(self.start.x - cut_radius),
                     y: None,
-                    z: Some((self.start.z - index as f64 * max_step_z).max(self.end_z)),
+                    z: Some(z),
                     i: Some(cut_radius),
                     j: None,
                     k: None,
@@ -191,6 +301,12 @@ impl Circle {
                 f: None,
             }));
 
+            if self.clear_bottom {
+                instructions.append(
+                    &mut self.clear_bottom_instructions(cut_radius, context.tool().radius()),
+                );
+            }
+
             instructions.push(Instruction::G0(G0 {
                 x: None,
                 y: None,
@@ -200,16 +316,191 @@ impl Circle {
             let tool = context.tool();
             let units = context.units();
 
-            // TODO: handle calculation for the case when tool and program units are different.
-            return Err(anyhow!(
-                "Unable to cut circle of diameter {:.2} {} with tool diameter {:.2} {}.",
-                cut_radius.abs() * 2.0,
-                units,
-                tool.diameter(),
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "circle",
+                feature_size: self.radius * 2.0,
+                tool_diameter: tool.diameter(),
                 units,
-            ));
+            });
         }
 
         Ok(instructions)
     }
+
+    // Spirals inward from the edge to the center with half circles that alternate between the
+    // sides of the center, shrinking the radius by half the tool radius for each half turn.
+    fn clear_bottom_instructions(&self, cut_radius: f64, tool_radius: f64) -> Vec<Instruction> {
+        let mut instructions = vec![];
+
+        if tool_radius <= 0.0 {
+            return instructions;
+        }
+
+        let half_step = tool_radius / 2.0;
+        let mut from = -cut_radius;
+
+        while from.abs() > half_step {
+            let to = -from.signum() * (from.abs() - half_step);
+
+            instructions.push(Instruction::G2(G2 {
+                x: Some(self.start.x + to),
+                y: Some(self.start.y),
+                z: None,
+                i: Some((to - from) / 2.0),
+                j: Some(0.0),
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }));
+
+            from = to;
+        }
+
+        instructions.push(Instruction::G1(G1 {
+            x: Some(self.start.x),
+            y: Some(self.start.y),
+            z: None,
+            f: None,
+        }));
+
+        instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    fn context() -> InnerContext {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+
+        InnerContext::new(Units::Metric, &tool, 5.0, 50.0)
+    }
+
+    fn arcs(instructions: &[Instruction]) -> Vec<&G2> {
+        instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G2(arc) => Some(arc),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_circle_layers() -> Result<()> {
+        let circle = Circle::new(
+            Vector3::new(10.0, 20.0, 0.0),
+            5.0,
+            -3.05,
+            1.0,
+            ToolPathCompensation::None,
+        );
+        let instructions = circle.to_instructions(&context())?;
+
+        // Each revolution descends one step, the last two are flat at the end depth
+        let depths = arcs(&instructions)
+            .iter()
+            .map(|arc| arc.z.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(depths, vec![-1.0, -2.0, -3.0, -3.05, -3.05, -3.05]);
+        assert_eq!(circle.layers(2.0)?, depths[..5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_circle_max_ramp_per_revolution() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let circle = Circle::new(
+            Vector3::new(10.0, 20.0, 0.0),
+            5.0,
+            -6.0,
+            3.0,
+            ToolPathCompensation::None,
+        );
+
+        assert!(circle.to_instructions(&context).is_ok());
+        assert!(circle
+            .clone()
+            .with_max_ramp_per_revolution(3.0)
+            .to_instructions(&context)
+            .is_ok());
+
+        let error = circle
+            .with_max_ramp_per_revolution(1.0)
+            .to_instructions(&context)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::RampTooSteep {
+                center,
+                step_z,
+                max_ramp,
+                ..
+            } if center == Vector2::new(10.0, 20.0) && step_z == 3.0 && max_ramp == 1.0
+        ));
+        assert!(error.to_string().contains("circle at x = 10, y = 20"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_circle_clear_bottom() -> Result<()> {
+        let circle = Circle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            10.0,
+            -2.0,
+            1.0,
+            ToolPathCompensation::Inner,
+        );
+        assert_eq!(arcs(&circle.to_instructions(&context())?).len(), 4);
+
+        // The 8 mm tool path radius shrinks by 1 mm for each half circle
+        let circle = circle.with_clear_bottom(true);
+        let instructions = circle.to_instructions(&context())?;
+        let arcs = arcs(&instructions);
+        assert_eq!(arcs.len(), 4 + 7);
+        assert_eq!(arcs[4].x, Some(7.0));
+        assert_eq!(arcs[10].x, Some(1.0));
+
+        assert!(matches!(
+            instructions[instructions.len() - 2],
+            Instruction::G1(G1 {
+                x: Some(x),
+                y: Some(y),
+                z: None,
+                ..
+            }) if x == 0.0 && y == 0.0
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_circle_clear_bottom_outer_compensation() {
+        // Spiraling in from outside of the circle would remove the round piece itself
+        let circle = Circle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            10.0,
+            -2.0,
+            1.0,
+            ToolPathCompensation::Outer,
+        );
+        assert!(circle.to_instructions(&context()).is_ok());
+
+        let circle = circle.with_clear_bottom(true);
+        let error = circle.to_instructions(&context()).unwrap_err();
+        assert!(matches!(error, CncError::ClearBottomWithOuterCompensation));
+        assert!(error.to_string().contains("outer tool compensation"));
+    }
 }
@@ -0,0 +1,311 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::cuts::step_z;
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// Clear a round pocket by spiraling outward from the center, followed by a finishing pass
+/// along the edge of the pocket.
+///
+/// Unlike [Circle](struct.Circle.html), which only cuts at the edge of the circle, the
+/// circular pocket removes all material inside the circle. The tool path is always
+/// compensated so that the pocket gets the given radius.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CircularPocket {
+    /// Center point in 3D space, the z value is the start depth of the cut.
+    pub center: Vector3,
+    /// The radius of the pocket.
+    pub radius: f64,
+    /// The end depth of the cut on the z axis.
+    pub end_z: f64,
+    /// The maximum depth to cut on the z axis on each pass.
+    pub max_step_z: f64,
+    /// The distance the spiral grows outward for each turn, limited to the tool diameter.
+    pub step_over: f64,
+}
+
+impl CircularPocket {
+    /// Creates a new `CircularPocket` struct.
+    #[must_use]
+    pub fn new(center: Vector3, radius: f64, end_z: f64, max_step_z: f64, step_over: f64) -> Self {
+        Self {
+            center,
+            radius,
+            end_z,
+            max_step_z,
+            step_over,
+        }
+    }
+
+    /// Returns a new `CircularPocket` with the transform applied to its coordinates and radius.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        Self {
+            center: transform.apply(self.center),
+            radius: transform.apply_length(self.radius),
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+            step_over: self.step_over,
+        }
+    }
+
+    /// Returns the point where the cut starts, at the center of the pocket.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.center
+    }
+
+    /// Returns the point where the cut ends. The finishing pass ends at the edge of the pocket
+    /// on the side given by the tool, so the center on the end depth is returned.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.center.with_z(self.end_z)
+    }
+
+    /// Returns the bounds of the cut.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        Bounds {
+            min: Vector3::new(
+                self.center.x - self.radius,
+                self.center.y - self.radius,
+                self.end_z,
+            ),
+            max: Vector3::new(
+                self.center.x + self.radius,
+                self.center.y + self.radius,
+                self.center.z,
+            ),
+        }
+    }
+
+    /// Returns the bounds of the area swept by a tool of the given radius, the whole pocket,
+    /// or the tool itself when it is wider than the pocket.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        Self {
+            radius: self.radius.max(tool_radius),
+            ..self.clone()
+        }
+        .bounds()
+    }
+
+    /// Returns the z depths of the layers that the pocket is cleared at.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self) -> Result<Vec<f64>> {
+        Ok(depth_layers(
+            self.center.z,
+            self.end_z,
+            step_z(self.max_step_z)?,
+        ))
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let tool = context.tool();
+        let units = context.units();
+        let cut_radius = self.radius - tool.radius();
+
+        if cut_radius < 0.0 {
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "circular pocket",
+                feature_size: self.radius * 2.0,
+                tool_diameter: tool.diameter(),
+                units,
+            });
+        }
+
+        if self.step_over <= 0.0 {
+            return Err(CncError::InvalidStepOver {
+                feature: "circular pocket",
+                step_over: self.step_over,
+            });
+        }
+
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Cut circular pocket at: x = {}, y = {}, radius = {}",
+                    round_precision(self.center.x),
+                    round_precision(self.center.y),
+                    round_precision(self.radius)
+                ),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(self.center.x),
+                y: Some(self.center.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(self.center.z),
+                f: Some(tool.feed_rate()),
+            }),
+        ];
+
+        for z in self.layers()? {
+            instructions.append(&mut self.generate_layer_instructions(
+                z,
+                cut_radius,
+                self.step_over.min(tool.diameter()),
+            ));
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
+        Ok(instructions)
+    }
+
+    fn generate_layer_instructions(
+        &self,
+        z: f64,
+        cut_radius: f64,
+        step_over: f64,
+    ) -> Vec<Instruction> {
+        let mut instructions = vec![
+            Instruction::G1(G1 {
+                x: Some(self.center.x),
+                y: Some(self.center.y),
+                z: None,
+                f: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(z),
+                f: None,
+            }),
+        ];
+
+        if cut_radius < 0.001 {
+            return instructions;
+        }
+
+        // Spiral outward with half circles that alternate between the sides of the center,
+        // growing the radius by half the step over for each half turn
+        let half_step = step_over / 2.0;
+        let mut from: f64 = 0.0;
+        let mut index = 1.0;
+
+        while from.abs() < cut_radius {
+            let side = if from > 0.0 { -1.0 } else { 1.0 };
+            let to = side * (index * half_step).min(cut_radius);
+
+            instructions.push(Instruction::G2(G2 {
+                x: Some(self.center.x + to),
+                y: Some(self.center.y),
+                z: None,
+                i: Some((to - from) / 2.0),
+                j: Some(0.0),
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }));
+
+            from = to;
+            index += 1.0;
+        }
+
+        // Finishing pass along the edge of the pocket
+        instructions.push(Instruction::G2(G2 {
+            x: Some(self.center.x + from),
+            y: Some(self.center.y),
+            z: None,
+            i: Some(-from),
+            j: Some(0.0),
+            k: None,
+            r: None,
+            p: None,
+            f: None,
+        }));
+
+        instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    fn context() -> InnerContext {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+
+        InnerContext::new(Units::Metric, &tool, 5.0, 50.0)
+    }
+
+    #[test]
+    fn test_circular_pocket() -> Result<()> {
+        let pocket = CircularPocket::new(Vector3::new(20.0, 30.0, 0.0), 10.0, -2.0, 1.0, 2.0);
+        let instructions = pocket.to_instructions(&context())?;
+
+        let arcs = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G2(arc) => Some(arc),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Eight half circles reach the 8 mm tool path radius, followed by the finishing
+        // pass, for each of the two layers
+        assert_eq!(arcs.len(), 18);
+
+        let mut from = 20.0;
+        for arc in &arcs[..9] {
+            let to = arc.x.unwrap();
+            let center = from + arc.i.unwrap();
+            assert!(((center - from).abs() - (to - center).abs()).abs() < 1e-9);
+            assert!((to - 20.0).abs() <= 8.0 + 1e-9);
+            from = to;
+        }
+
+        assert_eq!(arcs[8].x, Some(12.0));
+        assert_eq!(arcs[8].i, Some(8.0));
+
+        let bounds = pocket.bounds();
+        assert_eq!(bounds.min, Vector3::new(10.0, 20.0, -2.0));
+        assert_eq!(bounds.max, Vector3::new(30.0, 40.0, 0.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_circular_pocket_too_small_for_tool() {
+        let pocket = CircularPocket::new(Vector3::new(0.0, 0.0, 0.0), 1.5, -1.0, 1.0, 1.0);
+        let error = pocket.to_instructions(&context()).unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::ToolWiderThanFeature {
+                feature: "circular pocket",
+                feature_size,
+                tool_diameter,
+                units: Units::Metric,
+            } if feature_size == 3.0 && tool_diameter == 4.0
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+
+/// Drill holes at several points to the same depth.
+///
+/// Each hole is drilled like [Circle::drill](struct.Circle.html#method.drill), but the holes
+/// share the retract between them and are drilled in nearest neighbor order, starting with
+/// the first point, to reduce the travel.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrillPoints {
+    /// The points to drill at in 3D space, the z values are the start depths of the holes.
+    pub points: Vec<Vector3>,
+    /// The end depth of the holes on the z axis.
+    pub end_z: f64,
+}
+
+impl DrillPoints {
+    /// Creates a new `DrillPoints` struct.
+    #[must_use]
+    pub fn new(points: Vec<Vector3>, end_z: f64) -> Self {
+        Self { points, end_z }
+    }
+
+    /// Returns a new `DrillPoints` with the transform applied to its coordinates.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        Self {
+            points: self
+                .points
+                .iter()
+                .map(|point| transform.apply(*point))
+                .collect(),
+            end_z: transform.apply_z(self.end_z),
+        }
+    }
+
+    /// Returns the point where the cut starts, at the first point.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.points.first().copied().unwrap_or_default()
+    }
+
+    /// Returns the point where the cut ends, at the bottom of the last hole drilled.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.ordered_points()
+            .last()
+            .map_or_else(Vector3::default, |point| point.with_z(self.end_z))
+    }
+
+    /// Returns the bounds of the cut.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::minmax();
+
+        for point in &self.points {
+            bounds.min = Vector3::new(
+                bounds.min.x.min(point.x),
+                bounds.min.y.min(point.y),
+                bounds.min.z.min(self.end_z),
+            );
+            bounds.max = Vector3::new(
+                bounds.max.x.max(point.x),
+                bounds.max.y.max(point.y),
+                bounds.max.z.max(point.z),
+            );
+        }
+
+        bounds
+    }
+
+    /// Returns the points in the order they are drilled.
+    #[must_use]
+    pub fn ordered_points(&self) -> Vec<Vector3> {
+        let mut remaining = self.points.clone();
+        let mut ordered = Vec::with_capacity(remaining.len());
+
+        if remaining.is_empty() {
+            return ordered;
+        }
+
+        let mut position = remaining.remove(0);
+        ordered.push(position);
+
+        while !remaining.is_empty() {
+            let (index, _) = remaining
+                .iter()
+                .enumerate()
+                .map(|(index, point)| (index, point.xy().distance_to(position.xy())))
+                .fold((0, f64::MAX), |nearest, candidate| {
+                    if candidate.1 < nearest.1 {
+                        candidate
+                    } else {
+                        nearest
+                    }
+                });
+
+            position = remaining.remove(index);
+            ordered.push(position);
+        }
+
+        ordered
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let mut instructions = vec![];
+
+        if self.points.is_empty() {
+            return Ok(instructions);
+        }
+
+        instructions.append(&mut vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!("Drill {} holes", self.points.len()),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+        ]);
+
+        for (index, point) in self.ordered_points().into_iter().enumerate() {
+            instructions.append(&mut vec![
+                Instruction::G0(G0 {
+                    x: Some(point.x),
+                    y: Some(point.y),
+                    z: None,
+                }),
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(self.end_z),
+                    f: if index == 0 {
+                        Some(context.tool().feed_rate())
+                    } else {
+                        None
+                    },
+                }),
+                Instruction::G0(G0 {
+                    x: None,
+                    y: None,
+                    z: Some(context.z_safe()),
+                }),
+            ]);
+        }
+
+        Ok(instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    #[test]
+    fn test_drill_points() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let drills = DrillPoints::new(
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(20.0, 0.0, 0.0),
+                Vector3::new(5.0, 5.0, 1.0),
+            ],
+            -3.0,
+        );
+
+        assert_eq!(
+            drills.ordered_points(),
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(5.0, 5.0, 1.0),
+                Vector3::new(20.0, 0.0, 0.0),
+            ]
+        );
+
+        let instructions = drills.to_instructions(&context)?;
+        let retracts = instructions
+            .iter()
+            .filter(|instruction| {
+                matches!(
+                    instruction,
+                    Instruction::G0(G0 {
+                        z: Some(z),
+                        ..
+                    }) if *z == 5.0
+                )
+            })
+            .count();
+        // One retract before the first hole and one after each hole
+        assert_eq!(retracts, 4);
+        assert_eq!(instructions.len(), 3 + 3 * 3);
+        assert_eq!(
+            instructions[6],
+            Instruction::G0(G0 {
+                x: Some(5.0),
+                y: Some(5.0),
+                z: None,
+            })
+        );
+
+        let bounds = drills.bounds();
+        assert_eq!(bounds.min, Vector3::new(0.0, 0.0, -3.0));
+        assert_eq!(bounds.max, Vector3::new(20.0, 5.0, 1.0));
+
+        Ok(())
+    }
+}
@@ -1,5 +1,10 @@
+use std::time::Duration;
+
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 
+use crate::cuts::{chip_clear_instructions, is_chip_clear_layer, step_z, transform_rectangle};
+use crate::error::CncError;
 use crate::instructions::*;
 use crate::program::*;
 use crate::types::*;
@@ -7,7 +12,7 @@ use crate::utils::*;
 
 /// Cut a frame around an area. Unlike [Area](struct.Area.html), the frame cut will only cut at the edge
 /// of the area, and not cut inside.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Frame {
     /// Start point in 3D space.
     pub start: Vector3,
@@ -22,6 +27,22 @@ pub struct Frame {
     /// `ToolPathCompensation::Outer` is useful for cutting out rectangle
     /// pieces.
     pub compensation: ToolPathCompensation,
+    /// Radius of the rounded corners, the corners are sharp when set to 0.0. The radius is
+    /// compensated together with the walls, so inner compensation shrinks the radius by the
+    /// tool radius, and outer compensation grows it by the tool radius.
+    #[serde(default)]
+    pub corner_radius: f64,
+    /// Fully retract to `z_safe` after every given number of layers to let the chips clear,
+    /// instead of only lifting the tool slightly between the layers. Off by default.
+    #[serde(default)]
+    pub chip_clear_every: Option<u32>,
+    /// The time to wait at `z_safe` for the chips to clear, see `chip_clear_every`.
+    #[serde(default)]
+    pub chip_clear_dwell: Option<Duration>,
+    /// Number of extra loops around the frame at `end_z` after the final pass, to remove the
+    /// material left by the tool deflecting. No extra loops by default.
+    #[serde(default)]
+    pub spring_passes: u32,
 }
 
 impl Frame {
@@ -40,9 +61,87 @@ impl Frame {
             end_z,
             max_step_z,
             compensation,
+            corner_radius: 0.0,
+            chip_clear_every: None,
+            chip_clear_dwell: None,
+            spring_passes: 0,
         }
     }
 
+    /// Creates a new `Frame` struct with rounded corners of the given radius.
+    #[must_use]
+    pub fn new_rounded(
+        start: Vector3,
+        size: Vector2,
+        corner_radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+        compensation: ToolPathCompensation,
+    ) -> Self {
+        Self {
+            corner_radius,
+            ..Self::new(start, size, end_z, max_step_z, compensation)
+        }
+    }
+
+    /// Returns the `Frame` changed to fully retract to `z_safe` after every `every` layers, and
+    /// wait for the dwell if any, to let the chips clear before plunging back down.
+    #[must_use]
+    pub fn with_chip_clearing(mut self, every: u32, dwell: Option<Duration>) -> Self {
+        self.chip_clear_every = Some(every);
+        self.chip_clear_dwell = dwell;
+        self
+    }
+
+    /// Returns the `Frame` changed to repeat the final loop at `end_z` the given number of
+    /// extra times.
+    #[must_use]
+    pub fn with_spring_passes(mut self, spring_passes: u32) -> Self {
+        self.spring_passes = spring_passes;
+        self
+    }
+
+    /// Returns a new `Frame` with the transform applied to its coordinates and size.
+    ///
+    /// Returns error for rotations that are not a multiple of 90° as the frame is always
+    /// aligned with the x and y axis.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        if !transform.is_axis_aligned() {
+            return Err(anyhow!(
+                "Unable to transform frame, only rotations in multiples of 90° are supported"
+            ));
+        }
+
+        let (start, size) = transform_rectangle(self.start, self.size, transform);
+
+        Ok(Self {
+            start,
+            size,
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+            compensation: self.compensation.clone(),
+            corner_radius: transform.apply_length(self.corner_radius),
+            chip_clear_every: self.chip_clear_every,
+            chip_clear_dwell: self.chip_clear_dwell,
+            spring_passes: self.spring_passes,
+        })
+    }
+
+    /// Returns the point where the cut starts, at the beginning of the lower edge. The tool
+    /// compensation is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.start.add_x(self.corner_radius)
+    }
+
+    /// Returns the point where the cut ends, at the end of the lower edge on the end depth.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.start
+            .add_x(self.size.x - self.corner_radius)
+            .with_z(self.end_z)
+    }
+
     /// Returns the bounds of the cut.
     #[must_use]
     pub fn bounds(&self) -> Bounds {
@@ -56,20 +155,59 @@ impl Frame {
         }
     }
 
+    /// Returns the bounds of the area swept by a tool of the given radius, the rectangle that
+    /// the tool center follows grown by the tool radius.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        let bounds = self.bounds();
+        let offset = match self.compensation {
+            ToolPathCompensation::None => tool_radius,
+            ToolPathCompensation::Inner => return bounds,
+            ToolPathCompensation::Outer => tool_radius * 2.0,
+        };
+
+        Bounds {
+            min: bounds.min.add_x(-offset).add_y(-offset),
+            max: bounds.max.add_x(offset).add_y(offset),
+        }
+    }
+
+    /// Returns the z depths of the passes around the frame, each pass ramps down to its depth,
+    /// followed by a flat pass and the spring passes at `end_z`.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self) -> Result<Vec<f64>> {
+        let mut depths = depth_layers(self.start.z, self.end_z, step_z(self.max_step_z)?);
+        depths.extend(std::iter::repeat_n(
+            self.end_z,
+            self.spring_passes as usize + 1,
+        ));
+
+        Ok(depths)
+    }
+
     /// Converts the struct to G-code instructions.
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
         let tool_radius = context.tool().radius();
         let tool_diameter = context.tool().diameter();
         let tool_units = context.tool().units();
 
         if self.size.x < tool_diameter {
-            // TODO: handle calculation for the case when tool and program units are different.
-            return Err(anyhow!("Unable to cut frame, tool is {:.2} {} to wider than x dimension (tool diameter is {:.2} {})", tool_diameter - self.size.x, tool_units, tool_diameter, tool_units));
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "frame",
+                feature_size: self.size.x,
+                tool_diameter,
+                units: tool_units,
+            });
         }
 
         if self.size.y < tool_diameter {
-            // TODO: handle calculation for the case when tool and program units are different.
-            return Err(anyhow!("Unable to cut frame, tool is {:.2} {} to wider than y dimension (tool diameter is {:.2} {})", tool_diameter - self.size.y, tool_units, tool_diameter, tool_units));
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "frame",
+                feature_size: self.size.y,
+                tool_diameter,
+                units: tool_units,
+            });
         }
 
         let start = match self.compensation {
@@ -89,17 +227,42 @@ impl Frame {
             }
         };
 
+        let corner_radius = match self.compensation {
+            ToolPathCompensation::None => self.corner_radius,
+            ToolPathCompensation::Inner => self.corner_radius - tool_radius,
+            ToolPathCompensation::Outer => self.corner_radius + tool_radius,
+        };
+
+        if self.corner_radius > 0.0 && corner_radius < 0.0 {
+            return Err(anyhow!("Unable to cut frame, corner radius {:.2} {} is smaller than the tool radius {:.2} {}", self.corner_radius, tool_units, tool_radius, tool_units).into());
+        }
+
+        if corner_radius > size.x.min(size.y) / 2.0 {
+            return Err(anyhow!("Unable to cut frame, corner radius {:.2} {} is larger than half the frame width or height", self.corner_radius, tool_units).into());
+        }
+
+        let corner_radius = corner_radius.max(0.0);
         let mut instructions = Vec::new();
 
         instructions.append(&mut vec![
             Instruction::Empty(Empty {}),
             Instruction::Comment(Comment {
-                text: format!(
-                    "Cut frame: x = {}, y = {}, size = {}",
-                    round_precision(start.x),
-                    round_precision(start.y),
-                    size
-                ),
+                text: if corner_radius > 0.0 {
+                    format!(
+                        "Cut frame: x = {}, y = {}, size = {}, corner_radius = {}",
+                        round_precision(start.x),
+                        round_precision(start.y),
+                        size,
+                        round_precision(corner_radius)
+                    )
+                } else {
+                    format!(
+                        "Cut frame: x = {}, y = {}, size = {}",
+                        round_precision(start.x),
+                        round_precision(start.y),
+                        size
+                    )
+                },
             }),
             Instruction::G0(G0 {
                 x: None,
@@ -107,7 +270,7 @@ impl Frame {
                 z: Some(context.z_safe()),
             }),
             Instruction::G0(G0 {
-                x: Some(start.x),
+                x: Some(start.x + corner_radius),
                 y: Some(start.y),
                 z: None,
             }),
@@ -119,22 +282,44 @@ impl Frame {
             }),
         ]);
 
-        let max_step_z = self.max_step_z.abs();
+        let depths = depth_layers(start.z, self.end_z, step_z(self.max_step_z)?);
+        let layers = depths.len() as u32;
         let mut start_z = start.z;
-        let mut end_z = start_z;
-        let layers = ((start_z - self.end_z).abs() / max_step_z).floor() as u32;
 
-        for _layer in 1..=layers {
-            end_z -= max_step_z;
-            instructions.append(&mut self.generate_layer_instructions(start, size, start_z, end_z));
+        let generate_layer_instructions = |start_z, end_z| {
+            if corner_radius > 0.0 {
+                self.generate_rounded_layer_instructions(start, size, corner_radius, start_z, end_z)
+            } else {
+                self.generate_layer_instructions(start, size, start_z, end_z)
+            }
+        };
+
+        for (layer, end_z) in (1..).zip(depths) {
+            instructions.append(&mut generate_layer_instructions(start_z, end_z));
             start_z = end_z;
+
+            // The last layer is the final pass at the end depth after the loop
+            if is_chip_clear_layer(self.chip_clear_every, layer, layers + 1) {
+                instructions.append(&mut chip_clear_instructions(
+                    context,
+                    end_z,
+                    self.chip_clear_dwell,
+                ));
+            }
         }
 
-        instructions
-            .append(&mut self.generate_layer_instructions(start, size, self.end_z, self.end_z));
+        instructions.append(&mut generate_layer_instructions(self.end_z, self.end_z));
+
+        // The comment marks each spring pass, and keeps the repeated loops apart in the output
+        for pass in 1..=self.spring_passes {
+            instructions.push(Instruction::Comment(Comment {
+                text: format!("Spring pass {}", pass),
+            }));
+            instructions.append(&mut generate_layer_instructions(self.end_z, self.end_z));
+        }
 
         instructions.push(Instruction::G1(G1 {
-            x: Some(start.x + size.x),
+            x: Some(start.x + size.x - corner_radius),
             y: None,
             z: None,
             f: None,
@@ -147,7 +332,7 @@ impl Frame {
         }));
 
         instructions.push(Instruction::G0(G0 {
-            x: Some(start.x),
+            x: Some(start.x + corner_radius),
             y: Some(start.y),
             z: None,
         }));
@@ -201,4 +386,300 @@ impl Frame {
 
         instructions
     }
+
+    // Generates one counterclockwise layer starting at the beginning of the lower edge, with
+    // the corners cut as quarter circle arcs and z descending evenly over the full length.
+    fn generate_rounded_layer_instructions(
+        &self,
+        start: Vector3,
+        size: Vector2,
+        corner_radius: f64,
+        start_z: f64,
+        end_z: f64,
+    ) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+
+        let edge_x = size.x - corner_radius * 2.0;
+        let edge_y = size.y - corner_radius * 2.0;
+        let corner = corner_radius * std::f64::consts::FRAC_PI_2;
+        let length = (edge_x + edge_y + corner * 2.0) * 2.0;
+        let delta_z = end_z - start_z;
+        let x_step_z = (edge_x / length) * delta_z;
+        let y_step_z = (edge_y / length) * delta_z;
+        let corner_step_z = (corner / length) * delta_z;
+
+        let min_x = start.x;
+        let min_y = start.y;
+        let max_x = start.x + size.x;
+        let max_y = start.y + size.y;
+        let r = corner_radius;
+
+        // Each side as the edge end point, followed by the corner arc end point and its
+        // center offset relative to the edge end point.
+        let sides = [
+            (
+                Vector2::new(max_x - r, min_y),
+                Vector2::new(max_x, min_y + r),
+                Vector2::new(0.0, r),
+            ),
+            (
+                Vector2::new(max_x, max_y - r),
+                Vector2::new(max_x - r, max_y),
+                Vector2::new(-r, 0.0),
+            ),
+            (
+                Vector2::new(min_x + r, max_y),
+                Vector2::new(min_x, max_y - r),
+                Vector2::new(0.0, -r),
+            ),
+            (
+                Vector2::new(min_x, min_y + r),
+                Vector2::new(min_x + r, min_y),
+                Vector2::new(r, 0.0),
+            ),
+        ];
+
+        let mut z = start_z;
+        for (index, (edge_end, arc_end, center_offset)) in sides.into_iter().enumerate() {
+            z += if index % 2 == 0 { x_step_z } else { y_step_z };
+            instructions.push(Instruction::G1(G1 {
+                x: Some(edge_end.x),
+                y: Some(edge_end.y),
+                z: Some(z),
+                f: None,
+            }));
+
+            z += corner_step_z;
+            instructions.push(Instruction::G3(G3 {
+                x: Some(arc_end.x),
+                y: Some(arc_end.y),
+                z: Some(if index == 3 { end_z } else { z }),
+                i: Some(center_offset.x),
+                j: Some(center_offset.y),
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }));
+        }
+
+        instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cuts::Cut;
+    use crate::tools::Tool;
+
+    use super::*;
+
+    fn arcs(instructions: &[Instruction]) -> Vec<G3> {
+        instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G3(g3) => Some(g3.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rounded_frame() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            1_000.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 10.0, 50.0);
+
+        let frame = Frame::new_rounded(
+            Vector3::new(10.0, 20.0, 0.0),
+            Vector2::new(50.0, 30.0),
+            5.0,
+            -1.0,
+            1.0,
+            ToolPathCompensation::None,
+        );
+
+        assert_eq!(
+            frame.bounds(),
+            Bounds {
+                min: Vector3::new(10.0, 20.0, -1.0),
+                max: Vector3::new(60.0, 50.0, 0.0),
+            }
+        );
+
+        let instructions = frame.to_instructions(&context)?;
+        let arcs = arcs(&instructions);
+
+        // Two layers of four corners
+        assert_eq!(arcs.len(), 8);
+
+        let corners = arcs[4..]
+            .iter()
+            .map(|arc| {
+                (
+                    arc.x.unwrap(),
+                    arc.y.unwrap(),
+                    arc.i.unwrap(),
+                    arc.j.unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            corners,
+            vec![
+                (60.0, 25.0, 0.0, 5.0),
+                (55.0, 50.0, -5.0, 0.0),
+                (10.0, 45.0, 0.0, -5.0),
+                (15.0, 20.0, 5.0, 0.0),
+            ]
+        );
+
+        // The walls start and end tangent to the corner arcs
+        assert!(instructions.contains(&Instruction::G1(G1 {
+            x: Some(55.0),
+            y: Some(20.0),
+            z: Some(-1.0),
+            f: None,
+        })));
+        assert!(instructions.contains(&Instruction::G1(G1 {
+            x: Some(10.0),
+            y: Some(25.0),
+            z: Some(-1.0),
+            f: None,
+        })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rounded_frame_compensation() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            1_000.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 10.0, 50.0);
+
+        let inner = Frame::new_rounded(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(50.0, 30.0),
+            5.0,
+            -1.0,
+            1.0,
+            ToolPathCompensation::Inner,
+        );
+        let arc = arcs(&inner.to_instructions(&context)?)[0].clone();
+        assert_eq!((arc.x, arc.y, arc.j), (Some(48.0), Some(5.0), Some(3.0)));
+
+        let outer = Frame::new_rounded(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(50.0, 30.0),
+            5.0,
+            -1.0,
+            1.0,
+            ToolPathCompensation::Outer,
+        );
+        let arc = arcs(&outer.to_instructions(&context)?)[0].clone();
+        assert_eq!((arc.x, arc.y, arc.j), (Some(52.0), Some(5.0), Some(7.0)));
+
+        let too_small = Frame::new_rounded(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(50.0, 30.0),
+            1.0,
+            -1.0,
+            1.0,
+            ToolPathCompensation::Inner,
+        );
+        assert!(too_small.to_instructions(&context).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_chip_clearing() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 10.0, 50.0);
+        let frame = Frame::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(50.0, 30.0),
+            -6.0,
+            1.0,
+            ToolPathCompensation::Outer,
+        )
+        .with_chip_clearing(2, None);
+
+        let retract_depths = frame
+            .to_instructions(&context)?
+            .windows(3)
+            .filter_map(|window| match window {
+                [Instruction::G0(G0 {
+                    z: Some(z_safe), ..
+                }), Instruction::G0(_), Instruction::G1(G1 { z, .. })]
+                    if *z_safe == 10.0 =>
+                {
+                    *z
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // The first match is the approach, then the six layers down to -6 are cleared after
+        // every second layer, before the final pass at the end depth
+        assert_eq!(retract_depths, vec![0.0, -2.0, -4.0, -6.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_spring_passes() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            1_000.0,
+        );
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let mut context = program.context(tool);
+        context.append_cut(Cut::frame_outer_spring(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(50.0, 30.0),
+            -2.0,
+            1.0,
+            2,
+        ));
+
+        // Each loop at the end depth passes along the right edge once
+        let gcode = program.to_gcode()?;
+        let loops = gcode
+            .lines()
+            .filter(|line| *line == "G1 X52 Y30 Z-2")
+            .count();
+        assert_eq!(loops, 3);
+        assert!(gcode.contains(";(Spring pass 2)"));
+
+        let frame = Frame::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(50.0, 30.0),
+            -2.0,
+            1.0,
+            ToolPathCompensation::Outer,
+        );
+        assert_eq!(frame.layers()?, vec![-1.0, -2.0, -2.0]);
+        assert_eq!(
+            frame.with_spring_passes(2).layers()?,
+            vec![-1.0, -2.0, -2.0, -2.0, -2.0]
+        );
+
+        Ok(())
+    }
 }
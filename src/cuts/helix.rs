@@ -0,0 +1,265 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// Cut a helix or spiral around a center point, descending from `start_z` to `end_z` while the
+/// radius changes linearly from `start_radius` to `end_radius`.
+///
+/// Each revolution is cut as two half circle arcs that alternate between the sides of the
+/// center, so the radius can change between the arcs. The cut starts on the negative x side
+/// of the center and no tool compensation is applied.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Helix {
+    /// Center point in 2D space.
+    pub center: Vector2,
+    /// The radius at the start of the cut.
+    pub start_radius: f64,
+    /// The radius at the end of the cut.
+    pub end_radius: f64,
+    /// The start depth of the cut on the z axis.
+    pub start_z: f64,
+    /// The end depth of the cut on the z axis.
+    pub end_z: f64,
+    /// The number of revolutions to cut.
+    pub revolutions: u32,
+    /// The direction to cut the arcs with.
+    pub direction: Direction,
+}
+
+impl Helix {
+    /// Creates a new `Helix` struct.
+    #[must_use]
+    pub fn new(
+        center: Vector2,
+        start_radius: f64,
+        end_radius: f64,
+        start_z: f64,
+        end_z: f64,
+        revolutions: u32,
+        direction: Direction,
+    ) -> Self {
+        Self {
+            center,
+            start_radius,
+            end_radius,
+            start_z,
+            end_z,
+            revolutions,
+            direction,
+        }
+    }
+
+    /// Returns a new `Helix` with the transform applied to its coordinates and radii.
+    /// Mirroring flips the direction.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let direction = self.direction.transformed(transform, &Axis::Z);
+
+        Self {
+            center: transform
+                .apply(Vector3::new(self.center.x, self.center.y, self.start_z))
+                .xy(),
+            start_radius: transform.apply_length(self.start_radius),
+            end_radius: transform.apply_length(self.end_radius),
+            start_z: transform.apply_z(self.start_z),
+            end_z: transform.apply_z(self.end_z),
+            revolutions: self.revolutions,
+            direction,
+        }
+    }
+
+    /// Returns the bounds of the cut.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        let radius = self.start_radius.max(self.end_radius);
+
+        Bounds {
+            min: Vector3::new(
+                self.center.x - radius,
+                self.center.y - radius,
+                self.start_z.min(self.end_z),
+            ),
+            max: Vector3::new(
+                self.center.x + radius,
+                self.center.y + radius,
+                self.start_z.max(self.end_z),
+            ),
+        }
+    }
+
+    /// Returns the point where the cut starts, on the negative x side of the center.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.point_at(0)
+    }
+
+    /// Returns the point where the cut ends, on the side of the center given by the number of
+    /// half turns.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.point_at(self.revolutions * 2)
+    }
+
+    // Returns the point at the given half turn, on alternating sides of the center, with the
+    // radius and depth interpolated over all half turns.
+    fn point_at(&self, half_turn: u32) -> Vector3 {
+        let fraction = f64::from(half_turn) / f64::from((self.revolutions * 2).max(1));
+        let radius = self.start_radius + (self.end_radius - self.start_radius) * fraction;
+        let side = if half_turn.is_multiple_of(2) {
+            -1.0
+        } else {
+            1.0
+        };
+
+        Vector3::new(
+            self.center.x + side * radius,
+            self.center.y,
+            self.start_z + (self.end_z - self.start_z) * fraction,
+        )
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        if self.revolutions == 0 {
+            return Err(anyhow!("Unable to cut helix, it must have at least 1 revolution").into());
+        }
+
+        if self.start_radius < 0.0 || self.end_radius < 0.0 {
+            return Err(anyhow!(
+                "Unable to cut helix, the radii must not be negative, got {} and {}",
+                self.start_radius,
+                self.end_radius
+            )
+            .into());
+        }
+
+        let start = self.point_at(0);
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Cut helix at: x = {}, y = {}, revolutions = {}",
+                    round_precision(self.center.x),
+                    round_precision(self.center.y),
+                    self.revolutions
+                ),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(start.x),
+                y: Some(start.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(start.z),
+                f: Some(context.tool().feed_rate()),
+            }),
+        ];
+
+        let mut from = start;
+        for half_turn in 1..=self.revolutions * 2 {
+            let to = self.point_at(half_turn);
+            let i = Some((to.x - from.x) / 2.0);
+            let j = Some(0.0);
+
+            instructions.push(match self.direction {
+                Direction::Clockwise => Instruction::G2(G2 {
+                    x: Some(to.x),
+                    y: Some(to.y),
+                    z: Some(to.z),
+                    i,
+                    j,
+                    k: None,
+                    r: None,
+                    p: None,
+                    f: None,
+                }),
+                Direction::Counterclockwise => Instruction::G3(G3 {
+                    x: Some(to.x),
+                    y: Some(to.y),
+                    z: Some(to.z),
+                    i,
+                    j,
+                    k: None,
+                    r: None,
+                    p: None,
+                    f: None,
+                }),
+            });
+
+            from = to;
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
+        Ok(instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    #[test]
+    fn test_helix_revolutions_and_radius() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let helix = Helix::new(
+            Vector2::new(10.0, 10.0),
+            2.0,
+            6.0,
+            0.0,
+            -4.0,
+            2,
+            Direction::Counterclockwise,
+        );
+
+        let arcs = helix
+            .to_instructions(&context)?
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G3(arc) => Some(arc),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Two half circles per revolution, growing 1 mm in radius and descending 1 mm each
+        assert_eq!(arcs.len(), 4);
+        assert_eq!(arcs[0].x, Some(13.0));
+        assert_eq!(arcs[0].z, Some(-1.0));
+        assert_eq!(arcs[0].i, Some(2.5));
+        assert_eq!(arcs[1].x, Some(6.0));
+        assert_eq!(arcs[1].i, Some(-3.5));
+        assert_eq!(arcs[3].x, Some(4.0));
+        assert_eq!(arcs[3].y, Some(10.0));
+        assert_eq!(arcs[3].z, Some(-4.0));
+
+        let bounds = helix.bounds();
+        assert_eq!(bounds.min, Vector3::new(4.0, 4.0, -4.0));
+        assert_eq!(bounds.max, Vector3::new(16.0, 16.0, 0.0));
+
+        assert!(Helix {
+            revolutions: 0,
+            ..helix
+        }
+        .to_instructions(&context)
+        .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cuts::{step_z, transform_rectangle};
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// Clear a rectangular pocket around a rectangular island that is left standing, such as a
+/// raised boss inside a pocket.
+///
+/// Each layer first cuts along the walls of the pocket, then clears the material on all four
+/// sides of the island with passes that never enter it, and finishes with an offset ring along
+/// the walls of the island. The tool path is always compensated so that the walls of both the
+/// pocket and the island get the given sizes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IslandPocket {
+    /// Start point of the pocket in 3D space, the z value is the start depth of the cut.
+    pub start: Vector3,
+    /// Size of the pocket.
+    pub size: Vector2,
+    /// Start point of the island that is left standing.
+    pub island_start: Vector2,
+    /// Size of the island that is left standing.
+    pub island_size: Vector2,
+    /// The end depth of the cut on the z axis.
+    pub end_z: f64,
+    /// The maximum depth to cut on the z axis on each pass.
+    pub max_step_z: f64,
+}
+
+impl IslandPocket {
+    /// Creates a new `IslandPocket` struct.
+    #[must_use]
+    pub fn new(
+        start: Vector3,
+        size: Vector2,
+        island_start: Vector2,
+        island_size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+    ) -> Self {
+        Self {
+            start,
+            size,
+            island_start,
+            island_size,
+            end_z,
+            max_step_z,
+        }
+    }
+
+    /// Returns a new `IslandPocket` with the transform applied to its coordinates and sizes.
+    ///
+    /// Returns error for rotations that are not a multiple of 90° as the pocket is always
+    /// aligned with the x and y axis.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        if !transform.is_axis_aligned() {
+            return Err(anyhow!(
+                "Unable to transform island pocket, only rotations in multiples of 90° are supported"
+            ));
+        }
+
+        let (start, size) = transform_rectangle(self.start, self.size, transform);
+        let (island_start, island_size) = transform_rectangle(
+            Vector3::new(self.island_start.x, self.island_start.y, self.start.z),
+            self.island_size,
+            transform,
+        );
+
+        Ok(Self {
+            start,
+            size,
+            island_start: island_start.xy(),
+            island_size,
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+        })
+    }
+
+    /// Returns the point where the cut starts, at the start corner of the pocket. The tool
+    /// compensation is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.start
+    }
+
+    /// Returns the point where the cut ends, the ring around the island ends at the corner of
+    /// the island with the largest x and the smallest y. The tool compensation is not applied.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        Vector3::new(
+            self.island_start.x + self.island_size.x,
+            self.island_start.y,
+            self.end_z,
+        )
+    }
+
+    /// Returns the bounds of the cut, the outer rectangle of the pocket.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        Bounds {
+            min: Vector3::new(self.start.x, self.start.y, self.end_z),
+            max: Vector3::new(
+                self.start.x + self.size.x,
+                self.start.y + self.size.y,
+                self.start.z,
+            ),
+        }
+    }
+
+    /// Returns the bounds of the area swept by a tool of the given radius. The tool center is
+    /// kept a tool radius inside of the walls, so the whole pocket rectangle is swept.
+    #[must_use]
+    pub fn compensated_bounds(&self, _tool_radius: f64) -> Bounds {
+        self.bounds()
+    }
+
+    /// Returns the z depths of the layers that the pocket is cleared at.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self) -> Result<Vec<f64>> {
+        Ok(depth_layers(
+            self.start.z,
+            self.end_z,
+            step_z(self.max_step_z)?,
+        ))
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let tool = context.tool();
+        let tool_radius = tool.radius();
+
+        if self.island_start.x < self.start.x
+            || self.island_start.y < self.start.y
+            || self.island_start.x + self.island_size.x > self.start.x + self.size.x
+            || self.island_start.y + self.island_size.y > self.start.y + self.size.y
+        {
+            return Err(anyhow!(
+                "Unable to cut island pocket, the island is not inside of the pocket"
+            )
+            .into());
+        }
+
+        // The tool has to fit in the gaps on all sides of the island
+        let gap = [
+            self.island_start.x - self.start.x,
+            self.island_start.y - self.start.y,
+            self.start.x + self.size.x - self.island_start.x - self.island_size.x,
+            self.start.y + self.size.y - self.island_start.y - self.island_size.y,
+        ]
+        .into_iter()
+        .fold(f64::INFINITY, f64::min);
+
+        if gap < tool.diameter() - DEFAULT_EPSILON {
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "island pocket gap",
+                feature_size: gap,
+                tool_diameter: tool.diameter(),
+                units: context.units(),
+            });
+        }
+
+        // The tool center is kept a tool radius inside of the pocket and outside of the island
+        let outer_min = self.start.xy().add_x(tool_radius).add_y(tool_radius);
+        let outer_max = (self.start.xy() + self.size)
+            .add_x(-tool_radius)
+            .add_y(-tool_radius);
+        let island_min = self.island_start.add_x(-tool_radius).add_y(-tool_radius);
+        let island_max = (self.island_start + self.island_size)
+            .add_x(tool_radius)
+            .add_y(tool_radius);
+
+        let step = tool_radius * 1.8;
+        let bottom_rows = spaced(outer_min.y, island_min.y, step);
+        let top_rows = spaced(island_max.y, outer_max.y, step);
+        let side_rows = spaced(island_min.y, island_max.y, step);
+        let side_rows = &side_rows[1..side_rows.len() - 1];
+
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Cut island pocket at: x = {}, y = {}, size = {}, island_x = {}, island_y = {}, island_size = {}",
+                    round_precision(self.start.x),
+                    round_precision(self.start.y),
+                    self.size,
+                    round_precision(self.island_start.x),
+                    round_precision(self.island_start.y),
+                    self.island_size
+                ),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(outer_min.x),
+                y: Some(outer_min.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(self.start.z),
+                f: Some(tool.feed_rate()),
+            }),
+        ];
+
+        for z in self.layers()? {
+            // Back to the start corner through the area cleared on the previous layer
+            instructions.push(line_to(Some(outer_min.x), Some(outer_min.y)));
+            instructions.push(Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(z.min(context.z_safe())),
+                f: None,
+            }));
+
+            // Along the walls of the pocket
+            instructions.append(&mut vec![
+                line_to(Some(outer_max.x), None),
+                line_to(None, Some(outer_max.y)),
+                line_to(Some(outer_min.x), None),
+                line_to(None, Some(outer_min.y)),
+            ]);
+
+            // Below the island
+            let x = raster(&mut instructions, &bottom_rows, outer_min.x, outer_max.x);
+
+            // Left of the island, moving back to the left wall in the last row below the island
+            if x != outer_min.x {
+                instructions.push(line_to(Some(outer_min.x), None));
+            }
+            let x = raster(&mut instructions, side_rows, outer_min.x, island_min.x);
+
+            // Above the island
+            if x != outer_min.x {
+                instructions.push(line_to(Some(outer_min.x), None));
+            }
+            raster(&mut instructions, &top_rows, outer_min.x, outer_max.x);
+
+            // Right of the island, from the top down along the right wall
+            instructions.push(line_to(Some(outer_max.x), None));
+            let rows = side_rows.iter().rev().copied().collect::<Vec<_>>();
+            raster(&mut instructions, &rows, outer_max.x, island_max.x);
+
+            // The offset ring along the walls of the island
+            instructions.append(&mut vec![
+                line_to(Some(island_max.x), None),
+                line_to(None, Some(island_min.y)),
+                line_to(None, Some(island_max.y)),
+                line_to(Some(island_min.x), None),
+                line_to(None, Some(island_min.y)),
+                line_to(Some(island_max.x), None),
+            ]);
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
+        Ok(instructions)
+    }
+}
+
+fn line_to(x: Option<f64>, y: Option<f64>) -> Instruction {
+    Instruction::G1(G1 {
+        x,
+        y,
+        z: None,
+        f: None,
+    })
+}
+
+// Returns evenly spaced values from `from` to `to`, both included, at most `step` apart.
+fn spaced(from: f64, to: f64, step: f64) -> Vec<f64> {
+    let steps = ((to - from) / step - DEFAULT_EPSILON).ceil().max(1.0) as u32;
+
+    (0..=steps)
+        .map(|index| from + (to - from) * f64::from(index) / f64::from(steps))
+        .collect()
+}
+
+// Appends zig-zag passes between `from_x` and `to_x` for each row, starting at `from_x`, and
+// returns the x position where the passes end.
+fn raster(instructions: &mut Vec<Instruction>, rows: &[f64], from_x: f64, to_x: f64) -> f64 {
+    let mut x = from_x;
+
+    for y in rows {
+        x = if x == from_x { to_x } else { from_x };
+        instructions.push(line_to(None, Some(*y)));
+        instructions.push(line_to(Some(x), None));
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuts::Cut;
+    use crate::tools::Tool;
+
+    fn context() -> InnerContext {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+
+        InnerContext::new(Units::Metric, &tool, 5.0, 50.0)
+    }
+
+    #[test]
+    fn test_island_pocket() -> Result<()> {
+        let pocket = IslandPocket::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(60.0, 40.0),
+            Vector2::new(20.0, 12.0),
+            Vector2::new(15.0, 10.0),
+            -2.0,
+            1.0,
+        );
+
+        let bounds = pocket.bounds();
+        assert_eq!(bounds.min, Vector3::new(0.0, 0.0, -2.0));
+        assert_eq!(bounds.max, Vector3::new(60.0, 40.0, 0.0));
+
+        // Follow the tool center through all moves and check that it never gets closer to the
+        // island than the tool radius, or leaves the pocket
+        let instructions = pocket.to_instructions(&context())?;
+        let mut position = None;
+        let mut min = Vector2::new(f64::MAX, f64::MAX);
+        let mut max = Vector2::new(f64::MIN, f64::MIN);
+        let mut moves = 0;
+
+        for instruction in &instructions {
+            let (x, y) = match instruction {
+                Instruction::G0(G0 { x, y, .. }) | Instruction::G1(G1 { x, y, .. }) => (*x, *y),
+                _ => continue,
+            };
+
+            let Some(from) = position else {
+                // Starts at the first rapid move to the pocket
+                position = x.zip(y).map(|(x, y)| Vector2::new(x, y));
+                continue;
+            };
+            let target = Vector2::new(x.unwrap_or(from.x), y.unwrap_or(from.y));
+
+            for index in 0..=20 {
+                let t = f64::from(index) / 20.0;
+                let point = Vector2::new(
+                    from.x + (target.x - from.x) * t,
+                    from.y + (target.y - from.y) * t,
+                );
+
+                assert!(
+                    point.x <= 18.0 + DEFAULT_EPSILON
+                        || point.x >= 37.0 - DEFAULT_EPSILON
+                        || point.y <= 10.0 + DEFAULT_EPSILON
+                        || point.y >= 24.0 - DEFAULT_EPSILON,
+                    "the tool enters the island at {:?}",
+                    point
+                );
+                assert!(point.x >= 2.0 - DEFAULT_EPSILON && point.x <= 58.0 + DEFAULT_EPSILON);
+                assert!(point.y >= 2.0 - DEFAULT_EPSILON && point.y <= 38.0 + DEFAULT_EPSILON);
+            }
+
+            position = Some(target);
+            min = Vector2::new(min.x.min(target.x), min.y.min(target.y));
+            max = Vector2::new(max.x.max(target.x), max.y.max(target.y));
+            moves += 1;
+        }
+
+        assert!(moves > 20);
+        assert_eq!(position, Some(Vector2::new(37.0, 10.0)));
+
+        // The tool sweeps out to the walls of the pocket, but not past them
+        let swept = Cut::IslandPocket(pocket.clone()).compensated_bounds(2.0);
+        assert_eq!(swept, bounds);
+        assert_eq!(
+            (swept.min.xy(), swept.max.xy()),
+            (min - Vector2::new(2.0, 2.0), max + Vector2::new(2.0, 2.0))
+        );
+
+        // Each layer ends with the offset ring along the walls of the island
+        let ring = instructions[instructions.len() - 5..instructions.len() - 1]
+            .iter()
+            .map(|instruction| match instruction {
+                Instruction::G1(G1 { x, y, .. }) => (*x, *y),
+                _ => (None, None),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ring,
+            vec![
+                (None, Some(24.0)),
+                (Some(18.0), None),
+                (None, Some(10.0)),
+                (Some(37.0), None),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_island_pocket_gap_too_narrow_for_tool() {
+        let pocket = IslandPocket::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(60.0, 40.0),
+            Vector2::new(3.0, 12.0),
+            Vector2::new(15.0, 10.0),
+            -2.0,
+            1.0,
+        );
+
+        let error = pocket.to_instructions(&context()).unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::ToolWiderThanFeature {
+                feature: "island pocket gap",
+                feature_size,
+                tool_diameter,
+                units: Units::Metric,
+            } if feature_size == 3.0 && tool_diameter == 4.0
+        ));
+
+        let pocket = IslandPocket::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(60.0, 40.0),
+            Vector2::new(50.0, 12.0),
+            Vector2::new(15.0, 10.0),
+            -2.0,
+            1.0,
+        );
+        assert!(pocket.to_instructions(&context()).is_err());
+    }
+}
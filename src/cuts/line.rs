@@ -1,24 +1,70 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
+use crate::error::CncError;
 use crate::instructions::*;
 use crate::program::*;
 use crate::types::*;
 use crate::utils::*;
 
 /// Linear move from one 3D point to another.
-#[derive(Debug, Clone)]
+///
+/// By default the tool plunges straight down to the start point. With `ramp` enabled the tool
+/// instead descends from `z_safe` along the line towards the end point, and then cuts the line
+/// back to the start point at full depth, for tools that can not plunge vertically.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Line {
     /// Starting point in 3D space.
     pub from: Vector3,
     /// End point in 3D space.
     pub to: Vector3,
+    /// Ramp down along the line instead of plunging straight down.
+    #[serde(default)]
+    pub ramp: bool,
 }
 
 impl Line {
     /// Creates an `Line` struct.
     #[must_use]
     pub fn new(from: Vector3, to: Vector3) -> Self {
-        Self { from, to }
+        Self {
+            from,
+            to,
+            ramp: false,
+        }
+    }
+
+    /// Returns the `Line` with the ramped entry changed.
+    #[must_use]
+    pub fn with_ramp(mut self, ramp: bool) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    /// Returns a new `Line` with the transform applied to its coordinates.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        Self {
+            from: transform.apply(self.from),
+            to: transform.apply(self.to),
+            ramp: self.ramp,
+        }
+    }
+
+    /// Returns the point where the cut starts.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.from
+    }
+
+    /// Returns the point where the cut ends, ramped lines end back at the start point.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        if self.ramp {
+            self.from
+        } else {
+            self.to
+        }
     }
 
     /// Bounds in 3D space for the linear move.
@@ -38,7 +84,7 @@ impl Line {
     }
 
     /// Converts the struct to G-code instructions.
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
         let mut instructions = vec![];
 
         instructions.append(&mut vec![
@@ -64,25 +110,100 @@ impl Line {
                 y: Some(self.from.y),
                 z: None,
             }),
-            Instruction::G1(G1 {
-                x: None,
-                y: None,
-                z: Some(self.from.z),
-                f: Some(context.tool().feed_rate()),
-            }),
-            Instruction::G1(G1 {
-                x: Some(self.to.x),
-                y: Some(self.to.y),
-                z: Some(self.to.z),
-                f: None,
-            }),
-            Instruction::G0(G0 {
-                x: None,
-                y: None,
-                z: Some(context.z_safe()),
-            }),
         ]);
 
+        if self.ramp {
+            instructions.append(&mut vec![
+                Instruction::G1(G1 {
+                    x: Some(self.to.x),
+                    y: Some(self.to.y),
+                    z: Some(self.to.z),
+                    f: Some(context.tool().feed_rate()),
+                }),
+                Instruction::G1(G1 {
+                    x: Some(self.from.x),
+                    y: Some(self.from.y),
+                    z: Some(self.from.z),
+                    f: None,
+                }),
+            ]);
+        } else {
+            instructions.append(&mut vec![
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(self.from.z),
+                    f: Some(context.tool().feed_rate()),
+                }),
+                Instruction::G1(G1 {
+                    x: Some(self.to.x),
+                    y: Some(self.to.y),
+                    z: Some(self.to.z),
+                    f: None,
+                }),
+            ]);
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
         Ok(instructions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    #[test]
+    fn test_line_ramp() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let line = Line::new(Vector3::new(0.0, 0.0, -1.0), Vector3::new(20.0, 0.0, -1.0));
+
+        let straight = line.to_instructions(&context)?;
+        assert_eq!(
+            straight[4..6],
+            [
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(-1.0),
+                    f: Some(context.tool().feed_rate()),
+                }),
+                Instruction::G1(G1 {
+                    x: Some(20.0),
+                    y: Some(0.0),
+                    z: Some(-1.0),
+                    f: None,
+                }),
+            ]
+        );
+
+        let ramped = line.with_ramp(true).to_instructions(&context)?;
+        assert_eq!(ramped[..4], straight[..4]);
+        assert_eq!(
+            ramped[4..6],
+            [
+                Instruction::G1(G1 {
+                    x: Some(20.0),
+                    y: Some(0.0),
+                    z: Some(-1.0),
+                    f: Some(context.tool().feed_rate()),
+                }),
+                Instruction::G1(G1 {
+                    x: Some(0.0),
+                    y: Some(0.0),
+                    z: Some(-1.0),
+                    f: None,
+                }),
+            ]
+        );
+        assert_eq!(ramped[6..], straight[6..]);
+
+        Ok(())
+    }
+}
@@ -1,31 +1,70 @@
 //! Module providing a variety of cuts that can be added to a program tool context.
 
+use std::time::Duration;
+
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
+use crate::error::CncError;
 use crate::instructions::*;
 use crate::program::*;
+use crate::tools::Tool;
 use crate::types::*;
 
 mod arc;
 pub use arc::*;
 
+mod bore;
+pub use bore::*;
+
+mod chamfer;
+pub use chamfer::*;
+
 mod circle;
 pub use circle::*;
 
+mod circular_pocket;
+pub use circular_pocket::*;
+
+mod drill_points;
+pub use drill_points::*;
+
 mod frame;
 pub use frame::*;
 
+mod helix;
+pub use helix::*;
+
+mod island_pocket;
+pub use island_pocket::*;
+
 mod line;
 pub use line::*;
 
 mod path;
 pub use path::*;
 
+mod polygon;
+pub use polygon::*;
+
+mod ring;
+pub use ring::*;
+
+mod surface_scan;
+pub use surface_scan::*;
+
+mod thread_mill;
+pub use thread_mill::*;
+
+#[cfg(feature = "svg")]
+mod svg;
+
 mod area;
 pub use area::*;
 
 /// Enum variant providing the cuts available for adding to a program.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
 pub enum Cut {
     /// 3D arc where the axis to turn around can be selected.
     Arc(Arc),
@@ -40,6 +79,26 @@ pub enum Cut {
     /// Top/down rectangle area cut that is useful for pocket cuts as
     /// well as for planing cuts.
     Area(Area),
+    /// Top/down regular polygon cut.
+    Polygon(Polygon),
+    /// Top/down round pocket cut that clears the whole inside of a circle.
+    CircularPocket(CircularPocket),
+    /// Helical thread milling cut for internal or external threads.
+    ThreadMill(ThreadMill),
+    /// Top/down drilling of several holes to the same depth.
+    DrillPoints(DrillPoints),
+    /// Top/down round hole cut following a helix with a single repeated arc command.
+    Bore(Bore),
+    /// Top/down helix or spiral cut with a changing radius.
+    Helix(Helix),
+    /// Chamfer along the top edge of a part with a conical tool.
+    Chamfer(Chamfer),
+    /// 3D finishing cut following a heightmap surface with a ballnose tool.
+    SurfaceScan(SurfaceScan),
+    /// Top/down ring shaped groove cut that clears between an inner and an outer radius.
+    Ring(Ring),
+    /// Top/down rectangle pocket cut that leaves a rectangle island standing in the middle.
+    IslandPocket(IslandPocket),
 }
 
 impl Cut {
@@ -87,6 +146,73 @@ impl Cut {
         Self::Circle(Circle::drill(start, end_z))
     }
 
+    /// Helper for drilling several holes to the same depth, sharing the retract between the
+    /// holes and drilling them in an order that reduces the travel.
+    #[must_use]
+    pub fn drill_points(points: Vec<Vector3>, end_z: f64) -> Self {
+        Self::DrillPoints(DrillPoints::new(points, end_z))
+    }
+
+    /// Helper for boring round holes, spiraling down with a single arc command that repeats
+    /// one revolution for each `max_step_z` of depth.
+    #[must_use]
+    pub fn bore(center: Vector3, radius: f64, end_z: f64, max_step_z: f64) -> Self {
+        Self::Bore(Bore::new(center, radius, end_z, max_step_z))
+    }
+
+    /// Helper for boring round holes that are larger than the tool, clearing the whole hole
+    /// layer by layer with a helical descent around the center followed by an outward spiral
+    /// with `step_over` between the turns, see
+    /// [Bore::with_step_over](struct.Bore.html#method.with_step_over).
+    #[must_use]
+    pub fn bore_hole(
+        center: Vector3,
+        radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+        step_over: f64,
+    ) -> Self {
+        Self::Bore(Bore::new(center, radius, end_z, max_step_z).with_step_over(step_over))
+    }
+
+    /// Helper for creating helix or spiral cuts, descending from `start_z` to `end_z` over the
+    /// revolutions while the radius changes from `start_radius` to `end_radius`.
+    #[must_use]
+    pub fn helix(
+        center: Vector2,
+        start_radius: f64,
+        end_radius: f64,
+        start_z: f64,
+        end_z: f64,
+        revolutions: u32,
+        direction: Direction,
+    ) -> Self {
+        Self::Helix(Helix::new(
+            center,
+            start_radius,
+            end_radius,
+            start_z,
+            end_z,
+            revolutions,
+            direction,
+        ))
+    }
+
+    /// Helper for creating chamfer cuts along the top edge of a part with a conical tool, see
+    /// [Chamfer](struct.Chamfer.html). The segments are relative to the start point, and the
+    /// part is expected to be on the left side of them.
+    #[must_use]
+    pub fn chamfer(start: Vector3, segments: Vec<Segment>, depth: f64, width: f64) -> Self {
+        Self::Chamfer(Chamfer::new(start, segments, depth, width))
+    }
+
+    /// Helper for creating 3D surface cuts with a ballnose tool, following the heightmap with
+    /// raster passes spaced at most `step_over` apart and stepped along `raster_axis`.
+    #[must_use]
+    pub fn surface_scan(heightmap: Heightmap, step_over: f64, raster_axis: Axis) -> Self {
+        Self::SurfaceScan(SurfaceScan::new(heightmap, step_over, raster_axis))
+    }
+
     /// Helper for creating 3D arc cuts.
     #[must_use]
     pub fn arc(
@@ -105,6 +231,13 @@ impl Cut {
         Self::Line(Line::new(from, to))
     }
 
+    /// Helper for creating a 3D line cut that ramps down along the line instead of plunging
+    /// straight down at the start point.
+    #[must_use]
+    pub fn line_ramped(from: Vector3, to: Vector3) -> Self {
+        Self::Line(Line::new(from, to).with_ramp(true))
+    }
+
     /// Helper for creating top/down path cuts consisting of several
     /// [Segment](enum.Segment.html) structs (lines, arcs, points).
     #[must_use]
@@ -112,6 +245,27 @@ impl Cut {
         Self::Path(Path::new(start, segments, end_z, max_step_z))
     }
 
+    /// Helper for creating top/down path cuts through the waypoints, with the corners rounded
+    /// by arcs of the given radius, see
+    /// [Path::from_points_smooth](struct.Path.html#method.from_points_smooth).
+    ///
+    /// Returns error if a corner is too tight for the radius.
+    pub fn from_points_smooth(
+        start: Vector3,
+        points: &[Vector2],
+        corner_radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+    ) -> Result<Self> {
+        Ok(Self::Path(Path::from_points_smooth(
+            start,
+            points,
+            corner_radius,
+            end_z,
+            max_step_z,
+        )?))
+    }
+
     /// Helper for creating top/down rectangle frame cuts without tool compensation
     #[must_use]
     pub fn frame(start: Vector3, size: Vector2, end_z: f64, max_step_z: f64) -> Self {
@@ -150,6 +304,82 @@ impl Cut {
         ))
     }
 
+    /// Helper for creating top/down rectangle frame cuts with inner tool compensation, where
+    /// the final loop at `end_z` is repeated `spring_passes` extra times for a cleaner wall.
+    #[must_use]
+    pub fn frame_inner_spring(
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+        spring_passes: u32,
+    ) -> Self {
+        Self::Frame(
+            Frame::new(start, size, end_z, max_step_z, ToolPathCompensation::Inner)
+                .with_spring_passes(spring_passes),
+        )
+    }
+
+    /// Helper for creating top/down rectangle frame cuts with outer tool compensation, where
+    /// the final loop at `end_z` is repeated `spring_passes` extra times for a cleaner wall.
+    #[must_use]
+    pub fn frame_outer_spring(
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+        spring_passes: u32,
+    ) -> Self {
+        Self::Frame(
+            Frame::new(start, size, end_z, max_step_z, ToolPathCompensation::Outer)
+                .with_spring_passes(spring_passes),
+        )
+    }
+
+    /// Helper for creating top/down rectangle frame cuts with rounded corners, the corners
+    /// are cut as tangent arcs of the given radius.
+    #[must_use]
+    pub fn rounded_frame(
+        start: Vector3,
+        size: Vector2,
+        corner_radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+        compensation: ToolPathCompensation,
+    ) -> Self {
+        Self::Frame(Frame::new_rounded(
+            start,
+            size,
+            corner_radius,
+            end_z,
+            max_step_z,
+            compensation,
+        ))
+    }
+
+    /// Helper for creating top/down regular polygon cuts, where `radius` is the distance from
+    /// the center to the vertices and `rotation` is the angle in radians of the first vertex.
+    #[must_use]
+    pub fn polygon(
+        center: Vector3,
+        radius: f64,
+        sides: u32,
+        rotation: f64,
+        end_z: f64,
+        max_step_z: f64,
+        compensation: ToolPathCompensation,
+    ) -> Self {
+        Self::Polygon(Polygon::new(
+            center,
+            radius,
+            sides,
+            rotation,
+            end_z,
+            max_step_z,
+            compensation,
+        ))
+    }
+
     /// Helper for creating top/down planing cuts.
     #[must_use]
     pub fn plane(start: Vector3, size: Vector2, end_z: f64, max_step_z: f64) -> Self {
@@ -162,6 +392,22 @@ impl Cut {
         ))
     }
 
+    /// Helper for planing a surface with the passes spaced along the given raster axis, see
+    /// [Area::raster_axis](struct.Area.html#structfield.raster_axis).
+    #[must_use]
+    pub fn plane_along(
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+        raster_axis: Axis,
+    ) -> Self {
+        Self::Area(
+            Area::new(start, size, end_z, max_step_z, ToolPathCompensation::Outer)
+                .with_raster_axis(raster_axis),
+        )
+    }
+
     /// Helper for creating top/down pocket cuts.
     #[must_use]
     pub fn pocket(start: Vector3, size: Vector2, end_z: f64, max_step_z: f64) -> Self {
@@ -174,6 +420,163 @@ impl Cut {
         ))
     }
 
+    /// Helper for creating top/down pocket cuts that leave an island standing, where `start`
+    /// and `size` are the outer rectangle of the pocket and `island_start` and `island_size`
+    /// the inner rectangle to leave standing, see
+    /// [IslandPocket](struct.IslandPocket.html).
+    #[must_use]
+    pub fn pocket_with_island(
+        start: Vector3,
+        size: Vector2,
+        island_start: Vector2,
+        island_size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+    ) -> Self {
+        Self::IslandPocket(IslandPocket::new(
+            start,
+            size,
+            island_start,
+            island_size,
+            end_z,
+            max_step_z,
+        ))
+    }
+
+    /// Helper for creating top/down pocket cuts with the passes spaced along the given raster
+    /// axis, see [Area::raster_axis](struct.Area.html#structfield.raster_axis).
+    #[must_use]
+    pub fn pocket_along(
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+        raster_axis: Axis,
+    ) -> Self {
+        Self::Area(
+            Area::new(start, size, end_z, max_step_z, ToolPathCompensation::Inner)
+                .with_raster_axis(raster_axis),
+        )
+    }
+
+    /// Helper for creating top/down pocket cuts relative to the top of the stock, the pocket
+    /// starts at z = 0 and ends `depth_below_top` below it. Append it with
+    /// [Context::append_relative_cut](../program/struct.Context.html#method.append_relative_cut)
+    /// to place it at the stock top of the program.
+    #[must_use]
+    pub fn pocket_depth(
+        start: Vector2,
+        size: Vector2,
+        depth_below_top: f64,
+        max_step_z: f64,
+    ) -> Self {
+        Self::pocket(
+            Vector3::new(start.x, start.y, 0.0),
+            size,
+            -depth_below_top.abs(),
+            max_step_z,
+        )
+    }
+
+    /// Helper for planing a surface relative to the top of the stock, see
+    /// [Cut::pocket_depth](enum.Cut.html#method.pocket_depth).
+    #[must_use]
+    pub fn plane_depth(
+        start: Vector2,
+        size: Vector2,
+        depth_below_top: f64,
+        max_step_z: f64,
+    ) -> Self {
+        Self::plane(
+            Vector3::new(start.x, start.y, 0.0),
+            size,
+            -depth_below_top.abs(),
+            max_step_z,
+        )
+    }
+
+    /// Helper for drilling a hole relative to the top of the stock, see
+    /// [Cut::pocket_depth](enum.Cut.html#method.pocket_depth).
+    #[must_use]
+    pub fn drill_depth(start: Vector2, depth_below_top: f64) -> Self {
+        Self::drill(Vector3::new(start.x, start.y, 0.0), -depth_below_top.abs())
+    }
+
+    /// Helper for creating top/down pocket cuts that only clear the corners left by a previous
+    /// larger tool with the diameter `previous_tool_diameter`, also known as rest machining.
+    #[must_use]
+    pub fn pocket_rest(
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+        previous_tool_diameter: f64,
+    ) -> Self {
+        Self::Area(
+            Area::new(start, size, end_z, max_step_z, ToolPathCompensation::Inner)
+                .with_rest_from(previous_tool_diameter),
+        )
+    }
+
+    /// Helper for creating top/down round pocket cuts, spiraling outward from the center with
+    /// `step_over` between each turn.
+    #[must_use]
+    pub fn circular_pocket(
+        center: Vector3,
+        radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+        step_over: f64,
+    ) -> Self {
+        Self::CircularPocket(CircularPocket::new(
+            center, radius, end_z, max_step_z, step_over,
+        ))
+    }
+
+    /// Helper for creating top/down ring shaped grooves between `inner_radius` and
+    /// `outer_radius`, cut with full circles at most `step_over` apart on each layer.
+    #[must_use]
+    pub fn ring(
+        center: Vector3,
+        inner_radius: f64,
+        outer_radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+        step_over: f64,
+    ) -> Self {
+        Self::Ring(Ring::new(
+            center,
+            inner_radius,
+            outer_radius,
+            end_z,
+            max_step_z,
+            step_over,
+        ))
+    }
+
+    /// Helper for creating helical thread milling cuts, moving down `pitch * thread_starts`
+    /// for each revolution until `end_z` is reached.
+    #[must_use]
+    pub fn thread_mill(
+        center: Vector3,
+        major_diameter: f64,
+        pitch: f64,
+        end_z: f64,
+        thread_starts: u32,
+        internal: bool,
+        direction: Direction,
+    ) -> Self {
+        Self::ThreadMill(ThreadMill::new(
+            center,
+            major_diameter,
+            pitch,
+            end_z,
+            thread_starts,
+            internal,
+            direction,
+        ))
+    }
+
     /// Helper for planing with a slope, deprecated so not recommended to use.
     #[deprecated(
         since = "0.1.0",
@@ -208,11 +611,269 @@ impl Cut {
             Self::Line(c) => c.bounds(),
             Self::Path(c) => c.bounds(),
             Self::Area(c) => c.bounds(),
+            Self::Polygon(c) => c.bounds(),
+            Self::CircularPocket(c) => c.bounds(),
+            Self::ThreadMill(c) => c.bounds(),
+            Self::DrillPoints(c) => c.bounds(),
+            Self::Bore(c) => c.bounds(),
+            Self::Helix(c) => c.bounds(),
+            Self::Chamfer(c) => c.bounds(),
+            Self::SurfaceScan(c) => c.bounds(),
+            Self::Ring(c) => c.bounds(),
+            Self::IslandPocket(c) => c.bounds(),
+        }
+    }
+
+    /// Returns the point where the cut starts cutting, useful for ordering and chaining cuts
+    /// without generating their instructions. The points follow the nominal cut geometry, the
+    /// tool path compensation is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        match self {
+            Self::Arc(c) => c.entry_point(),
+            Self::Circle(c) => c.entry_point(),
+            Self::Frame(c) => c.entry_point(),
+            Self::Line(c) => c.entry_point(),
+            Self::Path(c) => c.entry_point(),
+            Self::Area(c) => c.entry_point(),
+            Self::Polygon(c) => c.entry_point(),
+            Self::CircularPocket(c) => c.entry_point(),
+            Self::ThreadMill(c) => c.entry_point(),
+            Self::DrillPoints(c) => c.entry_point(),
+            Self::Bore(c) => c.entry_point(),
+            Self::Helix(c) => c.entry_point(),
+            Self::Chamfer(c) => c.entry_point(),
+            Self::SurfaceScan(c) => c.entry_point(),
+            Self::Ring(c) => c.entry_point(),
+            Self::IslandPocket(c) => c.entry_point(),
+        }
+    }
+
+    /// Returns the point where the cut stops cutting before the tool is retracted, see
+    /// [entry_point](enum.Cut.html#method.entry_point).
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        match self {
+            Self::Arc(c) => c.exit_point(),
+            Self::Circle(c) => c.exit_point(),
+            Self::Frame(c) => c.exit_point(),
+            Self::Line(c) => c.exit_point(),
+            Self::Path(c) => c.exit_point(),
+            Self::Area(c) => c.exit_point(),
+            Self::Polygon(c) => c.exit_point(),
+            Self::CircularPocket(c) => c.exit_point(),
+            Self::ThreadMill(c) => c.exit_point(),
+            Self::DrillPoints(c) => c.exit_point(),
+            Self::Bore(c) => c.exit_point(),
+            Self::Helix(c) => c.exit_point(),
+            Self::Chamfer(c) => c.exit_point(),
+            Self::SurfaceScan(c) => c.exit_point(),
+            Self::Ring(c) => c.exit_point(),
+            Self::IslandPocket(c) => c.exit_point(),
+        }
+    }
+
+    /// Calculates the bounds of the area swept by a tool of the given radius, the tool center
+    /// path with the tool path compensation and any clearance moves around the cut applied,
+    /// grown by the tool radius. The bounds are never smaller than the nominal bounds of the
+    /// cut.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        let bounds = match self {
+            Self::Arc(c) => grow_bounds(c.bounds(), tool_radius),
+            Self::Circle(c) => c.compensated_bounds(tool_radius),
+            Self::Frame(c) => c.compensated_bounds(tool_radius),
+            Self::Line(c) => grow_bounds(c.bounds(), tool_radius),
+            Self::Path(c) => grow_bounds(c.bounds(), tool_radius),
+            Self::Area(c) => c.compensated_bounds(tool_radius),
+            Self::Polygon(c) => c.compensated_bounds(tool_radius),
+            Self::CircularPocket(c) => c.compensated_bounds(tool_radius),
+            Self::ThreadMill(c) => c.compensated_bounds(tool_radius),
+            Self::DrillPoints(c) => grow_bounds(c.bounds(), tool_radius),
+            Self::Bore(c) => c.compensated_bounds(tool_radius),
+            Self::Helix(c) => grow_bounds(c.bounds(), tool_radius),
+            Self::Chamfer(c) => c.compensated_bounds(tool_radius),
+            Self::SurfaceScan(c) => grow_bounds(c.bounds(), tool_radius),
+            Self::Ring(c) => c.compensated_bounds(tool_radius),
+            Self::IslandPocket(c) => c.compensated_bounds(tool_radius),
+        };
+
+        bounds.union(&self.bounds())
+    }
+
+    /// Returns the z depths of the passes that the cut is made in, in the order they are cut,
+    /// for the given tool. Cuts that are not cut in layers of `max_step_z`, such as lines,
+    /// arcs, helixes, thread mills, chamfers, drill points, and surface scans, return an empty
+    /// list.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite, or if a polygon is too small for
+    /// the tool.
+    pub fn layers(&self, tool: &Tool) -> Result<Vec<f64>> {
+        match self {
+            Self::Circle(c) => c.layers(tool.radius()),
+            Self::Frame(c) => c.layers(),
+            Self::Path(c) => c.layers(),
+            Self::Area(c) => c.layers(),
+            Self::Polygon(c) => c.to_path(tool.radius())?.layers(),
+            Self::CircularPocket(c) => c.layers(),
+            Self::Bore(c) => c.layers(),
+            Self::Ring(c) => c.layers(),
+            Self::IslandPocket(c) => c.layers(),
+            Self::Arc(_)
+            | Self::Line(_)
+            | Self::ThreadMill(_)
+            | Self::DrillPoints(_)
+            | Self::Helix(_)
+            | Self::Chamfer(_)
+            | Self::SurfaceScan(_) => Ok(vec![]),
+        }
+    }
+
+    /// Returns the maximum depth to cut on each pass, for the cuts that are cut in layers, see
+    /// [layers](enum.Cut.html#method.layers).
+    #[must_use]
+    pub fn max_step_z(&self) -> Option<f64> {
+        match self {
+            Self::Circle(c) => Some(c.max_step_z),
+            Self::Frame(c) => Some(c.max_step_z),
+            Self::Path(c) => Some(c.max_step_z),
+            Self::Area(c) => Some(c.max_step_z),
+            Self::Polygon(c) => Some(c.max_step_z),
+            Self::CircularPocket(c) => Some(c.max_step_z),
+            Self::Bore(c) => Some(c.max_step_z),
+            Self::Ring(c) => Some(c.max_step_z),
+            Self::IslandPocket(c) => Some(c.max_step_z),
+            Self::Arc(_)
+            | Self::Line(_)
+            | Self::ThreadMill(_)
+            | Self::DrillPoints(_)
+            | Self::Helix(_)
+            | Self::Chamfer(_)
+            | Self::SurfaceScan(_) => None,
+        }
+    }
+
+    /// Returns how deep the cut goes, measured from the start z value down to the deepest
+    /// z value of the cut.
+    #[must_use]
+    #[allow(deprecated)]
+    pub fn depth(&self) -> f64 {
+        match self {
+            Self::Arc(c) => (c.from.z - c.to.z).abs(),
+            Self::Circle(c) => c.start.z - c.end_z,
+            Self::Frame(c) => c.start.z - c.end_z,
+            Self::Line(c) => (c.from.z - c.to.z).abs(),
+            Self::Path(c) => c.start.z - c.end_z,
+            Self::Area(c) => c.start.z - c.end_z.min(c.end_z_stop),
+            Self::Polygon(c) => c.center.z - c.end_z,
+            Self::CircularPocket(c) => c.center.z - c.end_z,
+            Self::ThreadMill(c) => c.center.z - c.end_z,
+            Self::DrillPoints(c) => c
+                .points
+                .iter()
+                .map(|point| point.z - c.end_z)
+                .fold(0.0, f64::max),
+            Self::Bore(c) => c.center.z - c.end_z,
+            Self::Helix(c) => (c.start_z - c.end_z).abs(),
+            Self::Chamfer(c) => c.depth,
+            Self::SurfaceScan(c) => {
+                let (min_z, max_z) = c.heightmap.height_range();
+                max_z - min_z
+            }
+            Self::Ring(c) => c.center.z - c.end_z,
+            Self::IslandPocket(c) => c.start.z - c.end_z,
+        }
+    }
+
+    /// Returns a copy of the cut moved by the given offset.
+    ///
+    /// Returns error if the cut can not be transformed, see
+    /// [Cut::transform](enum.Cut.html#method.transform).
+    pub fn translated(&self, offset: Vector3) -> Result<Self> {
+        self.transform(&Transform::Translate(offset))
+    }
+
+    /// Returns a copy of the cut with the coordinates, lengths, depth steps, step overs, and
+    /// feed rates converted from one unit to another, for example to use an imperial cut in a
+    /// metric program.
+    ///
+    /// Returns error if the cut can not be scaled.
+    pub fn convert_units(&self, from: Units, to: Units) -> Result<Self> {
+        let factor = from.convert(1.0, to);
+        let mut cut = self.transform(&Transform::Scale(factor))?;
+
+        // Scaling keeps the depth steps, step overs, and feed rates, they are converted here
+        match &mut cut {
+            Self::Circle(c) => c.max_step_z *= factor,
+            Self::Frame(c) => c.max_step_z *= factor,
+            Self::Path(c) => {
+                c.max_step_z *= factor;
+                convert_feed_rates(&mut c.segments, factor);
+            }
+            Self::Area(c) => c.max_step_z *= factor,
+            Self::Polygon(c) => c.max_step_z *= factor,
+            Self::CircularPocket(c) => {
+                c.max_step_z *= factor;
+                c.step_over *= factor;
+            }
+            Self::Bore(c) => c.max_step_z *= factor,
+            Self::IslandPocket(c) => c.max_step_z *= factor,
+            Self::Ring(c) => {
+                c.max_step_z *= factor;
+                c.step_over *= factor;
+            }
+            Self::Chamfer(c) => convert_feed_rates(&mut c.segments, factor),
+            Self::Arc(_)
+            | Self::Line(_)
+            | Self::ThreadMill(_)
+            | Self::DrillPoints(_)
+            | Self::Helix(_)
+            | Self::SurfaceScan(_) => {}
         }
+
+        Ok(cut)
+    }
+
+    /// Returns a new cut with the transform applied, see
+    /// [Transform](../types/enum.Transform.html) for the available transforms.
+    ///
+    /// Returns error if the cut can not be represented after the transform, for example
+    /// when rotating a frame or an area by an angle that is not a multiple of 90°.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        Ok(match self {
+            Self::Arc(c) => Self::Arc(c.transform(transform)?),
+            Self::Circle(c) => Self::Circle(c.transform(transform)),
+            Self::Frame(c) => Self::Frame(c.transform(transform)?),
+            Self::Line(c) => Self::Line(c.transform(transform)),
+            Self::Path(c) => Self::Path(c.transform(transform)),
+            Self::Area(c) => Self::Area(c.transform(transform)?),
+            Self::Polygon(c) => Self::Polygon(c.transform(transform)),
+            Self::CircularPocket(c) => Self::CircularPocket(c.transform(transform)),
+            Self::ThreadMill(c) => Self::ThreadMill(c.transform(transform)),
+            Self::DrillPoints(c) => Self::DrillPoints(c.transform(transform)),
+            Self::Bore(c) => Self::Bore(c.transform(transform)),
+            Self::Helix(c) => Self::Helix(c.transform(transform)),
+            Self::Chamfer(c) => Self::Chamfer(c.transform(transform)),
+            Self::SurfaceScan(c) => Self::SurfaceScan(c.transform(transform)?),
+            Self::Ring(c) => Self::Ring(c.transform(transform)),
+            Self::IslandPocket(c) => Self::IslandPocket(c.transform(transform)?),
+        })
     }
 
     /// Converts the cuts to a list of G-code instructions
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
+    ///
+    /// Tools with other units than the program are converted to the program units first.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        if context.tool().units() != context.units() {
+            let tool = context.tool().to_units(context.units());
+            return self.to_instructions(&InnerContext::new(
+                context.units(),
+                &tool,
+                context.z_safe(),
+                context.z_tool_change(),
+            ));
+        }
+
         match self {
             Self::Arc(c) => c.to_instructions(context),
             Self::Circle(c) => c.to_instructions(context),
@@ -220,6 +881,325 @@ impl Cut {
             Self::Line(c) => c.to_instructions(context),
             Self::Path(c) => c.to_instructions(context),
             Self::Area(c) => c.to_instructions(context),
+            Self::Polygon(c) => c.to_instructions(context),
+            Self::CircularPocket(c) => c.to_instructions(context),
+            Self::ThreadMill(c) => c.to_instructions(context),
+            Self::DrillPoints(c) => c.to_instructions(context),
+            Self::Bore(c) => c.to_instructions(context),
+            Self::Helix(c) => c.to_instructions(context),
+            Self::Chamfer(c) => c.to_instructions(context),
+            Self::SurfaceScan(c) => c.to_instructions(context),
+            Self::Ring(c) => c.to_instructions(context),
+            Self::IslandPocket(c) => c.to_instructions(context),
+        }
+    }
+}
+
+// Multiplies the feed rates of the line and arc segments by the factor.
+fn convert_feed_rates(segments: &mut [Segment], factor: f64) {
+    for segment in segments {
+        match segment {
+            Segment::Line(line) => line.feed_rate = line.feed_rate.map(|rate| rate * factor),
+            Segment::Arc(arc) => arc.feed_rate = arc.feed_rate.map(|rate| rate * factor),
+            Segment::Point(_) => {}
+        }
+    }
+}
+
+// Validates the maximum depth to cut on each pass, returning its absolute value. A zero, NaN or
+// infinite value would give a broken number of layers.
+pub(crate) fn step_z(max_step_z: f64) -> Result<f64, CncError> {
+    if max_step_z == 0.0 || !max_step_z.is_finite() {
+        return Err(CncError::InvalidStepZ { max_step_z });
+    }
+
+    Ok(max_step_z.abs())
+}
+
+// Returns true if a full chip clearing retract should be made after the layer, which is every
+// `every` layers except after the last layer.
+pub(crate) fn is_chip_clear_layer(every: Option<u32>, layer: u32, layers: u32) -> bool {
+    match every {
+        Some(every) if every > 0 => layer < layers && layer.is_multiple_of(every),
+        _ => false,
+    }
+}
+
+// Retracts the tool to z_safe to let the chips clear, waits for the dwell, and plunges back
+// down to z at the same xy position.
+pub(crate) fn chip_clear_instructions(
+    context: &InnerContext,
+    z: f64,
+    dwell: Option<Duration>,
+) -> Vec<Instruction> {
+    let mut instructions = vec![Instruction::G0(G0 {
+        x: None,
+        y: None,
+        z: Some(context.z_safe()),
+    })];
+
+    if let Some(dwell) = dwell {
+        instructions.push(Instruction::G4(G4 { p: dwell }));
+    }
+
+    instructions.append(&mut vec![
+        Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some((z + 0.5).min(context.z_safe())),
+        }),
+        Instruction::G1(G1 {
+            x: None,
+            y: None,
+            z: Some(z),
+            f: Some(context.tool().feed_rate()),
+        }),
+    ]);
+
+    instructions
+}
+
+// Returns the bounds grown by the offset along the x and y axis, such as the area swept by a
+// tool of the offset radius when the tool center stays inside the bounds.
+pub(crate) fn grow_bounds(bounds: Bounds, offset: f64) -> Bounds {
+    Bounds {
+        min: bounds.min.add_x(-offset).add_y(-offset),
+        max: bounds.max.add_x(offset).add_y(offset),
+    }
+}
+
+// Transforms an axis aligned rectangle, returning the new start (min corner) and size.
+pub(crate) fn transform_rectangle(
+    start: Vector3,
+    size: Vector2,
+    transform: &Transform,
+) -> (Vector3, Vector2) {
+    let a = transform.apply(start);
+    let b = transform.apply(start.add_x(size.x).add_y(size.y));
+
+    (
+        Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z),
+        Vector2::new((a.x - b.x).abs(), (a.y - b.y).abs()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motion::*;
+    use crate::tools::Tool;
+
+    // Returns the xy start of the first cutting move and the end of the last cutting move.
+    fn cutting_span(cut: &Cut, context: &InnerContext) -> Result<(Vector2, Vector3)> {
+        let mut state = MachineState::default();
+        let mut entry = None;
+        let mut exit = Vector3::ZERO;
+
+        for instruction in cut.to_instructions(context)? {
+            let from = state.position;
+            if let Some(motion) = state.apply(&instruction) {
+                if motion.kind != MoveKind::Rapid {
+                    entry.get_or_insert(from.xy());
+                    exit = state.position;
+                }
+            }
+        }
+
+        Ok((entry.unwrap(), exit))
+    }
+
+    #[test]
+    fn test_entry_and_exit_points() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let start = Vector3::new(10.0, 20.0, 0.0);
+        let none = ToolPathCompensation::None;
+
+        let cuts = vec![
+            Cut::arc(
+                start,
+                start.add_x(20.0).with_z(-1.0),
+                start.add_x(10.0).with_z(-0.5),
+                Axis::Z,
+                Direction::Clockwise,
+            ),
+            Cut::Circle(Circle::new(start, 10.0, -2.0, 1.0, none.clone())),
+            Cut::Circle(Circle::new(start, 10.0, -2.0, 1.0, none.clone()).with_clear_bottom(true)),
+            Cut::drill(start, -2.0),
+            Cut::Frame(Frame::new_rounded(
+                start,
+                Vector2::new(20.0, 10.0),
+                2.0,
+                -2.0,
+                1.0,
+                none.clone(),
+            )),
+            Cut::line(start, start.add_x(10.0).with_z(-1.0)),
+            Cut::line_ramped(start.with_z(-1.0), start.add_x(10.0).with_z(-1.0)),
+            Cut::path(
+                start,
+                vec![
+                    Segment::point(5.0, 0.0),
+                    Segment::line(Vector2::new(5.0, 0.0), Vector2::new(15.0, 5.0)),
+                ],
+                -2.0,
+                1.0,
+            ),
+            Cut::Area(Area::new(
+                start,
+                Vector2::new(20.0, 20.0),
+                -2.0,
+                1.0,
+                none.clone(),
+            )),
+            Cut::Polygon(Polygon::new(start, 10.0, 6, 0.5, -2.0, 1.0, none)),
+            Cut::drill_points(vec![start, start.add_x(30.0), start.add_x(10.0)], -2.0),
+            Cut::helix(start.xy(), 2.0, 5.0, 0.0, -3.0, 3, Direction::Clockwise),
+        ];
+
+        for cut in cuts {
+            let (entry, exit) = cutting_span(&cut, &context)?;
+            assert!(cut.entry_point().xy().distance_to(entry) < 1e-9, "{cut:?}");
+            assert!(cut.exit_point().distance_to(exit) < 1e-9, "{cut:?}");
+            assert_eq!(cut.entry_point().z, cut.bounds().max.z, "{cut:?}");
+        }
+
+        // Cuts that are always compensated give the nominal points
+        let bore = Cut::bore(start, 5.0, -2.0, 1.0);
+        assert_eq!(bore.entry_point(), start.add_x(-5.0));
+        assert_eq!(bore.exit_point(), start.add_x(-5.0).with_z(-2.0));
+
+        let pocket = Cut::circular_pocket(start, 10.0, -2.0, 1.0, 2.0);
+        assert_eq!(pocket.entry_point(), start);
+        assert_eq!(pocket.exit_point(), start.with_z(-2.0));
+
+        let thread = Cut::thread_mill(start, 10.0, 1.5, -6.0, 1, false, Direction::Clockwise);
+        assert_eq!(thread.entry_point(), start.add_x(5.0));
+        assert_eq!(thread.exit_point(), start.add_x(5.0).with_z(-6.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compensated_bounds() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+        let start = Vector3::new(10.0, 20.0, 0.0);
+        let size = Vector2::new(20.0, 10.0);
+
+        let cuts = vec![
+            Cut::circle(start, -2.0, 10.0, 1.0),
+            Cut::circle_inner(start, -2.0, 10.0, 1.0),
+            Cut::circle_outer(start, -2.0, 10.0, 1.0),
+            Cut::frame_inner(start, size, -2.0, 1.0),
+            Cut::frame_outer(start, size, -2.0, 1.0),
+            Cut::pocket(start, size, -2.0, 1.0),
+            Cut::plane(start, size, -2.0, 1.0),
+            Cut::polygon(start, 10.0, 6, 0.0, -2.0, 1.0, ToolPathCompensation::Outer),
+            Cut::circular_pocket(start, 10.0, -2.0, 1.0, 1.0),
+            Cut::thread_mill(start, 10.0, 1.5, -6.0, 1, false, Direction::Clockwise),
+            Cut::thread_mill(start, 10.0, 1.5, -6.0, 1, true, Direction::Clockwise),
+            Cut::bore(start, 5.0, -2.0, 1.0),
+            Cut::line(start, start.add_x(10.0).with_z(-1.0)),
+            Cut::helix(start.xy(), 3.0, 6.0, 0.0, -2.0, 2, Direction::Clockwise),
+        ];
+
+        // The tool stays within the compensated bounds at every position where it cuts, and
+        // the compensated bounds are never smaller than the nominal bounds
+        for cut in cuts {
+            let bounds = cut.compensated_bounds(tool.radius());
+            assert!(
+                bounds.contains_bounds(&cut.bounds()),
+                "{bounds:?} for {cut:?}"
+            );
+
+            let mut state = MachineState::default();
+
+            for instruction in cut.to_instructions(&context)? {
+                if state.apply(&instruction).is_some() && state.position.z < 0.0 {
+                    let position = state.position;
+                    assert!(
+                        position.x - tool.radius() >= bounds.min.x - 1e-9
+                            && position.x + tool.radius() <= bounds.max.x + 1e-9
+                            && position.y - tool.radius() >= bounds.min.y - 1e-9
+                            && position.y + tool.radius() <= bounds.max.y + 1e-9,
+                        "{position:?} outside {bounds:?} for {cut:?}"
+                    );
+                }
+            }
         }
+
+        // Outer compensation and clearance moves make the bounds grow
+        let circle = Cut::circle_outer(start, -2.0, 10.0, 1.0).compensated_bounds(2.0);
+        assert_eq!(circle.min, Vector3::new(-4.0, 6.0, -2.0));
+        assert_eq!(circle.max, Vector3::new(24.0, 34.0, 0.0));
+
+        let frame = Cut::frame_outer(start, size, -2.0, 1.0).compensated_bounds(2.0);
+        assert_eq!(frame.min, Vector3::new(6.0, 16.0, -2.0));
+        assert_eq!(frame.max, Vector3::new(34.0, 34.0, 0.0));
+
+        let thread = Cut::thread_mill(start, 10.0, 1.5, -6.0, 1, false, Direction::Clockwise);
+        assert!(thread.compensated_bounds(2.0).max.x > thread.bounds().max.x);
+
+        // Inner compensation sweeps the nominal area
+        let circle = Cut::circle_inner(start, -2.0, 10.0, 1.0);
+        assert_eq!(circle.compensated_bounds(2.0), circle.bounds());
+
+        // Cuts without compensation sweep a tool radius around the path
+        let line = Cut::line(start, start.add_x(10.0));
+        assert_eq!(
+            line.compensated_bounds(2.0),
+            Bounds {
+                min: Vector3::new(8.0, 18.0, 0.0),
+                max: Vector3::new(22.0, 22.0, 0.0),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_max_step_z() -> Result<()> {
+        let context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let size = Vector2::new(20.0, 20.0);
+        let cuts = |max_step_z| {
+            vec![
+                Cut::path(
+                    start,
+                    vec![Segment::line(Vector2::ZERO, size)],
+                    -2.0,
+                    max_step_z,
+                ),
+                Cut::pocket(start, size, -2.0, max_step_z),
+                Cut::circle(start, -2.0, 10.0, max_step_z),
+                Cut::frame(start, size, -2.0, max_step_z),
+                Cut::circular_pocket(start, 10.0, -2.0, max_step_z, 1.0),
+            ]
+        };
+
+        for max_step_z in [0.0, f64::NAN, f64::INFINITY] {
+            for cut in cuts(max_step_z) {
+                let error = cut.to_instructions(&context).unwrap_err();
+                assert!(matches!(error, CncError::InvalidStepZ { .. }));
+                assert!(error.to_string().starts_with("max_step_z must be"));
+            }
+        }
+
+        // Negative values are treated as their absolute value
+        for (negative, positive) in cuts(-1.0).iter().zip(cuts(1.0).iter()) {
+            assert_eq!(
+                negative.to_instructions(&context)?,
+                positive.to_instructions(&context)?
+            );
+        }
+
+        Ok(())
     }
 }
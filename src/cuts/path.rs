@@ -1,29 +1,39 @@
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 
+use crate::cuts::step_z;
+use crate::error::CncError;
 use crate::instructions::*;
 use crate::program::*;
 use crate::types::*;
 use crate::utils::*;
 
 /// A 2D line segment used when cutting top/down paths.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Line2D {
     /// Relative starting point in 2D space.
     pub from: Vector2,
     /// Relative end point in 2D space.
     pub to: Vector2,
+    /// Feed rate to cut the segment with, the tool feed rate is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feed_rate: Option<f64>,
 }
 
 impl Line2D {
     /// Creates a new `Line2D` struct
     #[must_use]
     pub fn new(from: Vector2, to: Vector2) -> Self {
-        Self { from, to }
+        Self {
+            from,
+            to,
+            feed_rate: None,
+        }
     }
 }
 
 /// A 2D arc segment used when cutting top/down paths.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Arc2D {
     /// Relative starting point in 2D space.
     pub from: Vector2,
@@ -37,6 +47,9 @@ pub struct Arc2D {
     pub axis: Axis,
     /// The direction to cut the arc with.
     pub direction: Direction,
+    /// Feed rate to cut the segment with, the tool feed rate is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feed_rate: Option<f64>,
 }
 
 impl Arc2D {
@@ -55,22 +68,35 @@ impl Arc2D {
             center,
             axis,
             direction,
+            feed_rate: None,
         }
     }
 
-    /// Returns the radius of the arc.
+    /// Returns the radius of the arc. The radius assumes a valid arc, see
+    /// [is_valid](#method.is_valid), for an arc with a center that is off the larger of the two
+    /// center distances is returned.
     #[must_use]
     pub fn radius(&self) -> f64 {
         self.from
             .distance_to(self.center)
             .max(self.to.distance_to(self.center))
     }
+
+    /// Returns true if the center is at the same distance from the from and to points, within
+    /// epsilon, see [DEFAULT_EPSILON](../types/constant.DEFAULT_EPSILON.html).
+    #[must_use]
+    pub fn is_valid(&self, epsilon: f64) -> bool {
+        (self.from.distance_to(self.center) - self.to.distance_to(self.center)).abs() <= epsilon
+    }
 }
 
 /// A path segment variant used when creating a cut [Path](struct.Path.html).
 ///
-/// All coordinate values for a segment is relative to the path start coordinate.
-#[derive(Debug, Clone)]
+/// All coordinate values for a segment is relative to the path start coordinate. Line and arc
+/// segments can have their own feed rate, while point segments are always cut with the tool
+/// feed rate.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
 pub enum Segment {
     /// A 2D line segment.
     Line(Line2D),
@@ -87,6 +113,15 @@ impl Segment {
         Self::Line(Line2D::new(from, to))
     }
 
+    /// Helper for creating a top/down line segment that is cut with a specific feed rate.
+    #[must_use]
+    pub fn line_with_feed(from: Vector2, to: Vector2, feed_rate: f64) -> Self {
+        Self::Line(Line2D {
+            feed_rate: Some(feed_rate),
+            ..Line2D::new(from, to)
+        })
+    }
+
     /// Helper for creating a top/down arc segment (alias for
     /// [Segment::arc_z](enum.Segment.html#method.arc_z)).
     #[must_use]
@@ -94,6 +129,21 @@ impl Segment {
         Self::arc_z(from, to, center, direction)
     }
 
+    /// Helper for creating a top/down arc segment that is cut with a specific feed rate.
+    #[must_use]
+    pub fn arc_with_feed(
+        from: Vector2,
+        to: Vector2,
+        center: Vector2,
+        direction: Direction,
+        feed_rate: f64,
+    ) -> Self {
+        Self::Arc(Arc2D {
+            feed_rate: Some(feed_rate),
+            ..Arc2D::new(from, to, center, Axis::Z, direction)
+        })
+    }
+
     /// Helper for creating an arc segment around the x axis.
     #[must_use]
     pub fn arc_x(from: Vector2, to: Vector2, center: Vector2, direction: Direction) -> Self {
@@ -112,6 +162,39 @@ impl Segment {
         Self::Arc(Arc2D::new(from, to, center, Axis::Z, direction))
     }
 
+    /// Helper for creating a top/down arc segment, validating that the center is at the same
+    /// distance from the from and to points.
+    ///
+    /// Returns error if the center distances differ by more than
+    /// [DEFAULT_EPSILON](../types/constant.DEFAULT_EPSILON.html).
+    pub fn try_arc(
+        from: Vector2,
+        to: Vector2,
+        center: Vector2,
+        direction: Direction,
+    ) -> Result<Self, CncError> {
+        Self::arc(from, to, center, direction).validated()
+    }
+
+    /// Returns the segment if it is valid, for validating segments from the other helpers,
+    /// for example `Segment::arc_x(from, to, center, direction).validated()?`.
+    ///
+    /// Returns error if the segment is an arc with center distances that differ by more than
+    /// [DEFAULT_EPSILON](../types/constant.DEFAULT_EPSILON.html).
+    pub fn validated(self) -> Result<Self, CncError> {
+        if let Self::Arc(arc) = &self {
+            if !arc.is_valid(DEFAULT_EPSILON) {
+                return Err(CncError::ArcRadiusMismatch {
+                    segment: None,
+                    distance_from: arc.from.distance_to(arc.center),
+                    distance_to: arc.to.distance_to(arc.center),
+                });
+            }
+        }
+
+        Ok(self)
+    }
+
     /// Helper for creating a 2D "waypoint" point segment.
     #[must_use]
     pub fn point(x: f64, y: f64) -> Self {
@@ -125,8 +208,20 @@ impl Segment {
     }
 }
 
+/// How the depth changes along a [Path](struct.Path.html) for each pass.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DepthMode {
+    /// The depth ramps down continuously along the segments of each pass.
+    #[default]
+    Ramp,
+    /// The depth is constant for all segments of a pass, and the tool plunges straight down
+    /// to the next depth at the start of each pass. Gives a flat floor for closed contours.
+    Constant,
+}
+
 /// Cut a top/down path from several segments.
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Path {
     /// Start point in 3D space.
     pub start: Vector3,
@@ -137,6 +232,9 @@ pub struct Path {
     pub end_z: f64,
     /// The maximum depth to cut on the z axis on each pass.
     pub max_step_z: f64,
+    /// How the depth changes along the path for each pass, defaults to `DepthMode::Ramp`.
+    #[serde(default)]
+    pub depth_mode: DepthMode,
 }
 
 impl Path {
@@ -148,9 +246,250 @@ impl Path {
             segments,
             end_z,
             max_step_z,
+            depth_mode: DepthMode::default(),
+        }
+    }
+
+    /// Creates a new `Path` through the waypoints, with the corners rounded by arcs of the
+    /// given radius. The lines are trimmed where the arcs meet them, and each arc turns the same
+    /// way as the path at its corner. Straight corners are left as is, as are the first and
+    /// last points. The waypoints are relative to the start point.
+    ///
+    /// Returns error if two waypoints in a row are the same, or if a line is too short to fit
+    /// the arcs at its ends.
+    pub fn from_points_smooth(
+        start: Vector3,
+        points: &[Vector2],
+        corner_radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+    ) -> Result<Self, CncError> {
+        // The distance from each waypoint to where its arc meets the lines, with the arc
+        // center and direction
+        let mut corners: Vec<Option<(f64, Vector2, Direction)>> = vec![None; points.len()];
+
+        for (index, window) in points.windows(3).enumerate() {
+            let (previous, corner, next) = (window[0], window[1], window[2]);
+            let incoming = (previous - corner).normalized();
+            let outgoing = (next - corner).normalized();
+
+            if previous.approx_eq(corner, DEFAULT_EPSILON)
+                || next.approx_eq(corner, DEFAULT_EPSILON)
+            {
+                return Err(anyhow!(
+                    "Unable to round path corner at x = {}, y = {}, the waypoint is repeated",
+                    round_precision(corner.x),
+                    round_precision(corner.y)
+                )
+                .into());
+            }
+
+            let angle = incoming.dot(outgoing).clamp(-1.0, 1.0).acos();
+            if corner_radius <= 0.0 || std::f64::consts::PI - angle < 1e-9 {
+                continue;
+            }
+
+            let trim = corner_radius / (angle / 2.0).tan();
+            let bisector = (incoming + outgoing).normalized();
+            let center_distance = corner_radius / (angle / 2.0).sin();
+            let center = corner + Vector2::splat(center_distance) * bisector;
+
+            let turn = (corner - previous).x * (next - corner).y
+                - (corner - previous).y * (next - corner).x;
+            let direction = if turn > 0.0 {
+                Direction::Counterclockwise
+            } else {
+                Direction::Clockwise
+            };
+
+            corners[index + 1] = Some((trim, center, direction));
+        }
+
+        let trim = |index: usize| corners[index].map_or(0.0, |(trim, _, _)| trim);
+
+        for index in 1..points.len() {
+            let length = points[index - 1].distance_to(points[index]);
+
+            if trim(index - 1) + trim(index) > length + DEFAULT_EPSILON {
+                return Err(CncError::CornerRadiusTooLarge {
+                    corner_radius,
+                    from: points[index - 1],
+                    to: points[index],
+                });
+            }
+        }
+
+        let mut segments = Vec::new();
+        let mut position = points.first().copied();
+
+        for (index, point) in points.iter().enumerate().skip(1) {
+            let from = position.unwrap_or(*point);
+            let direction = (*point - from).normalized();
+
+            match corners[index] {
+                Some((trim, center, arc_direction)) => {
+                    let arc_from = *point - Vector2::splat(trim) * direction;
+                    let arc_to =
+                        *point + Vector2::splat(trim) * (points[index + 1] - *point).normalized();
+
+                    if !from.approx_eq(arc_from, DEFAULT_EPSILON) {
+                        segments.push(Segment::line(from, arc_from));
+                    }
+                    segments.push(Segment::arc(arc_from, arc_to, center, arc_direction));
+                    position = Some(arc_to);
+                }
+                None => {
+                    if !from.approx_eq(*point, DEFAULT_EPSILON) {
+                        segments.push(Segment::line(from, *point));
+                    }
+                    position = Some(*point);
+                }
+            }
+        }
+
+        Ok(Self::new(start, segments, end_z, max_step_z))
+    }
+
+    /// Returns the `Path` with the depth mode changed, see [DepthMode](enum.DepthMode.html).
+    #[must_use]
+    pub fn with_depth_mode(mut self, depth_mode: DepthMode) -> Self {
+        self.depth_mode = depth_mode;
+        self
+    }
+
+    /// Checks that the segments describe a valid path, which is useful for finding errors when
+    /// building a path instead of when the program is converted to G-code.
+    ///
+    /// Returns error with the index of the offending segment if an arc segment does not have
+    /// the same distance from its center to the from and to points, or if a line or arc
+    /// segment does not start where the previous segment ends. Gaps between segments can be
+    /// closed with [connect_gaps](struct.Path.html#method.connect_gaps).
+    pub fn validate(&self) -> Result<(), CncError> {
+        let mut previous_end: Option<Vector2> = None;
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            let (from, to) = match segment {
+                Segment::Arc(arc) => {
+                    let distance_from = arc.from.distance_to(arc.center);
+                    let distance_to = arc.to.distance_to(arc.center);
+
+                    if (distance_from - distance_to).abs() > DEFAULT_EPSILON {
+                        return Err(CncError::ArcRadiusMismatch {
+                            segment: Some(index),
+                            distance_from,
+                            distance_to,
+                        });
+                    }
+
+                    (Some(arc.from), arc.to)
+                }
+                Segment::Line(line) => (Some(line.from), line.to),
+                Segment::Point(point) => (None, *point),
+            };
+
+            if let (Some(from), Some(previous_end)) = (from, previous_end) {
+                if !from.approx_eq(previous_end, DEFAULT_EPSILON) {
+                    return Err(CncError::SegmentGap {
+                        segment: index,
+                        start: from,
+                        previous_end,
+                    });
+                }
+            }
+
+            previous_end = Some(to);
+        }
+
+        Ok(())
+    }
+
+    /// Inserts line segments between any line or arc segment that does not start where the
+    /// previous segment ends, so that the tool follows a straight line over the gap.
+    pub fn connect_gaps(&mut self) {
+        let mut segments = Vec::with_capacity(self.segments.len());
+        let mut previous_end: Option<Vector2> = None;
+
+        for segment in self.segments.drain(..) {
+            let (from, to) = match &segment {
+                Segment::Arc(arc) => (Some(arc.from), arc.to),
+                Segment::Line(line) => (Some(line.from), line.to),
+                Segment::Point(point) => (None, *point),
+            };
+
+            if let (Some(from), Some(previous_end)) = (from, previous_end) {
+                if !from.approx_eq(previous_end, DEFAULT_EPSILON) {
+                    segments.push(Segment::line(previous_end, from));
+                }
+            }
+
+            segments.push(segment);
+            previous_end = Some(to);
+        }
+
+        self.segments = segments;
+    }
+
+    /// Returns a new `Path` with the transform applied to its start coordinate and segments.
+    /// Mirroring flips the direction of arc segments so that they stay valid, note that the
+    /// mirrored path will also be cut in the opposite rotational direction.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Line(line) => Segment::Line(Line2D {
+                    from: transform.apply_offset(line.from),
+                    to: transform.apply_offset(line.to),
+                    feed_rate: line.feed_rate,
+                }),
+                Segment::Arc(arc) => Segment::Arc(Arc2D {
+                    from: transform.apply_offset(arc.from),
+                    to: transform.apply_offset(arc.to),
+                    center: transform.apply_offset(arc.center),
+                    axis: arc.axis.clone(),
+                    direction: arc.direction.transformed(transform, &Axis::Z),
+                    feed_rate: arc.feed_rate,
+                }),
+                Segment::Point(point) => Segment::Point(transform.apply_offset(*point)),
+            })
+            .collect();
+
+        Self {
+            start: transform.apply(self.start),
+            segments,
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+            depth_mode: self.depth_mode,
         }
     }
 
+    /// Returns the point where the cut starts, at the start of the first segment.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        let from = match self.segments.first() {
+            Some(Segment::Arc(arc)) => arc.from,
+            Some(Segment::Line(line)) => line.from,
+            Some(Segment::Point(point)) => *point,
+            None => Vector2::ZERO,
+        };
+
+        self.start.add_x(from.x).add_y(from.y)
+    }
+
+    /// Returns the point where the cut ends, at the end of the last segment on the end depth.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        let to = match self.segments.last() {
+            Some(Segment::Arc(arc)) => arc.to,
+            Some(Segment::Line(line)) => line.to,
+            Some(Segment::Point(point)) => *point,
+            None => Vector2::ZERO,
+        };
+
+        self.start.add_x(to.x).add_y(to.y).with_z(self.end_z)
+    }
+
     /// Returns the bounds of the cut.
     #[must_use]
     pub fn bounds(&self) -> Bounds {
@@ -304,8 +643,21 @@ impl Path {
         bounds
     }
 
+    /// Returns the z depths of the passes around the path. With the ramped depth mode each
+    /// pass ramps down to its depth, followed by a flat pass at `end_z`.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self) -> Result<Vec<f64>> {
+        let mut depths = depth_layers(self.start.z, self.end_z, step_z(self.max_step_z)?);
+        if self.depth_mode == DepthMode::Ramp {
+            depths.push(self.end_z);
+        }
+
+        Ok(depths)
+    }
+
     /// Converts the struct to G-code instructions.
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
         let mut instructions = vec![];
 
         if self.segments.is_empty() {
@@ -373,31 +725,69 @@ impl Path {
             last_point = end;
         }
 
-        let max_step_z = self.max_step_z.abs();
+        let depths = depth_layers(self.start.z, self.end_z, step_z(self.max_step_z)?);
+        let mut feed_rate = context.tool().feed_rate();
 
-        let layers = ((self.start.z - self.end_z) / max_step_z).floor() as u32;
-        let mut start_z = self.start.z;
+        if self.depth_mode == DepthMode::Constant {
+            for (index, z) in depths.into_iter().enumerate() {
+                // Return to the start of open paths before plunging to the next depth
+                if index > 0 && last_point != start.xy() - self.start.xy() {
+                    instructions.push(Instruction::G1(G1 {
+                        x: Some(start.x),
+                        y: Some(start.y),
+                        z: None,
+                        f: None,
+                    }));
+                }
 
-        for _layer in 0..layers {
-            let end_z = start_z - max_step_z;
+                instructions.push(Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(z),
+                    f: None,
+                }));
 
+                instructions.append(&mut self.segments_to_instructions(
+                    context,
+                    z,
+                    z,
+                    &distances,
+                    total_distance,
+                    &mut feed_rate,
+                )?);
+            }
+
+            instructions.push(Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }));
+
+            return Ok(instructions);
+        }
+
+        // Each pass ramps down to its depth, the flat pass at the end depth cuts away the ramp
+        let mut start_z = self.start.z;
+        for end_z in depths {
             instructions.append(&mut self.segments_to_instructions(
-                context.units(),
+                context,
                 start_z,
                 end_z,
                 &distances,
                 total_distance,
+                &mut feed_rate,
             )?);
 
             start_z = end_z;
         }
 
         instructions.append(&mut self.segments_to_instructions(
-            context.units(),
+            context,
             self.end_z,
             self.end_z,
             &distances,
             total_distance,
+            &mut feed_rate,
         )?);
 
         instructions.push(Instruction::G0(G0 {
@@ -409,13 +799,16 @@ impl Path {
         Ok(instructions)
     }
 
+    // Converts the segments to instructions for one layer, `feed_rate` keeps track of the
+    // current feed rate so that it is only changed when a segment needs a different one.
     fn segments_to_instructions(
         &self,
-        units: Units,
+        context: &InnerContext,
         start_z: f64,
         end_z: f64,
         distances: &[f64],
         total_distance: f64,
+        feed_rate: &mut f64,
     ) -> Result<Vec<Instruction>> {
         let mut instructions = Vec::new();
         let mut from_z = start_z;
@@ -424,19 +817,31 @@ impl Path {
             let distance = distances[index];
             let to_z = from_z - distance / total_distance * (start_z - end_z);
 
+            let segment_feed_rate = match segment {
+                Segment::Arc(arc) => arc.feed_rate,
+                Segment::Line(line) => line.feed_rate,
+                Segment::Point(_) => None,
+            }
+            .unwrap_or(context.tool().feed_rate());
+            let f = if segment_feed_rate == *feed_rate {
+                None
+            } else {
+                *feed_rate = segment_feed_rate;
+                Some(segment_feed_rate)
+            };
+
             match segment {
                 Segment::Arc(arc) => {
                     let distance_from = arc.from.distance_to(arc.center);
                     let distance_to = arc.to.distance_to(arc.center);
 
-                    if (distance_from - distance_to).abs() > 0.0001 {
-                        return Err(anyhow!(
-                            "Arc distances from/center ({} {}) and to/center ({} {}) must be equal",
+                    if (distance_from - distance_to).abs() > DEFAULT_EPSILON {
+                        return Err(CncError::ArcRadiusMismatch {
+                            segment: Some(index),
                             distance_from,
-                            units,
                             distance_to,
-                            units,
-                        ));
+                        }
+                        .into());
                     }
 
                     instructions.push(Instruction::G1(G1 {
@@ -469,7 +874,7 @@ impl Path {
                                 k: None,
                                 r: None,
                                 p: None,
-                                f: None,
+                                f,
                             }));
                         }
                         Direction::Counterclockwise => {
@@ -482,7 +887,7 @@ impl Path {
                                 k: None,
                                 r: None,
                                 p: None,
-                                f: None,
+                                f,
                             }));
                         }
                     }
@@ -501,7 +906,7 @@ impl Path {
                         x: Some(self.start.x + line.to.x),
                         y: Some(self.start.y + line.to.y),
                         z: Some(to_z),
-                        f: None,
+                        f,
                     }));
                 }
                 Segment::Point(point) => {
@@ -509,7 +914,7 @@ impl Path {
                         x: Some(self.start.x + point.x),
                         y: Some(self.start.y + point.y),
                         z: Some(to_z),
-                        f: None,
+                        f,
                     }));
                 }
             }
@@ -520,3 +925,403 @@ impl Path {
         Ok(instructions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    #[test]
+    fn test_path_segment_feed_rate() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+
+        let path = Path::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            vec![
+                Segment::line(Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0)),
+                Segment::arc_with_feed(
+                    Vector2::new(10.0, 0.0),
+                    Vector2::new(10.0, 10.0),
+                    Vector2::new(10.0, 5.0),
+                    Direction::Counterclockwise,
+                    100.0,
+                ),
+                Segment::line_with_feed(Vector2::new(10.0, 10.0), Vector2::new(0.0, 10.0), 100.0),
+                Segment::point(0.0, 0.0),
+            ],
+            -1.0,
+            1.0,
+        );
+
+        let feed_rates = path
+            .to_instructions(&context)?
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G1(G1 { f: Some(f), .. })
+                | Instruction::G2(G2 { f: Some(f), .. })
+                | Instruction::G3(G3 { f: Some(f), .. }) => Some(f),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // The plunge, then slower on the arc and the following line, and back to the tool
+        // feed rate on the point, for each of the two layers
+        assert_eq!(feed_rates, vec![300.0, 100.0, 300.0, 100.0, 300.0]);
+
+        let transformed = path.transform(&Transform::MirrorX);
+        assert!(matches!(
+            &transformed.segments[1],
+            Segment::Arc(Arc2D {
+                feed_rate: Some(_),
+                direction: Direction::Clockwise,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    fn z_values(path: &Path, context: &InnerContext) -> Result<Vec<f64>> {
+        Ok(path
+            .to_instructions(context)?
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G1(G1 { z, .. }) => z,
+                _ => None,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_path_depth_mode() -> Result<()> {
+        let tool = Tool::default();
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+
+        let path = Path::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Segment::points(vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(10.0, 0.0),
+                Vector2::new(10.0, 10.0),
+                Vector2::new(0.0, 0.0),
+            ]),
+            -2.5,
+            1.0,
+        );
+
+        // Ramping passes has depths in between the layers
+        let ramp = z_values(&path, &context)?;
+        assert!(ramp.iter().any(|z| *z > -1.0 && *z < 0.0));
+
+        let path = path.with_depth_mode(DepthMode::Constant);
+        let constant = z_values(&path, &context)?;
+        assert_eq!(
+            constant,
+            vec![
+                0.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.5, -2.5, -2.5,
+                -2.5, -2.5
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_constant_depth_open_path() -> Result<()> {
+        let tool = Tool::default();
+        let context = InnerContext::new(Units::Metric, &tool, 5.0, 50.0);
+
+        let path = Path::new(
+            Vector3::new(5.0, 5.0, 0.0),
+            vec![Segment::line(
+                Vector2::new(0.0, 0.0),
+                Vector2::new(10.0, 0.0),
+            )],
+            -2.0,
+            1.0,
+        )
+        .with_depth_mode(DepthMode::Constant);
+
+        let instructions = path.to_instructions(&context)?;
+        let returns = instructions
+            .iter()
+            .filter(|instruction| {
+                matches!(
+                    instruction,
+                    Instruction::G1(G1 {
+                        x: Some(x),
+                        y: Some(y),
+                        z: None,
+                        ..
+                    }) if *x == 5.0 && *y == 5.0
+                )
+            })
+            .count();
+        assert_eq!(returns, 1);
+        assert_eq!(z_values(&path, &context)?.last(), Some(&-2.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_validate() {
+        let path = Path::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            vec![
+                Segment::line(Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0)),
+                Segment::arc(
+                    Vector2::new(10.0, 0.0),
+                    Vector2::new(10.0, 10.0),
+                    Vector2::new(10.0, 5.0),
+                    Direction::Counterclockwise,
+                ),
+                Segment::point(0.0, 10.0),
+                Segment::line(Vector2::new(0.0, 10.0), Vector2::new(0.0, 0.0)),
+            ],
+            -1.0,
+            1.0,
+        );
+        assert!(path.validate().is_ok());
+
+        let mut invalid = path.clone();
+        invalid.segments[1] = Segment::arc(
+            Vector2::new(10.0, 0.0),
+            Vector2::new(10.0, 10.0),
+            Vector2::new(10.0, 4.0),
+            Direction::Counterclockwise,
+        );
+        let error = invalid.validate().unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::ArcRadiusMismatch {
+                segment: Some(1),
+                ..
+            }
+        ));
+        assert!(
+            error.to_string().starts_with("Arc segment 1 distances"),
+            "{}",
+            error
+        );
+
+        let mut invalid = path;
+        invalid.segments[3] = Segment::line(Vector2::new(0.0, 9.0), Vector2::new(0.0, 0.0));
+        let error = invalid.validate().unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::SegmentGap {
+                segment: 3,
+                start,
+                previous_end,
+            } if start == Vector2::new(0.0, 9.0) && previous_end == Vector2::new(0.0, 10.0)
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Segment 3 starts at {x: 0, y: 9} but segment 2 ends at {x: 0, y: 10}"
+        );
+    }
+
+    #[test]
+    fn test_segment_try_arc() {
+        let from = Vector2::new(0.0, 0.0);
+        let to = Vector2::new(10.0, 0.0);
+
+        let valid = Arc2D::new(
+            from,
+            to,
+            Vector2::new(5.0, 0.0),
+            Axis::Z,
+            Direction::Clockwise,
+        );
+        assert!(valid.is_valid(DEFAULT_EPSILON));
+        assert_eq!(valid.radius(), 5.0);
+        assert!(Segment::try_arc(from, to, Vector2::new(5.0, 0.0), Direction::Clockwise).is_ok());
+
+        let invalid = Arc2D::new(
+            from,
+            to,
+            Vector2::new(4.0, 0.0),
+            Axis::Z,
+            Direction::Clockwise,
+        );
+        assert!(!invalid.is_valid(DEFAULT_EPSILON));
+        assert!(invalid.is_valid(2.0));
+        assert!(matches!(
+            Segment::try_arc(from, to, Vector2::new(4.0, 0.0), Direction::Clockwise).unwrap_err(),
+            CncError::ArcRadiusMismatch {
+                segment: None,
+                distance_from,
+                distance_to,
+            } if distance_from == 4.0 && distance_to == 6.0
+        ));
+        assert!(Segment::arc_x(
+            from,
+            to,
+            Vector2::new(4.0, 0.0),
+            Direction::Counterclockwise
+        )
+        .validated()
+        .is_err());
+        assert!(Segment::line(from, to).validated().is_ok());
+    }
+
+    #[test]
+    fn test_path_validate_tolerance() {
+        let path = |gap: f64, center_offset: f64| {
+            Path::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                vec![
+                    Segment::line(Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0)),
+                    Segment::arc(
+                        Vector2::new(10.0 + gap, 0.0),
+                        Vector2::new(10.0 + gap, 10.0),
+                        Vector2::new(10.0 + gap, 5.0 + center_offset),
+                        Direction::Counterclockwise,
+                    ),
+                ],
+                -1.0,
+                1.0,
+            )
+        };
+
+        assert!(path(0.00009, 0.0).validate().is_ok());
+        assert!(path(0.0, 0.00004).validate().is_ok());
+        assert!(matches!(
+            path(0.00011, 0.0).validate(),
+            Err(CncError::SegmentGap { segment: 1, .. })
+        ));
+        assert!(matches!(
+            path(0.0, 0.00006).validate(),
+            Err(CncError::ArcRadiusMismatch {
+                segment: Some(1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_path_connect_gaps() -> Result<()> {
+        let mut path = Path::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            vec![
+                Segment::line(Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0)),
+                Segment::line(Vector2::new(10.0, 2.0), Vector2::new(10.0, 10.0)),
+                Segment::arc(
+                    Vector2::new(10.0, 10.0),
+                    Vector2::new(0.0, 10.0),
+                    Vector2::new(5.0, 10.0),
+                    Direction::Counterclockwise,
+                ),
+                Segment::point(0.0, 5.0),
+                Segment::line(Vector2::new(0.0, 4.0), Vector2::new(0.0, 0.0)),
+            ],
+            -1.0,
+            1.0,
+        );
+        assert!(path.validate().is_err());
+
+        path.connect_gaps();
+        path.validate()?;
+
+        assert_eq!(path.segments.len(), 7);
+        assert!(matches!(
+            &path.segments[1],
+            Segment::Line(Line2D { from, to, .. })
+                if *from == Vector2::new(10.0, 0.0) && *to == Vector2::new(10.0, 2.0)
+        ));
+        assert!(matches!(
+            &path.segments[5],
+            Segment::Line(Line2D { from, to, .. })
+                if *from == Vector2::new(0.0, 5.0) && *to == Vector2::new(0.0, 4.0)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_from_points_smooth() -> Result<()> {
+        let path = Path::from_points_smooth(
+            Vector3::new(0.0, 0.0, 0.0),
+            &[
+                Vector2::new(0.0, 0.0),
+                Vector2::new(10.0, 0.0),
+                Vector2::new(10.0, 10.0),
+            ],
+            2.0,
+            -1.0,
+            1.0,
+        )?;
+        path.validate()?;
+
+        assert_eq!(path.segments.len(), 3);
+        assert!(matches!(
+            &path.segments[0],
+            Segment::Line(Line2D { from, to, .. })
+                if *from == Vector2::new(0.0, 0.0) && to.approx_eq(Vector2::new(8.0, 0.0), 1e-9)
+        ));
+        assert!(matches!(
+            &path.segments[1],
+            Segment::Arc(Arc2D { from, to, center, direction: Direction::Counterclockwise, .. })
+                if from.approx_eq(Vector2::new(8.0, 0.0), 1e-9)
+                    && to.approx_eq(Vector2::new(10.0, 2.0), 1e-9)
+                    && center.approx_eq(Vector2::new(8.0, 2.0), 1e-9)
+        ));
+        assert!(matches!(
+            &path.segments[2],
+            Segment::Line(Line2D { from, to, .. })
+                if from.approx_eq(Vector2::new(10.0, 2.0), 1e-9) && *to == Vector2::new(10.0, 10.0)
+        ));
+
+        // Turning right gives a clockwise arc
+        let path = Path::from_points_smooth(
+            Vector3::new(0.0, 0.0, 0.0),
+            &[
+                Vector2::new(0.0, 0.0),
+                Vector2::new(10.0, 0.0),
+                Vector2::new(10.0, -10.0),
+            ],
+            2.0,
+            -1.0,
+            1.0,
+        )?;
+        assert!(matches!(
+            &path.segments[1],
+            Segment::Arc(Arc2D { direction: Direction::Clockwise, center, .. })
+                if center.approx_eq(Vector2::new(8.0, -2.0), 1e-9)
+        ));
+
+        let error = Path::from_points_smooth(
+            Vector3::new(0.0, 0.0, 0.0),
+            &[
+                Vector2::new(0.0, 0.0),
+                Vector2::new(10.0, 0.0),
+                Vector2::new(10.0, 10.0),
+            ],
+            12.0,
+            -1.0,
+            1.0,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::CornerRadiusTooLarge {
+                corner_radius,
+                from,
+                to,
+            } if corner_radius == 12.0
+                && from == Vector2::new(0.0, 0.0)
+                && to == Vector2::new(10.0, 0.0)
+        ));
+        assert!(error.to_string().contains("too large"), "{}", error);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+use std::f64::consts::{PI, TAU};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cuts::{grow_bounds, Path, Segment};
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+
+/// Cut a regular polygon, such as a hexagon for a nut pocket, as a closed path of straight
+/// segments.
+///
+/// The `radius` is measured from the center to the vertices, so the polygon is inscribed in
+/// a circle of the radius. The tool compensation moves the flats of the polygon by the tool
+/// radius, keeping the vertices sharp.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Polygon {
+    /// Center point in 3D space, the z value is the start depth of the cut.
+    pub center: Vector3,
+    /// Distance from the center to the vertices.
+    pub radius: f64,
+    /// Number of sides, must be at least 3.
+    pub sides: u32,
+    /// Rotation in radians of the first vertex, counterclockwise from the x axis.
+    pub rotation: f64,
+    /// The end depth of the cut on the z axis.
+    pub end_z: f64,
+    /// The maximum depth to cut on the z axis on each pass.
+    pub max_step_z: f64,
+    /// Indicates how a path should be compensated by the radius of the tool.
+    /// `ToolPathCompensation::Inner` is useful for cutting polygon pockets,
+    /// `ToolPathCompensation::Outer` is useful for cutting out polygon pieces.
+    pub compensation: ToolPathCompensation,
+}
+
+impl Polygon {
+    /// Creates a new `Polygon` struct.
+    #[must_use]
+    pub fn new(
+        center: Vector3,
+        radius: f64,
+        sides: u32,
+        rotation: f64,
+        end_z: f64,
+        max_step_z: f64,
+        compensation: ToolPathCompensation,
+    ) -> Self {
+        Self {
+            center,
+            radius,
+            sides,
+            rotation,
+            end_z,
+            max_step_z,
+            compensation,
+        }
+    }
+
+    /// Returns the distance from the center to the middle of the flats.
+    #[must_use]
+    pub fn apothem(&self) -> f64 {
+        self.radius * (PI / f64::from(self.sides)).cos()
+    }
+
+    /// Returns the vertices of the polygon, relative to the center, for a given vertex radius.
+    fn vertices(&self, radius: f64) -> Vec<Vector2> {
+        (0..self.sides)
+            .map(|index| {
+                let angle = self.rotation + TAU * f64::from(index) / f64::from(self.sides);
+                Vector2::from_polar(radius, angle)
+            })
+            .collect()
+    }
+
+    /// Returns a new `Polygon` with the transform applied to its center, radius, and rotation.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let rotation = match transform {
+            Transform::RotateZ { angle, .. } => self.rotation + angle,
+            Transform::MirrorX => PI - self.rotation,
+            Transform::MirrorY => -self.rotation,
+            Transform::Translate(_) | Transform::Scale(_) => self.rotation,
+        };
+
+        Self {
+            center: transform.apply(self.center),
+            radius: transform.apply_length(self.radius),
+            sides: self.sides,
+            rotation,
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+            compensation: self.compensation.clone(),
+        }
+    }
+
+    /// Returns the point where the cut starts, at the first vertex. The tool compensation is
+    /// not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        let vertex = Vector2::from_polar(self.radius, self.rotation);
+        self.center.add_x(vertex.x).add_y(vertex.y)
+    }
+
+    /// Returns the point where the cut ends, at the first vertex on the end depth.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.entry_point().with_z(self.end_z)
+    }
+
+    /// Returns the bounds of the cut.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::minmax();
+
+        for vertex in self.vertices(self.radius) {
+            let point = self.center.add_x(vertex.x).add_y(vertex.y);
+            bounds = bounds.union(&Bounds {
+                min: point.with_z(self.end_z),
+                max: point,
+            });
+        }
+
+        bounds
+    }
+
+    /// Returns the bounds of the area swept by a tool of the given radius, the path that the
+    /// tool center follows grown by the tool radius. Polygons that are too small for the tool
+    /// return their nominal bounds.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        self.to_path(tool_radius).map_or_else(
+            |_| self.bounds(),
+            |path| grow_bounds(path.bounds(), tool_radius),
+        )
+    }
+
+    /// Returns the closed path that is cut for a tool of the given radius, with the tool
+    /// compensation applied.
+    ///
+    /// Returns error if the polygon has less than 3 sides, or if it is too small for the tool.
+    pub fn to_path(&self, tool_radius: f64) -> Result<Path> {
+        if self.sides < 3 {
+            return Err(anyhow!(
+                "Unable to cut polygon, it must have at least 3 sides, got {}",
+                self.sides
+            ));
+        }
+
+        let apothem = match self.compensation {
+            ToolPathCompensation::None => self.apothem(),
+            ToolPathCompensation::Inner => self.apothem() - tool_radius,
+            ToolPathCompensation::Outer => self.apothem() + tool_radius,
+        };
+
+        if apothem <= 0.0 {
+            return Err(anyhow!(
+                "Unable to cut polygon of radius {:.2}, it is too small for the tool radius {:.2}",
+                self.radius,
+                tool_radius
+            ));
+        }
+
+        let vertices = self.vertices(apothem / (PI / f64::from(self.sides)).cos());
+        let segments = vertices
+            .iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .map(|(from, to)| Segment::line(*from, *to))
+            .collect();
+
+        Ok(Path::new(
+            self.center,
+            segments,
+            self.end_z,
+            self.max_step_z,
+        ))
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        self.to_path(context.tool().radius())?
+            .to_instructions(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment_lengths(path: &Path) -> Vec<f64> {
+        path.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Line(line) => line.from.distance_to(line.to),
+                _ => panic!("Expected line segment"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_polygon_hexagon() -> Result<()> {
+        let polygon = Polygon::new(
+            Vector3::new(10.0, 10.0, 0.0),
+            10.0,
+            6,
+            0.0,
+            -3.0,
+            1.0,
+            ToolPathCompensation::None,
+        );
+
+        let path = polygon.to_path(2.0)?;
+        let lengths = segment_lengths(&path);
+
+        assert_eq!(lengths.len(), 6);
+        for length in lengths {
+            // The sides of a hexagon are as long as its radius
+            assert!((length - 10.0).abs() < 1e-9);
+        }
+
+        let bounds = polygon.bounds();
+        assert!((bounds.min.x - 0.0).abs() < 1e-9);
+        assert!((bounds.max.x - 20.0).abs() < 1e-9);
+        assert!((bounds.max.y - (10.0 + 75f64.sqrt())).abs() < 1e-9);
+        assert_eq!(bounds.min.z, -3.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_polygon_compensation() -> Result<()> {
+        let polygon = |compensation| {
+            Polygon::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                10.0,
+                4,
+                PI / 4.0,
+                -1.0,
+                1.0,
+                compensation,
+            )
+        };
+
+        // A square with 10 mm vertex radius has a side of 2 * 10 * cos(45°)
+        let side = 20.0 * (PI / 4.0).cos();
+
+        let inner = segment_lengths(&polygon(ToolPathCompensation::Inner).to_path(2.0)?);
+        assert!(inner
+            .iter()
+            .all(|length| (length - (side - 4.0)).abs() < 1e-9));
+
+        let outer = segment_lengths(&polygon(ToolPathCompensation::Outer).to_path(2.0)?);
+        assert!(outer
+            .iter()
+            .all(|length| (length - (side + 4.0)).abs() < 1e-9));
+
+        assert!(polygon(ToolPathCompensation::Inner).to_path(8.0).is_err());
+
+        Ok(())
+    }
+}
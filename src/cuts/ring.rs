@@ -0,0 +1,307 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cuts::step_z;
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// Clear a ring shaped groove between two radii with full circles, stepping outward from the
+/// inner radius to the outer radius on each layer, useful for O-ring grooves.
+///
+/// Unlike [Circle](struct.Circle.html), which only cuts a single wall, and
+/// [CircularPocket](struct.CircularPocket.html), which clears the whole disc, the ring only
+/// removes the material between the two radii. The tool path is always compensated so that
+/// the groove gets the given radii.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Ring {
+    /// Center point in 3D space, the z value is the start depth of the cut.
+    pub center: Vector3,
+    /// The radius of the inner wall of the groove.
+    pub inner_radius: f64,
+    /// The radius of the outer wall of the groove.
+    pub outer_radius: f64,
+    /// The end depth of the cut on the z axis.
+    pub end_z: f64,
+    /// The maximum depth to cut on the z axis on each pass.
+    pub max_step_z: f64,
+    /// The maximum distance between the circles of a layer, limited to the tool diameter.
+    pub step_over: f64,
+}
+
+impl Ring {
+    /// Creates a new `Ring` struct.
+    #[must_use]
+    pub fn new(
+        center: Vector3,
+        inner_radius: f64,
+        outer_radius: f64,
+        end_z: f64,
+        max_step_z: f64,
+        step_over: f64,
+    ) -> Self {
+        Self {
+            center,
+            inner_radius,
+            outer_radius,
+            end_z,
+            max_step_z,
+            step_over,
+        }
+    }
+
+    /// Returns a new `Ring` with the transform applied to its coordinates and radii.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        Self {
+            center: transform.apply(self.center),
+            inner_radius: transform.apply_length(self.inner_radius),
+            outer_radius: transform.apply_length(self.outer_radius),
+            end_z: transform.apply_z(self.end_z),
+            max_step_z: self.max_step_z,
+            step_over: self.step_over,
+        }
+    }
+
+    /// Returns the point where the cut starts, on the inner wall of the groove.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        self.center.add_x(self.inner_radius)
+    }
+
+    /// Returns the point where the cut ends, on the outer wall of the groove at the end depth.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.center.add_x(self.outer_radius).with_z(self.end_z)
+    }
+
+    /// Returns the bounds of the cut.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        Bounds {
+            min: Vector3::new(
+                self.center.x - self.outer_radius,
+                self.center.y - self.outer_radius,
+                self.end_z,
+            ),
+            max: Vector3::new(
+                self.center.x + self.outer_radius,
+                self.center.y + self.outer_radius,
+                self.center.z,
+            ),
+        }
+    }
+
+    /// Returns the bounds of the area swept by a tool of the given radius. The outermost
+    /// circle keeps the tool inside of the outer wall, so the groove itself is swept.
+    #[must_use]
+    pub fn compensated_bounds(&self, _tool_radius: f64) -> Bounds {
+        self.bounds()
+    }
+
+    /// Returns the z depths of the layers that the groove is cleared at.
+    ///
+    /// Returns error if `max_step_z` is zero or not finite.
+    pub fn layers(&self) -> Result<Vec<f64>> {
+        Ok(depth_layers(
+            self.center.z,
+            self.end_z,
+            step_z(self.max_step_z)?,
+        ))
+    }
+
+    /// Returns the radii of the circles cut on each layer for a tool of the given radius, from
+    /// the inner wall out to the outer wall, evenly spaced at most `step_over` apart.
+    ///
+    /// Returns error if the groove is narrower than the tool, or if `step_over` is not larger
+    /// than 0.
+    pub fn pass_radii(&self, tool_radius: f64) -> Result<Vec<f64>> {
+        let min_radius = self.inner_radius + tool_radius;
+        let max_radius = self.outer_radius - tool_radius;
+
+        if max_radius < min_radius - DEFAULT_EPSILON {
+            return Err(anyhow!(
+                "Unable to cut ring, the groove width {} is narrower than the tool diameter {}",
+                round_precision(self.outer_radius - self.inner_radius),
+                round_precision(tool_radius * 2.0)
+            ));
+        }
+
+        if self.step_over <= 0.0 {
+            return Err(CncError::InvalidStepOver {
+                feature: "ring",
+                step_over: self.step_over,
+            }
+            .into());
+        }
+
+        let width = (max_radius - min_radius).max(0.0);
+        let step_over = self.step_over.min(tool_radius * 2.0);
+        let steps = (width / step_over - DEFAULT_EPSILON).ceil().max(0.0) as u32;
+
+        if steps == 0 {
+            return Ok(vec![min_radius]);
+        }
+
+        Ok((0..=steps)
+            .map(|step| min_radius + width * f64::from(step) / f64::from(steps))
+            .collect())
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let tool = context.tool();
+
+        if self.outer_radius - self.inner_radius < tool.diameter() - DEFAULT_EPSILON {
+            return Err(CncError::ToolWiderThanFeature {
+                feature: "ring",
+                feature_size: self.outer_radius - self.inner_radius,
+                tool_diameter: tool.diameter(),
+                units: context.units(),
+            });
+        }
+
+        let radii = self.pass_radii(tool.radius())?;
+
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Cut ring at: x = {}, y = {}, inner_radius = {}, outer_radius = {}",
+                    round_precision(self.center.x),
+                    round_precision(self.center.y),
+                    round_precision(self.inner_radius),
+                    round_precision(self.outer_radius)
+                ),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(self.center.x + radii[0]),
+                y: Some(self.center.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(self.center.z),
+                f: Some(tool.feed_rate()),
+            }),
+        ];
+
+        for z in self.layers()? {
+            // Back to the inner circle through the groove cleared on the previous layer
+            instructions.append(&mut vec![
+                Instruction::G1(G1 {
+                    x: Some(self.center.x + radii[0]),
+                    y: Some(self.center.y),
+                    z: None,
+                    f: None,
+                }),
+                Instruction::G1(G1 {
+                    x: None,
+                    y: None,
+                    z: Some(z),
+                    f: None,
+                }),
+            ]);
+
+            for radius in &radii {
+                instructions.push(Instruction::G1(G1 {
+                    x: Some(self.center.x + radius),
+                    y: None,
+                    z: None,
+                    f: None,
+                }));
+
+                instructions.push(Instruction::G2(G2 {
+                    x: Some(self.center.x + radius),
+                    y: Some(self.center.y),
+                    z: None,
+                    i: Some(-radius),
+                    j: Some(0.0),
+                    k: None,
+                    r: None,
+                    p: None,
+                    f: None,
+                }));
+            }
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
+        Ok(instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    fn context() -> InnerContext {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+
+        InnerContext::new(Units::Metric, &tool, 5.0, 50.0)
+    }
+
+    #[test]
+    fn test_ring() -> Result<()> {
+        let ring = Ring::new(Vector3::new(20.0, 30.0, 0.0), 10.0, 20.0, -2.0, 1.0, 2.0);
+
+        // The tool center stays a tool radius away from both walls
+        assert_eq!(ring.pass_radii(2.0)?, vec![12.0, 14.0, 16.0, 18.0]);
+
+        let instructions = ring.to_instructions(&context())?;
+        let radii = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G2(arc) => Some(-arc.i.unwrap()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(radii, vec![12.0, 14.0, 16.0, 18.0, 12.0, 14.0, 16.0, 18.0]);
+
+        let bounds = ring.bounds();
+        assert_eq!(bounds.min, Vector3::new(0.0, 10.0, -2.0));
+        assert_eq!(bounds.max, Vector3::new(40.0, 50.0, 0.0));
+        assert_eq!(ring.compensated_bounds(2.0), bounds);
+
+        // A groove as wide as the tool is cut with a single circle
+        let ring = Ring::new(Vector3::new(0.0, 0.0, 0.0), 10.0, 14.0, -1.0, 1.0, 2.0);
+        assert_eq!(ring.pass_radii(2.0)?, vec![12.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ring_too_narrow_for_tool() {
+        let ring = Ring::new(Vector3::new(0.0, 0.0, 0.0), 10.0, 13.0, -1.0, 1.0, 1.0);
+        let error = ring.to_instructions(&context()).unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::ToolWiderThanFeature {
+                feature: "ring",
+                feature_size,
+                tool_diameter,
+                units: Units::Metric,
+            } if feature_size == 3.0 && tool_diameter == 4.0
+        ));
+    }
+}
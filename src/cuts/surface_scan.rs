@@ -0,0 +1,585 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cuts::transform_rectangle;
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::tools::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// A grid of surface z heights over a rectangular xy region, used as input for
+/// [SurfaceScan](struct.SurfaceScan.html) cuts.
+///
+/// The heights are given as rows, where the rows are spread evenly along the y axis from
+/// `start.y` to `start.y + size.y` and the heights in each row are spread evenly along the
+/// x axis from `start.x` to `start.x + size.x`. The height between the grid points is
+/// interpolated bilinearly.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Heightmap {
+    /// The xy position of the first height in the first row.
+    pub start: Vector2,
+    /// The size of the region covered by the grid.
+    pub size: Vector2,
+    /// The surface z heights, as rows along the y axis.
+    pub heights: Vec<Vec<f64>>,
+}
+
+impl Heightmap {
+    /// Creates a new `Heightmap` struct.
+    #[must_use]
+    pub fn new(start: Vector2, size: Vector2, heights: Vec<Vec<f64>>) -> Self {
+        Self {
+            start,
+            size,
+            heights,
+        }
+    }
+
+    /// Returns the number of grid points along the x and y axis.
+    #[must_use]
+    pub fn grid_size(&self) -> (usize, usize) {
+        (self.heights.first().map_or(0, Vec::len), self.heights.len())
+    }
+
+    /// Returns error if the grid has less than 2 x 2 points, rows of different lengths, heights
+    /// that are not finite, or a size that is not positive.
+    pub fn validate(&self) -> Result<()> {
+        let (columns, rows) = self.grid_size();
+
+        if columns < 2 || rows < 2 {
+            return Err(anyhow!(
+                "Unable to use heightmap, it needs at least 2 x 2 heights, got {} x {}",
+                columns,
+                rows
+            ));
+        }
+
+        if self.heights.iter().any(|row| row.len() != columns) {
+            return Err(anyhow!(
+                "Unable to use heightmap, all rows must have {} heights",
+                columns
+            ));
+        }
+
+        if self
+            .heights
+            .iter()
+            .flatten()
+            .any(|height| !height.is_finite())
+        {
+            return Err(anyhow!(
+                "Unable to use heightmap, all heights must be finite"
+            ));
+        }
+
+        if !(self.size.x > 0.0 && self.size.y > 0.0) {
+            return Err(anyhow!(
+                "Unable to use heightmap, the size must be positive, got {}",
+                self.size
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the interpolated surface height at the xy point, points outside of the grid
+    /// gets the height of the closest edge. Grids with a single row or column are only
+    /// interpolated along the other axis, and an empty grid has the height 0.
+    #[must_use]
+    pub fn height_at(&self, point: Vector2) -> f64 {
+        let (columns, rows) = self.grid_size();
+        if columns == 0 || rows == 0 {
+            return 0.0;
+        }
+
+        let (column_index, next_column, fraction_x) =
+            self.grid_cell(point.x - self.start.x, self.size.x, columns);
+        let (row_index, next_row, fraction_y) =
+            self.grid_cell(point.y - self.start.y, self.size.y, rows);
+
+        let height = |row: usize, column: usize| self.heights[row][column];
+        let near = height(row_index, column_index) * (1.0 - fraction_x)
+            + height(row_index, next_column) * fraction_x;
+        let far = height(next_row, column_index) * (1.0 - fraction_x)
+            + height(next_row, next_column) * fraction_x;
+
+        near * (1.0 - fraction_y) + far * fraction_y
+    }
+
+    // Converts a distance from the start along an axis to the indices of the grid points on
+    // each side of it, and the fraction of the way to the second point. An axis with a single
+    // point uses that point on both sides.
+    fn grid_cell(&self, distance: f64, size: f64, points: usize) -> (usize, usize, f64) {
+        if points < 2 {
+            return (0, 0, 0.0);
+        }
+
+        let position = (distance / size).clamp(0.0, 1.0) * (points - 1) as f64;
+        let index = (position.floor() as usize).min(points - 2);
+
+        (index, index + 1, position - index as f64)
+    }
+
+    /// Returns the distance between the grid points along the x and y axis.
+    #[must_use]
+    pub fn spacing(&self) -> Vector2 {
+        let (columns, rows) = self.grid_size();
+
+        Vector2::new(
+            self.size.x / (columns.max(2) - 1) as f64,
+            self.size.y / (rows.max(2) - 1) as f64,
+        )
+    }
+
+    /// Returns the lowest and highest height in the grid.
+    #[must_use]
+    pub fn height_range(&self) -> (f64, f64) {
+        self.heights
+            .iter()
+            .flatten()
+            .fold((f64::MAX, f64::MIN), |(min, max), height| {
+                (min.min(*height), max.max(*height))
+            })
+    }
+
+    /// Returns a new `Heightmap` with the transform applied, the grid is reordered so that the
+    /// rows still run along the y axis.
+    ///
+    /// Returns error for rotations that are not a multiple of 90°.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        if !transform.is_axis_aligned() {
+            return Err(anyhow!(
+                "Unable to transform heightmap, only rotations in multiples of 90° are supported"
+            ));
+        }
+
+        let (start, size) = transform_rectangle(
+            Vector3::new(self.start.x, self.start.y, 0.0),
+            self.size,
+            transform,
+        );
+
+        // Quarter turns swaps the x and y axis of the grid
+        let (columns, rows) = self.grid_size();
+        let swap_axis = transform.apply_offset(Vector2::new(1.0, 0.0)).x.abs() < 0.5;
+        let (new_columns, new_rows) = if swap_axis {
+            (rows, columns)
+        } else {
+            (columns, rows)
+        };
+
+        let spacing = self.spacing();
+        let new_spacing = Vector2::new(
+            size.x / (new_columns.max(2) - 1) as f64,
+            size.y / (new_rows.max(2) - 1) as f64,
+        );
+
+        let mut heights = vec![vec![0.0; new_columns]; new_rows];
+        for (row_index, row) in self.heights.iter().enumerate() {
+            for (column_index, height) in row.iter().enumerate() {
+                let point = transform.apply(Vector3::new(
+                    self.start.x + spacing.x * column_index as f64,
+                    self.start.y + spacing.y * row_index as f64,
+                    *height,
+                ));
+                let column = ((point.x - start.x) / new_spacing.x).round() as usize;
+                let row = ((point.y - start.y) / new_spacing.y).round() as usize;
+                heights[row.min(new_rows - 1)][column.min(new_columns - 1)] = point.z;
+            }
+        }
+
+        Ok(Self {
+            start: start.xy(),
+            size,
+            heights,
+        })
+    }
+}
+
+/// Top/down 3D finishing cut that follows the surface described by a
+/// [Heightmap](struct.Heightmap.html) with a ballnose tool, for example for relief carving.
+///
+/// The surface is cut with raster passes going back and forth, spaced at most `step_over`
+/// apart. The tool is lifted where needed so that the ball does not cut into the surface next
+/// to the point being cut, the heights are checked at the grid points under the ball.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SurfaceScan {
+    /// The surface to cut.
+    pub heightmap: Heightmap,
+    /// The maximum distance between two passes.
+    pub step_over: f64,
+    /// The axis that the passes are stepped along, with `Axis::Y` the passes run along the
+    /// x axis. `Axis::Z` is treated as `Axis::Y`.
+    pub raster_axis: Axis,
+}
+
+impl SurfaceScan {
+    /// Creates a new `SurfaceScan` struct.
+    #[must_use]
+    pub fn new(heightmap: Heightmap, step_over: f64, raster_axis: Axis) -> Self {
+        Self {
+            heightmap,
+            step_over,
+            raster_axis,
+        }
+    }
+
+    /// Returns a new `SurfaceScan` with the transform applied to the heightmap.
+    ///
+    /// Returns error for rotations that are not a multiple of 90°.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        let heightmap = self.heightmap.transform(transform)?;
+        let swap_axis = transform.apply_offset(Vector2::new(1.0, 0.0)).x.abs() < 0.5;
+        let raster_axis = match (swap_axis, &self.raster_axis) {
+            (true, Axis::X) => Axis::Y,
+            (true, _) => Axis::X,
+            (false, raster_axis) => raster_axis.clone(),
+        };
+
+        Ok(Self {
+            heightmap,
+            step_over: transform.apply_length(self.step_over),
+            raster_axis,
+        })
+    }
+
+    /// Returns the bounds of the heightmap grid.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        let (min_z, max_z) = self.heightmap.height_range();
+        let start = self.heightmap.start;
+        let size = self.heightmap.size;
+
+        Bounds {
+            min: Vector3::new(start.x, start.y, min_z),
+            max: Vector3::new(start.x + size.x, start.y + size.y, max_z),
+        }
+    }
+
+    /// Returns the point where the cut starts, at the start corner of the heightmap. The tool
+    /// offset is not applied.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        let point = self.heightmap.start;
+        Vector3::new(point.x, point.y, self.heightmap.height_at(point))
+    }
+
+    /// Returns the point where the cut ends, at the end of the last pass. The tool offset is
+    /// not applied.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        let passes = self.passes();
+        let point = self.point(passes, if passes.is_multiple_of(2) { 1.0 } else { 0.0 });
+        Vector3::new(point.x, point.y, self.heightmap.height_at(point))
+    }
+
+    // Returns true if the passes run along the y axis.
+    fn along_y(&self) -> bool {
+        matches!(self.raster_axis, Axis::X)
+    }
+
+    // Returns the index of the last pass, the passes are evenly spread over the stepping axis.
+    fn passes(&self) -> u32 {
+        let size = self.heightmap.size;
+        let length = if self.along_y() { size.x } else { size.y };
+
+        (length / self.step_over.abs()).ceil().max(1.0) as u32
+    }
+
+    // Returns the xy point on the pass at the fraction along the pass.
+    fn point(&self, pass: u32, fraction: f64) -> Vector2 {
+        let start = self.heightmap.start;
+        let size = self.heightmap.size;
+        let across = f64::from(pass) / f64::from(self.passes());
+
+        if self.along_y() {
+            Vector2::new(start.x + size.x * across, start.y + size.y * fraction)
+        } else {
+            Vector2::new(start.x + size.x * fraction, start.y + size.y * across)
+        }
+    }
+
+    // Returns the lowest height the ball tip can be at over the xy point without cutting into
+    // the surface at any of the grid points under the ball.
+    fn tool_height_at(&self, point: Vector2, radius: f64) -> f64 {
+        let heightmap = &self.heightmap;
+        let spacing = heightmap.spacing();
+        let (columns, rows) = heightmap.grid_size();
+
+        let range = |position: f64, start: f64, spacing: f64, points: usize| {
+            let first = ((position - radius - start) / spacing).ceil().max(0.0) as usize;
+            let last = ((position + radius - start) / spacing).floor().max(-1.0);
+            let last = if last < 0.0 {
+                None
+            } else {
+                Some((last as usize).min(points - 1))
+            };
+            (first, last)
+        };
+
+        let mut height = heightmap.height_at(point);
+        let (first_column, last_column) = range(point.x, heightmap.start.x, spacing.x, columns);
+        let (first_row, last_row) = range(point.y, heightmap.start.y, spacing.y, rows);
+
+        if let (Some(last_column), Some(last_row)) = (last_column, last_row) {
+            for row in first_row..=last_row {
+                for column in first_column..=last_column {
+                    let grid_point = Vector2::new(
+                        heightmap.start.x + spacing.x * column as f64,
+                        heightmap.start.y + spacing.y * row as f64,
+                    );
+                    let distance = grid_point.distance_to(point);
+                    if distance < radius {
+                        let lift = (radius * radius - distance * distance).sqrt() - radius;
+                        height = height.max(heightmap.heights[row][column] + lift);
+                    }
+                }
+            }
+        }
+
+        height
+    }
+
+    /// Converts the struct to G-code instructions.
+    ///
+    /// Returns error if the tool is not a ballnose tool, if the heightmap is invalid, or if
+    /// `step_over` is not a positive number.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let Tool::Ballnose(tool) = context.tool() else {
+            return Err(CncError::UnsupportedTool {
+                feature: "surface scan",
+                tool: context.tool(),
+                required: "a ballnose tool",
+            });
+        };
+
+        self.heightmap.validate()?;
+
+        if !(self.step_over > 0.0 && self.step_over.is_finite()) {
+            return Err(CncError::InvalidStepOver {
+                feature: "surface scan",
+                step_over: self.step_over,
+            });
+        }
+
+        let radius = tool.radius();
+        let spacing = self.heightmap.spacing();
+        let (length, grid_spacing) = if self.along_y() {
+            (self.heightmap.size.y, spacing.y)
+        } else {
+            (self.heightmap.size.x, spacing.x)
+        };
+        let samples = (length / grid_spacing.min(self.step_over)).ceil().max(1.0) as u32;
+
+        let start = self.heightmap.start;
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Surface scan at: x = {}, y = {}, size = {}, step over = {}",
+                    round_precision(start.x),
+                    round_precision(start.y),
+                    self.heightmap.size,
+                    round_precision(self.step_over)
+                ),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(start.x),
+                y: Some(start.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(self.tool_height_at(start, radius)),
+                f: Some(context.tool().feed_rate()),
+            }),
+        ];
+
+        for pass in 0..=self.passes() {
+            for sample in 0..=samples {
+                if pass == 0 && sample == 0 {
+                    continue;
+                }
+
+                // Every other pass is cut backwards, to cut back and forth
+                let fraction = f64::from(sample) / f64::from(samples);
+                let fraction = if pass.is_multiple_of(2) {
+                    fraction
+                } else {
+                    1.0 - fraction
+                };
+
+                let point = self.point(pass, fraction);
+                instructions.push(Instruction::G1(G1 {
+                    x: Some(point.x),
+                    y: Some(point.y),
+                    z: Some(self.tool_height_at(point, radius)),
+                    f: None,
+                }));
+            }
+        }
+
+        instructions.push(Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(context.z_safe()),
+        }));
+
+        Ok(instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slope() -> Heightmap {
+        Heightmap::new(
+            Vector2::new(10.0, 20.0),
+            Vector2::new(20.0, 10.0),
+            vec![vec![0.0, -1.0, -2.0], vec![-2.0, -3.0, -4.0]],
+        )
+    }
+
+    #[test]
+    fn test_heightmap() -> Result<()> {
+        let heightmap = slope();
+        heightmap.validate()?;
+
+        assert_eq!(heightmap.height_at(Vector2::new(10.0, 20.0)), 0.0);
+        assert_eq!(heightmap.height_at(Vector2::new(30.0, 30.0)), -4.0);
+        assert_eq!(heightmap.height_at(Vector2::new(15.0, 25.0)), -1.5);
+        assert_eq!(heightmap.height_at(Vector2::new(0.0, 0.0)), 0.0);
+
+        let mirrored = heightmap.transform(&Transform::MirrorX)?;
+        assert_eq!(mirrored.start, Vector2::new(-30.0, 20.0));
+        assert_eq!(mirrored.heights[0], vec![-2.0, -1.0, 0.0]);
+
+        assert!(
+            Heightmap::new(Vector2::ZERO, Vector2::new(1.0, 1.0), vec![vec![0.0]])
+                .validate()
+                .is_err()
+        );
+        assert!(Heightmap::new(
+            Vector2::ZERO,
+            Vector2::new(1.0, 1.0),
+            vec![vec![0.0, 0.0], vec![0.0]]
+        )
+        .validate()
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_heightmap_single_row_or_column() {
+        let point = Heightmap::new(Vector2::ZERO, Vector2::new(10.0, 10.0), vec![vec![-2.0]]);
+        assert_eq!(point.height_at(Vector2::ZERO), -2.0);
+        assert_eq!(point.height_at(Vector2::new(5.0, 20.0)), -2.0);
+
+        let scan = SurfaceScan::new(point, 1.0, Axis::X);
+        assert_eq!(scan.entry_point(), Vector3::new(0.0, 0.0, -2.0));
+        assert_eq!(scan.exit_point().z, -2.0);
+
+        let row = Heightmap::new(
+            Vector2::ZERO,
+            Vector2::new(10.0, 10.0),
+            vec![vec![0.0, -1.0, -2.0]],
+        );
+        assert_eq!(row.height_at(Vector2::new(2.5, 5.0)), -0.5);
+        assert_eq!(row.height_at(Vector2::new(10.0, 0.0)), -2.0);
+
+        let column = Heightmap::new(
+            Vector2::ZERO,
+            Vector2::new(10.0, 10.0),
+            vec![vec![0.0], vec![-4.0]],
+        );
+        assert_eq!(column.height_at(Vector2::new(5.0, 5.0)), -2.0);
+
+        let empty = Heightmap::new(Vector2::ZERO, Vector2::new(10.0, 10.0), vec![]);
+        assert_eq!(empty.height_at(Vector2::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_surface_scan() -> Result<()> {
+        let ballnose = Tool::ballnose(
+            Units::Metric,
+            20.0,
+            2.0,
+            Direction::Clockwise,
+            18000.0,
+            500.0,
+        );
+        let context = InnerContext::new(Units::Metric, &ballnose, 5.0, 50.0);
+        let scan = SurfaceScan::new(slope(), 5.0, Axis::Y);
+
+        let bounds = scan.bounds();
+        assert_eq!(bounds.min, Vector3::new(10.0, 20.0, -4.0));
+        assert_eq!(bounds.max, Vector3::new(30.0, 30.0, 0.0));
+
+        let moves = scan
+            .to_instructions(&context)?
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G1(g1) => Some(g1),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // A plunge and 3 passes along x with 5 points each, the first point is the plunge
+        assert_eq!(moves.len(), 1 + 4 + 5 * 2);
+        assert_eq!(moves[0].z, Some(0.0));
+        assert_eq!(moves[4].x, Some(30.0));
+        assert_eq!(moves[4].y, Some(20.0));
+        assert_eq!(moves[5].x, Some(30.0));
+        assert_eq!(moves[5].y, Some(25.0));
+
+        let end = moves.last().unwrap();
+        assert_eq!((end.x, end.y), (Some(30.0), Some(30.0)));
+        assert_eq!(scan.exit_point(), Vector3::new(30.0, 30.0, -4.0));
+
+        // The ball is lifted above the grid points that are higher next to the cut point
+        let peak = SurfaceScan::new(
+            Heightmap::new(
+                Vector2::ZERO,
+                Vector2::new(10.0, 10.0),
+                vec![
+                    vec![-5.0, -5.0, -5.0],
+                    vec![-5.0, 0.0, -5.0],
+                    vec![-5.0, -5.0, -5.0],
+                ],
+            ),
+            1.0,
+            Axis::Y,
+        );
+        let point = Vector2::new(2.5, 5.0);
+        assert_eq!(peak.heightmap.height_at(point), -2.5);
+        assert_eq!(
+            round_precision(peak.tool_height_at(point, 3.0)),
+            round_precision(2.75_f64.sqrt() - 3.0)
+        );
+
+        assert!(matches!(
+            scan.to_instructions(&InnerContext::new(
+                Units::Metric,
+                &Tool::default(),
+                5.0,
+                50.0
+            )),
+            Err(CncError::UnsupportedTool {
+                feature: "surface scan",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+}
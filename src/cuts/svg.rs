@@ -0,0 +1,591 @@
+use std::f64::consts::{PI, TAU};
+
+use anyhow::{anyhow, Result};
+
+use crate::cuts::{Path, Segment};
+use crate::types::*;
+
+impl Path {
+    /// Creates paths from SVG path data, such as the `d` attribute of an SVG `<path>` element.
+    ///
+    /// Every subpath, started by a move command, becomes a separate `Path` that starts at
+    /// `start`. The SVG coordinates are multiplied with `scale` to convert them to the program
+    /// units, and the y axis is flipped as SVG uses a y axis that points down, so the drawing
+    /// ends up below the x axis.
+    ///
+    /// Circular arcs are converted to arc segments, while Bézier curves and elliptical arcs
+    /// are flattened to line segments that deviate at the most `tolerance` from the curve.
+    ///
+    /// Returns error if the path data can not be parsed.
+    pub fn from_svg_path(
+        data: &str,
+        start: Vector3,
+        end_z: f64,
+        max_step_z: f64,
+        scale: f64,
+        tolerance: f64,
+    ) -> Result<Vec<Path>> {
+        let parser = SvgPathParser {
+            scale,
+            height: 0.0,
+            tolerance: tolerance.abs().max(f64::EPSILON),
+        };
+
+        Ok(parser
+            .parse(data)?
+            .into_iter()
+            .map(|segments| Path::new(start, segments, end_z, max_step_z))
+            .collect())
+    }
+
+    /// Creates paths from all `<path>` elements in an SVG file, see
+    /// [Path::from_svg_path](struct.Path.html#method.from_svg_path).
+    ///
+    /// When the SVG has a `viewBox` the y axis is flipped within it, so that the drawing ends
+    /// up above the x axis with the same placement as in the SVG. Transforms, as well as
+    /// shapes other than paths, are not supported and are ignored.
+    ///
+    /// Returns error if the file can not be read or if any of the paths can not be parsed.
+    pub fn from_svg_file<P: AsRef<std::path::Path>>(
+        file: P,
+        start: Vector3,
+        end_z: f64,
+        max_step_z: f64,
+        scale: f64,
+        tolerance: f64,
+    ) -> Result<Vec<Path>> {
+        let svg = std::fs::read_to_string(file)?;
+
+        let height = svg_attribute(&svg, "<svg", "viewBox")
+            .and_then(|view_box| {
+                let values = view_box
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|value| !value.is_empty())
+                    .map(str::parse::<f64>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()?;
+                (values.len() == 4).then(|| values[1] * 2.0 + values[3])
+            })
+            .unwrap_or_default();
+
+        let parser = SvgPathParser {
+            scale,
+            height,
+            tolerance: tolerance.abs().max(f64::EPSILON),
+        };
+
+        let mut paths = vec![];
+        let mut rest = svg.as_str();
+
+        while let Some(index) = rest.find("<path") {
+            rest = &rest[index..];
+
+            if let Some(data) = svg_attribute(rest, "<path", "d") {
+                for segments in parser.parse(&data)? {
+                    paths.push(Path::new(start, segments, end_z, max_step_z));
+                }
+            }
+
+            rest = &rest["<path".len()..];
+        }
+
+        Ok(paths)
+    }
+}
+
+// Returns the value of an attribute on the first element with the given tag in the markup.
+fn svg_attribute(markup: &str, tag: &str, name: &str) -> Option<String> {
+    let element = &markup[markup.find(tag)?..];
+    let element = &element[..element.find('>')?];
+
+    let mut rest = element;
+    while let Some(index) = rest.find(name) {
+        let preceding = rest[..index].chars().last();
+        let value = rest[index + name.len()..].trim_start();
+
+        if preceding.is_some_and(char::is_whitespace) {
+            if let Some(value) = value.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next()?;
+                let value = &value[1..];
+                return Some(value[..value.find(quote)?].to_string());
+            }
+        }
+
+        rest = &rest[index + name.len()..];
+    }
+
+    None
+}
+
+struct SvgPathParser {
+    scale: f64,
+    height: f64,
+    tolerance: f64,
+}
+
+impl SvgPathParser {
+    // Converts a point from SVG coordinates to program coordinates.
+    fn point(&self, point: Vector2) -> Vector2 {
+        Vector2::new(point.x * self.scale, (self.height - point.y) * self.scale)
+    }
+
+    fn line(&self, from: Vector2, to: Vector2) -> Segment {
+        Segment::line(self.point(from), self.point(to))
+    }
+
+    fn parse(&self, data: &str) -> Result<Vec<Vec<Segment>>> {
+        let tokens = tokenize(data)?;
+        let mut tokens = tokens.iter().peekable();
+
+        let mut subpaths = vec![];
+        let mut segments = vec![];
+        let mut current = Vector2::ZERO;
+        let mut subpath_start = Vector2::ZERO;
+        let mut last_control: Option<Vector2> = None;
+        let mut command = None;
+
+        while let Some(token) = tokens.peek() {
+            let name = match token {
+                Token::Command(name) => Some(*name),
+                Token::Number(_) => None,
+            };
+
+            let name = match (name, command) {
+                (Some(name), _) => {
+                    tokens.next();
+                    name
+                }
+                // Repeated parameters reuse the previous command, a move turns into a line
+                (None, Some('M')) => 'L',
+                (None, Some('m')) => 'l',
+                (None, Some(name)) if !name.eq_ignore_ascii_case(&'Z') => name,
+                (None, _) => {
+                    return Err(anyhow!(
+                        "Unexpected number in SVG path data, numbers must follow a command"
+                    ))
+                }
+            };
+            if command.is_none() && !name.eq_ignore_ascii_case(&'M') {
+                return Err(anyhow!("SVG path data must start with a move command"));
+            }
+            command = Some(name);
+
+            let relative = name.is_ascii_lowercase();
+            let origin = if relative { current } else { Vector2::ZERO };
+            let mut number = || -> Result<f64> {
+                match tokens.next() {
+                    Some(Token::Number(value)) => Ok(*value),
+                    _ => Err(anyhow!("Missing parameter for SVG path command {}", name)),
+                }
+            };
+
+            let mut control = None;
+
+            match name.to_ascii_uppercase() {
+                'M' => {
+                    let to = origin + Vector2::new(number()?, number()?);
+                    if !segments.is_empty() {
+                        subpaths.push(std::mem::take(&mut segments));
+                    }
+                    current = to;
+                    subpath_start = to;
+                }
+                'L' => {
+                    let to = origin + Vector2::new(number()?, number()?);
+                    segments.push(self.line(current, to));
+                    current = to;
+                }
+                'H' => {
+                    let to = Vector2::new(origin.x + number()?, current.y);
+                    segments.push(self.line(current, to));
+                    current = to;
+                }
+                'V' => {
+                    let to = Vector2::new(current.x, origin.y + number()?);
+                    segments.push(self.line(current, to));
+                    current = to;
+                }
+                'C' | 'S' => {
+                    let control1 = if name.eq_ignore_ascii_case(&'S') {
+                        reflect(current, last_control)
+                    } else {
+                        origin + Vector2::new(number()?, number()?)
+                    };
+                    let control2 = origin + Vector2::new(number()?, number()?);
+                    let to = origin + Vector2::new(number()?, number()?);
+
+                    let flatness = (current - control1 * Vector2::splat(2.0) + control2)
+                        .length()
+                        .max((control1 - control2 * Vector2::splat(2.0) + to).length());
+                    let steps = self.steps(6.0 * flatness);
+
+                    let mut from = current;
+                    for step in 1..=steps {
+                        let t = f64::from(step) / f64::from(steps);
+                        let u = 1.0 - t;
+                        let point = current * Vector2::splat(u * u * u)
+                            + control1 * Vector2::splat(3.0 * u * u * t)
+                            + control2 * Vector2::splat(3.0 * u * t * t)
+                            + to * Vector2::splat(t * t * t);
+                        segments.push(self.line(from, point));
+                        from = point;
+                    }
+
+                    control = Some(control2);
+                    current = to;
+                }
+                'Q' | 'T' => {
+                    let control1 = if name.eq_ignore_ascii_case(&'T') {
+                        reflect(current, last_control)
+                    } else {
+                        origin + Vector2::new(number()?, number()?)
+                    };
+                    let to = origin + Vector2::new(number()?, number()?);
+
+                    let flatness = (current - control1 * Vector2::splat(2.0) + to).length();
+                    let steps = self.steps(2.0 * flatness);
+
+                    let mut from = current;
+                    for step in 1..=steps {
+                        let t = f64::from(step) / f64::from(steps);
+                        let u = 1.0 - t;
+                        let point = current * Vector2::splat(u * u)
+                            + control1 * Vector2::splat(2.0 * u * t)
+                            + to * Vector2::splat(t * t);
+                        segments.push(self.line(from, point));
+                        from = point;
+                    }
+
+                    control = Some(control1);
+                    current = to;
+                }
+                'A' => {
+                    let radius = Vector2::new(number()?.abs(), number()?.abs());
+                    let rotation = number()?.to_radians();
+                    let large_arc = number()? != 0.0;
+                    let sweep = number()? != 0.0;
+                    let to = origin + Vector2::new(number()?, number()?);
+
+                    self.arc(
+                        &mut segments,
+                        current,
+                        to,
+                        radius,
+                        rotation,
+                        large_arc,
+                        sweep,
+                    );
+                    current = to;
+                }
+                'Z' => {
+                    if current != subpath_start {
+                        segments.push(self.line(current, subpath_start));
+                    }
+                    current = subpath_start;
+                }
+                _ => return Err(anyhow!("Unsupported SVG path command {}", name)),
+            }
+
+            last_control = control;
+        }
+
+        if !segments.is_empty() {
+            subpaths.push(segments);
+        }
+
+        Ok(subpaths)
+    }
+
+    // Returns the number of line segments needed to flatten a curve, based on the maximum
+    // second derivative of the curve.
+    fn steps(&self, second_derivative: f64) -> u32 {
+        let deviation = second_derivative * self.scale.abs() / 8.0;
+        ((deviation / self.tolerance).sqrt().ceil() as u32).max(1)
+    }
+
+    // Converts an SVG arc from the endpoint to the center parameterization, see
+    // https://www.w3.org/TR/SVG/implnote.html#ArcConversionEndpointToCenter
+    #[allow(clippy::too_many_arguments)]
+    fn arc(
+        &self,
+        segments: &mut Vec<Segment>,
+        from: Vector2,
+        to: Vector2,
+        mut radius: Vector2,
+        rotation: f64,
+        large_arc: bool,
+        sweep: bool,
+    ) {
+        if from == to {
+            return;
+        }
+
+        if radius.x == 0.0 || radius.y == 0.0 {
+            segments.push(self.line(from, to));
+            return;
+        }
+
+        let half = ((from - to) * Vector2::splat(0.5)).rotate(-rotation);
+
+        let lambda = (half.x / radius.x).powi(2) + (half.y / radius.y).powi(2);
+        if lambda > 1.0 {
+            radius = radius * Vector2::splat(lambda.sqrt());
+        }
+
+        let numerator = (radius.x * radius.y).powi(2)
+            - (radius.x * half.y).powi(2)
+            - (radius.y * half.x).powi(2);
+        let denominator = (radius.x * half.y).powi(2) + (radius.y * half.x).powi(2);
+        let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+        let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+        let center_offset = Vector2::new(
+            coefficient * radius.x * half.y / radius.y,
+            -coefficient * radius.y * half.x / radius.x,
+        );
+        let center = center_offset.rotate(rotation) + (from + to) * Vector2::splat(0.5);
+
+        // Sweep in SVG is in the positive angle direction, which is clockwise on screen as
+        // the y axis points down, and it stays clockwise after the y axis is flipped
+        if (radius.x - radius.y).abs() < 1e-9 {
+            segments.push(Segment::arc(
+                self.point(from),
+                self.point(to),
+                self.point(center),
+                if sweep {
+                    Direction::Clockwise
+                } else {
+                    Direction::Counterclockwise
+                },
+            ));
+            return;
+        }
+
+        let angle = |point: Vector2| {
+            let point = point.rotate(-rotation);
+            point.y.atan2(point.x)
+        };
+        let start_angle = angle((half - center_offset) / radius);
+        let mut sweep_angle = angle((Vector2::ZERO - half - center_offset) / radius) - start_angle;
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += TAU;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= TAU;
+        }
+
+        let max_radius = radius.x.max(radius.y) * self.scale.abs();
+        let step_angle = if self.tolerance < max_radius {
+            2.0 * (1.0 - self.tolerance / max_radius).acos()
+        } else {
+            PI / 2.0
+        };
+        let steps = ((sweep_angle.abs() / step_angle).ceil() as u32).max(1);
+
+        let mut previous = from;
+        for step in 1..=steps {
+            let theta = start_angle + sweep_angle * f64::from(step) / f64::from(steps);
+            let point = if step == steps {
+                to
+            } else {
+                Vector2::new(radius.x * theta.cos(), radius.y * theta.sin()).rotate(rotation)
+                    + center
+            };
+            segments.push(self.line(previous, point));
+            previous = point;
+        }
+    }
+}
+
+// Reflects the previous control point around the current point, as used by the smooth curve
+// commands.
+fn reflect(current: Vector2, control: Option<Vector2>) -> Vector2 {
+    control.map_or(current, |control| current * Vector2::splat(2.0) - control)
+}
+
+enum Token {
+    Command(char),
+    Number(f64),
+}
+
+fn tokenize(data: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = data.char_indices().peekable();
+
+    while let Some((index, character)) = chars.next() {
+        if character.is_whitespace() || character == ',' {
+            continue;
+        }
+
+        if character.is_ascii_alphabetic() && !matches!(character, 'e' | 'E') {
+            tokens.push(Token::Command(character));
+            continue;
+        }
+
+        if !(character.is_ascii_digit() || matches!(character, '-' | '+' | '.')) {
+            return Err(anyhow!(
+                "Unexpected character {} in SVG path data",
+                character
+            ));
+        }
+
+        // Numbers end at the next sign that is not part of an exponent, or at the next
+        // decimal point after one has been used
+        let mut end = index + character.len_utf8();
+        let mut has_point = character == '.';
+        let mut has_exponent = false;
+        let mut previous = character;
+        while let Some(&(next_index, next)) = chars.peek() {
+            let accept = next.is_ascii_digit()
+                || (next == '.' && !has_point && !has_exponent)
+                || (matches!(next, 'e' | 'E') && !has_exponent)
+                || (matches!(next, '-' | '+') && matches!(previous, 'e' | 'E'));
+
+            if !accept {
+                break;
+            }
+
+            has_point |= next == '.';
+            has_exponent |= matches!(next, 'e' | 'E');
+            previous = next;
+            end = next_index + next.len_utf8();
+            chars.next();
+        }
+
+        let number = &data[index..end];
+        tokens.push(Token::Number(number.parse().map_err(|_| {
+            anyhow!("Invalid number {} in SVG path data", number)
+        })?));
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(path: &Path) -> Vec<Vector2> {
+        let mut points = vec![];
+        for segment in &path.segments {
+            match segment {
+                Segment::Line(line) => {
+                    if points.is_empty() {
+                        points.push(line.from);
+                    }
+                    points.push(line.to);
+                }
+                Segment::Arc(arc) => {
+                    if points.is_empty() {
+                        points.push(arc.from);
+                    }
+                    points.push(arc.to);
+                }
+                Segment::Point(point) => points.push(*point),
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_from_svg_path_lines() -> Result<()> {
+        let paths = Path::from_svg_path(
+            "M10,10 h20 v-5.5 L 10 5 z m 40,0 l1e1-10",
+            Vector3::new(0.0, 0.0, 1.0),
+            -1.0,
+            1.0,
+            2.0,
+            0.1,
+        )?;
+
+        assert_eq!(paths.len(), 2);
+        assert_eq!(
+            points(&paths[0]),
+            vec![
+                Vector2::new(20.0, -20.0),
+                Vector2::new(60.0, -20.0),
+                Vector2::new(60.0, -9.0),
+                Vector2::new(20.0, -10.0),
+                Vector2::new(20.0, -20.0),
+            ]
+        );
+        assert_eq!(
+            points(&paths[1]),
+            vec![Vector2::new(100.0, -20.0), Vector2::new(120.0, 0.0)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_svg_path_curves() -> Result<()> {
+        let paths = Path::from_svg_path(
+            "M0 0 A 10 10 0 0 1 20 0 C 20 10 30 10 30 0 Q 35 -10 40 0",
+            Vector3::ZERO,
+            -1.0,
+            1.0,
+            1.0,
+            0.01,
+        )?;
+
+        assert_eq!(paths.len(), 1);
+
+        let arc = match &paths[0].segments[0] {
+            Segment::Arc(arc) => arc,
+            _ => panic!("Expected arc segment"),
+        };
+        assert_eq!(arc.to, Vector2::new(20.0, 0.0));
+        assert!(arc.center.distance_to(Vector2::new(10.0, 0.0)) < 1e-9);
+        assert_eq!(arc.direction, Direction::Clockwise);
+
+        let points = points(&paths[0]);
+        assert!(points.len() > 10);
+        assert_eq!(points.last(), Some(&Vector2::new(40.0, 0.0)));
+
+        // The flattened cubic curve peaks at 7.5 below the x axis after the flip
+        let lowest = points
+            .iter()
+            .filter(|point| point.x > 20.0 && point.x < 30.0)
+            .map(|point| point.y)
+            .fold(f64::MAX, f64::min);
+        assert!((lowest + 7.5).abs() < 0.01);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_svg_file() -> Result<()> {
+        let file = std::env::temp_dir().join("cnccoder_test_from_svg_file.svg");
+        std::fs::write(
+            &file,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 50">
+                <path id="first" d="M 10 10 L 20 10" />
+                <path d='M 0 50 H 100' />
+            </svg>"#,
+        )?;
+
+        let paths = Path::from_svg_file(&file, Vector3::ZERO, -1.0, 1.0, 1.0, 0.1)?;
+        std::fs::remove_file(&file)?;
+
+        assert_eq!(paths.len(), 2);
+        assert_eq!(
+            points(&paths[0]),
+            vec![Vector2::new(10.0, 40.0), Vector2::new(20.0, 40.0)]
+        );
+        assert_eq!(
+            points(&paths[1]),
+            vec![Vector2::new(0.0, 0.0), Vector2::new(100.0, 0.0)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_svg_path_errors() {
+        let parse = |data| Path::from_svg_path(data, Vector3::ZERO, -1.0, 1.0, 1.0, 0.1);
+
+        assert!(parse("L 10 10").is_err());
+        assert!(parse("M 10").is_err());
+        assert!(parse("M 10 10 X 5").is_err());
+        assert!(parse("M 10 10 L 5 5 Z 5").is_err());
+    }
+}
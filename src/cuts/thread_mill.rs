@@ -0,0 +1,431 @@
+use std::f64::consts::TAU;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::error::CncError;
+use crate::instructions::*;
+use crate::program::*;
+use crate::types::*;
+use crate::utils::*;
+
+/// Cut a thread with a single-point thread mill, following a helix down from the center z
+/// value to `end_z`.
+///
+/// The tool path is compensated by the tool radius, for internal threads the tool tip reaches
+/// the major diameter, and for external threads the tool tip reaches the minor diameter of a
+/// 60° ISO metric thread profile with the given pitch.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThreadMill {
+    /// Center point in 3D space, the z value is the start depth of the cut.
+    pub center: Vector3,
+    /// The major (nominal) diameter of the thread.
+    pub major_diameter: f64,
+    /// The distance between two adjacent thread crests.
+    pub pitch: f64,
+    /// The end depth of the cut on the z axis.
+    pub end_z: f64,
+    /// The number of thread starts, each start is cut as its own helix and the tool moves
+    /// `pitch * thread_starts` down for each revolution.
+    pub thread_starts: u32,
+    /// Cut an internal thread (threaded hole) if true, otherwise an external thread.
+    pub internal: bool,
+    /// The direction to follow the helix with.
+    pub direction: Direction,
+}
+
+impl ThreadMill {
+    /// Creates a new `ThreadMill` struct.
+    #[must_use]
+    pub fn new(
+        center: Vector3,
+        major_diameter: f64,
+        pitch: f64,
+        end_z: f64,
+        thread_starts: u32,
+        internal: bool,
+        direction: Direction,
+    ) -> Self {
+        Self {
+            center,
+            major_diameter,
+            pitch,
+            end_z,
+            thread_starts,
+            internal,
+            direction,
+        }
+    }
+
+    /// Returns a new `ThreadMill` with the transform applied to its coordinates and sizes.
+    /// Mirroring flips the helix direction.
+    #[must_use]
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let direction = self.direction.transformed(transform, &Axis::Z);
+
+        Self {
+            center: transform.apply(self.center),
+            major_diameter: transform.apply_length(self.major_diameter),
+            pitch: transform.apply_length(self.pitch),
+            end_z: transform.apply_z(self.end_z),
+            thread_starts: self.thread_starts,
+            internal: self.internal,
+            direction,
+        }
+    }
+
+    /// Returns the point where the cut starts, at the center for internal threads and at the
+    /// major diameter on the positive x side for external threads.
+    #[must_use]
+    pub fn entry_point(&self) -> Vector3 {
+        if self.internal {
+            self.center
+        } else {
+            self.center.add_x(self.major_diameter / 2.0)
+        }
+    }
+
+    /// Returns the point where the cut ends, below the entry point on the end depth.
+    #[must_use]
+    pub fn exit_point(&self) -> Vector3 {
+        self.entry_point().with_z(self.end_z)
+    }
+
+    /// Returns the bounds of the cut.
+    #[must_use]
+    pub fn bounds(&self) -> Bounds {
+        let radius = self.major_diameter / 2.0;
+
+        Bounds {
+            min: Vector3::new(self.center.x - radius, self.center.y - radius, self.end_z),
+            max: Vector3::new(
+                self.center.x + radius,
+                self.center.y + radius,
+                self.center.z,
+            ),
+        }
+    }
+
+    /// Returns the bounds of the area swept by a tool of the given radius. Internal threads
+    /// sweep the whole hole, and external threads also sweep the clearance outside of the
+    /// thread where the tool enters and leaves the cut.
+    #[must_use]
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        let major_radius = self.major_diameter / 2.0;
+        let radius = if self.internal {
+            major_radius.max(tool_radius)
+        } else {
+            major_radius - self.thread_depth() + tool_radius * 3.0
+        };
+
+        Bounds {
+            min: Vector3::new(self.center.x - radius, self.center.y - radius, self.end_z),
+            max: Vector3::new(
+                self.center.x + radius,
+                self.center.y + radius,
+                self.center.z,
+            ),
+        }
+    }
+
+    /// Returns the depth of the thread profile, measured from the major to the minor radius.
+    #[must_use]
+    pub fn thread_depth(&self) -> f64 {
+        // 5/8 of the height of the fundamental triangle of a 60° thread
+        self.pitch * 3.0_f64.sqrt() / 2.0 * 5.0 / 8.0
+    }
+
+    /// Converts the struct to G-code instructions.
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
+        let tool = context.tool();
+        let units = context.units();
+        let major_radius = self.major_diameter / 2.0;
+        let cut_radius = if self.internal {
+            major_radius - tool.radius()
+        } else {
+            major_radius - self.thread_depth() + tool.radius()
+        };
+
+        if cut_radius <= 0.0 {
+            return Err(anyhow!(
+                "Unable to mill thread of diameter {:.2} {} with tool diameter {:.2} {}",
+                self.major_diameter,
+                units,
+                tool.diameter(),
+                units,
+            )
+            .into());
+        }
+
+        if self.pitch <= 0.0 {
+            return Err(anyhow!(
+                "Unable to mill thread, pitch must be larger than 0, got {}",
+                self.pitch
+            )
+            .into());
+        }
+
+        let mut instructions = vec![
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Mill {} thread at: x = {}, y = {}, diameter = {}, pitch = {}",
+                    if self.internal {
+                        "internal"
+                    } else {
+                        "external"
+                    },
+                    round_precision(self.center.x),
+                    round_precision(self.center.y),
+                    round_precision(self.major_diameter),
+                    round_precision(self.pitch)
+                ),
+            }),
+        ];
+
+        let starts = self.thread_starts.max(1);
+        for start in 0..starts {
+            let angle = TAU * f64::from(start) / f64::from(starts);
+            instructions.append(&mut self.generate_helix_instructions(
+                context,
+                cut_radius,
+                angle,
+                self.pitch * f64::from(starts),
+            ));
+        }
+
+        Ok(instructions)
+    }
+
+    fn point_at(&self, radius: f64, angle: f64) -> Vector2 {
+        self.center.xy() + Vector2::from_polar(radius, angle)
+    }
+
+    fn arc(&self, from: Vector2, to: Vector2, z: f64) -> Instruction {
+        let i = Some(self.center.x - from.x);
+        let j = Some(self.center.y - from.y);
+
+        match self.direction {
+            Direction::Clockwise => Instruction::G2(G2 {
+                x: Some(to.x),
+                y: Some(to.y),
+                z: Some(z),
+                i,
+                j,
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }),
+            Direction::Counterclockwise => Instruction::G3(G3 {
+                x: Some(to.x),
+                y: Some(to.y),
+                z: Some(z),
+                i,
+                j,
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }),
+        }
+    }
+
+    fn generate_helix_instructions(
+        &self,
+        context: &InnerContext,
+        cut_radius: f64,
+        start_angle: f64,
+        lead: f64,
+    ) -> Vec<Instruction> {
+        // Internal threads are entered and left from the center of the hole, and external
+        // threads from outside of the thread
+        let clearance = |angle: f64| {
+            if self.internal {
+                self.center.xy()
+            } else {
+                self.point_at(cut_radius + context.tool().radius(), angle)
+            }
+        };
+        let start = self.point_at(cut_radius, start_angle);
+        let entry = clearance(start_angle);
+
+        let mut instructions = vec![
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+            Instruction::G0(G0 {
+                x: Some(entry.x),
+                y: Some(entry.y),
+                z: None,
+            }),
+            Instruction::G1(G1 {
+                x: None,
+                y: None,
+                z: Some(self.center.z),
+                f: Some(context.tool().feed_rate()),
+            }),
+            Instruction::G1(G1 {
+                x: Some(start.x),
+                y: Some(start.y),
+                z: None,
+                f: None,
+            }),
+        ];
+
+        let depth = self.center.z - self.end_z;
+        let revolutions = (depth / lead).floor() as u32;
+
+        for revolution in 1..=revolutions {
+            instructions.push(self.arc(start, start, self.center.z - f64::from(revolution) * lead));
+        }
+
+        // Partial revolution for the remaining depth
+        let mut end_angle = start_angle;
+        let remaining = depth - f64::from(revolutions) * lead;
+        if remaining > 0.001 {
+            end_angle += match self.direction {
+                Direction::Clockwise => -TAU * remaining / lead,
+                Direction::Counterclockwise => TAU * remaining / lead,
+            };
+            let end = self.point_at(cut_radius, end_angle);
+            instructions.push(self.arc(start, end, self.end_z));
+        }
+
+        let exit = clearance(end_angle);
+        instructions.append(&mut vec![
+            Instruction::G1(G1 {
+                x: Some(exit.x),
+                y: Some(exit.y),
+                z: None,
+                f: None,
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(context.z_safe()),
+            }),
+        ]);
+
+        instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::Tool;
+
+    fn context() -> InnerContext {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+
+        InnerContext::new(Units::Metric, &tool, 5.0, 50.0)
+    }
+
+    fn helix_arcs(instructions: &[Instruction]) -> Vec<&G3> {
+        instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G3(arc) => Some(arc),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_thread_mill_revolutions() -> Result<()> {
+        // M10x1.5 internal thread, 6 mm deep
+        let thread = ThreadMill::new(
+            Vector3::new(10.0, 20.0, 0.0),
+            10.0,
+            1.5,
+            -6.0,
+            1,
+            true,
+            Direction::Counterclockwise,
+        );
+        let instructions = thread.to_instructions(&context())?;
+        let arcs = helix_arcs(&instructions);
+
+        assert_eq!(arcs.len(), 4);
+        for (index, arc) in arcs.iter().enumerate() {
+            assert_eq!(arc.x, Some(13.0));
+            assert_eq!(arc.y, Some(20.0));
+            assert_eq!(arc.z, Some(-1.5 * (index + 1) as f64));
+            assert_eq!(arc.i, Some(-3.0));
+        }
+
+        // A partial last revolution reaches the end depth
+        let thread = ThreadMill::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            10.0,
+            1.5,
+            -6.75,
+            1,
+            true,
+            Direction::Counterclockwise,
+        );
+        let instructions = thread.to_instructions(&context())?;
+        let arcs = helix_arcs(&instructions);
+
+        assert_eq!(arcs.len(), 5);
+        assert_eq!(arcs[4].z, Some(-6.75));
+        assert!((arcs[4].x.unwrap() + 3.0).abs() < 1e-9);
+        assert!(arcs[4].y.unwrap().abs() < 1e-9);
+
+        let bounds = thread.bounds();
+        assert_eq!(bounds.min, Vector3::new(-5.0, -5.0, -6.75));
+        assert_eq!(bounds.max, Vector3::new(5.0, 5.0, 0.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_mill_external_multiple_starts() -> Result<()> {
+        let thread = ThreadMill::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            10.0,
+            1.0,
+            -4.0,
+            2,
+            false,
+            Direction::Counterclockwise,
+        );
+        let instructions = thread.to_instructions(&context())?;
+        let arcs = helix_arcs(&instructions);
+
+        // Each start moves down 2 mm per revolution
+        assert_eq!(arcs.len(), 4);
+        assert_eq!(arcs[0].z, Some(-2.0));
+
+        let radius = 5.0 - thread.thread_depth() + 2.0;
+        assert!((arcs[0].x.unwrap() - radius).abs() < 1e-9);
+        assert!((arcs[2].x.unwrap() + radius).abs() < 1e-9);
+        assert!(arcs[2].y.unwrap().abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_mill_too_small_for_tool() {
+        let thread = ThreadMill::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            3.0,
+            0.5,
+            -2.0,
+            1,
+            true,
+            Direction::Clockwise,
+        );
+        assert!(thread.to_instructions(&context()).is_err());
+    }
+}
@@ -0,0 +1,379 @@
+//! Module providing the typed errors for failures that users of the crate may want to react to.
+
+use std::fmt;
+
+use crate::program::format_number;
+use crate::tools::Tool;
+use crate::types::*;
+use crate::utils::round_precision;
+
+/// Errors for specific failures when building or converting a program.
+///
+/// Converting cuts, contexts and programs to instructions or G-code, and validating programs,
+/// returns these errors directly, while the other fallible methods of the crate return
+/// [anyhow::Result], where the errors listed here can be told apart from other failures with
+/// `error.downcast_ref::<CncError>()`:
+/// ```
+/// use cnccoder::prelude::*;
+///
+/// let mut program = Program::new(Units::Metric, 5.0, 50.0);
+/// let mut context = program.context(Tool::default());
+/// context.append_cut(Cut::pocket(
+///     Vector3::new(0.0, 0.0, 0.0),
+///     Vector2::new(1.0, 1.0),
+///     -1.0,
+///     1.0,
+/// ));
+///
+/// let error = program.to_gcode().unwrap_err();
+/// assert!(matches!(error, CncError::ToolWiderThanFeature { .. }));
+/// ```
+#[derive(Debug)]
+pub enum CncError {
+    /// The tool is too wide to cut the feature.
+    ToolWiderThanFeature {
+        /// The kind of feature that was cut, for example `"area"` or `"circle"`.
+        feature: &'static str,
+        /// The size of the feature that the tool is wider than.
+        feature_size: f64,
+        /// The diameter of the tool.
+        tool_diameter: f64,
+        /// The units of the sizes.
+        units: Units,
+    },
+    /// Contexts or programs with different units were merged.
+    MismatchedUnits {
+        /// The units of the context or program that was merged into.
+        expected: Units,
+        /// The units of the context or program that was merged.
+        actual: Units,
+    },
+    /// An arc has different distances from its center to its from and to points.
+    ArcRadiusMismatch {
+        /// The index of the segment for arcs in paths.
+        segment: Option<usize>,
+        /// The distance from the center to the from point.
+        distance_from: f64,
+        /// The distance from the center to the to point.
+        distance_to: f64,
+    },
+    /// A path segment does not start where the previous segment ends.
+    SegmentGap {
+        /// The index of the segment.
+        segment: usize,
+        /// The start of the segment.
+        start: Vector2,
+        /// The end of the previous segment.
+        previous_end: Vector2,
+    },
+    /// The maximum depth to cut on each pass is zero or not finite.
+    InvalidStepZ {
+        /// The invalid maximum depth.
+        max_step_z: f64,
+    },
+    /// The step over between passes is zero, negative or not finite.
+    InvalidStepOver {
+        /// The kind of feature that was cut, for example `"ring"` or `"bore"`.
+        feature: &'static str,
+        /// The invalid step over.
+        step_over: f64,
+    },
+    /// The feature can only be cut with another kind of tool.
+    UnsupportedTool {
+        /// The kind of feature that was cut, for example `"surface scan"`.
+        feature: &'static str,
+        /// The tool that was used.
+        tool: Tool,
+        /// The kind of tool that is required, for example `"a ballnose tool"`.
+        required: &'static str,
+    },
+    /// A cut goes deeper into the stock than the usable cutting length of its tool.
+    CutTooDeep {
+        /// The index of the cut in its context.
+        index: usize,
+        /// The minimum x and y of the cut bounds.
+        position: Vector2,
+        /// The depth of the cut below the stock surface.
+        depth: f64,
+        /// The usable cutting length of the tool.
+        tool_length: f64,
+        /// The units of the depth and length.
+        units: Units,
+        /// The tool of the cut.
+        tool: Tool,
+    },
+    /// The tool change height is below the safe height.
+    ZToolChangeBelowZSafe {
+        /// The tool change height.
+        z_tool_change: f64,
+        /// The safe height.
+        z_safe: f64,
+        /// The units of the heights.
+        units: Units,
+        /// The tool for heights set per tool context, `None` for the program heights.
+        tool: Option<Tool>,
+    },
+    /// The safe height is below the top of the workpiece.
+    ZSafeBelowWorkpiece {
+        /// The safe height.
+        z_safe: f64,
+        /// The max z value of the workpiece.
+        max_z: f64,
+        /// The units of the heights.
+        units: Units,
+        /// The tool for heights set per tool context, `None` for the program heights.
+        tool: Option<Tool>,
+    },
+    /// The tool probe is not below the tool change height.
+    ProbeAboveToolChange {
+        /// The z value of the probe.
+        probe_z: f64,
+        /// The tool change height.
+        z_tool_change: f64,
+    },
+    /// The feed rate of the tool probe is zero, negative or not finite.
+    InvalidProbeFeedRate {
+        /// The invalid feed rate.
+        feed_rate: f64,
+    },
+    /// A circle would descend steeper than its max ramp per revolution.
+    RampTooSteep {
+        /// The center of the circle.
+        center: Vector2,
+        /// The radius of the circle.
+        radius: f64,
+        /// The depth that each revolution descends.
+        step_z: f64,
+        /// The max depth that each revolution may descend.
+        max_ramp: f64,
+    },
+    /// The corner radius of a path through waypoints does not fit on one of its lines.
+    CornerRadiusTooLarge {
+        /// The corner radius.
+        corner_radius: f64,
+        /// The start of the line.
+        from: Vector2,
+        /// The end of the line.
+        to: Vector2,
+    },
+    /// The bottom of a circle was to be cleared with outer tool compensation, which would
+    /// remove the round piece itself.
+    ClearBottomWithOuterCompensation,
+    /// The finish left on the walls of a pocket leaves no room for the pocket itself.
+    FinishTooLarge {
+        /// The finish on each wall.
+        finish: f64,
+        /// The size of the pocket.
+        size: Vector2,
+    },
+    /// Writing the G-code failed.
+    Io(std::io::Error),
+    /// Any other failure, keeping the underlying error and its chain of sources.
+    Other(anyhow::Error),
+}
+
+impl fmt::Display for CncError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ToolWiderThanFeature {
+                feature,
+                feature_size,
+                tool_diameter,
+                units,
+            } => write!(
+                formatter,
+                "Unable to cut {}, tool is {:.2} {} wider than the {:.2} {} feature (tool diameter is {:.2} {})",
+                feature,
+                tool_diameter - feature_size,
+                units,
+                feature_size,
+                units,
+                tool_diameter,
+                units
+            ),
+            Self::MismatchedUnits { expected, actual } => write!(
+                formatter,
+                "Failed to merge due to mismatching units, expected {} but got {}",
+                expected, actual
+            ),
+            Self::ArcRadiusMismatch {
+                segment,
+                distance_from,
+                distance_to,
+            } => {
+                match segment {
+                    Some(index) => write!(formatter, "Arc segment {} distances", index)?,
+                    None => write!(formatter, "Arc distances")?,
+                }
+
+                write!(
+                    formatter,
+                    " from/center ({}) and to/center ({}) must be equal",
+                    distance_from, distance_to
+                )
+            }
+            Self::SegmentGap {
+                segment,
+                start,
+                previous_end,
+            } => write!(
+                formatter,
+                "Segment {} starts at {} but segment {} ends at {}",
+                segment,
+                start,
+                segment - 1,
+                previous_end
+            ),
+            Self::InvalidStepZ { max_step_z } => write!(
+                formatter,
+                "max_step_z must be a finite value other than 0, got {}",
+                max_step_z
+            ),
+            Self::InvalidStepOver { feature, step_over } => write!(
+                formatter,
+                "Unable to cut {}, step over must be larger than 0, got {}",
+                feature, step_over
+            ),
+            Self::UnsupportedTool {
+                feature,
+                tool,
+                required,
+            } => write!(
+                formatter,
+                "Unable to cut {} with tool {}, {} is required",
+                feature, tool, required
+            ),
+            Self::CutTooDeep {
+                index,
+                position,
+                depth,
+                tool_length,
+                units,
+                tool,
+            } => write!(
+                formatter,
+                "Cut {} at x = {}, y = {} is {} {} deep which exceeds the tool length of {} {} for tool {}",
+                index + 1,
+                format_number(position.x),
+                format_number(position.y),
+                format_number(*depth),
+                units,
+                format_number(*tool_length),
+                units,
+                tool
+            ),
+            Self::ZToolChangeBelowZSafe {
+                z_tool_change,
+                z_safe,
+                units,
+                tool,
+            } => {
+                write!(formatter, "z_tool_change {} {}", z_tool_change, units)?;
+                if let Some(tool) = tool {
+                    write!(formatter, " for tool {}", tool)?;
+                }
+                write!(
+                    formatter,
+                    " must be larger than or equal to the z_safe value of {} {}",
+                    z_safe, units
+                )
+            }
+            Self::ZSafeBelowWorkpiece {
+                z_safe,
+                max_z,
+                units,
+                tool,
+            } => {
+                write!(formatter, "z_safe {} {}", z_safe, units)?;
+                if let Some(tool) = tool {
+                    write!(formatter, " for tool {}", tool)?;
+                }
+                write!(
+                    formatter,
+                    " must be larger than or equal to the workpiece max z value of {} {}",
+                    max_z, units
+                )
+            }
+            Self::ProbeAboveToolChange {
+                probe_z,
+                z_tool_change,
+            } => write!(
+                formatter,
+                "Unable to probe tools, the probe at z = {} must be below the tool change height z = {}",
+                format_number(*probe_z),
+                format_number(*z_tool_change)
+            ),
+            Self::InvalidProbeFeedRate { feed_rate } => write!(
+                formatter,
+                "Unable to probe tools, the probe feed rate must be positive, got {}",
+                feed_rate
+            ),
+            Self::RampTooSteep {
+                center,
+                radius,
+                step_z,
+                max_ramp,
+            } => write!(
+                formatter,
+                "Unable to cut circle at x = {}, y = {} with radius {}, descending {} per revolution is steeper than the max ramp of {} per revolution",
+                round_precision(center.x),
+                round_precision(center.y),
+                round_precision(*radius),
+                round_precision(*step_z),
+                round_precision(*max_ramp)
+            ),
+            Self::CornerRadiusTooLarge {
+                corner_radius,
+                from,
+                to,
+            } => write!(
+                formatter,
+                "Unable to round path corners, corner radius {} is too large for the line from x = {}, y = {} to x = {}, y = {}",
+                round_precision(*corner_radius),
+                round_precision(from.x),
+                round_precision(from.y),
+                round_precision(to.x),
+                round_precision(to.y)
+            ),
+            Self::ClearBottomWithOuterCompensation => write!(
+                formatter,
+                "Unable to cut circle, the bottom can not be cleared with outer tool compensation"
+            ),
+            Self::FinishTooLarge { finish, size } => write!(
+                formatter,
+                "Unable to cut rectangle pocket of size {}, the finish {} on each wall leaves no room for the pocket",
+                size, finish
+            ),
+            Self::Io(_) => write!(formatter, "Unable to write G-code"),
+            Self::Other(error) => write!(formatter, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            // The wrapped error is shown as the error itself, so the chain continues from its
+            // own source
+            Self::Other(error) => error.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for CncError {
+    fn from(error: anyhow::Error) -> Self {
+        // Keep the variant of errors that were only passed through anyhow
+        match error.downcast::<CncError>() {
+            Ok(error) => error,
+            Err(error) => Self::Other(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for CncError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
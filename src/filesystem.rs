@@ -1,6 +1,10 @@
 //! Provides helpers for writing G-code and project files to disk.
 
-use std::{fs::File, io::Write};
+use std::{
+    fs::{create_dir_all, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 
@@ -45,18 +49,85 @@ use crate::{camotics::*, program::*};
 /// }
 /// ```
 pub fn write_project(program: &Program, camotics_resolution: f64) -> Result<()> {
+    write_project_to(program, camotics_resolution, Path::new("."))?;
+
+    Ok(())
+}
+
+/// The paths of the files written by
+/// [write_project_to](fn.write_project_to.html) and
+/// [write_project_files](fn.write_project_files.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectPaths {
+    /// Path of the written .gcode file.
+    pub gcode: PathBuf,
+    /// Path of the written .camotics file.
+    pub camotics: PathBuf,
+}
+
+/// Writes .gcode and .camotics files, named after the program, from a program to the output
+/// directory, the directory is created if it is missing.
+///
+/// Returns the paths of the written files, or error if the files could not be written.
+pub fn write_project_to(
+    program: &Program,
+    camotics_resolution: f64,
+    out_dir: &Path,
+) -> Result<ProjectPaths> {
     let name = program.name();
-    let camotics = Camotics::from_program(name, program, camotics_resolution);
+
+    write_project_files(
+        program,
+        camotics_resolution,
+        out_dir,
+        &format!("{}.gcode", name),
+        &format!("{}.camotics", name),
+    )
+}
+
+/// Writes a program to the output directory as a G-code file and a Camotics project file with
+/// the given filenames, the directory is created if it is missing.
+///
+/// Returns the paths of the written files, or error if the files could not be written.
+pub fn write_project_files(
+    program: &Program,
+    camotics_resolution: f64,
+    out_dir: &Path,
+    gcode_filename: &str,
+    camotics_filename: &str,
+) -> Result<ProjectPaths> {
+    create_dir_all(out_dir)?;
+
+    let paths = ProjectPaths {
+        gcode: out_dir.join(gcode_filename),
+        camotics: out_dir.join(camotics_filename),
+    };
+
+    let mut camotics = Camotics::from_program(program.name(), program, camotics_resolution);
+    camotics.files = vec![gcode_filename.to_string()];
     let gcode = program.to_gcode()?;
 
-    let mut camotics_file = File::create(format!("{}.camotics", name))?;
+    let mut camotics_file = File::create(&paths.camotics)?;
     camotics_file.write_all(camotics.to_json_string().as_bytes())?;
     camotics_file.sync_all()?;
 
-    let mut gcode_file = File::create(format!("{}.gcode", name))?;
+    let mut gcode_file = File::create(&paths.gcode)?;
     gcode_file.write_all(gcode.as_bytes())?;
     gcode_file.sync_all()?;
 
+    Ok(paths)
+}
+
+/// Writes a top/down SVG preview of the program tool paths to disk, see
+/// [Program::to_svg](../program/struct.Program.html#method.to_svg).
+#[cfg(feature = "svg")]
+pub fn write_svg<P: AsRef<Path>>(program: &Program, path: P, tool_width: bool) -> Result<()> {
+    let svg = program.to_svg(tool_width)?;
+
+    let mut svg_file = File::create(path)?;
+    svg_file.write_all(svg.as_bytes())?;
+    svg_file.sync_all()?;
+
     Ok(())
 }
 
@@ -132,13 +203,13 @@ mod tests {
                 "margin": 0.0,
                 "bounds": {
                 "min": [
-                    -28.0,
                     -30.0,
+                    -32.0,
                     -0.1
                 ],
                 "max": [
-                    67.0,
-                    102.0,
+                    69.0,
+                    104.0,
                     3.0
                 ]
                 }
@@ -163,9 +234,10 @@ mod tests {
 ;(Created on: MASKED)
 ;(Created by: MASKED)
 ;(Generator: MASKED)
-;(Workarea: size_x = 95 mm, size_y = 132 mm, size_z = 3.1 mm, min_x = -28 mm, min_y = -30 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm)
+;(Workarea: size_x = 99 mm, size_y = 136 mm, size_z = 3.1 mm, min_x = -30 mm, min_y = -32 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm)
 
 G17
+G94
 
 ;(Tool change: type = Cylindrical, diameter = 4 mm, length = 50 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400 mm/min)
 G21
@@ -186,12 +258,13 @@ G1 X0 Y0 Z2
 G1 X-28 Y-30 Z1
 G1 X0 Y0 Z1
 G1 X-28 Y-30 Z0
+G1 X0 Y0 Z0
+G1 X-28 Y-30 Z-0.1
 G1 X0 Y0 Z-0.1
 G1 X-28 Y-30 Z-0.1
 G0 Z10
 
 ;(Cut path at: x = 0, y = 0)
-G0 Z10
 G0 X23 Y12
 G1 Z3 F400
 G1 X23 Y12 Z3
@@ -204,7 +277,10 @@ G1 X67 Y102 Z1.451
 G1 X23 Y12 Z1
 G1 X5 Y10 Z0.95
 G1 X67 Y102 Z0.451
-G1 X23 Y12 Z-0
+G1 X23 Y12 Z0
+G1 X23 Y12 Z0
+G1 X5 Y10 Z-0.005
+G1 X67 Y102 Z-0.055
 G1 X23 Y12 Z-0.1
 G1 X5 Y10 Z-0.1
 G1 X67 Y102 Z-0.1
@@ -216,4 +292,41 @@ M2"#.to_string().trim());
 
         Ok(())
     }
+
+    #[test]
+    fn test_write_project_files() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_name("test-temp-files");
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5000.0,
+            400.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::circle(Vector3::new(0.0, 0.0, 0.0), -1.0, 10.0, 1.0));
+
+        let out_dir = std::env::temp_dir().join("cnccoder-test-write-project-files/nested");
+        let _ = std::fs::remove_dir_all(&out_dir);
+
+        let paths = write_project_to(&program, 0.5, &out_dir)?;
+        assert_eq!(paths.gcode, out_dir.join("test-temp-files.gcode"));
+        assert_eq!(paths.camotics, out_dir.join("test-temp-files.camotics"));
+        assert_eq!(read_to_string(&paths.gcode)?, program.to_gcode()?);
+
+        let paths = write_project_files(&program, 0.5, &out_dir, "part.nc", "simulation.camotics")?;
+        assert_eq!(paths.gcode, out_dir.join("part.nc"));
+        assert_eq!(paths.camotics, out_dir.join("simulation.camotics"));
+
+        let camotics = serde_json::from_str::<Value>(&read_to_string(&paths.camotics)?)?;
+        assert_eq!(camotics["files"], serde_json::json!(["part.nc"]));
+
+        std::fs::remove_dir_all(out_dir.parent().unwrap())?;
+
+        Ok(())
+    }
 }
@@ -6,10 +6,13 @@
 use std::fmt::Write as _;
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+
+use crate::types::{CommentStyle, DwellFormat, Flavor};
 use crate::utils::round_precision;
 
 /// Rapid move
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G0 {
     /// X Coordinate
     pub x: Option<f64>,
@@ -41,7 +44,7 @@ impl G0 {
 }
 
 /// Linear Move
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G1 {
     /// X Coordinate
     pub x: Option<f64>,
@@ -81,7 +84,7 @@ impl G1 {
 /// Arc Move (clockwise)
 ///
 /// Use either R or I, J, mixing all three is not allowed
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G2 {
     /// X Coordinate
     pub x: Option<f64>,
@@ -151,7 +154,7 @@ impl G2 {
 /// Arc Move (counterclockwise)
 ///
 /// Use either R or I, J, mixing all three is not allowed
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G3 {
     /// X Coordinate
     pub x: Option<f64>,
@@ -219,7 +222,7 @@ impl G3 {
 }
 
 /// Dwell (pause duration)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G4 {
     /// Duration to pause (serializedto seconds)
     pub p: Duration,
@@ -228,12 +231,12 @@ pub struct G4 {
 impl G4 {
     /// Generate G-code string
     pub fn to_gcode(&self) -> String {
-        format!("G4 P{}", round_precision(self.p.as_secs_f64()))
+        DwellFormat::Seconds.format(self.p)
     }
 }
 
 /// Select Plane XY
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G17 {}
 
 impl G17 {
@@ -244,7 +247,7 @@ impl G17 {
 }
 
 /// Select Plane ZX
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G18 {}
 
 impl G18 {
@@ -255,7 +258,7 @@ impl G18 {
 }
 
 /// Select Plane YZ
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G19 {}
 
 impl G19 {
@@ -266,7 +269,7 @@ impl G19 {
 }
 
 /// Inch Units
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G20 {}
 
 impl G20 {
@@ -277,7 +280,7 @@ impl G20 {
 }
 
 /// Millimeter Units
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G21 {}
 
 impl G21 {
@@ -287,8 +290,107 @@ impl G21 {
     }
 }
 
+/// Inverse Time Feed Mode (feed rates are given as moves per minute)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct G93 {}
+
+impl G93 {
+    /// Generate G-code string
+    pub fn to_gcode(&self) -> String {
+        "G93".to_string()
+    }
+}
+
+/// Units per Minute Feed Mode
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct G94 {}
+
+impl G94 {
+    /// Generate G-code string
+    pub fn to_gcode(&self) -> String {
+        "G94".to_string()
+    }
+}
+
+/// Set Coordinate System, L20 sets the work offset so that the current position gets the
+/// given coordinates
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct G10 {
+    /// Setting type, 2 sets the offset directly and 20 relative to the current position
+    pub l: u32,
+    /// Coordinate system, 0 is the active coordinate system and 1 to 9 are G54 to G59.3
+    pub p: u32,
+    /// X Coordinate
+    pub x: Option<f64>,
+    /// Y Coordinate
+    pub y: Option<f64>,
+    /// Z Coordinate
+    pub z: Option<f64>,
+}
+
+impl G10 {
+    /// Generate G-code string
+    pub fn to_gcode(&self) -> String {
+        let mut command = format!("G10 L{} P{}", self.l, self.p);
+
+        if let Some(x) = self.x {
+            let _ = write!(command, " X{}", round_precision(x));
+        }
+
+        if let Some(y) = self.y {
+            let _ = write!(command, " Y{}", round_precision(y));
+        }
+
+        if let Some(z) = self.z {
+            let _ = write!(command, " Z{}", round_precision(z));
+        }
+
+        command
+    }
+}
+
+/// Straight Probe, moves toward the target until the probe is triggered and stops with an
+/// alarm if it is not
+#[allow(non_camel_case_types)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct G38_2 {
+    /// X Coordinate
+    pub x: Option<f64>,
+    /// Y Coordinate
+    pub y: Option<f64>,
+    /// Z Coordinate
+    pub z: Option<f64>,
+    /// Feedrate
+    pub f: Option<f64>,
+}
+
+impl G38_2 {
+    /// Generate G-code string
+    pub fn to_gcode(&self) -> String {
+        let mut command = "G38.2".to_string();
+
+        if let Some(x) = self.x {
+            let _ = write!(command, " X{}", round_precision(x));
+        }
+
+        if let Some(y) = self.y {
+            let _ = write!(command, " Y{}", round_precision(y));
+        }
+
+        if let Some(z) = self.z {
+            let _ = write!(command, " Z{}", round_precision(z));
+        }
+
+        if let Some(f) = self.f {
+            let _ = write!(command, " F{}", round_precision(f));
+        }
+
+        command
+    }
+}
+
 /// Tool Length Offset (applies offset to all coordinates)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct G43 {
     /// Tool number (offset will be looked up in the tool table)
     pub h: u32,
@@ -302,7 +404,7 @@ impl G43 {
 }
 
 /// Set Feed Rate
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct F {
     /// Feed rate (units per minute)
     pub x: f64,
@@ -316,7 +418,7 @@ impl F {
 }
 
 /// Set Spindle Speed
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct S {
     /// Feed rate (rpm)
     pub x: f64,
@@ -330,7 +432,7 @@ impl S {
 }
 
 /// Program Pause (user must resume)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct M0 {}
 
 impl M0 {
@@ -340,8 +442,19 @@ impl M0 {
     }
 }
 
+/// Optional Program Pause (only pauses when optional stops are enabled on the machine)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct M1 {}
+
+impl M1 {
+    /// Generate G-code string
+    pub fn to_gcode(&self) -> String {
+        "M1".to_string()
+    }
+}
+
 /// Program End (stop spindle and reset all offsets)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct M2 {}
 
 impl M2 {
@@ -352,7 +465,7 @@ impl M2 {
 }
 
 /// Start Spindle (clockwise)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct M3 {}
 
 impl M3 {
@@ -363,7 +476,7 @@ impl M3 {
 }
 
 /// Start Spindle (counterclockwise)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct M4 {}
 
 impl M4 {
@@ -374,7 +487,7 @@ impl M4 {
 }
 
 /// Stop Spindle
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct M5 {}
 
 impl M5 {
@@ -385,7 +498,7 @@ impl M5 {
 }
 
 /// Manual Tool Change
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct M6 {
     /// Tool number
     pub t: u8,
@@ -399,7 +512,7 @@ impl M6 {
 }
 
 /// Empty Line
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Empty {}
 
 impl Empty {
@@ -410,7 +523,7 @@ impl Empty {
 }
 
 /// Comment
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Comment {
     /// Comment
     pub text: String,
@@ -419,16 +532,12 @@ pub struct Comment {
 impl Comment {
     /// Generate G-code string
     pub fn to_gcode(&self) -> String {
-        if self.text.is_empty() {
-            return String::new();
-        }
-
-        format!(";({})", self.text)
+        CommentStyle::default().format(&self.text)
     }
 }
 
 /// Message to print
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Message {
     /// Message
     pub text: String,
@@ -441,11 +550,25 @@ impl Message {
     }
 }
 
+/// Raw G-code line that is passed through as is
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Raw {
+    /// G-code
+    pub text: String,
+}
+
+impl Raw {
+    /// Generate G-code string
+    pub fn to_gcode(&self) -> String {
+        self.text.clone()
+    }
+}
+
 /// The Instruction enum is used to represent a single G-code command in a program.
 /// See the
 /// [Grbl reference](https://github.com/gnea/grbl/wiki/Grbl-v1.1-Commands#g---view-gcode-parser-state)
 /// for more details.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Instruction {
     /// Command G0, Rapid Move
     G0(G0),
@@ -467,14 +590,24 @@ pub enum Instruction {
     G20(G20),
     /// Command G20, Millimeter Units
     G21(G21),
+    /// Command G10, Set Coordinate System
+    G10(G10),
+    /// Command G38.2, Straight Probe
+    G38_2(G38_2),
     /// Command G43, Tool Length Offset
     G43(G43),
+    /// Command G93, Inverse Time Feed Mode
+    G93(G93),
+    /// Command G94, Units per Minute Feed Mode
+    G94(G94),
     /// Command F, Set Feed Rate
     F(F),
     /// Command S, Set Spindle Speed
     S(S),
     /// Command M0, Program Pause
     M0(M0),
+    /// Command M1, Optional Program Pause
+    M1(M1),
     /// Command M2, Program End
     M2(M2),
     /// Command M3, Start Spindle (clockwise)
@@ -491,6 +624,8 @@ pub enum Instruction {
     Comment(Comment),
     /// Command Message, Message to point
     Message(Message),
+    /// Command Raw, G-code line passed through as is
+    Raw(Raw),
 }
 
 impl Instruction {
@@ -507,10 +642,15 @@ impl Instruction {
             Instruction::G19(instruction) => instruction.to_gcode(),
             Instruction::G20(instruction) => instruction.to_gcode(),
             Instruction::G21(instruction) => instruction.to_gcode(),
+            Instruction::G10(instruction) => instruction.to_gcode(),
+            Instruction::G38_2(instruction) => instruction.to_gcode(),
             Instruction::G43(instruction) => instruction.to_gcode(),
+            Instruction::G93(instruction) => instruction.to_gcode(),
+            Instruction::G94(instruction) => instruction.to_gcode(),
             Instruction::F(instruction) => instruction.to_gcode(),
             Instruction::S(instruction) => instruction.to_gcode(),
             Instruction::M0(instruction) => instruction.to_gcode(),
+            Instruction::M1(instruction) => instruction.to_gcode(),
             Instruction::M2(instruction) => instruction.to_gcode(),
             Instruction::M3(instruction) => instruction.to_gcode(),
             Instruction::M4(instruction) => instruction.to_gcode(),
@@ -519,6 +659,24 @@ impl Instruction {
             Instruction::Empty(instruction) => instruction.to_gcode(),
             Instruction::Comment(instruction) => instruction.to_gcode(),
             Instruction::Message(instruction) => instruction.to_gcode(),
+            Instruction::Raw(instruction) => instruction.to_gcode(),
+        }
+    }
+
+    /// Converts instruction to G-code for a specific [Flavor](../types/enum.Flavor.html).
+    ///
+    /// Instructions that are written the same way for all flavors are converted with
+    /// [Instruction::to_gcode](enum.Instruction.html#method.to_gcode). The Marlin spindle
+    /// speed is set as part of M3/M4, which is handled when converting the whole
+    /// [Program](../program/struct.Program.html#method.to_gcode).
+    pub fn to_flavored_gcode(&self, flavor: Flavor) -> String {
+        match (flavor, self) {
+            (_, Instruction::Comment(comment)) => flavor.comment_style().format(&comment.text),
+            (Flavor::Mach3, Instruction::M2(_)) => "M30".to_string(),
+            (_, Instruction::G4(g4)) => flavor.dwell_format().format(g4.p),
+            (Flavor::Marlin, Instruction::Message(message)) => format!("M117 {}", message.text),
+            (Flavor::Marlin, Instruction::M6(m6)) => format!("M0 Change to tool T{}", m6.t),
+            _ => self.to_gcode(),
         }
     }
 }
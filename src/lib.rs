@@ -16,8 +16,9 @@
 //! that can be used to simulate the G-code so that it can be verified before ran
 //! on an actual machine, reducing the risk of damaging the CNC machine and injury.
 //!
-//! G-code does come in several flavors, but so far the project is only targeting
-//! CNC machines using the [Grbl](https://github.com/gnea/grbl) controller.
+//! G-code does come in several flavors, the project is primarily targeting CNC machines
+//! using the [Grbl](https://github.com/gnea/grbl) controller, but LinuxCNC, Mach3, and
+//! Marlin flavored G-code can also be generated with `Program::set_flavor`.
 //!
 //! Example of a simple planing program (from `examples/planing.rs`):
 //! ```
@@ -78,10 +79,18 @@
 
 #[cfg(feature = "filesystem")]
 pub mod camotics;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod cuts;
+pub mod error;
 pub mod instructions;
+mod motion;
+#[cfg(feature = "svg")]
+mod preview;
 pub mod program;
 pub mod programs;
+#[cfg(feature = "stl")]
+mod stl;
 pub mod tools;
 pub mod types;
 pub mod utils;
@@ -102,8 +111,13 @@ pub mod prelude {
     #[cfg(feature = "filesystem")]
     #[doc(hidden)]
     pub use crate::camotics::*;
+    #[cfg(feature = "config")]
+    #[doc(hidden)]
+    pub use crate::config::*;
     #[doc(hidden)]
     pub use crate::cuts::*;
+    #[doc(hidden)]
+    pub use crate::error::*;
     #[cfg(feature = "filesystem")]
     #[doc(hidden)]
     pub use crate::filesystem::*;
@@ -0,0 +1,322 @@
+//! Internal helpers for following the machine state through a list of instructions, used when
+//! analysing programs, for example to estimate machining time.
+
+use std::f64::consts::TAU;
+
+use crate::instructions::*;
+use crate::types::*;
+
+/// The kind of move an instruction resulted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MoveKind {
+    /// Rapid G0 move.
+    Rapid,
+    /// Linear G1 cutting move.
+    Linear,
+    /// Arc G2/G3 cutting move.
+    Arc,
+}
+
+/// A single move and its travel length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Move {
+    pub kind: MoveKind,
+    pub length: f64,
+    pub feed_rate: Option<f64>,
+}
+
+/// The commanded state of the machine (position, plane, units, and feed rate) after an
+/// instruction, see [Program::simulate](struct.Program.html#method.simulate). The machine is
+/// assumed to start at xyz 0.0.
+#[derive(Debug, Clone)]
+pub struct MachineState {
+    /// The commanded position of the tool.
+    pub position: Vector3,
+    /// The axis of the active arc plane, `Axis::Z` for the G17 xy plane.
+    pub plane: Axis,
+    /// The active units, changed by G20 and G21.
+    pub units: Units,
+    /// The active feed rate, if one has been set.
+    pub feed_rate: Option<f64>,
+}
+
+impl Default for MachineState {
+    fn default() -> Self {
+        Self {
+            position: Vector3::ZERO,
+            plane: Axis::Z,
+            units: Units::Metric,
+            feed_rate: None,
+        }
+    }
+}
+
+impl MachineState {
+    /// Updates the state from an instruction, returns the resulting move if the
+    /// instruction moved the machine.
+    pub(crate) fn apply(&mut self, instruction: &Instruction) -> Option<Move> {
+        match instruction {
+            Instruction::G0(g0) => {
+                let to = self.target(g0.x, g0.y, g0.z);
+                Some(self.move_to(MoveKind::Rapid, to, None))
+            }
+            Instruction::G1(g1) => {
+                self.update_feed_rate(g1.f);
+                let to = self.target(g1.x, g1.y, g1.z);
+                Some(self.move_to(MoveKind::Linear, to, None))
+            }
+            Instruction::G2(g2) => {
+                self.update_feed_rate(g2.f);
+                let to = self.target(g2.x, g2.y, g2.z);
+                let length =
+                    self.arc_length(to, [g2.i, g2.j, g2.k], g2.r, g2.p, Direction::Clockwise);
+                Some(self.move_to(MoveKind::Arc, to, Some(length)))
+            }
+            Instruction::G3(g3) => {
+                self.update_feed_rate(g3.f);
+                let to = self.target(g3.x, g3.y, g3.z);
+                let length = self.arc_length(
+                    to,
+                    [g3.i, g3.j, g3.k],
+                    g3.r,
+                    g3.p,
+                    Direction::Counterclockwise,
+                );
+                Some(self.move_to(MoveKind::Arc, to, Some(length)))
+            }
+            Instruction::G38_2(g38_2) => {
+                // Assumes the probe is triggered at the end of the move
+                self.update_feed_rate(g38_2.f);
+                let to = self.target(g38_2.x, g38_2.y, g38_2.z);
+                Some(self.move_to(MoveKind::Linear, to, None))
+            }
+            Instruction::G17(_) => {
+                self.plane = Axis::Z;
+                None
+            }
+            Instruction::G18(_) => {
+                self.plane = Axis::Y;
+                None
+            }
+            Instruction::G19(_) => {
+                self.plane = Axis::X;
+                None
+            }
+            Instruction::G20(_) => {
+                self.units = Units::Imperial;
+                None
+            }
+            Instruction::G21(_) => {
+                self.units = Units::Metric;
+                None
+            }
+            Instruction::F(f) => {
+                self.feed_rate = Some(f.x);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn update_feed_rate(&mut self, feed_rate: Option<f64>) {
+        if feed_rate.is_some() {
+            self.feed_rate = feed_rate;
+        }
+    }
+
+    fn target(&self, x: Option<f64>, y: Option<f64>, z: Option<f64>) -> Vector3 {
+        Vector3::new(
+            x.unwrap_or(self.position.x),
+            y.unwrap_or(self.position.y),
+            z.unwrap_or(self.position.z),
+        )
+    }
+
+    fn move_to(&mut self, kind: MoveKind, to: Vector3, length: Option<f64>) -> Move {
+        let length = length.unwrap_or_else(|| self.position.distance_to(to));
+        self.position = to;
+
+        Move {
+            kind,
+            length,
+            feed_rate: if kind == MoveKind::Rapid {
+                None
+            } else {
+                self.feed_rate
+            },
+        }
+    }
+
+    // Splits a point into (first plane axis, second plane axis, linear axis) for the active
+    // plane, G17 uses XY, G18 uses ZX, and G19 uses YZ.
+    fn plane_coordinates(&self, point: Vector3) -> (f64, f64, f64) {
+        match self.plane {
+            Axis::Z => (point.x, point.y, point.z),
+            Axis::Y => (point.z, point.x, point.y),
+            Axis::X => (point.y, point.z, point.x),
+        }
+    }
+
+    fn arc_length(
+        &self,
+        to: Vector3,
+        offsets: [Option<f64>; 3],
+        radius: Option<f64>,
+        turns: Option<u32>,
+        direction: Direction,
+    ) -> f64 {
+        let (from_u, from_v, from_w) = self.plane_coordinates(self.position);
+        let (to_u, to_v, to_w) = self.plane_coordinates(to);
+        let [i, j, k] = offsets.map(|offset| offset.unwrap_or(0.0));
+        let (offset_u, offset_v, _) = self.plane_coordinates(Vector3::new(i, j, k));
+        let extra_turns = f64::from(turns.unwrap_or(1).max(1) - 1);
+
+        let (radius, angle) = if let Some(radius) = radius {
+            let chord = Vector2::new(from_u, from_v).distance_to(Vector2::new(to_u, to_v));
+            let half_angle = (chord / (2.0 * radius.abs())).clamp(-1.0, 1.0).asin();
+            let angle = if radius < 0.0 {
+                TAU - 2.0 * half_angle
+            } else {
+                2.0 * half_angle
+            };
+            (radius.abs(), angle)
+        } else {
+            let center = Vector2::new(from_u + offset_u, from_v + offset_v);
+            let from_angle = (from_v - center.y).atan2(from_u - center.x);
+            let to_angle = (to_v - center.y).atan2(to_u - center.x);
+            let delta = match direction {
+                Direction::Clockwise => from_angle - to_angle,
+                Direction::Counterclockwise => to_angle - from_angle,
+            };
+            let angle = delta.rem_euclid(TAU);
+
+            (
+                Vector2::new(from_u, from_v).distance_to(center),
+                if angle < 1e-9 { TAU } else { angle },
+            )
+        };
+
+        let arc = radius * (angle + extra_turns * TAU);
+        let linear = to_w - from_w;
+
+        (arc * arc + linear * linear).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn g2(x: f64, y: f64, i: f64, j: f64, p: Option<u32>) -> Instruction {
+        Instruction::G2(G2 {
+            x: Some(x),
+            y: Some(y),
+            z: None,
+            i: Some(i),
+            j: Some(j),
+            k: None,
+            r: None,
+            p,
+            f: Some(100.0),
+        })
+    }
+
+    #[test]
+    fn test_linear_moves() {
+        let mut state = MachineState::default();
+
+        let rapid = state.apply(&Instruction::G0(G0 {
+            x: Some(3.0),
+            y: Some(4.0),
+            z: None,
+        }));
+        assert_eq!(
+            rapid,
+            Some(Move {
+                kind: MoveKind::Rapid,
+                length: 5.0,
+                feed_rate: None
+            })
+        );
+
+        let linear = state.apply(&Instruction::G1(G1 {
+            x: None,
+            y: None,
+            z: Some(-2.0),
+            f: Some(300.0),
+        }));
+        assert_eq!(
+            linear,
+            Some(Move {
+                kind: MoveKind::Linear,
+                length: 2.0,
+                feed_rate: Some(300.0)
+            })
+        );
+        assert_eq!(state.position, Vector3::new(3.0, 4.0, -2.0));
+    }
+
+    #[test]
+    fn test_modal_state() {
+        let mut state = MachineState::default();
+        assert_eq!(state.units, Units::Metric);
+
+        assert_eq!(state.apply(&Instruction::G20(G20 {})), None);
+        assert_eq!(state.apply(&Instruction::G18(G18 {})), None);
+        assert_eq!(state.apply(&Instruction::F(F { x: 20.0 })), None);
+        assert_eq!(state.units, Units::Imperial);
+        assert!(matches!(state.plane, Axis::Y));
+        assert_eq!(state.feed_rate, Some(20.0));
+
+        state.apply(&Instruction::G21(G21 {}));
+        state.apply(&Instruction::G17(G17 {}));
+        assert_eq!(state.units, Units::Metric);
+        assert!(matches!(state.plane, Axis::Z));
+    }
+
+    #[test]
+    fn test_arc_length() {
+        let mut state = MachineState {
+            position: Vector3::new(-10.0, 0.0, 0.0),
+            ..Default::default()
+        };
+
+        // Quarter circle clockwise from -x to +y
+        let quarter = state.apply(&g2(0.0, 10.0, 10.0, 0.0, None)).unwrap();
+        assert!((quarter.length - PI * 5.0).abs() < 1e-9);
+
+        // Full circle back to the same position
+        let full = state.apply(&g2(0.0, 10.0, 0.0, -10.0, None)).unwrap();
+        assert!((full.length - PI * 20.0).abs() < 1e-9);
+
+        // Three full circles
+        let full = state.apply(&g2(0.0, 10.0, 0.0, -10.0, Some(3))).unwrap();
+        assert!((full.length - PI * 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_arc_length_counterclockwise() {
+        let mut state = MachineState {
+            position: Vector3::new(-10.0, 0.0, 0.0),
+            ..Default::default()
+        };
+
+        // Three quarters of a circle counterclockwise from -x to +y
+        let arc = state
+            .apply(&Instruction::G3(G3 {
+                x: Some(0.0),
+                y: Some(10.0),
+                z: None,
+                i: Some(10.0),
+                j: Some(0.0),
+                k: None,
+                r: None,
+                p: None,
+                f: None,
+            }))
+            .unwrap();
+        assert!((arc.length - PI * 15.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,285 @@
+//! Renders a top/down SVG preview of the tool paths of a program.
+
+use std::collections::BTreeMap;
+use std::f64::consts::{PI, TAU};
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use crate::instructions::*;
+use crate::motion::*;
+use crate::program::*;
+use crate::types::*;
+use crate::utils::round_precision;
+
+// Colors used for the cuts of each tool, repeated if there are more tools than colors.
+const TOOL_COLORS: [&str; 6] = [
+    "#1f77b4", "#2ca02c", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+const RAPID_COLOR: &str = "#d62728";
+
+// SVG path data for a set of moves, only moving the pen when the moves are not connected.
+#[derive(Default)]
+struct PathData {
+    data: String,
+    position: Option<Vector2>,
+}
+
+impl PathData {
+    fn move_to(&mut self, from: Vector2) {
+        if self.position != Some(from) {
+            let _ = write!(
+                self.data,
+                "M{} {} ",
+                round_precision(from.x),
+                round_precision(from.y)
+            );
+        }
+    }
+
+    fn line(&mut self, from: Vector2, to: Vector2) {
+        if from == to {
+            return;
+        }
+
+        self.move_to(from);
+        let _ = write!(
+            self.data,
+            "L{} {} ",
+            round_precision(to.x),
+            round_precision(to.y)
+        );
+        self.position = Some(to);
+    }
+
+    fn arc(
+        &mut self,
+        from: Vector2,
+        to: Vector2,
+        radius: f64,
+        large: bool,
+        counterclockwise: bool,
+    ) {
+        self.move_to(from);
+        let _ = write!(
+            self.data,
+            "A{} {} 0 {} {} {} {} ",
+            round_precision(radius),
+            round_precision(radius),
+            u8::from(large),
+            u8::from(counterclockwise),
+            round_precision(to.x),
+            round_precision(to.y)
+        );
+        self.position = Some(to);
+    }
+
+    // Adds an arc around a center point, full circles are split in two halves as SVG can not
+    // draw an arc that ends where it starts.
+    fn arc_around(&mut self, from: Vector2, to: Vector2, center: Vector2, counterclockwise: bool) {
+        let radius = from.distance_to(center);
+        let from_angle = (from.y - center.y).atan2(from.x - center.x);
+        let to_angle = (to.y - center.y).atan2(to.x - center.x);
+        let angle = if counterclockwise {
+            to_angle - from_angle
+        } else {
+            from_angle - to_angle
+        }
+        .rem_euclid(TAU);
+
+        if angle < 1e-9 {
+            let opposite = center * Vector2::splat(2.0) - from;
+            self.arc(from, opposite, radius, false, counterclockwise);
+            self.arc(opposite, to, radius, false, counterclockwise);
+        } else {
+            self.arc(from, to, radius, angle > PI, counterclockwise);
+        }
+    }
+}
+
+impl Program {
+    /// Renders a top/down SVG preview of the tool paths, useful as a quick visual check
+    /// before simulating the program.
+    ///
+    /// Cutting moves are drawn in a color per tool and rapid moves as dashed red lines, moves
+    /// along the z axis only are not visible. When `tool_width` is set the cuts are also drawn
+    /// with a transparent stroke as wide as the tool diameter, showing the material that is
+    /// removed. Arcs that are not in the xy plane are drawn as straight lines.
+    pub fn to_svg(&self, tool_width: bool) -> Result<String> {
+        let tools = self
+            .tools()
+            .into_iter()
+            .filter_map(|tool| Some((self.tool_number(&tool)?, tool)))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut state = MachineState::default();
+        let mut rapids = PathData::default();
+        let mut cuts = BTreeMap::<u8, PathData>::new();
+        let mut tool_number = 0;
+
+        for instruction in self.to_instructions()? {
+            let from = state.position.xy();
+            let xy_plane = matches!(state.plane, Axis::Z);
+
+            if let Instruction::M6(m6) = &instruction {
+                tool_number = m6.t;
+            }
+
+            let Some(motion) = state.apply(&instruction) else {
+                continue;
+            };
+
+            let to = state.position.xy();
+            let path = cuts.entry(tool_number).or_default();
+
+            match (&instruction, motion.kind) {
+                (_, MoveKind::Rapid) => rapids.line(from, to),
+                (Instruction::G2(arc), MoveKind::Arc) if xy_plane => {
+                    arc_to(path, from, to, arc.i, arc.j, arc.r, false);
+                }
+                (Instruction::G3(arc), MoveKind::Arc) if xy_plane => {
+                    arc_to(path, from, to, arc.i, arc.j, arc.r, true);
+                }
+                _ => path.line(from, to),
+            }
+        }
+
+        // The program bounds already cover the area swept by the tools
+        let bounds = self.bounds();
+        let min = bounds.min.xy();
+        let size = bounds.max.xy() - bounds.min.xy();
+        let units = match self.units() {
+            Units::Metric => "mm",
+            Units::Imperial => "in",
+        };
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}{units}" height="{}{units}">"#,
+            round_precision(min.x),
+            round_precision(-(min.y + size.y)),
+            round_precision(size.x),
+            round_precision(size.y),
+            round_precision(size.x),
+            round_precision(size.y),
+        )?;
+        writeln!(svg, "  <title>{}</title>", escape(self.name()))?;
+        writeln!(
+            svg,
+            r#"  <g transform="scale(1 -1)" fill="none" stroke-linecap="round" stroke-linejoin="round">"#
+        )?;
+
+        for (number, path) in cuts.iter().filter(|(_, path)| !path.data.is_empty()) {
+            let color = TOOL_COLORS[usize::from(*number) % TOOL_COLORS.len()];
+            let data = path.data.trim_end();
+
+            if let Some(tool) = tools.get(number).filter(|_| tool_width) {
+                writeln!(
+                    svg,
+                    r#"    <path d="{data}" stroke="{color}" stroke-opacity="0.25" stroke-width="{}"/>"#,
+                    round_precision(tool.diameter())
+                )?;
+            }
+
+            writeln!(
+                svg,
+                r#"    <path d="{data}" stroke="{color}" stroke-width="1" vector-effect="non-scaling-stroke"/>"#
+            )?;
+        }
+
+        if !rapids.data.is_empty() {
+            writeln!(
+                svg,
+                r#"    <path d="{}" stroke="{RAPID_COLOR}" stroke-width="1" stroke-dasharray="4 2" vector-effect="non-scaling-stroke"/>"#,
+                rapids.data.trim_end()
+            )?;
+        }
+
+        writeln!(svg, "  </g>")?;
+        writeln!(svg, "</svg>")?;
+
+        Ok(svg)
+    }
+}
+
+fn arc_to(
+    path: &mut PathData,
+    from: Vector2,
+    to: Vector2,
+    i: Option<f64>,
+    j: Option<f64>,
+    r: Option<f64>,
+    counterclockwise: bool,
+) {
+    match (i, j, r) {
+        (None, None, Some(radius)) => {
+            path.arc(from, to, radius.abs(), radius < 0.0, counterclockwise);
+        }
+        _ => {
+            let center = from + Vector2::new(i.unwrap_or(0.0), j.unwrap_or(0.0));
+            path.arc_around(from, to, center, counterclockwise);
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuts::*;
+    use crate::tools::*;
+
+    #[test]
+    fn test_program_to_svg() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_name("svg <preview>");
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            500.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::path(
+            Vector3::new(0.0, 0.0, 0.0),
+            vec![Segment::line(
+                Vector2::new(0.0, 0.0),
+                Vector2::new(20.0, 0.0),
+            )],
+            -1.0,
+            1.0,
+        ));
+        context.append_cut(Cut::circle(Vector3::new(50.0, 10.0, 0.0), -1.0, 10.0, 1.0));
+
+        let svg = program.to_svg(true)?;
+        let lines = svg.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[0],
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-2 -22 64 24" width="64mm" height="24mm">"#
+        );
+        assert_eq!(lines[1], "  <title>svg &lt;preview&gt;</title>");
+        assert!(lines[3].contains(r#"stroke-width="4""#));
+        assert!(lines[3].starts_with(r#"    <path d="M0 0 L20 0 L0 0 L20 0"#));
+        // The full circles are split in two half circles
+        assert!(lines[3].contains("M40 10 A10 10 0 0 0 60 10 A10 10 0 0 0 40 10"));
+        assert!(lines[4].contains(r#"vector-effect="non-scaling-stroke""#));
+        assert!(lines[5].contains(RAPID_COLOR));
+        assert!(lines[5].contains("L40 10"));
+        assert_eq!(lines[lines.len() - 1], "</svg>");
+
+        assert!(!program.to_svg(false)?.contains("stroke-opacity"));
+
+        Ok(())
+    }
+}
@@ -50,20 +50,54 @@
 use std::cell::RefCell;
 use std::collections::hash_map::Entry::Vacant;
 use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::io::Write;
 use std::rc::Rc;
 use std::time::Duration;
 
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use time::OffsetDateTime;
 
 use crate::cuts::*;
+use crate::error::CncError;
 use crate::instructions::*;
+pub use crate::motion::MachineState;
+use crate::motion::*;
 use crate::prelude::round_precision;
 use crate::tools::*;
 use crate::types::*;
-use crate::utils::scale;
+use crate::utils::{scale, with_precision, DEFAULT_PRECISION};
 
-fn format_number(value: f64) -> String {
+// A Context is only created by the program for a tool that it has a context for, and contexts
+// are never removed from a program, so the tool context of a Context always exists.
+const CONTEXT_MISSING: &str = "the program is missing the tool context of the Context";
+
+// Time allowance used for each manual tool change when estimating the program duration.
+const TOOL_CHANGE_DURATION: Duration = Duration::from_secs(60);
+
+// Heights used by programs that are created without them, shared by the default program and
+// the builder.
+const DEFAULT_Z_SAFE: f64 = 50.0;
+const DEFAULT_Z_TOOL_CHANGE: f64 = 100.0;
+
+fn default_rapid_rate(units: Units) -> f64 {
+    match units {
+        Units::Metric => 2000.0,
+        Units::Imperial => 2000.0 / 25.4,
+    }
+}
+
+// An extra decimal for imperial units keeps the same detail as metric, as 0.001 inches is
+// about 0.025 mm.
+fn default_precision(units: Units) -> u8 {
+    match units {
+        Units::Metric => DEFAULT_PRECISION,
+        Units::Imperial => DEFAULT_PRECISION + 1,
+    }
+}
+
+pub(crate) fn format_number(value: f64) -> String {
     if value.is_finite() {
         let new_value = round_precision(value);
         if new_value.is_finite() {
@@ -77,8 +111,10 @@ fn format_number(value: f64) -> String {
     .to_string()
 }
 
-/// A high level respresentation of a CNC program operation, Cut, Comment, Message, or Empty.
-#[derive(Debug, Clone)]
+/// A high level respresentation of a CNC program operation, Cut, Comment, Message, Pause,
+/// OptionalStop, Raw, or Empty.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
 pub enum Operation {
     /// A high level cut operation.
     Cut(Cut),
@@ -88,6 +124,13 @@ pub enum Operation {
     Comment(Comment),
     /// A program message.
     Message(Message),
+    /// A program pause that shows a message and waits for the operator to resume the program.
+    Pause(Message),
+    /// An optional program pause that only waits for the operator when optional stops are
+    /// enabled on the machine, the message is only shown if it is not empty.
+    OptionalStop(Message),
+    /// Raw G-code that is passed through to the program as is.
+    Raw(Raw),
 }
 
 impl Operation {
@@ -98,18 +141,120 @@ impl Operation {
             Self::Empty(_) => Bounds::default(),
             Self::Comment(_) => Bounds::default(),
             Self::Message(_) => Bounds::default(),
+            Self::Pause(_) => Bounds::default(),
+            Self::OptionalStop(_) => Bounds::default(),
+            Self::Raw(_) => Bounds::default(),
+        }
+    }
+
+    /// The bounds of the area swept by a tool of the given radius for the operation, see
+    /// [Cut::compensated_bounds](../cuts/enum.Cut.html#method.compensated_bounds).
+    pub fn compensated_bounds(&self, tool_radius: f64) -> Bounds {
+        match self {
+            Self::Cut(o) => o.compensated_bounds(tool_radius),
+            _ => self.bounds(),
+        }
+    }
+
+    /// Returns a new operation with the transform applied, only cut operations are affected.
+    pub fn transform(&self, transform: &Transform) -> Result<Self> {
+        match self {
+            Self::Cut(o) => Ok(Self::Cut(o.transform(transform)?)),
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /// Returns a copy of the operation with the cut converted from one unit to another, see
+    /// [Cut::convert_units](../cuts/enum.Cut.html#method.convert_units). Other operations,
+    /// including raw G-code, are kept as they are.
+    pub fn convert_units(&self, from: Units, to: Units) -> Result<Self> {
+        match self {
+            Self::Cut(o) => Ok(Self::Cut(o.convert_units(from, to)?)),
+            _ => Ok(self.clone()),
         }
     }
 
     /// Converts operation to G-code instructions.
-    pub fn to_instructions(&self, context: InnerContext) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self, context: &InnerContext) -> Result<Vec<Instruction>, CncError> {
         match self {
             Self::Cut(o) => o.to_instructions(context),
             Self::Empty(_) => Ok(vec![Instruction::Empty(Empty {})]),
             Self::Comment(i) => Ok(vec![Instruction::Comment(i.clone())]),
             Self::Message(i) => Ok(vec![Instruction::Message(i.clone())]),
+            Self::Pause(i) => Ok(vec![
+                Instruction::Message(i.clone()),
+                Instruction::M0(M0 {}),
+            ]),
+            Self::OptionalStop(i) if i.text.is_empty() => Ok(vec![Instruction::M1(M1 {})]),
+            Self::OptionalStop(i) => Ok(vec![
+                Instruction::Message(i.clone()),
+                Instruction::M1(M1 {}),
+            ]),
+            Self::Raw(i) => Ok(vec![Instruction::Raw(i.clone())]),
         }
     }
+
+    /// Converts operation to G-code instructions, where the comment of a cut is followed by
+    /// comments with the depths, number of passes, feed rate, and estimated cutting time of the
+    /// cut, see [Program::set_verbose_cut_headers](struct.Program.html#method.set_verbose_cut_headers).
+    pub fn to_instructions_with_header(&self, context: &InnerContext) -> Result<Vec<Instruction>> {
+        let mut instructions = self.to_instructions(context)?;
+
+        let Self::Cut(cut) = self else {
+            return Ok(instructions);
+        };
+
+        let units = context.units();
+        let tool = context.tool().to_units(units);
+        let bounds = cut.bounds();
+        let passes = cut.layers(&tool)?.len().max(1);
+        let max_step_z = cut
+            .max_step_z()
+            .map(|max_step_z| format!(", max_step_z = {} {units}", format_number(max_step_z)))
+            .unwrap_or_default();
+
+        // Only the cutting moves are counted, the rapid rate is set by the program
+        let mut state = MachineState::default();
+        let minutes = instructions
+            .iter()
+            .filter_map(|instruction| state.apply(instruction))
+            .filter_map(|motion| match motion.feed_rate {
+                Some(rate) if rate > 0.0 => Some(motion.length / rate),
+                _ => None,
+            })
+            .sum::<f64>();
+
+        let header = vec![
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Cut details: start_z = {} {units}, end_z = {} {units}{}, passes = {}",
+                    format_number(bounds.max.z),
+                    format_number(bounds.min.z),
+                    max_step_z,
+                    passes
+                ),
+            }),
+            Instruction::Comment(Comment {
+                text: format!(
+                    "Cut details: tool_diameter = {} {units}, feed_rate = {} {units}/min, estimated_time = {} s",
+                    format_number(tool.diameter()),
+                    format_number(tool.feed_rate()),
+                    (minutes * 60.0).round()
+                ),
+            }),
+        ];
+
+        // The header goes after the blank line and comment that starts the cut
+        let position = instructions
+            .iter()
+            .take_while(|instruction| {
+                matches!(instruction, Instruction::Empty(_) | Instruction::Comment(_))
+            })
+            .count();
+        instructions.splice(position..position, header);
+
+        Ok(instructions)
+    }
 }
 
 /// A program context that keeps the state data for operations paired with a specific tool.
@@ -119,7 +264,7 @@ impl Operation {
 /// This struct is mainly for internal use, most of the time you would use the ToolContext
 /// struct instead.
 #[doc(hidden)]
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InnerContext {
     units: Units,
     tool: Tool,
@@ -145,7 +290,11 @@ impl InnerContext {
     /// Returns error if tool or units are not the same in both contexts.
     pub fn merge(&mut self, context: InnerContext) -> Result<()> {
         if self.units != context.units {
-            return Err(anyhow!("Failed to merge due to mismatching units"));
+            return Err(CncError::MismatchedUnits {
+                expected: self.units,
+                actual: context.units,
+            }
+            .into());
         }
 
         if self.tool != context.tool {
@@ -172,6 +321,263 @@ impl InnerContext {
         self.append(Operation::Cut(cut));
     }
 
+    /// Appends a rectangular pocket followed by a finishing pass along its walls.
+    ///
+    /// The generated operations are, in order:
+    /// 1. A [pocket](../cuts/enum.Cut.html#method.pocket) cut in layers of `max_step_z`,
+    ///    leaving `finish` of material on the walls.
+    /// 2. An [inner frame](../cuts/enum.Cut.html#method.frame_inner) cut along the walls in a
+    ///    single pass at the full depth, removing the remaining material.
+    ///
+    /// Nothing is appended when `end_z` is at `start.z`, as there is nothing to cut.
+    ///
+    /// Returns error if the finish on both sides is as wide as the pocket.
+    pub fn append_rectangle_pocket(
+        &mut self,
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+        finish: f64,
+    ) -> Result<(), CncError> {
+        let finish = finish.max(0.0);
+        let depth = (start.z - end_z).abs();
+
+        if finish * 2.0 >= size.x.min(size.y) {
+            return Err(CncError::FinishTooLarge { finish, size });
+        }
+
+        // The frame is cut in a single pass of the full depth, which needs some depth to cut
+        if depth == 0.0 {
+            return Ok(());
+        }
+
+        self.append_cut(Cut::pocket(
+            start + Vector3::new(finish, finish, 0.0),
+            size - Vector2::splat(finish * 2.0),
+            end_z,
+            max_step_z,
+        ));
+        self.append_cut(Cut::frame_inner(start, size, end_z, depth));
+
+        Ok(())
+    }
+
+    /// Appends copies of the cut in a grid of `rows` along the y axis and `cols` along the x
+    /// axis, each moved by `spacing` from the previous one. The copies are appended row by row,
+    /// starting with the cut at its original position.
+    ///
+    /// Returns error if the cut can not be moved, in which case no copies are appended.
+    pub fn append_cut_array(
+        &mut self,
+        cut: Cut,
+        rows: u32,
+        cols: u32,
+        spacing: Vector2,
+    ) -> Result<()> {
+        let mut cuts = vec![];
+
+        for row in 0..rows {
+            for col in 0..cols {
+                cuts.push(cut.translated(Vector3::new(
+                    f64::from(col) * spacing.x,
+                    f64::from(row) * spacing.y,
+                    0.0,
+                ))?);
+            }
+        }
+
+        for cut in cuts {
+            self.append_cut(cut);
+        }
+
+        Ok(())
+    }
+
+    /// Appends a comment to the context, for example to describe the following cuts.
+    pub fn append_comment(&mut self, text: &str) {
+        self.append(Operation::Comment(Comment {
+            text: text.to_string(),
+        }));
+    }
+
+    /// Appends an empty line to the context, consecutive empty lines are merged into one in
+    /// the generated G-code.
+    pub fn append_empty(&mut self) {
+        self.append(Operation::Empty(Empty {}));
+    }
+
+    /// Appends a raw G-code line to the context, it is added to the program as is, for
+    /// example for machine specific M-codes that are not supported by the crate.
+    pub fn append_raw(&mut self, gcode: &str) {
+        self.append(Operation::Raw(Raw {
+            text: gcode.to_string(),
+        }));
+    }
+
+    /// Appends a pause to the context that shows the message and waits for the operator to
+    /// resume the program, for example to flip the workpiece before the next cut.
+    pub fn append_pause(&mut self, message: &str) {
+        self.append(Operation::Pause(Message {
+            text: message.to_string(),
+        }));
+    }
+
+    /// Appends an optional stop to the context, that only waits for the operator when
+    /// optional stops are enabled on the machine, for example for inspection points that are
+    /// skipped in production. An empty message is not shown.
+    pub fn append_optional_stop(&mut self, message: &str) {
+        self.append(Operation::OptionalStop(Message {
+            text: message.to_string(),
+        }));
+    }
+
+    /// Inserts an operation at the index in the context, shifting the operations after it.
+    ///
+    /// Returns error if the index is larger than the number of operations.
+    pub fn insert(&mut self, index: usize, operation: Operation) -> Result<()> {
+        if index > self.operations.len() {
+            return Err(anyhow!(
+                "Unable to insert operation at index {}, the context has {} operations",
+                index,
+                self.operations.len()
+            ));
+        }
+
+        self.operations.insert(index, operation);
+
+        Ok(())
+    }
+
+    /// Inserts a cut operation at the index in the context, shifting the operations after it.
+    ///
+    /// Returns error if the index is larger than the number of operations.
+    pub fn insert_cut(&mut self, index: usize, cut: Cut) -> Result<()> {
+        self.insert(index, Operation::Cut(cut))
+    }
+
+    /// Removes and returns the operation at the index in the context.
+    ///
+    /// Returns error if there is no operation at the index.
+    pub fn remove_operation(&mut self, index: usize) -> Result<Operation> {
+        if index >= self.operations.len() {
+            return Err(anyhow!(
+                "Unable to remove operation at index {}, the context has {} operations",
+                index,
+                self.operations.len()
+            ));
+        }
+
+        Ok(self.operations.remove(index))
+    }
+
+    /// Moves the operation at index `from` to index `to`, keeping the order of the other
+    /// operations.
+    ///
+    /// Returns error if any of the indices is out of bounds.
+    pub fn move_operation(&mut self, from: usize, to: usize) -> Result<()> {
+        let len = self.operations.len();
+
+        if from >= len || to >= len {
+            return Err(anyhow!(
+                "Unable to move operation from index {} to index {}, the context has {} operations",
+                from,
+                to,
+                len
+            ));
+        }
+
+        let operation = self.operations.remove(from);
+        self.operations.insert(to, operation);
+
+        Ok(())
+    }
+
+    /// Applies a transform to all operations in the context.
+    ///
+    /// Returns error if any of the cuts can not be transformed, in which case the context
+    /// is left unchanged.
+    pub fn transform(&mut self, transform: &Transform) -> Result<()> {
+        self.operations = self.transformed_operations(transform)?;
+        Ok(())
+    }
+
+    fn transformed_operations(&self, transform: &Transform) -> Result<Vec<Operation>> {
+        self.operations
+            .iter()
+            .map(|operation| operation.transform(transform))
+            .collect()
+    }
+
+    // Returns a copy of the context with the heights and operations converted to the units.
+    fn converted(&self, units: Units) -> Result<Self> {
+        Ok(Self {
+            units,
+            tool: self.tool,
+            z_safe: self.units.convert(self.z_safe, units),
+            z_tool_change: self.units.convert(self.z_tool_change, units),
+            operations: self
+                .operations
+                .iter()
+                .map(|operation| operation.convert_units(self.units, units))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Validates that none of the cuts goes deeper into the stock than the usable cutting
+    /// length of the tool. The depth is measured from the stock surface at z = 0, as the part
+    /// of a cut above the surface only cuts air.
+    ///
+    /// Returns error naming the first cut that is too deep.
+    pub fn validate(&self) -> Result<(), CncError> {
+        let tool_length = self.tool.to_units(self.units).length();
+
+        for (index, operation) in self.operations.iter().enumerate() {
+            if let Operation::Cut(cut) = operation {
+                let depth = cut.depth() - cut.bounds().max.z.max(0.0);
+                if depth > tool_length {
+                    let bounds = cut.bounds();
+                    return Err(CncError::CutTooDeep {
+                        index,
+                        position: Vector2::new(bounds.min.x, bounds.min.y),
+                        depth,
+                        tool_length,
+                        units: self.units,
+                        tool: self.tool,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reorders the cut operations to reduce the rapid travel between them, by greedily
+    /// picking the nearest cut (comparing the centers of the cut bounds), starting from
+    /// origo.
+    ///
+    /// Operations that are not cuts, such as comments and messages, act as barriers and
+    /// cuts are never moved past them.
+    pub fn optimize_travel(&mut self) {
+        let mut position = Vector2::ZERO;
+        let mut operations = Vec::with_capacity(self.operations.len());
+        let mut group: Vec<Operation> = vec![];
+
+        for operation in self.operations.drain(..) {
+            if matches!(operation, Operation::Cut(_)) {
+                group.push(operation);
+                continue;
+            }
+
+            position = nearest_neighbor_order(&mut group, &mut operations, position);
+            operations.push(operation);
+        }
+
+        nearest_neighbor_order(&mut group, &mut operations, position);
+
+        self.operations = operations;
+    }
+
     /// Returns the units used by the context.
     pub fn units(&self) -> Units {
         self.units
@@ -195,42 +601,23 @@ impl InnerContext {
         self.z_tool_change
     }
 
+    /// Overrides the z safe value of the program for this context.
+    pub fn set_z_safe(&mut self, z_safe: f64) {
+        self.z_safe = z_safe;
+    }
+
+    /// Overrides the z tool change value of the program for this context.
+    pub fn set_z_tool_change(&mut self, z_tool_change: f64) {
+        self.z_tool_change = z_tool_change;
+    }
+
     /// Returns the bounds for the context
     pub fn bounds(&self) -> Bounds {
         let mut bounds = Bounds::minmax();
 
         for operation in self.operations.iter() {
-            let operation_bounds = operation.bounds();
-            bounds.min.x = if bounds.min.x > operation_bounds.min.x {
-                operation_bounds.min.x
-            } else {
-                bounds.min.x
-            };
-            bounds.min.y = if bounds.min.y > operation_bounds.min.y {
-                operation_bounds.min.y
-            } else {
-                bounds.min.y
-            };
-            bounds.min.z = if bounds.min.z > operation_bounds.min.z {
-                operation_bounds.min.z
-            } else {
-                bounds.min.z
-            };
-            bounds.max.x = if bounds.max.x < operation_bounds.max.x {
-                operation_bounds.max.x
-            } else {
-                bounds.max.x
-            };
-            bounds.max.y = if bounds.max.y < operation_bounds.max.y {
-                operation_bounds.max.y
-            } else {
-                bounds.max.y
-            };
-            bounds.max.z = if bounds.max.z < operation_bounds.max.z {
-                operation_bounds.max.z
-            } else {
-                bounds.max.z
-            };
+            bounds = bounds
+                .union(&operation.compensated_bounds(self.tool.to_units(self.units).radius()));
         }
 
         bounds
@@ -241,21 +628,78 @@ impl InnerContext {
         self.operations.clone()
     }
 
+    /// Returns an iterator over the operations for this context, without cloning them.
+    pub fn operations_iter(&self) -> impl Iterator<Item = &Operation> {
+        self.operations.iter()
+    }
+
+    /// Returns the number of operations in this context.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns true if this context has no operations.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
     /// Converts context to G-code instructions.
-    pub fn to_instructions(&self) -> Result<Vec<Instruction>> {
+    ///
+    /// The retract to `z_safe` at the start of a cut is left out when the previous cut already
+    /// ended with it, as the tool has not moved since.
+    pub fn to_instructions(&self) -> Result<Vec<Instruction>, CncError> {
         let mut instructions = vec![];
+        let mut retracts = RetractTrimmer::new(self.z_safe);
 
         for operation in &self.operations {
-            instructions.append(&mut operation.to_instructions((*self).clone())?);
+            instructions.extend(
+                operation
+                    .to_instructions(self)?
+                    .into_iter()
+                    .filter(|instruction| retracts.keep(instruction)),
+            );
         }
 
         Ok(instructions)
     }
 }
 
+// Drains the group into the output, in nearest neighbor order from the position, returns the
+// position of the last operation.
+fn nearest_neighbor_order(
+    group: &mut Vec<Operation>,
+    output: &mut Vec<Operation>,
+    mut position: Vector2,
+) -> Vector2 {
+    while !group.is_empty() {
+        let mut nearest_index = 0;
+        let mut nearest_distance = f64::MAX;
+
+        for (index, operation) in group.iter().enumerate() {
+            let distance = position.distance_to(operation.bounds().center().xy());
+            if distance < nearest_distance {
+                nearest_index = index;
+                nearest_distance = distance;
+            }
+        }
+
+        let operation = group.remove(nearest_index);
+        position = operation.bounds().center().xy();
+        output.push(operation);
+    }
+
+    position
+}
+
 /// A program tool context that updates the state data for operations paired with a specific
 /// tool. The reason for grouping the operations per tool is to reduce the amound of tool
 /// changes, which is expecially useful for CNC machines that needs manual tool changes.
+///
+/// A `Context` is created with [Program::context](struct.Program.html#method.context) or
+/// [Program::context_for](struct.Program.html#method.context_for), which guarantees that the
+/// program has a tool context for it.
 #[derive(Debug, Clone)]
 pub struct Context<'a> {
     tool: Tool,
@@ -270,10 +714,10 @@ impl<'a> Context<'a> {
         let program = self.program.borrow();
 
         let mut binding = program.contexts.borrow_mut();
-        let program_context = binding.get_mut(&self.tool).unwrap();
+        let program_context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
 
         let binding = context.program.borrow().contexts.borrow();
-        let merge_context = binding.get(&context.tool()).unwrap();
+        let merge_context = binding.get(&context.tool()).expect(CONTEXT_MISSING);
 
         program_context.merge(merge_context.clone())
     }
@@ -282,7 +726,7 @@ impl<'a> Context<'a> {
     pub fn append(&mut self, operation: Operation) {
         let program = self.program.borrow();
         let mut binding = program.contexts.borrow_mut();
-        let context = binding.get_mut(&self.tool).unwrap();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
         context.append(operation);
     }
 
@@ -291,11 +735,153 @@ impl<'a> Context<'a> {
         self.append(Operation::Cut(cut));
     }
 
+    /// Appends a cut made relative to the top of the stock, where z = 0 is the
+    /// [stock top](struct.Program.html#method.set_stock_top) of the program, for example
+    /// [Cut::pocket_depth](../cuts/enum.Cut.html#method.pocket_depth).
+    ///
+    /// The cut is placed at the stock top when it is appended, so the stock top must be set
+    /// before, and changing it afterwards does not move the cut.
+    ///
+    /// Returns error if the program has no stock top, or if the cut can not be moved.
+    pub fn append_relative_cut(&mut self, cut: Cut) -> Result<()> {
+        let Some(stock_top) = self.program.borrow().stock_top else {
+            return Err(anyhow!(
+                "Unable to append relative cut, the program has no stock top"
+            ));
+        };
+
+        self.append_cut(cut.translated(Vector3::new(0.0, 0.0, stock_top))?);
+        Ok(())
+    }
+
+    /// Appends a rectangular pocket followed by a finishing pass along its walls, see
+    /// [InnerContext::append_rectangle_pocket](struct.InnerContext.html#method.append_rectangle_pocket).
+    pub fn append_rectangle_pocket(
+        &mut self,
+        start: Vector3,
+        size: Vector2,
+        end_z: f64,
+        max_step_z: f64,
+        finish: f64,
+    ) -> Result<(), CncError> {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.append_rectangle_pocket(start, size, end_z, max_step_z, finish)
+    }
+
+    /// Appends copies of the cut in a grid, see
+    /// [InnerContext::append_cut_array](struct.InnerContext.html#method.append_cut_array).
+    pub fn append_cut_array(
+        &mut self,
+        cut: Cut,
+        rows: u32,
+        cols: u32,
+        spacing: Vector2,
+    ) -> Result<()> {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.append_cut_array(cut, rows, cols, spacing)
+    }
+
+    /// Appends a comment to the context, for example to describe the following cuts.
+    pub fn append_comment(&mut self, text: &str) {
+        self.append(Operation::Comment(Comment {
+            text: text.to_string(),
+        }));
+    }
+
+    /// Appends an empty line to the context, consecutive empty lines are merged into one in
+    /// the generated G-code.
+    pub fn append_empty(&mut self) {
+        self.append(Operation::Empty(Empty {}));
+    }
+
+    /// Appends a raw G-code line to the context, it is added to the program as is, for
+    /// example for machine specific M-codes that are not supported by the crate.
+    pub fn append_raw(&mut self, gcode: &str) {
+        self.append(Operation::Raw(Raw {
+            text: gcode.to_string(),
+        }));
+    }
+
+    /// Appends a pause to the context that shows the message and waits for the operator to
+    /// resume the program, for example to flip the workpiece before the next cut.
+    pub fn append_pause(&mut self, message: &str) {
+        self.append(Operation::Pause(Message {
+            text: message.to_string(),
+        }));
+    }
+
+    /// Appends an optional stop to the context, that only waits for the operator when
+    /// optional stops are enabled on the machine, for example for inspection points that are
+    /// skipped in production. An empty message is not shown.
+    pub fn append_optional_stop(&mut self, message: &str) {
+        self.append(Operation::OptionalStop(Message {
+            text: message.to_string(),
+        }));
+    }
+
+    /// Inserts an operation at the index in the context, see
+    /// [InnerContext::insert](struct.InnerContext.html#method.insert).
+    pub fn insert(&mut self, index: usize, operation: Operation) -> Result<()> {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.insert(index, operation)
+    }
+
+    /// Inserts a cut operation at the index in the context, shifting the operations after it.
+    ///
+    /// Returns error if the index is larger than the number of operations.
+    pub fn insert_cut(&mut self, index: usize, cut: Cut) -> Result<()> {
+        self.insert(index, Operation::Cut(cut))
+    }
+
+    /// Removes and returns the operation at the index in the context, see
+    /// [InnerContext::remove_operation](struct.InnerContext.html#method.remove_operation).
+    pub fn remove_operation(&mut self, index: usize) -> Result<Operation> {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.remove_operation(index)
+    }
+
+    /// Moves the operation at index `from` to index `to`, see
+    /// [InnerContext::move_operation](struct.InnerContext.html#method.move_operation).
+    pub fn move_operation(&mut self, from: usize, to: usize) -> Result<()> {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.move_operation(from, to)
+    }
+
+    /// Reorders the cut operations to reduce the rapid travel between them, see
+    /// [InnerContext::optimize_travel](struct.InnerContext.html#method.optimize_travel).
+    pub fn optimize_travel(&mut self) {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.optimize_travel();
+    }
+
+    /// Applies a transform to all operations in the context.
+    ///
+    /// Returns error if any of the cuts can not be transformed, in which case the context
+    /// is left unchanged.
+    pub fn transform(&mut self, transform: &Transform) -> Result<()> {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.transform(transform)
+    }
+
     /// Returns the units used by the context.
     pub fn units(&self) -> Units {
         let program = self.program.borrow();
         let mut binding = program.contexts.borrow_mut();
-        let context = binding.get_mut(&self.tool).unwrap();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
         context.units()
     }
 
@@ -311,7 +897,7 @@ impl<'a> Context<'a> {
     pub fn z_safe(&self) -> f64 {
         let program = self.program.borrow();
         let mut binding = program.contexts.borrow_mut();
-        let context = binding.get_mut(&self.tool).unwrap();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
         context.z_safe()
     }
 
@@ -319,15 +905,33 @@ impl<'a> Context<'a> {
     pub fn z_tool_change(&self) -> f64 {
         let program = self.program.borrow();
         let mut binding = program.contexts.borrow_mut();
-        let context = binding.get_mut(&self.tool).unwrap();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
         context.z_tool_change()
     }
 
+    /// Overrides the z safe value of the program for this context, for example to give a long
+    /// drill more clearance than the other tools. Defaults to the program z safe value.
+    pub fn set_z_safe(&mut self, z_safe: f64) {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.set_z_safe(z_safe);
+    }
+
+    /// Overrides the z height used for the manual tool change of this context. Defaults to
+    /// the program z tool change value.
+    pub fn set_z_tool_change(&mut self, z_tool_change: f64) {
+        let program = self.program.borrow();
+        let mut binding = program.contexts.borrow_mut();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
+        context.set_z_tool_change(z_tool_change);
+    }
+
     /// Returns the bounds for this context.
     pub fn bounds(&self) -> Bounds {
         let program = self.program.borrow();
         let mut binding = program.contexts.borrow_mut();
-        let context = binding.get_mut(&self.tool).unwrap();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
         context.bounds()
     }
 
@@ -335,23 +939,60 @@ impl<'a> Context<'a> {
     pub fn operations(&self) -> Vec<Operation> {
         let program = self.program.borrow();
         let mut binding = program.contexts.borrow_mut();
-        let context = binding.get_mut(&self.tool).unwrap();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
         context.operations()
     }
 
+    /// Calls the closure for each operation in this context, without cloning the operations.
+    pub fn for_each_operation<F>(&self, f: F)
+    where
+        F: FnMut(&Operation),
+    {
+        let program = self.program.borrow();
+        let binding = program.contexts.borrow();
+        let context = binding.get(&self.tool).expect(CONTEXT_MISSING);
+        context.operations_iter().for_each(f);
+    }
+
+    /// Returns the number of operations in this context.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let program = self.program.borrow();
+        let binding = program.contexts.borrow();
+        binding.get(&self.tool).expect(CONTEXT_MISSING).len()
+    }
+
+    /// Returns true if this context has no operations.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Converts context to G-code instructions.
-    pub fn to_instructions(&self) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self) -> Result<Vec<Instruction>, CncError> {
         let program = self.program.borrow();
         let mut binding = program.contexts.borrow_mut();
-        let context = binding.get_mut(&self.tool).unwrap();
+        let context = binding.get_mut(&self.tool).expect(CONTEXT_MISSING);
         context.to_instructions()
     }
 }
 
-#[derive(Debug, Clone)]
+/// A tool length probe, or touch plate, that each tool is measured against after the tool
+/// change, see [Program::enable_tool_probing](struct.Program.html#method.enable_tool_probing).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ToolProbe {
+    /// The position of the probe, where the z value is the height of the probe surface in
+    /// work coordinates.
+    pub point: Vector3,
+    /// The feed rate to probe with.
+    pub feed_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct ProgramMeta {
     name: String,
     description: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
     created_on: OffsetDateTime,
     created_by: String,
     generator: String,
@@ -407,31 +1048,266 @@ impl Default for ProgramMeta {
     }
 }
 
-/// A program that stores information about all structs and tools used in a project. Several programs can
-/// also be merged into a single one.
-#[derive(Debug, Clone)]
-pub struct Program {
-    z_safe: f64,
-    z_tool_change: f64,
-    meta: ProgramMeta,
-    units: Units,
-    contexts: Rc<RefCell<HashMap<Tool, InnerContext>>>,
-    tool_ordering: Rc<RefCell<ToolOrdering>>,
+/// Travel statistics for a program, see
+/// [Program::travel_stats](struct.Program.html#method.travel_stats).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TravelStats {
+    /// Total distance traveled while cutting (G1, G2, and G3 moves).
+    pub cut_distance: f64,
+    /// Total distance traveled with rapid moves (G0).
+    pub rapid_distance: f64,
+    /// Number of G0 rapid moves.
+    pub g0_count: usize,
+    /// Number of G1 linear moves.
+    pub g1_count: usize,
+    /// Number of G2 clockwise arc moves.
+    pub g2_count: usize,
+    /// Number of G3 counterclockwise arc moves.
+    pub g3_count: usize,
+    /// Number of tool changes.
+    pub tool_change_count: usize,
 }
 
-impl Program {
-    /// Creates a new `Program` struct.
-    #[must_use]
-    pub fn new(units: Units, z_safe: f64, z_tool_change: f64) -> Self {
-        Self {
-            z_safe,
-            z_tool_change,
-            meta: ProgramMeta::default(),
-            units,
-            contexts: Rc::new(RefCell::new(HashMap::new())),
-            tool_ordering: Rc::new(RefCell::new(ToolOrdering::default())),
-        }
-    }
+// Keeps track of the position of each axis as written to the G-code, to leave out coordinate
+// words that would not move the axis. An axis position is unknown until it has been written.
+#[derive(Default)]
+struct KnownPosition {
+    x: Option<f64>,
+    y: Option<f64>,
+    z: Option<f64>,
+}
+
+impl KnownPosition {
+    // Returns the instruction with the unchanged coordinates of G0 and G1 moves left out, or
+    // None if the move would not do anything.
+    fn omit_unchanged(&mut self, instruction: Instruction) -> Option<Instruction> {
+        match instruction {
+            Instruction::G0(g0) => {
+                let (x, y, z) = self.update(g0.x, g0.y, g0.z);
+                if x.is_none() && y.is_none() && z.is_none() {
+                    return None;
+                }
+
+                Some(Instruction::G0(G0 { x, y, z }))
+            }
+            Instruction::G1(g1) => {
+                let (x, y, z) = self.update(g1.x, g1.y, g1.z);
+                if x.is_none() && y.is_none() && z.is_none() && g1.f.is_none() {
+                    return None;
+                }
+
+                Some(Instruction::G1(G1 { x, y, z, ..g1 }))
+            }
+            Instruction::G2(ref g2) => {
+                self.update(g2.x, g2.y, g2.z);
+                Some(instruction)
+            }
+            Instruction::G3(ref g3) => {
+                self.update(g3.x, g3.y, g3.z);
+                Some(instruction)
+            }
+            Instruction::G10(_)
+            | Instruction::G20(_)
+            | Instruction::G21(_)
+            | Instruction::G38_2(_)
+            | Instruction::G43(_)
+            | Instruction::M0(_)
+            | Instruction::M1(_)
+            | Instruction::M6(_)
+            | Instruction::Raw(_) => {
+                *self = Self::default();
+                Some(instruction)
+            }
+            instruction => Some(instruction),
+        }
+    }
+
+    // Updates the known position, returning the coordinates that change it. The coordinates
+    // are compared as written to the G-code.
+    fn update(
+        &mut self,
+        x: Option<f64>,
+        y: Option<f64>,
+        z: Option<f64>,
+    ) -> (Option<f64>, Option<f64>, Option<f64>) {
+        let changed = |known: &mut Option<f64>, value: Option<f64>| {
+            let value = value?;
+            if *known == Some(round_precision(value)) {
+                return None;
+            }
+
+            *known = Some(round_precision(value));
+            Some(value)
+        };
+
+        (
+            changed(&mut self.x, x),
+            changed(&mut self.y, y),
+            changed(&mut self.z, z),
+        )
+    }
+}
+
+// Leaves out retracts to z_safe when the tool is already retracted to z_safe, which happens
+// when one cut ends with a retract and the next cut starts with one. Only instructions that do
+// not move the tool are allowed between the retracts.
+struct RetractTrimmer {
+    z_safe: f64,
+    retracted: bool,
+}
+
+impl RetractTrimmer {
+    fn new(z_safe: f64) -> Self {
+        Self {
+            z_safe,
+            retracted: false,
+        }
+    }
+
+    fn keep(&mut self, instruction: &Instruction) -> bool {
+        match instruction {
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(z),
+            }) if *z == self.z_safe => !std::mem::replace(&mut self.retracted, true),
+            Instruction::Empty(_)
+            | Instruction::Comment(_)
+            | Instruction::Message(_)
+            | Instruction::G17(_)
+            | Instruction::G18(_)
+            | Instruction::G19(_) => true,
+            _ => {
+                self.retracted = false;
+                true
+            }
+        }
+    }
+}
+
+// Trims duplicated consecutive instructions and repeated plane selections from a stream of
+// instructions, an instruction is returned once the following instruction is known.
+#[derive(Default)]
+struct InstructionTrimmer {
+    workplane: Option<Instruction>,
+    pending: Option<Instruction>,
+}
+
+impl InstructionTrimmer {
+    fn push(&mut self, instruction: Instruction) -> Option<Instruction> {
+        let previous = self.pending.replace(instruction)?;
+        Self::trim(&mut self.workplane, previous, self.pending.as_ref())
+    }
+
+    fn finish(&mut self) -> Option<Instruction> {
+        let previous = self.pending.take()?;
+        Self::trim(&mut self.workplane, previous, None)
+    }
+
+    fn trim(
+        workplane: &mut Option<Instruction>,
+        instruction: Instruction,
+        next: Option<&Instruction>,
+    ) -> Option<Instruction> {
+        if matches!(
+            instruction,
+            Instruction::G17(_) | Instruction::G18(_) | Instruction::G19(_)
+        ) {
+            if workplane.as_ref() == Some(&instruction) {
+                return None;
+            }
+
+            *workplane = Some(instruction.clone());
+        }
+
+        // Raw lines, comments, pauses, and arcs are kept as is, since repeating them can be
+        // intentional, a repeated arc is another full revolution
+        if !matches!(
+            instruction,
+            Instruction::Raw(_)
+                | Instruction::Comment(_)
+                | Instruction::M0(_)
+                | Instruction::M1(_)
+                | Instruction::G2(_)
+                | Instruction::G3(_)
+        ) && next == Some(&instruction)
+        {
+            return None;
+        }
+
+        Some(instruction)
+    }
+}
+
+/// A program that stores information about all structs and tools used in a project. Several programs can
+/// also be merged into a single one.
+#[derive(Debug, Clone)]
+pub struct Program {
+    z_safe: f64,
+    z_tool_change: f64,
+    rapid_rate: f64,
+    feed_rate_scale: f64,
+    spindle_speed_scale: f64,
+    spindle_spinup: Option<Duration>,
+    dry_run: bool,
+    tool_length_offsets: bool,
+    park: Option<Vector3>,
+    tool_change_position: Option<Vector2>,
+    spindle_off_dwell: Option<Duration>,
+    stock_top: Option<f64>,
+    omit_unchanged_coordinates: bool,
+    verbose_cut_headers: bool,
+    precision: Option<u8>,
+    tool_probe: Option<ToolProbe>,
+    minimal_spindle_commands: bool,
+    flavor: Flavor,
+    feed_mode: FeedMode,
+    comment_style: Option<CommentStyle>,
+    dwell_format: Option<DwellFormat>,
+    meta: ProgramMeta,
+    units: Units,
+    contexts: Rc<RefCell<HashMap<Tool, InnerContext>>>,
+    tool_ordering: Rc<RefCell<ToolOrdering>>,
+}
+
+impl Program {
+    /// Creates a new `Program` struct.
+    #[must_use]
+    pub fn new(units: Units, z_safe: f64, z_tool_change: f64) -> Self {
+        Self {
+            z_safe,
+            z_tool_change,
+            rapid_rate: default_rapid_rate(units),
+            feed_rate_scale: 1.0,
+            spindle_speed_scale: 1.0,
+            spindle_spinup: None,
+            dry_run: false,
+            tool_length_offsets: false,
+            park: None,
+            tool_change_position: None,
+            spindle_off_dwell: None,
+            stock_top: None,
+            omit_unchanged_coordinates: false,
+            verbose_cut_headers: false,
+            precision: None,
+            tool_probe: None,
+            minimal_spindle_commands: false,
+            flavor: Flavor::default(),
+            feed_mode: FeedMode::default(),
+            comment_style: None,
+            dwell_format: None,
+            meta: ProgramMeta::default(),
+            units,
+            contexts: Rc::new(RefCell::new(HashMap::new())),
+            tool_ordering: Rc::new(RefCell::new(ToolOrdering::default())),
+        }
+    }
+
+    /// Creates a new [ProgramBuilder] for chainable setup of a `Program`.
+    #[must_use]
+    pub fn builder() -> ProgramBuilder {
+        ProgramBuilder::new()
+    }
 
     /// Creates a new empty `Program` with the same same settings as the supplied one.
     #[must_use]
@@ -439,6 +1315,25 @@ impl Program {
         Self {
             z_safe: program.z_safe,
             z_tool_change: program.z_tool_change,
+            rapid_rate: program.rapid_rate,
+            feed_rate_scale: program.feed_rate_scale,
+            spindle_speed_scale: program.spindle_speed_scale,
+            spindle_spinup: program.spindle_spinup,
+            dry_run: program.dry_run,
+            tool_length_offsets: program.tool_length_offsets,
+            park: program.park,
+            tool_change_position: program.tool_change_position,
+            spindle_off_dwell: program.spindle_off_dwell,
+            stock_top: program.stock_top,
+            omit_unchanged_coordinates: program.omit_unchanged_coordinates,
+            verbose_cut_headers: program.verbose_cut_headers,
+            precision: program.precision,
+            tool_probe: program.tool_probe,
+            minimal_spindle_commands: program.minimal_spindle_commands,
+            flavor: program.flavor,
+            feed_mode: program.feed_mode,
+            comment_style: program.comment_style,
+            dwell_format: program.dwell_format,
             meta: ProgramMeta::default(),
             units: program.units,
             contexts: Rc::new(RefCell::new(HashMap::new())),
@@ -468,6 +1363,58 @@ impl Program {
         &self.meta.description
     }
 
+    /// Set who created the program, defaults to the current user and hostname.
+    pub fn set_created_by(&mut self, created_by: &str) {
+        self.meta.created_by = created_by.into();
+    }
+
+    /// Get who created the program
+    #[must_use]
+    pub fn created_by(&self) -> &str {
+        self.meta.created_by.as_str()
+    }
+
+    /// Set the generator of the program, defaults to the command line used to run the
+    /// generating binary.
+    pub fn set_generator(&mut self, generator: &str) {
+        self.meta.generator = generator.into();
+    }
+
+    /// Get the generator of the program
+    #[must_use]
+    pub fn generator(&self) -> &str {
+        self.meta.generator.as_str()
+    }
+
+    /// Set when the program was created, defaults to the time the program was created.
+    pub fn set_created_on(&mut self, created_on: OffsetDateTime) {
+        self.meta.created_on = created_on;
+    }
+
+    /// Get when the program was created
+    #[must_use]
+    pub fn created_on(&self) -> OffsetDateTime {
+        self.meta.created_on
+    }
+
+    /// Returns the program with the creation time set to the unix epoch and the creator and
+    /// generator cleared, so that the same program always generates the same G-code. Useful
+    /// for snapshot tests, note that the name still needs to be set with
+    /// [set_name](#method.set_name) as it is random by default.
+    #[must_use]
+    pub fn with_deterministic_meta(mut self) -> Self {
+        self.meta.created_on = OffsetDateTime::UNIX_EPOCH;
+        self.meta.created_by = String::new();
+        self.meta.generator = String::new();
+        self
+    }
+
+    /// Returns the units used for the program measurements.
+    #[must_use]
+    pub fn units(&self) -> Units {
+        self.units
+    }
+
     /// Returns the z safe value set for this context.
     ///
     /// The value indicates the z height where the machine tool can safely travel
@@ -483,6 +1430,343 @@ impl Program {
         self.z_tool_change
     }
 
+    /// Returns the rapid (G0) travel rate of the machine (mm/min for metric and inches/min for
+    /// imperial), used when estimating the program duration.
+    #[must_use]
+    pub fn rapid_rate(&self) -> f64 {
+        self.rapid_rate
+    }
+
+    /// Sets the rapid (G0) travel rate of the machine (mm/min for metric and inches/min for
+    /// imperial), used when estimating the program duration. Defaults to 2000 mm/min.
+    pub fn set_rapid_rate(&mut self, rapid_rate: f64) {
+        self.rapid_rate = rapid_rate;
+    }
+
+    /// Returns the factor that all feed rates are scaled with in the generated G-code.
+    #[must_use]
+    pub fn feed_rate_scale(&self) -> f64 {
+        self.feed_rate_scale
+    }
+
+    /// Sets a factor that all feed rates are scaled with in the generated G-code, for example
+    /// 0.5 to run the whole program at half the feed rate. The tool settings are not affected.
+    /// Defaults to 1.0.
+    pub fn set_feed_rate_scale(&mut self, scale: f64) {
+        self.feed_rate_scale = scale;
+    }
+
+    /// Returns the factor that all spindle speeds are scaled with in the generated G-code.
+    #[must_use]
+    pub fn spindle_speed_scale(&self) -> f64 {
+        self.spindle_speed_scale
+    }
+
+    /// Sets a factor that all spindle speeds are scaled with in the generated G-code. The tool
+    /// settings are not affected. Defaults to 1.0.
+    pub fn set_spindle_speed_scale(&mut self, scale: f64) {
+        self.spindle_speed_scale = scale;
+    }
+
+    /// Returns the time to wait for the spindle to spin up after each tool change, or `None`
+    /// if the time is derived from the spindle speed of the tool.
+    #[must_use]
+    pub fn spindle_spinup(&self) -> Option<Duration> {
+        self.spindle_spinup
+    }
+
+    /// Sets the time to wait (G4) for the spindle to spin up after each tool change, for
+    /// example zero for a spindle with speed feedback. By default the time is scaled from 3
+    /// seconds at 0 rpm to 20 seconds at 50 000 rpm.
+    pub fn set_spindle_spinup(&mut self, duration: Duration) {
+        self.spindle_spinup = Some(duration);
+    }
+
+    /// Returns true if the program is generated as a dry run, see
+    /// [set_dry_run](struct.Program.html#method.set_dry_run).
+    #[must_use]
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Enables dry run mode, where all z values in the generated G-code are raised to at least
+    /// `z_safe`, so that the program can be run in the air to verify the positioning without
+    /// cutting. The x and y motion is kept, and arcs outside of the xy plane are replaced by
+    /// straight moves. The cuts in the program are not affected. Defaults to false.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Returns true if a tool length offset (G43) is applied after each tool change.
+    #[must_use]
+    pub fn tool_length_offsets(&self) -> bool {
+        self.tool_length_offsets
+    }
+
+    /// Enables emitting `G43 H<tool number>` after each tool change, for machines that use a
+    /// tool table with the tool length offsets. Defaults to false, as Grbl has no tool table.
+    pub fn set_tool_length_offsets(&mut self, enabled: bool) {
+        self.tool_length_offsets = enabled;
+    }
+
+    /// Returns the tool length probe that the tools are measured against after each tool
+    /// change, see [enable_tool_probing](struct.Program.html#method.enable_tool_probing).
+    #[must_use]
+    pub fn tool_probe(&self) -> Option<ToolProbe> {
+        self.tool_probe
+    }
+
+    /// Enables measuring each tool against a tool length probe or touch plate after the tool
+    /// change. The tool is moved above `probe_point` and probes down with G38.2 at the feed
+    /// rate `feed`, after which the work z coordinate is set to `probe_point.z` with
+    /// `G10 L20 P0`. The probe move may travel as far below the probe surface as the tool change
+    /// height is above it. Probing is left out in dry run mode. Defaults to disabled, as not
+    /// all machines have a probe.
+    pub fn enable_tool_probing(&mut self, probe_point: Vector3, feed: f64) {
+        self.tool_probe = Some(ToolProbe {
+            point: probe_point,
+            feed_rate: feed,
+        });
+    }
+
+    /// Disables the tool length probing after each tool change.
+    pub fn disable_tool_probing(&mut self) {
+        self.tool_probe = None;
+    }
+
+    /// Returns true if the spindle speed is only set when it changes, see
+    /// [set_minimal_spindle_commands](struct.Program.html#method.set_minimal_spindle_commands).
+    #[must_use]
+    pub fn minimal_spindle_commands(&self) -> bool {
+        self.minimal_spindle_commands
+    }
+
+    /// Enables leaving out the spindle speed (S) in a tool change when the tool uses the same
+    /// speed as the previous tool, as the speed is kept between the tool changes. Defaults to
+    /// false, which sets the speed in every tool change.
+    pub fn set_minimal_spindle_commands(&mut self, enabled: bool) {
+        self.minimal_spindle_commands = enabled;
+    }
+
+    /// Returns true if coordinate words that do not change the position are left out of the
+    /// generated G-code.
+    #[must_use]
+    pub fn omit_unchanged_coordinates(&self) -> bool {
+        self.omit_unchanged_coordinates
+    }
+
+    /// Enables leaving out the coordinate words of G0 and G1 moves that are the same as the
+    /// current position, which shrinks the generated G-code. The position is only known after
+    /// the axis has been moved by the program, and is forgotten after pauses, tool changes, and
+    /// raw G-code. Only affects the G-code text, not the instructions. Defaults to false.
+    pub fn set_omit_unchanged_coordinates(&mut self, enabled: bool) {
+        self.omit_unchanged_coordinates = enabled;
+    }
+
+    /// Returns true if each cut is preceded by comments describing the cut, see
+    /// [set_verbose_cut_headers](struct.Program.html#method.set_verbose_cut_headers).
+    #[must_use]
+    pub fn verbose_cut_headers(&self) -> bool {
+        self.verbose_cut_headers
+    }
+
+    /// Enables comments after the comment of each cut with the start and end depth, the depth
+    /// step, the number of passes, the feed rate, and the estimated cutting time, which makes
+    /// it easier to follow the program on the machine. Defaults to false.
+    pub fn set_verbose_cut_headers(&mut self, enabled: bool) {
+        self.verbose_cut_headers = enabled;
+    }
+
+    /// Returns the number of decimals that values are rounded to in the generated G-code.
+    #[must_use]
+    pub fn precision(&self) -> u8 {
+        self.precision
+            .unwrap_or_else(|| default_precision(self.units))
+    }
+
+    /// Sets the number of decimals that values are rounded to in the generated G-code, fewer
+    /// decimals gives smaller files, while fine work can need more. The precision is limited
+    /// to 9 decimals. Only affects the G-code text, not the instructions. Defaults to 3
+    /// decimals for metric programs, and 4 decimals for imperial programs.
+    pub fn set_precision(&mut self, precision: u8) {
+        self.precision = Some(precision.min(9));
+    }
+
+    /// Returns the z height of the top of the stock, see
+    /// [set_stock_top](struct.Program.html#method.set_stock_top).
+    #[must_use]
+    pub fn stock_top(&self) -> Option<f64> {
+        self.stock_top
+    }
+
+    /// Sets the z height of the top of the stock. Cuts appended with
+    /// [Context::append_relative_cut](struct.Context.html#method.append_relative_cut) are
+    /// placed relative to it, and `z_safe` is validated to be above it. Set the stock top before
+    /// creating the tool contexts.
+    pub fn set_stock_top(&mut self, z: f64) {
+        self.stock_top = Some(z);
+    }
+
+    /// Returns the position the machine moves to at the end of the program, see
+    /// [set_end_sequence](struct.Program.html#method.set_end_sequence).
+    #[must_use]
+    pub fn park(&self) -> Option<Vector3> {
+        self.park
+    }
+
+    /// Returns the x and y position the machine moves to for tool changes, see
+    /// [set_tool_change_position](struct.Program.html#method.set_tool_change_position).
+    #[must_use]
+    pub fn tool_change_position(&self) -> Option<Vector2> {
+        self.tool_change_position
+    }
+
+    /// Sets an x and y position that is easy to reach, for example at the front left of the
+    /// machine, that the machine moves to after lifting to `z_tool_change` for each tool
+    /// change. Defaults to `None`, which changes tools above the last cut.
+    pub fn set_tool_change_position(&mut self, position: Option<Vector2>) {
+        self.tool_change_position = position;
+    }
+
+    /// Returns the time to wait after stopping the spindle at the end of the program, see
+    /// [set_end_sequence](struct.Program.html#method.set_end_sequence).
+    #[must_use]
+    pub fn spindle_off_dwell(&self) -> Option<Duration> {
+        self.spindle_off_dwell
+    }
+
+    /// Sets the end sequence emitted after the last tool. The tool is always raised to
+    /// `z_tool_change` first. With a spindle off dwell the spindle is stopped (M5) and the
+    /// program waits (G4) for it to spin down, and with a park position the machine then
+    /// moves to the x and y coordinates of it before lowering to its z coordinate. By default
+    /// neither is used and the program ends directly with M2.
+    pub fn set_end_sequence(&mut self, park: Option<Vector3>, spindle_off_dwell: Option<Duration>) {
+        self.park = park;
+        self.spindle_off_dwell = spindle_off_dwell;
+    }
+
+    /// Returns the G-code flavor used when converting the program to G-code.
+    #[must_use]
+    pub fn flavor(&self) -> Flavor {
+        self.flavor
+    }
+
+    /// Sets the G-code flavor used when converting the program to G-code, see
+    /// [Flavor](../types/enum.Flavor.html) for the differences. Defaults to Grbl.
+    pub fn set_flavor(&mut self, flavor: Flavor) {
+        self.flavor = flavor;
+    }
+
+    /// Returns the feed rate mode used when converting the program to G-code.
+    #[must_use]
+    pub fn feed_mode(&self) -> FeedMode {
+        self.feed_mode
+    }
+
+    /// Sets the feed rate mode used when converting the program to G-code, see
+    /// [FeedMode](../types/enum.FeedMode.html). Defaults to units per minute (G94).
+    pub fn set_feed_mode(&mut self, feed_mode: FeedMode) {
+        self.feed_mode = feed_mode;
+    }
+
+    /// Returns the style used for comments when converting the program to G-code.
+    #[must_use]
+    pub fn comment_style(&self) -> CommentStyle {
+        self.comment_style
+            .unwrap_or_else(|| self.flavor.comment_style())
+    }
+
+    /// Sets the style used for comments when converting the program to G-code, overriding
+    /// the default comment style of the [flavor](struct.Program.html#method.set_flavor).
+    pub fn set_comment_style(&mut self, comment_style: CommentStyle) {
+        self.comment_style = Some(comment_style);
+    }
+
+    /// Returns the format of the G4 dwell durations when converting the program to G-code.
+    #[must_use]
+    pub fn dwell_format(&self) -> DwellFormat {
+        self.dwell_format
+            .unwrap_or_else(|| self.flavor.dwell_format())
+    }
+
+    /// Sets the format of the G4 dwell durations when converting the program to G-code,
+    /// overriding the default dwell format of the
+    /// [flavor](struct.Program.html#method.set_flavor), for controllers that are configured to
+    /// read the P word in milliseconds.
+    pub fn set_dwell_format(&mut self, dwell_format: DwellFormat) {
+        self.dwell_format = Some(dwell_format);
+    }
+
+    // Applies the feed rate and spindle speed scales, and the dry run mode, to an instruction.
+    fn apply_overrides(&self, instruction: Instruction) -> Instruction {
+        let feed_rate = |f: Option<f64>| f.map(|f| f * self.feed_rate_scale);
+        let instruction = if self.dry_run {
+            self.apply_dry_run(instruction)
+        } else {
+            instruction
+        };
+
+        match instruction {
+            Instruction::G1(g1) => Instruction::G1(G1 {
+                f: feed_rate(g1.f),
+                ..g1
+            }),
+            Instruction::G2(g2) => Instruction::G2(G2 {
+                f: feed_rate(g2.f),
+                ..g2
+            }),
+            Instruction::G3(g3) => Instruction::G3(G3 {
+                f: feed_rate(g3.f),
+                ..g3
+            }),
+            Instruction::F(f) => Instruction::F(F {
+                x: f.x * self.feed_rate_scale,
+            }),
+            Instruction::S(s) => Instruction::S(S {
+                x: s.x * self.spindle_speed_scale,
+            }),
+            instruction => instruction,
+        }
+    }
+
+    // Raises all z values to at least z_safe, arcs outside of the xy plane (with a k offset) can
+    // not keep their shape and are replaced by straight moves.
+    fn apply_dry_run(&self, instruction: Instruction) -> Instruction {
+        let z_safe = |z: Option<f64>| z.map(|z| z.max(self.z_safe));
+
+        match instruction {
+            Instruction::G0(g0) => Instruction::G0(G0 {
+                z: z_safe(g0.z),
+                ..g0
+            }),
+            Instruction::G1(g1) => Instruction::G1(G1 {
+                z: z_safe(g1.z),
+                ..g1
+            }),
+            Instruction::G2(g2) if g2.k.is_some() => Instruction::G1(G1 {
+                x: g2.x,
+                y: g2.y,
+                z: z_safe(g2.z),
+                f: g2.f,
+            }),
+            Instruction::G2(g2) => Instruction::G2(G2 {
+                z: z_safe(g2.z),
+                ..g2
+            }),
+            Instruction::G3(g3) if g3.k.is_some() => Instruction::G1(G1 {
+                x: g3.x,
+                y: g3.y,
+                z: z_safe(g3.z),
+                f: g3.f,
+            }),
+            Instruction::G3(g3) => Instruction::G3(G3 {
+                z: z_safe(g3.z),
+                ..g3
+            }),
+            instruction => instruction,
+        }
+    }
+
     /// Returns the tools position in a program, this number will then be used in the G-code T commands
     /// (T1 is the first tool, T2 is the second tool and so on).
     #[must_use]
@@ -498,6 +1782,33 @@ impl Program {
         tool_ordering.set_ordering(tool, ordering);
     }
 
+    /// Reorders all tools added to the program by the sort key, for example
+    /// `ToolSortKey::DiameterDesc` to cut with the larger tools first. Replaces any ordering
+    /// set with [set_tool_ordering](struct.Program.html#method.set_tool_ordering), tools added
+    /// afterwards are placed last.
+    pub fn order_tools_by(&self, key: ToolSortKey) {
+        let mut tool_ordering = self.tool_ordering.borrow_mut();
+        tool_ordering.order_by(key);
+    }
+
+    /// Returns the tool number used in the G-code T commands for a tool, which is the tool
+    /// ordering unless a number has been pinned with
+    /// [set_tool_number](struct.Program.html#method.set_tool_number).
+    #[must_use]
+    pub fn tool_number(&self, tool: &Tool) -> Option<u8> {
+        let tool_ordering = self.tool_ordering.borrow();
+        tool_ordering.number(tool)
+    }
+
+    /// Pins the tool number used in the G-code T commands for a tool, for machines where a
+    /// tool is always kept in the same tool changer pocket. The order the tools are used in is
+    /// still decided by the tool ordering, and tools without a pinned number are given the
+    /// first free number when their ordering collides with a pinned number.
+    pub fn set_tool_number(&self, tool: &Tool, number: u8) {
+        let mut tool_ordering = self.tool_ordering.borrow_mut();
+        tool_ordering.set_number(tool, number);
+    }
+
     fn create_context_if_missing_for_tool(&mut self, tool: &Tool) {
         let mut contexts = self.contexts.borrow_mut();
         if let Vacant(entry) = contexts.entry(*tool) {
@@ -534,7 +1845,7 @@ impl Program {
     ///     1.0,
     /// ));
     /// ```
-    pub fn context(&mut self, tool: Tool) -> Context {
+    pub fn context(&mut self, tool: Tool) -> Context<'_> {
         self.create_context_if_missing_for_tool(&tool);
         Context {
             tool,
@@ -542,6 +1853,22 @@ impl Program {
         }
     }
 
+    /// Returns the existing context for the tool, unlike
+    /// [Program::context](struct.Program.html#method.context) no context is created for tools
+    /// that are not used by the program yet.
+    ///
+    /// Returns error if the program has no context for the tool.
+    pub fn context_for(&mut self, tool: Tool) -> Result<Context<'_>> {
+        if !self.contexts.borrow().contains_key(&tool) {
+            return Err(anyhow!("Program has no context for tool {}", tool));
+        }
+
+        Ok(Context {
+            tool,
+            program: Rc::new(RefCell::new(self)),
+        })
+    }
+
     /// This is the main way of adding cuts to a program.
     /// It opens a new context for a tool where the program can be extended.
     ///
@@ -593,7 +1920,11 @@ impl Program {
     /// Returns error if tool or units are not the same in both programs.
     pub fn merge(&mut self, program: &Program) -> Result<()> {
         if self.units != program.units {
-            return Err(anyhow!("Failed to merge due to mismatching units"));
+            return Err(CncError::MismatchedUnits {
+                expected: self.units,
+                actual: program.units,
+            }
+            .into());
         }
 
         self.z_safe = self.z_safe.min(program.z_safe);
@@ -615,6 +1946,86 @@ impl Program {
         Ok(())
     }
 
+    /// Merges another program into this program, converting the coordinates, heights, depth
+    /// steps, and feed rates of the other program into the units of this program first. The
+    /// tools keep their own units, as they are converted when the G-code is generated. Raw
+    /// G-code operations are merged as they are.
+    ///
+    /// Returns error if a cut can not be converted.
+    pub fn merge_converting(&mut self, program: &Program) -> Result<()> {
+        if self.units == program.units {
+            return self.merge(program);
+        }
+
+        let mut converted = Program::new_empty_from(program);
+        converted.units = self.units;
+        converted.z_safe = program.units.convert(program.z_safe, self.units);
+        converted.z_tool_change = program.units.convert(program.z_tool_change, self.units);
+
+        let contexts = program.contexts.borrow();
+        for tool in program.tools() {
+            if let Some(context) = contexts.get(&tool) {
+                converted.create_context_if_missing_for_tool(&tool);
+                converted
+                    .contexts
+                    .borrow_mut()
+                    .insert(tool, context.converted(self.units)?);
+            }
+        }
+
+        self.merge(&converted)
+    }
+
+    /// Applies a transform to all cuts in the program.
+    ///
+    /// Returns error if any of the cuts can not be transformed, in which case the program
+    /// is left unchanged.
+    pub fn transform(&mut self, transform: &Transform) -> Result<()> {
+        let mut contexts = self.contexts.borrow_mut();
+
+        let mut transformed = HashMap::new();
+        for (tool, context) in contexts.iter() {
+            transformed.insert(*tool, context.transformed_operations(transform)?);
+        }
+
+        for (tool, operations) in transformed {
+            if let Some(context) = contexts.get_mut(&tool) {
+                context.operations = operations;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves all cuts in the program by the given offset.
+    pub fn translate(&mut self, offset: Vector3) -> Result<()> {
+        self.transform(&Transform::Translate(offset))
+    }
+
+    /// Rotates all cuts in the program counterclockwise around the z axis by `angle` radians,
+    /// pivoting around the `pivot` xy coordinate.
+    ///
+    /// Returns error if the program contains frame or area cuts and the angle is not a
+    /// multiple of 90°, or arcs that are not cut around the z axis.
+    pub fn rotate_z(&mut self, angle: f64, pivot: Vector2) -> Result<()> {
+        self.transform(&Transform::RotateZ { angle, pivot })
+    }
+
+    /// Scales all cuts in the program, including depths, by the given factor relative to origo.
+    pub fn scale(&mut self, factor: f64) -> Result<()> {
+        self.transform(&Transform::Scale(factor))
+    }
+
+    /// Mirrors all cuts in the program around x = 0.
+    pub fn mirror_x(&mut self) -> Result<()> {
+        self.transform(&Transform::MirrorX)
+    }
+
+    /// Mirrors all cuts in the program around y = 0.
+    pub fn mirror_y(&mut self) -> Result<()> {
+        self.transform(&Transform::MirrorY)
+    }
+
     /// Returns an ordered vec with all tools used by a program.
     #[must_use]
     pub fn tools(&self) -> Vec<Tool> {
@@ -622,7 +2033,7 @@ impl Program {
         tool_ordering.tools_ordered()
     }
 
-    /// Returns the bounds of the program.
+    /// Returns the bounds of the program, the area swept by the tools of all cuts.
     #[must_use]
     pub fn bounds(&self) -> Bounds {
         let mut bounds = Bounds::minmax();
@@ -631,45 +2042,217 @@ impl Program {
 
         for tool in tools {
             if let Some(context) = contexts.get(&tool) {
-                let context_bounds = context.bounds();
-                bounds.min.x = if bounds.min.x > context_bounds.min.x {
-                    context_bounds.min.x
-                } else {
-                    bounds.min.x
-                };
-                bounds.min.y = if bounds.min.y > context_bounds.min.y {
-                    context_bounds.min.y
-                } else {
-                    bounds.min.y
-                };
-                bounds.min.z = if bounds.min.z > context_bounds.min.z {
-                    context_bounds.min.z
-                } else {
-                    bounds.min.z
-                };
-                bounds.max.x = if bounds.max.x < context_bounds.max.x {
-                    context_bounds.max.x
-                } else {
-                    bounds.max.x
-                };
-                bounds.max.y = if bounds.max.y < context_bounds.max.y {
-                    context_bounds.max.y
-                } else {
-                    bounds.max.y
-                };
-                bounds.max.z = if bounds.max.z < context_bounds.max.z {
-                    context_bounds.max.z
-                } else {
-                    bounds.max.z
-                };
+                bounds = bounds.union(&context.bounds());
             }
         }
 
         bounds
     }
 
+    /// Returns the bounds of each tool context in tool order, to get the work area of each tool.
+    #[must_use]
+    pub fn bounds_per_tool(&self) -> Vec<(Tool, Bounds)> {
+        let contexts = self.contexts.borrow();
+
+        self.tools()
+            .into_iter()
+            .filter_map(|tool| contexts.get(&tool).map(|context| (tool, context.bounds())))
+            .collect()
+    }
+
+    /// Returns the size of the stock needed for the program, the program bounds expanded by
+    /// the margin on each side. The top of the stock is kept at the top of the program bounds,
+    /// so the z margin is only added below the deepest cut.
+    #[must_use]
+    pub fn required_stock(&self, margin: Vector3) -> Bounds {
+        let bounds = self.bounds();
+
+        Bounds {
+            min: bounds.min - margin,
+            max: bounds.max + margin.with_z(0.0),
+        }
+    }
+
+    /// Returns the pairs of cuts that overlap in the xy plane within the same tool context, as a
+    /// heuristic to catch cuts that were placed on top of each other by mistake. The cuts are
+    /// compared by their nominal bounds, so cuts that only touch at an edge are not reported.
+    ///
+    /// The indices count all operations of the program, context by context in tool order,
+    /// which is the same order that the operations are converted to G-code in.
+    #[must_use]
+    pub fn overlapping_cuts(&self) -> Vec<(usize, usize)> {
+        let contexts = self.contexts.borrow();
+        let mut overlapping = vec![];
+        let mut offset = 0;
+
+        for tool in self.tools() {
+            let Some(context) = contexts.get(&tool) else {
+                continue;
+            };
+
+            let cuts = context
+                .operations_iter()
+                .enumerate()
+                .filter_map(|(index, operation)| match operation {
+                    Operation::Cut(cut) => {
+                        let bounds = cut.bounds();
+                        Some((
+                            offset + index,
+                            Bounds {
+                                min: bounds.min.with_z(0.0),
+                                max: bounds.max.with_z(0.0),
+                            },
+                        ))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            for (position, (index, bounds)) in cuts.iter().enumerate() {
+                for (other_index, other_bounds) in &cuts[position + 1..] {
+                    if let Some(intersection) = bounds.intersection(other_bounds) {
+                        let size = intersection.size();
+                        if size.x > 0.0 && size.y > 0.0 {
+                            overlapping.push((*index, *other_index));
+                        }
+                    }
+                }
+            }
+
+            offset += context.len();
+        }
+
+        overlapping
+    }
+
+    /// Returns the z depths of the passes of each cut in the program, see
+    /// [Cut::layers](../cuts/enum.Cut.html#method.layers), for example to document how many
+    /// passes a job makes.
+    ///
+    /// The indices count all operations of the program in the same way as for
+    /// [overlapping_cuts](#method.overlapping_cuts), operations that are not cuts are left out.
+    ///
+    /// Returns error if the depths of a cut can not be calculated.
+    pub fn layers(&self) -> Result<Vec<(usize, Vec<f64>)>> {
+        let contexts = self.contexts.borrow();
+        let mut layers = vec![];
+        let mut offset = 0;
+
+        for tool in self.tools() {
+            let Some(context) = contexts.get(&tool) else {
+                continue;
+            };
+
+            for (index, operation) in context.operations_iter().enumerate() {
+                if let Operation::Cut(cut) = operation {
+                    layers.push((offset + index, cut.layers(&tool)?));
+                }
+            }
+
+            offset += context.len();
+        }
+
+        Ok(layers)
+    }
+
+    /// Validates the program, checking that no cut goes deeper into the stock than the usable
+    /// cutting length of its tool. The validation is also made when converting the program to
+    /// G-code.
+    ///
+    /// Returns error naming the offending cut and tool.
+    pub fn validate(&self) -> Result<(), CncError> {
+        let contexts = self.contexts.borrow();
+
+        for tool in self.tools() {
+            if let Some(context) = contexts.get(&tool) {
+                context.validate()?;
+            }
+        }
+
+        if let Some(probe) = self.tool_probe {
+            if probe.point.z >= self.z_tool_change {
+                return Err(CncError::ProbeAboveToolChange {
+                    probe_z: probe.point.z,
+                    z_tool_change: self.z_tool_change,
+                });
+            }
+
+            if !(probe.feed_rate > 0.0 && probe.feed_rate.is_finite()) {
+                return Err(CncError::InvalidProbeFeedRate {
+                    feed_rate: probe.feed_rate,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     /// Converts a program to G-code instructions
-    pub fn to_instructions(&self) -> Result<Vec<Instruction>> {
+    pub fn to_instructions(&self) -> Result<Vec<Instruction>, CncError> {
+        let mut instructions = vec![];
+
+        self.generate_instructions(
+            |instruction| {
+                instructions.push(instruction);
+                Ok(())
+            },
+            |_| {},
+        )?;
+
+        Ok(instructions)
+    }
+
+    // Measures the tool against the probe, starting and ending at the tool change height.
+    fn tool_probe_instructions(&self, probe: &ToolProbe) -> Vec<Instruction> {
+        let travel = self.z_tool_change - probe.point.z;
+        let mut instructions = vec![
+            Instruction::Comment(Comment {
+                text: "Probe tool length".to_string(),
+            }),
+            Instruction::G0(G0 {
+                x: Some(probe.point.x),
+                y: Some(probe.point.y),
+                z: None,
+            }),
+            Instruction::G38_2(G38_2 {
+                x: None,
+                y: None,
+                z: Some(probe.point.z - travel),
+                f: Some(probe.feed_rate),
+            }),
+            Instruction::G10(G10 {
+                l: 20,
+                p: 0,
+                x: None,
+                y: None,
+                z: Some(probe.point.z),
+            }),
+            Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(self.z_tool_change),
+            }),
+        ];
+
+        // The probe feed rate is in units per minute, also in inverse time mode
+        if self.feed_mode == FeedMode::InverseTime {
+            instructions.insert(2, Instruction::G94(G94 {}));
+            instructions.push(Instruction::G93(G93 {}));
+        }
+
+        instructions
+    }
+
+    // Generates the program instructions one by one, with duplicated instructions trimmed and
+    // the feed rate and spindle speed overrides applied. The progress is called with the
+    // fraction of completed operations after each operation.
+    fn generate_instructions(
+        &self,
+        mut emit: impl FnMut(Instruction) -> Result<(), CncError>,
+        mut progress: impl FnMut(f64),
+    ) -> Result<(), CncError> {
+        self.validate()?;
+
         let contexts = self.contexts.borrow();
         let tools = self.tools();
         let z_safe = self.z_safe();
@@ -677,30 +2260,55 @@ impl Program {
         let bounds = self.bounds();
         let size = bounds.size();
         let units = self.units;
-
-        if z_tool_change < z_safe {
-            return Err(anyhow!(
-                "z_tool_change {} {} must be larger than or equal to the z_safe value of {} {}",
-                z_tool_change,
-                units,
-                z_safe,
-                units
-            ));
+        let max_z = bounds.max.z.max(self.stock_top.unwrap_or(f64::MIN));
+
+        validate_heights(z_safe, z_tool_change, max_z, units, None)?;
+
+        for tool in &tools {
+            if let Some(context) = contexts.get(tool) {
+                validate_heights(
+                    context.z_safe,
+                    context.z_tool_change,
+                    max_z,
+                    units,
+                    Some(*tool),
+                )?;
+            }
         }
 
-        if z_safe < bounds.max.z {
-            return Err(anyhow!(
-                "z_safe {} {} must be larger than or equal to the workpiece max z value of {} {}",
-                z_safe,
-                units,
-                bounds.max.z,
-                units
-            ));
-        }
+        let mut trimmer = InstructionTrimmer::default();
+        let mut state = MachineState::default();
+        let mut feed_state = MachineState::default();
+        let arc_turns = self.flavor.supports_arc_turns();
+        let mut push = |instruction: Instruction| -> Result<()> {
+            let from = state.position;
+            let xy_plane = matches!(state.plane, Axis::Z);
+            state.apply(&instruction);
+
+            let instructions = if arc_turns || !xy_plane {
+                vec![instruction]
+            } else {
+                expand_arc_turns(from, instruction)
+            };
+
+            for instruction in instructions {
+                let mut instruction = self.apply_overrides(instruction);
+                if self.feed_mode == FeedMode::InverseTime {
+                    instruction = inverse_time_feed(&mut feed_state, instruction);
+                }
+
+                if let Some(instruction) = trimmer.push(instruction) {
+                    emit(instruction)?;
+                }
+            }
+            Ok(())
+        };
 
-        let mut raw_instructions = self.meta.to_instructions();
+        for instruction in self.meta.to_instructions() {
+            push(instruction)?;
+        }
 
-        raw_instructions.push(Instruction::Comment(Comment {
+        push(Instruction::Comment(Comment {
             text: format!(
                 "Workarea: size_x = {} {units}, size_y = {} {units}, size_z = {} {units}, min_x = {} {units}, min_y = {} {units}, max_z = {} {units}, z_safe = {} {units}, z_tool_change = {} {units}",
                format_number(size.x),
@@ -712,19 +2320,32 @@ impl Program {
                format_number(z_safe),
                format_number(z_tool_change),
             )
-        }));
+        }))?;
+
+        push(Instruction::Empty(Empty {}))?;
+        push(Instruction::G17(G17 {}))?;
+        push(match self.feed_mode {
+            FeedMode::UnitsPerMinute => Instruction::G94(G94 {}),
+            FeedMode::InverseTime => Instruction::G93(G93 {}),
+        })?;
 
-        raw_instructions.push(Instruction::Empty(Empty {}));
-        raw_instructions.push(Instruction::G17(G17 {}));
+        let operation_count = contexts
+            .values()
+            .map(|context| context.operations.len())
+            .sum::<usize>();
+        let mut completed_operations = 0;
+        let mut commanded_spindle_speed = None;
+        let mut z_end = self.z_tool_change;
 
         for tool in tools {
             if let Some(context) = contexts.get(&tool) {
-                let tool_number = self.tool_ordering(&tool).unwrap();
+                let tool_number = self.tool_number(&tool).unwrap();
+                z_end = z_end.max(context.z_tool_change);
 
-                raw_instructions.push(Instruction::Empty(Empty {}));
+                push(Instruction::Empty(Empty {}))?;
 
                 // Tool change
-                raw_instructions.append(&mut vec![
+                let mut tool_change = vec![
                     Instruction::Comment(Comment {
                         text: format!("Tool change: {}", tool),
                     }),
@@ -737,195 +2358,886 @@ impl Program {
                         y: None,
                         z: Some(context.z_tool_change),
                     }),
+                ];
+
+                if let Some(position) = self.tool_change_position {
+                    tool_change.push(Instruction::G0(G0 {
+                        x: Some(position.x),
+                        y: Some(position.y),
+                        z: None,
+                    }));
+                }
+
+                tool_change.append(&mut vec![
                     Instruction::M5(M5 {}),
                     Instruction::M6(M6 { t: tool_number }),
-                    Instruction::S(S {
+                ]);
+
+                if self.tool_length_offsets {
+                    tool_change.push(Instruction::G43(G43 {
+                        h: u32::from(tool_number),
+                    }));
+                }
+
+                if let (Some(probe), false) = (self.tool_probe, self.dry_run) {
+                    tool_change.append(&mut self.tool_probe_instructions(&probe));
+                }
+
+                if !self.minimal_spindle_commands
+                    || commanded_spindle_speed != Some(tool.spindle_speed())
+                {
+                    tool_change.push(Instruction::S(S {
                         x: tool.spindle_speed(),
-                    }),
+                    }));
+                    commanded_spindle_speed = Some(tool.spindle_speed());
+                }
+
+                tool_change.extend([
                     if tool.direction() == Direction::Clockwise {
                         Instruction::M3(M3 {})
                     } else {
                         Instruction::M4(M4 {})
                     },
                     Instruction::G4(G4 {
-                        p: Duration::from_secs(
-                            scale(tool.spindle_speed(), 0.0, 50_000.0, 3.0, 20.0) as u64,
-                        ),
+                        p: self.spindle_spinup.unwrap_or_else(|| {
+                            Duration::from_secs(scale(
+                                tool.spindle_speed(),
+                                0.0,
+                                50_000.0,
+                                3.0,
+                                20.0,
+                            ) as u64)
+                        }),
                     }),
                 ]);
 
+                for instruction in tool_change {
+                    push(instruction)?;
+                }
+
                 // Add tool instructions
-                raw_instructions.append(&mut context.to_instructions()?);
+                let mut retracts = RetractTrimmer::new(context.z_safe);
+                for operation in &context.operations {
+                    let instructions = if self.verbose_cut_headers {
+                        operation.to_instructions_with_header(context)?
+                    } else {
+                        operation.to_instructions(context)?
+                    };
+
+                    for instruction in instructions {
+                        if retracts.keep(&instruction) {
+                            push(instruction)?;
+                        }
+                    }
+
+                    completed_operations += 1;
+                    progress(completed_operations as f64 / operation_count as f64);
+                }
             }
         }
 
-        // End program
-        raw_instructions.push(Instruction::G0(G0 {
+        // End program, retracting to the highest tool change height as a long tool may still be
+        // mounted
+        push(Instruction::G0(G0 {
             x: None,
             y: None,
-            z: Some(self.z_tool_change),
-        }));
-        raw_instructions.push(Instruction::Empty(Empty {}));
-        raw_instructions.push(Instruction::M2(M2 {}));
+            z: Some(z_end),
+        }))?;
 
-        // Trim duplicated instructions
-        let mut workplane = Instruction::Empty(Empty {});
-        let raw_length = raw_instructions.len();
-        let mut instructions = vec![];
-        for (index, instruction) in raw_instructions.iter().enumerate() {
-            if *instruction == Instruction::G17(G17 {})
-                || *instruction == Instruction::G18(G18 {})
-                || *instruction == Instruction::G19(G19 {})
-            {
-                if *instruction == workplane {
-                    continue;
-                } else {
-                    workplane = instruction.clone();
+        if let Some(dwell) = self.spindle_off_dwell {
+            push(Instruction::M5(M5 {}))?;
+            push(Instruction::G4(G4 { p: dwell }))?;
+        }
+
+        if let Some(park) = self.park {
+            push(Instruction::G0(G0 {
+                x: Some(park.x),
+                y: Some(park.y),
+                z: None,
+            }))?;
+            push(Instruction::G0(G0 {
+                x: None,
+                y: None,
+                z: Some(park.z),
+            }))?;
+        }
+
+        push(Instruction::Empty(Empty {}))?;
+        push(Instruction::M2(M2 {}))?;
+
+        if let Some(instruction) = trimmer.finish() {
+            emit(instruction)?;
+        }
+
+        if operation_count == 0 {
+            progress(1.0);
+        }
+
+        Ok(())
+    }
+
+    /// Estimates how long the program will take to run on the machine.
+    ///
+    /// The estimate is made from the generated instructions, where rapid moves use the
+    /// [rapid rate](struct.Program.html#method.set_rapid_rate), cutting moves use the active
+    /// feed rate, dwells use their duration, and each tool change adds one minute. Machine
+    /// acceleration is not accounted for so the actual duration will be somewhat longer.
+    pub fn estimated_duration(&self) -> Result<Duration> {
+        let mut state = MachineState::default();
+        let mut minutes = 0.0;
+        let mut duration = Duration::ZERO;
+
+        for instruction in self.to_instructions()? {
+            match &instruction {
+                Instruction::G4(g4) => duration += g4.p,
+                Instruction::M6(_) => duration += TOOL_CHANGE_DURATION,
+                _ => {}
+            }
+
+            if let Some(motion) = state.apply(&instruction) {
+                match motion.feed_rate {
+                    // Inverse time feed rates are the number of moves per minute
+                    Some(rate) if self.feed_mode == FeedMode::InverseTime && rate > 0.0 => {
+                        minutes += 1.0 / rate;
+                    }
+                    rate => {
+                        let rate = rate.unwrap_or(self.rapid_rate);
+                        if rate > 0.0 {
+                            minutes += motion.length / rate;
+                        }
+                    }
                 }
             }
+        }
 
-            if index < raw_length - 1 && instruction == &raw_instructions[index + 1] {
-                continue;
+        Ok(duration + Duration::from_secs_f64(minutes * 60.0))
+    }
+
+    /// Returns the total cutting and rapid travel distances as well as the number of moves per
+    /// move type for the program, computed from the generated instructions.
+    pub fn travel_stats(&self) -> Result<TravelStats> {
+        let mut state = MachineState::default();
+        let mut stats = TravelStats::default();
+
+        for instruction in self.to_instructions()? {
+            match &instruction {
+                Instruction::G0(_) => stats.g0_count += 1,
+                Instruction::G1(_) => stats.g1_count += 1,
+                Instruction::G2(_) => stats.g2_count += 1,
+                Instruction::G3(_) => stats.g3_count += 1,
+                Instruction::M6(_) => stats.tool_change_count += 1,
+                _ => {}
             }
 
-            instructions.push(instruction.clone());
+            if let Some(motion) = state.apply(&instruction) {
+                match motion.kind {
+                    MoveKind::Rapid => stats.rapid_distance += motion.length,
+                    MoveKind::Linear | MoveKind::Arc => stats.cut_distance += motion.length,
+                }
+            }
         }
 
-        Ok(instructions)
+        Ok(stats)
     }
 
-    /// Converts program to G-code
-    pub fn to_gcode(&self) -> Result<String> {
+    /// Replays the program instructions, returning each instruction together with the
+    /// commanded machine state after it, for example to debug a program or to analyse the
+    /// moves. The machine is assumed to start at xyz 0.0 in the units of the program.
+    ///
+    /// Returns error if the program can not be converted to instructions.
+    pub fn simulate(&self) -> Result<Vec<(Instruction, MachineState)>> {
+        let mut state = MachineState {
+            units: self.units,
+            ..MachineState::default()
+        };
+
         Ok(self
             .to_instructions()?
-            .iter()
-            .map(|instruction| instruction.to_gcode())
-            .collect::<Vec<String>>()
-            .join("\n"))
+            .into_iter()
+            .map(|instruction| {
+                state.apply(&instruction);
+                (instruction, state.clone())
+            })
+            .collect())
     }
-}
 
-impl Default for Program {
-    fn default() -> Self {
-        Self {
-            z_safe: 50.0,
-            z_tool_change: 100.0,
-            meta: ProgramMeta::default(),
-            units: Units::default(),
-            contexts: Rc::new(RefCell::new(HashMap::new())),
-            tool_ordering: Rc::new(RefCell::new(ToolOrdering::default())),
-        }
+    /// Serializes the program, including all tool contexts and cuts, to JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    fn mask_non_pure_comments(gcode: &str) -> String {
-        let pattern =
-            regex::Regex::new(r"(Created\s+on|Created\s+by|Generator):\s*[^\)]+").unwrap();
-        let gcode = pattern.replace_all(gcode, "$1: MASKED");
+    /// Deserializes a program from JSON created with
+    /// [Program::to_json](struct.Program.html#method.to_json).
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
 
-        gcode.to_string()
+    /// Converts program to G-code, written in the selected
+    /// [flavor](struct.Program.html#method.set_flavor).
+    pub fn to_gcode(&self) -> Result<String, CncError> {
+        let mut gcode = vec![];
+        self.write_gcode(&mut gcode)?;
+        Ok(String::from_utf8(gcode).map_err(anyhow::Error::from)?)
     }
 
-    #[test]
-    fn test_program_new() {
-        let program = Program::new(Units::Metric, 10.0, 50.0);
-        assert_eq!(program.z_safe, 10.0);
-        assert_eq!(program.z_tool_change, 50.0);
+    /// Writes the program as G-code to a writer, line by line as the instructions are
+    /// generated, which avoids keeping the whole program in memory. Consider wrapping the
+    /// writer in a `BufWriter` when writing to files.
+    pub fn write_gcode<W: Write>(&self, writer: W) -> Result<(), CncError> {
+        self.write_gcode_with_progress(writer, |_| {})
     }
 
-    #[test]
-    fn test_program_empty() -> Result<()> {
-        let mut program = Program::new(Units::Metric, 10.0, 50.0);
-        program.set_name("empty");
+    /// Writes the program as G-code to a writer, see
+    /// [Program::write_gcode](struct.Program.html#method.write_gcode). The progress callback
+    /// is called after each operation with the fraction (0.0 to 1.0) of completed operations.
+    pub fn write_gcode_with_progress<W: Write>(
+        &self,
+        writer: W,
+        progress: impl FnMut(f64),
+    ) -> Result<(), CncError> {
+        self.write_gcode_lines(writer, progress, false)
+    }
 
-        let tool = Tool::cylindrical(
-            Units::Metric,
-            50.0,
-            4.0,
-            Direction::Clockwise,
-            5_000.0,
-            400.0,
-        );
+    /// Converts program to G-code without comments and empty lines, for controllers with small
+    /// program buffers. Use [Program::to_gcode](struct.Program.html#method.to_gcode) for the
+    /// commented output.
+    pub fn to_gcode_minified(&self) -> Result<String, CncError> {
+        let mut gcode = vec![];
+        self.write_gcode_lines(&mut gcode, |_| {}, true)?;
+        Ok(String::from_utf8(gcode).map_err(anyhow::Error::from)?)
+    }
 
-        let mut context = program.context(tool);
-        context.append_cut(Cut::drill(Vector3::default(), -1.0));
+    fn write_gcode_lines<W: Write>(
+        &self,
+        writer: W,
+        progress: impl FnMut(f64),
+        minified: bool,
+    ) -> Result<(), CncError> {
+        with_precision(self.precision(), || {
+            self.write_gcode_lines_with_precision(writer, progress, minified)
+        })
+    }
 
-        assert_eq!(program.tools().len(), 1);
+    fn write_gcode_lines_with_precision<W: Write>(
+        &self,
+        mut writer: W,
+        progress: impl FnMut(f64),
+        minified: bool,
+    ) -> Result<(), CncError> {
+        let comment_style = self.comment_style();
+        let dwell_format = self.dwell_format();
+        let mut first_line = true;
+        let mut spindle_speed = None;
+        let mut position = KnownPosition::default();
+
+        self.generate_instructions(
+            |instruction| {
+                let instruction = if self.omit_unchanged_coordinates {
+                    match position.omit_unchanged(instruction) {
+                        Some(instruction) => instruction,
+                        None => return Ok(()),
+                    }
+                } else {
+                    instruction
+                };
 
-        let mut instructions = program.to_instructions()?;
+                let line = match &instruction {
+                    Instruction::Comment(_) | Instruction::Empty(_) if minified => {
+                        return Ok(());
+                    }
+                    Instruction::Comment(comment) => comment_style.format(&comment.text),
+                    Instruction::G4(g4) => dwell_format.format(g4.p),
+                    // Marlin sets the spindle speed as a parameter to M3/M4
+                    Instruction::S(s) if self.flavor == Flavor::Marlin => {
+                        spindle_speed = Some(s.x);
+                        return Ok(());
+                    }
+                    // The speed is kept for the following M3/M4, that may not have an S before it
+                    Instruction::M3(_) | Instruction::M4(_)
+                        if self.flavor == Flavor::Marlin && spindle_speed.is_some() =>
+                    {
+                        format!(
+                            "{} S{}",
+                            instruction.to_gcode(),
+                            round_precision(spindle_speed.unwrap_or_default())
+                        )
+                    }
+                    _ => instruction.to_flavored_gcode(self.flavor),
+                };
 
-        for i in instructions.iter_mut() {
-            if let Instruction::Comment(comment) = i {
-                comment.text = mask_non_pure_comments(&comment.text);
-            }
-        }
+                if !first_line {
+                    writer.write_all(b"\n")?;
+                }
+                first_line = false;
 
-        assert_eq!(instructions, vec![
-            Instruction::Comment(Comment { text: "Name: empty".into() }),
-            Instruction::Comment(Comment { text: "Created on: MASKED".into()  }),
-            Instruction::Comment(Comment { text: "Created by: MASKED".into()  }),
-            Instruction::Comment(Comment { text: "Generator: MASKED" .into() }),
-            Instruction::Comment(Comment { text: "Workarea: size_x = 0 mm, size_y = 0 mm, size_z = 1 mm, min_x = 0 mm, min_y = 0 mm, max_z = 0 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
-            Instruction::Empty(Empty {}),
-            Instruction::G17(G17 {}),
-            Instruction::Empty(Empty {}),
-            Instruction::Comment(Comment { text: "Tool change: type = Cylindrical, diameter = 4 mm, length = 50 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400 mm/min".to_string() }),
-            Instruction::G21(G21 {}),
-            Instruction::G0(G0 { x: None, y: None, z: Some(50.0) }),
-            Instruction::M5(M5 {}),
-            Instruction::M6(M6 { t: 1 }),
-            Instruction::S(S { x: 5_000.0 }),
-            Instruction::M3(M3 {}),
-            Instruction::G4(G4 { p: Duration::from_secs(4) }),
-            Instruction::Empty(Empty {}),
-            Instruction::Comment(Comment { text: "Drill hole at: x = 0, y = 0".to_string() }),
-            Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
-            Instruction::G0(G0 { x: Some(0.0), y: Some(0.0), z: None }),
-            Instruction::G1(G1 { x: None, y: None, z: Some(-1.0), f: Some(400.0) }),
-            Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
-            Instruction::G0(G0 { x: None, y: None, z: Some(50.0) }),
-            Instruction::Empty(Empty {}),
-            Instruction::M2(M2 {}),
-        ]);
+                writer.write_all(line.as_bytes())?;
+                Ok(())
+            },
+            progress,
+        )?;
 
-        let mut other_program = Program::new_empty_from(&program);
-        other_program.set_name("empty2");
+        writer.flush()?;
 
-        assert_eq!(other_program.z_safe, 10.0);
-        assert_eq!(other_program.z_tool_change, 50.0);
-        assert_eq!(other_program.tools().len(), 0);
+        Ok(())
+    }
+}
 
-        let mut instructions = other_program.to_instructions()?;
+// Replaces the feed rate of a cutting move with the inverse time feed rate, the number of times
+// the move can be made per minute at the feed rate. Every cutting move gets an F word, as the
+// inverse time feed rate is not modal.
+fn inverse_time_feed(state: &mut MachineState, instruction: Instruction) -> Instruction {
+    let Some(motion) = state.apply(&instruction) else {
+        return instruction;
+    };
+
+    let f = match motion.feed_rate {
+        Some(feed_rate) if motion.length > 0.0 => Some(feed_rate / motion.length),
+        feed_rate => feed_rate,
+    };
+
+    match instruction {
+        Instruction::G1(g1) => Instruction::G1(G1 { f, ..g1 }),
+        Instruction::G2(g2) => Instruction::G2(G2 { f, ..g2 }),
+        Instruction::G3(g3) => Instruction::G3(G3 { f, ..g3 }),
+        instruction => instruction,
+    }
+}
 
-        for i in instructions.iter_mut() {
-            if let Instruction::Comment(comment) = i {
-                comment.text = mask_non_pure_comments(&comment.text);
-            }
+// Expands an xy plane arc with a P word (number of turns) to one arc per revolution, for
+// controllers that do not support the P word. The z value is interpolated by the angle.
+fn expand_arc_turns(from: Vector3, instruction: Instruction) -> Vec<Instruction> {
+    let (arc, clockwise) = match instruction {
+        Instruction::G2(g2) => (g2, true),
+        Instruction::G3(g3) => (
+            G2 {
+                x: g3.x,
+                y: g3.y,
+                z: g3.z,
+                i: g3.i,
+                j: g3.j,
+                k: g3.k,
+                r: g3.r,
+                p: g3.p,
+                f: g3.f,
+            },
+            false,
+        ),
+        instruction => return vec![instruction],
+    };
+
+    let turns = match arc.p {
+        Some(turns) if turns > 1 && arc.r.is_none() => turns,
+        _ => {
+            return vec![to_arc_instruction(
+                G2 {
+                    p: arc.p.filter(|turns| *turns > 1),
+                    ..arc
+                },
+                clockwise,
+            )]
         }
-
-        assert_eq!(instructions, vec![
-            Instruction::Comment(Comment { text: "Name: empty2".into() }),
-            Instruction::Comment(Comment { text: "Created on: MASKED".into()  }),
-            Instruction::Comment(Comment { text: "Created by: MASKED".into()  }),
-            Instruction::Comment(Comment { text: "Generator: MASKED" .into() }),
-            Instruction::Comment(Comment { text: "Workarea: size_x = 0 mm, size_y = 0 mm, size_z = 0 mm, min_x = 0 mm, min_y = 0 mm, max_z = 0 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
-            Instruction::Empty(Empty {}),
-                Instruction::G17(G17 {}),
-                Instruction::G0(G0 {
-                    x: None,
-                    y: None,
-                    z: Some(50.0)
-                }),
-                Instruction::Empty(Empty {}),
-                Instruction::M2(M2 {}),
-            ]
-        );
-
-        Ok(())
+    };
+
+    let to = Vector3::new(
+        arc.x.unwrap_or(from.x),
+        arc.y.unwrap_or(from.y),
+        arc.z.unwrap_or(from.z),
+    );
+    let center = from.xy() + Vector2::new(arc.i.unwrap_or(0.0), arc.j.unwrap_or(0.0));
+    let from_angle = (from.y - center.y).atan2(from.x - center.x);
+    let to_angle = (to.y - center.y).atan2(to.x - center.x);
+    let mut last_angle = if clockwise {
+        from_angle - to_angle
+    } else {
+        to_angle - from_angle
+    }
+    .rem_euclid(TAU);
+    if last_angle < 1e-9 {
+        last_angle = TAU;
+    }
+    let total_angle = f64::from(turns - 1) * TAU + last_angle;
+
+    let mut instructions = (1..turns)
+        .map(|turn| {
+            let z = from.z + (to.z - from.z) * f64::from(turn) * TAU / total_angle;
+            G2 {
+                x: Some(from.x),
+                y: Some(from.y),
+                z: arc.z.map(|_| z),
+                p: None,
+                f: if turn == 1 { arc.f } else { None },
+                ..arc.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    instructions.push(G2 {
+        p: None,
+        f: None,
+        ..arc
+    });
+
+    instructions
+        .into_iter()
+        .map(|arc| to_arc_instruction(arc, clockwise))
+        .collect()
+}
+
+fn to_arc_instruction(arc: G2, clockwise: bool) -> Instruction {
+    if clockwise {
+        Instruction::G2(arc)
+    } else {
+        Instruction::G3(G3 {
+            x: arc.x,
+            y: arc.y,
+            z: arc.z,
+            i: arc.i,
+            j: arc.j,
+            k: arc.k,
+            r: arc.r,
+            p: arc.p,
+            f: arc.f,
+        })
+    }
+}
+
+// Validates that the tool change height is above the safe height, and that the safe height
+// is above the workpiece.
+fn validate_heights(
+    z_safe: f64,
+    z_tool_change: f64,
+    max_z: f64,
+    units: Units,
+    tool: Option<Tool>,
+) -> Result<(), CncError> {
+    if z_tool_change < z_safe {
+        return Err(CncError::ZToolChangeBelowZSafe {
+            z_tool_change,
+            z_safe,
+            units,
+            tool,
+        });
+    }
+
+    if z_safe < max_z {
+        return Err(CncError::ZSafeBelowWorkpiece {
+            z_safe,
+            max_z,
+            units,
+            tool,
+        });
+    }
+
+    Ok(())
+}
+
+// Plain representation of a program used for serialization, where the shared tool contexts
+// are flattened to a list.
+#[derive(Serialize, Deserialize)]
+struct SerializedProgram {
+    z_safe: f64,
+    z_tool_change: f64,
+    rapid_rate: f64,
+    feed_rate_scale: f64,
+    spindle_speed_scale: f64,
+    #[serde(default)]
+    spindle_spinup: Option<Duration>,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    tool_length_offsets: bool,
+    #[serde(default)]
+    park: Option<Vector3>,
+    #[serde(default)]
+    tool_change_position: Option<Vector2>,
+    #[serde(default)]
+    spindle_off_dwell: Option<Duration>,
+    #[serde(default)]
+    stock_top: Option<f64>,
+    #[serde(default)]
+    omit_unchanged_coordinates: bool,
+    #[serde(default)]
+    verbose_cut_headers: bool,
+    #[serde(default)]
+    precision: Option<u8>,
+    #[serde(default)]
+    tool_probe: Option<ToolProbe>,
+    #[serde(default)]
+    minimal_spindle_commands: bool,
+    flavor: Flavor,
+    #[serde(default)]
+    feed_mode: FeedMode,
+    comment_style: Option<CommentStyle>,
+    dwell_format: Option<DwellFormat>,
+    meta: ProgramMeta,
+    units: Units,
+    contexts: Vec<InnerContext>,
+    tool_ordering: ToolOrdering,
+}
+
+impl Serialize for Program {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let contexts = self.contexts.borrow();
+
+        SerializedProgram {
+            z_safe: self.z_safe,
+            z_tool_change: self.z_tool_change,
+            rapid_rate: self.rapid_rate,
+            feed_rate_scale: self.feed_rate_scale,
+            spindle_speed_scale: self.spindle_speed_scale,
+            spindle_spinup: self.spindle_spinup,
+            dry_run: self.dry_run,
+            tool_length_offsets: self.tool_length_offsets,
+            park: self.park,
+            tool_change_position: self.tool_change_position,
+            spindle_off_dwell: self.spindle_off_dwell,
+            stock_top: self.stock_top,
+            omit_unchanged_coordinates: self.omit_unchanged_coordinates,
+            verbose_cut_headers: self.verbose_cut_headers,
+            precision: self.precision,
+            tool_probe: self.tool_probe,
+            minimal_spindle_commands: self.minimal_spindle_commands,
+            flavor: self.flavor,
+            feed_mode: self.feed_mode,
+            comment_style: self.comment_style,
+            dwell_format: self.dwell_format,
+            meta: self.meta.clone(),
+            units: self.units,
+            contexts: self
+                .tools()
+                .iter()
+                .filter_map(|tool| contexts.get(tool).cloned())
+                .collect(),
+            tool_ordering: self.tool_ordering.borrow().clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Program {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let program = SerializedProgram::deserialize(deserializer)?;
+
+        Ok(Self {
+            z_safe: program.z_safe,
+            z_tool_change: program.z_tool_change,
+            rapid_rate: program.rapid_rate,
+            feed_rate_scale: program.feed_rate_scale,
+            spindle_speed_scale: program.spindle_speed_scale,
+            spindle_spinup: program.spindle_spinup,
+            dry_run: program.dry_run,
+            tool_length_offsets: program.tool_length_offsets,
+            park: program.park,
+            tool_change_position: program.tool_change_position,
+            spindle_off_dwell: program.spindle_off_dwell,
+            stock_top: program.stock_top,
+            omit_unchanged_coordinates: program.omit_unchanged_coordinates,
+            verbose_cut_headers: program.verbose_cut_headers,
+            precision: program.precision,
+            tool_probe: program.tool_probe,
+            minimal_spindle_commands: program.minimal_spindle_commands,
+            flavor: program.flavor,
+            feed_mode: program.feed_mode,
+            comment_style: program.comment_style,
+            dwell_format: program.dwell_format,
+            meta: program.meta,
+            units: program.units,
+            contexts: Rc::new(RefCell::new(
+                program
+                    .contexts
+                    .into_iter()
+                    .map(|context| (context.tool, context))
+                    .collect(),
+            )),
+            tool_ordering: Rc::new(RefCell::new(program.tool_ordering)),
+        })
+    }
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Self {
+            z_safe: DEFAULT_Z_SAFE,
+            z_tool_change: DEFAULT_Z_TOOL_CHANGE,
+            rapid_rate: default_rapid_rate(Units::default()),
+            feed_rate_scale: 1.0,
+            spindle_speed_scale: 1.0,
+            spindle_spinup: None,
+            dry_run: false,
+            tool_length_offsets: false,
+            park: None,
+            tool_change_position: None,
+            spindle_off_dwell: None,
+            stock_top: None,
+            omit_unchanged_coordinates: false,
+            verbose_cut_headers: false,
+            precision: None,
+            tool_probe: None,
+            minimal_spindle_commands: false,
+            flavor: Flavor::default(),
+            feed_mode: FeedMode::default(),
+            comment_style: None,
+            dwell_format: None,
+            meta: ProgramMeta::default(),
+            units: Units::default(),
+            contexts: Rc::new(RefCell::new(HashMap::new())),
+            tool_ordering: Rc::new(RefCell::new(ToolOrdering::default())),
+        }
+    }
+}
+
+/// A builder for [Program], as an alternative to calling [Program::new] followed by the
+/// setters. Settings that are not given use the same values as [Program::default].
+///
+/// Example:
+/// ```
+/// use anyhow::Result;
+/// use cnccoder::prelude::*;
+///
+/// fn main() -> Result<()> {
+///     let mut program = Program::builder()
+///         .units(Units::Metric)
+///         .z_safe(10.0)
+///         .z_tool_change(50.0)
+///         .name("planing")
+///         .description("Plane a 100 x 100 mm area")
+///         .flavor(Flavor::Grbl)
+///         .build();
+///
+///     let tool = Tool::cylindrical(
+///         Units::Metric,
+///         20.0,
+///         10.0,
+///         Direction::Clockwise,
+///         5000.0,
+///         500.0,
+///     );
+///
+///     let mut context = program.context(tool);
+///
+///     context.append_cut(Cut::plane(
+///         Vector3::new(0.0, 0.0, 3.0),
+///         Vector2::new(100.0, 100.0),
+///         0.0,
+///         1.0,
+///     ));
+///
+///     assert_eq!(program.name(), "planing");
+///     assert_eq!(program.z_safe(), 10.0);
+///
+///     let gcode = program.to_gcode()?;
+///     assert!(gcode.contains("Plane a 100 x 100 mm area"));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProgramBuilder {
+    units: Units,
+    z_safe: f64,
+    z_tool_change: f64,
+    name: Option<String>,
+    description: Vec<String>,
+    flavor: Flavor,
+}
+
+impl ProgramBuilder {
+    /// Creates a new `ProgramBuilder` with default settings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            units: Units::default(),
+            z_safe: DEFAULT_Z_SAFE,
+            z_tool_change: DEFAULT_Z_TOOL_CHANGE,
+            name: None,
+            description: Vec::new(),
+            flavor: Flavor::default(),
+        }
+    }
+
+    /// Sets the units of the program.
+    #[must_use]
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Sets the safe z height where the tool can travel freely.
+    #[must_use]
+    pub fn z_safe(mut self, z_safe: f64) -> Self {
+        self.z_safe = z_safe;
+        self
+    }
+
+    /// Sets the z height to move to for tool changes.
+    #[must_use]
+    pub fn z_tool_change(mut self, z_tool_change: f64) -> Self {
+        self.z_tool_change = z_tool_change;
+        self
+    }
+
+    /// Sets the name of the program, defaults to a random name.
+    #[must_use]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Adds a line to the program description, can be called several times.
+    #[must_use]
+    pub fn description(mut self, description: &str) -> Self {
+        self.description.push(description.into());
+        self
+    }
+
+    /// Sets the G-code flavor of the program.
+    #[must_use]
+    pub fn flavor(mut self, flavor: Flavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+
+    /// Builds the `Program`.
+    #[must_use]
+    pub fn build(self) -> Program {
+        let mut program = Program::new(self.units, self.z_safe, self.z_tool_change);
+
+        if let Some(name) = &self.name {
+            program.set_name(name);
+        }
+
+        for description in &self.description {
+            program.add_description(description);
+        }
+
+        program.set_flavor(self.flavor);
+
+        program
+    }
+}
+
+impl Default for ProgramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask_non_pure_comments(gcode: &str) -> String {
+        let pattern =
+            regex::Regex::new(r"(Created\s+on|Created\s+by|Generator):\s*[^\)]+").unwrap();
+        let gcode = pattern.replace_all(gcode, "$1: MASKED");
+
+        gcode.to_string()
+    }
+
+    #[test]
+    fn test_program_new() {
+        let program = Program::new(Units::Metric, 10.0, 50.0);
+        assert_eq!(program.z_safe, 10.0);
+        assert_eq!(program.z_tool_change, 50.0);
+    }
+
+    #[test]
+    fn test_program_empty() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_name("empty");
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::drill(Vector3::default(), -1.0));
+
+        assert_eq!(program.tools().len(), 1);
+
+        let mut instructions = program.to_instructions()?;
+
+        for i in instructions.iter_mut() {
+            if let Instruction::Comment(comment) = i {
+                comment.text = mask_non_pure_comments(&comment.text);
+            }
+        }
+
+        assert_eq!(instructions, vec![
+            Instruction::Comment(Comment { text: "Name: empty".into() }),
+            Instruction::Comment(Comment { text: "Created on: MASKED".into()  }),
+            Instruction::Comment(Comment { text: "Created by: MASKED".into()  }),
+            Instruction::Comment(Comment { text: "Generator: MASKED" .into() }),
+            Instruction::Comment(Comment { text: "Workarea: size_x = 4 mm, size_y = 4 mm, size_z = 1 mm, min_x = -2 mm, min_y = -2 mm, max_z = 0 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
+            Instruction::Empty(Empty {}),
+            Instruction::G17(G17 {}),
+            Instruction::G94(G94 {}),
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment { text: "Tool change: type = Cylindrical, diameter = 4 mm, length = 50 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400 mm/min".to_string() }),
+            Instruction::G21(G21 {}),
+            Instruction::G0(G0 { x: None, y: None, z: Some(50.0) }),
+            Instruction::M5(M5 {}),
+            Instruction::M6(M6 { t: 1 }),
+            Instruction::S(S { x: 5_000.0 }),
+            Instruction::M3(M3 {}),
+            Instruction::G4(G4 { p: Duration::from_secs(4) }),
+            Instruction::Empty(Empty {}),
+            Instruction::Comment(Comment { text: "Drill hole at: x = 0, y = 0".to_string() }),
+            Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
+            Instruction::G0(G0 { x: Some(0.0), y: Some(0.0), z: None }),
+            Instruction::G1(G1 { x: None, y: None, z: Some(-1.0), f: Some(400.0) }),
+            Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
+            Instruction::G0(G0 { x: None, y: None, z: Some(50.0) }),
+            Instruction::Empty(Empty {}),
+            Instruction::M2(M2 {}),
+        ]);
+
+        let mut other_program = Program::new_empty_from(&program);
+        other_program.set_name("empty2");
+
+        assert_eq!(other_program.z_safe, 10.0);
+        assert_eq!(other_program.z_tool_change, 50.0);
+        assert_eq!(other_program.tools().len(), 0);
+
+        let mut instructions = other_program.to_instructions()?;
+
+        for i in instructions.iter_mut() {
+            if let Instruction::Comment(comment) = i {
+                comment.text = mask_non_pure_comments(&comment.text);
+            }
+        }
+
+        assert_eq!(instructions, vec![
+            Instruction::Comment(Comment { text: "Name: empty2".into() }),
+            Instruction::Comment(Comment { text: "Created on: MASKED".into()  }),
+            Instruction::Comment(Comment { text: "Created by: MASKED".into()  }),
+            Instruction::Comment(Comment { text: "Generator: MASKED" .into() }),
+            Instruction::Comment(Comment { text: "Workarea: size_x = 0 mm, size_y = 0 mm, size_z = 0 mm, min_x = 0 mm, min_y = 0 mm, max_z = 0 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
+            Instruction::Empty(Empty {}),
+                Instruction::G17(G17 {}),
+                Instruction::G94(G94 {}),
+                Instruction::G0(G0 {
+                    x: None,
+                    y: None,
+                    z: Some(50.0)
+                }),
+                Instruction::Empty(Empty {}),
+                Instruction::M2(M2 {}),
+            ]
+        );
+
+        Ok(())
     }
 
     #[test]
@@ -987,6 +3299,64 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_program_order_tools_by() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let small = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            2.0,
+            Direction::Clockwise,
+            10_000.0,
+            400.0,
+        );
+        let large = Tool::cylindrical(
+            Units::Imperial,
+            1.0,
+            0.25,
+            Direction::Clockwise,
+            10_000.0,
+            20.0,
+        );
+        let medium = Tool::ballnose(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            400.0,
+        );
+
+        for tool in [small, large, medium] {
+            program
+                .context(tool)
+                .append_cut(Cut::drill(Vector3::ZERO, -1.0));
+        }
+        program.set_tool_ordering(&medium, 1);
+        assert_eq!(program.tools(), vec![medium, small, large]);
+
+        program.order_tools_by(ToolSortKey::DiameterDesc);
+        assert_eq!(program.tools(), vec![large, medium, small]);
+        assert_eq!(program.tool_ordering(&large), Some(1));
+        assert_eq!(program.tool_number(&small), Some(3));
+
+        program.order_tools_by(ToolSortKey::DiameterAsc);
+        assert_eq!(program.tools(), vec![small, medium, large]);
+
+        program.order_tools_by(ToolSortKey::Name);
+        assert_eq!(program.tools(), vec![medium, large, small]);
+
+        // Tools added after sorting are placed last
+        let extra = Tool::default();
+        program
+            .context(extra)
+            .append_cut(Cut::drill(Vector3::ZERO, -1.0));
+        assert_eq!(program.tools(), vec![medium, large, small, extra]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_program_tools() -> Result<()> {
         let mut program = Program::new(Units::Metric, 10.0, 50.0);
@@ -1088,9 +3458,10 @@ mod tests {
             Instruction::Comment(Comment { text: "Created on: MASKED".into()  }),
             Instruction::Comment(Comment { text: "Created by: MASKED".into()  }),
             Instruction::Comment(Comment { text: "Generator: MASKED" .into() }),
-            Instruction::Comment(Comment { text: "Workarea: size_x = 20 mm, size_y = 20 mm, size_z = 3.1 mm, min_x = 0 mm, min_y = 0 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
+            Instruction::Comment(Comment { text: "Workarea: size_x = 35.4 mm, size_y = 34.7 mm, size_z = 3.1 mm, min_x = -2.7 mm, min_y = -2 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
             Instruction::Empty(Empty {}),
             Instruction::G17(G17 {}),
+            Instruction::G94(G94 {}),
             Instruction::Empty(Empty {}),
             Instruction::Comment(Comment { text: "Tool change: type = Cylindrical, diameter = 4 mm, length = 50 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400 mm/min".to_string() }),
             Instruction::G21(G21 {}),
@@ -1111,6 +3482,8 @@ mod tests {
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(-0.1), f: None }),
             Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
@@ -1127,13 +3500,15 @@ mod tests {
             Instruction::Comment(Comment { text: "Cut path at: x = 5, y = 10".to_string() }),
             Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
             Instruction::G0(G0 { x: Some(10.0), y: Some(20.0), z: None }),
-            Instruction::G1(G1 { x: None, y: None, z: Some(3.0), f: Some(400.0) }),
+            Instruction::G1(G1 { x: None, y: None, z: Some(3.0), f: Some(10160.0) }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(3.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(2.0), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(2.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
@@ -1159,9 +3534,10 @@ mod tests {
             Instruction::Comment(Comment { text: "Created on: MASKED".into()  }),
             Instruction::Comment(Comment { text: "Created by: MASKED".into()  }),
             Instruction::Comment(Comment { text: "Generator: MASKED" .into() }),
-            Instruction::Comment(Comment { text: "Workarea: size_x = 20 mm, size_y = 20 mm, size_z = 3.1 mm, min_x = 0 mm, min_y = 0 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
+            Instruction::Comment(Comment { text: "Workarea: size_x = 35.4 mm, size_y = 34.7 mm, size_z = 3.1 mm, min_x = -2.7 mm, min_y = -2 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm".into() }),
             Instruction::Empty(Empty {}),
             Instruction::G17(G17 {}),
+            Instruction::G94(G94 {}),
             Instruction::Empty(Empty {}),
             Instruction::Comment(Comment { text: "Tool change: type = Conical, angle = 45°, diameter = 1\", length = 1.207\", direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400\"/min".to_string() }),
             Instruction::G21(G21 {}),
@@ -1175,13 +3551,15 @@ mod tests {
             Instruction::Comment(Comment { text: "Cut path at: x = 5, y = 10".to_string() }),
             Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
             Instruction::G0(G0 { x: Some(10.0), y: Some(20.0), z: None }),
-            Instruction::G1(G1 { x: None, y: None, z: Some(3.0), f: Some(400.0) }),
+            Instruction::G1(G1 { x: None, y: None, z: Some(3.0), f: Some(10160.0) }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(3.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(2.0), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(2.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
@@ -1205,6 +3583,8 @@ mod tests {
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(-0.1), f: None }),
             Instruction::G0(G0 { x: None, y: None, z: Some(10.0) }),
@@ -1285,9 +3665,10 @@ mod tests {
             Instruction::Comment(Comment { text: "Created on: MASKED".into()  }),
             Instruction::Comment(Comment { text: "Created by: MASKED".into()  }),
             Instruction::Comment(Comment { text: "Generator: MASKED" .into() }),
-            Instruction::Comment(Comment { text: "Workarea: size_x = 20 mm, size_y = 20 mm, size_z = 3.1 mm, min_x = 0 mm, min_y = 0 mm, max_z = 3 mm, z_safe = 5 mm, z_tool_change = 40 mm".into() }),
+            Instruction::Comment(Comment { text: "Workarea: size_x = 35.4 mm, size_y = 34.7 mm, size_z = 3.1 mm, min_x = -2.7 mm, min_y = -2 mm, max_z = 3 mm, z_safe = 5 mm, z_tool_change = 40 mm".into() }),
             Instruction::Empty(Empty {}),
             Instruction::G17(G17 {}),
+            Instruction::G94(G94 {}),
             Instruction::Empty(Empty {}),
             Instruction::Comment(Comment { text: "Tool change: type = Cylindrical, diameter = 4 mm, length = 50 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400 mm/min".to_string() }),
             Instruction::G21(G21 {}),
@@ -1308,12 +3689,13 @@ mod tests {
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(0.0), y: Some(0.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(5.0), y: Some(10.0), z: Some(-0.1), f: None }),
             Instruction::G0(G0 { x: None, y: None, z: Some(5.0) }),
             Instruction::Empty(Empty {}),
             Instruction::Comment(Comment { text: "Cut path at: x = 10, y = 10".to_string() }),
-            Instruction::G0(G0 { x: None, y: None, z: Some(5.0) }),
             Instruction::G0(G0 { x: Some(10.0), y: Some(10.0), z: None }),
             Instruction::G1(G1 { x: None, y: None, z: Some(3.0), f: Some(400.0) }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(10.0), z: Some(3.0), f: None }),
@@ -1322,6 +3704,8 @@ mod tests {
             Instruction::G1(G1 { x: Some(15.0), y: Some(20.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(10.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(15.0), y: Some(20.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(10.0), y: Some(10.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(15.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(10.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(15.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G0(G0 { x: None, y: None, z: Some(5.0) }),
@@ -1338,17 +3722,19 @@ mod tests {
             Instruction::Comment(Comment { text: "Cut path at: x = 5, y = 10".to_string() }),
             Instruction::G0(G0 { x: None, y: None, z: Some(5.0) }),
             Instruction::G0(G0 { x: Some(10.0), y: Some(20.0), z: None }),
-            Instruction::G1(G1 { x: None, y: None, z: Some(3.0), f: Some(400.0) }),
+            Instruction::G1(G1 { x: None, y: None, z: Some(3.0), f: Some(10160.0) }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(3.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(2.0), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(2.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(1.0), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(0.0), f: None }),
+            Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(10.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G1(G1 { x: Some(20.0), y: Some(20.0), z: Some(-0.1), f: None }),
             Instruction::G0(G0 { x: None, y: None, z: Some(5.0) }),
-            Instruction::G0(G0 { x: None, y: None, z: Some(40.0) }),
+            Instruction::G0(G0 { x: None, y: None, z: Some(50.0) }),
             Instruction::Empty(Empty {}),
             Instruction::M2(M2 {}),
         ];
@@ -1364,6 +3750,138 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_merge_programs_with_mismatched_units() {
+        let mut program = Program::new(Units::Metric, 10.0, 40.0);
+        let imperial = Program::new(Units::Imperial, 1.0, 2.0);
+
+        let error = program.merge(&imperial).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<CncError>(),
+            Some(CncError::MismatchedUnits {
+                expected: Units::Metric,
+                actual: Units::Imperial,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_program_to_instructions_error() {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.context(Tool::default()).append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(4.0, 10.0),
+            -1.0,
+            1.0,
+        ));
+
+        assert!(matches!(
+            program.to_instructions().unwrap_err(),
+            CncError::ToolWiderThanFeature {
+                feature: "area",
+                feature_size,
+                tool_diameter,
+                units: Units::Metric,
+            } if feature_size == 4.0 && tool_diameter == 6.0
+        ));
+
+        // Errors without a variant of their own keep their message
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.context(Tool::default()).append_cut(Cut::helix(
+            Vector2::new(0.0, 0.0),
+            5.0,
+            5.0,
+            0.0,
+            -1.0,
+            0,
+            Direction::Clockwise,
+        ));
+
+        let error = program.to_instructions().unwrap_err();
+        assert!(matches!(error, CncError::Other(_)));
+        assert!(error.to_string().starts_with("Unable to cut helix"));
+
+        // The chain of sources of other errors is kept
+        let error = CncError::from(anyhow!("Unable to read file").context("Unable to load job"));
+        assert_eq!(error.to_string(), "Unable to load job");
+        assert_eq!(
+            std::error::Error::source(&error).map(ToString::to_string),
+            Some("Unable to read file".to_string())
+        );
+    }
+
+    #[test]
+    fn test_merge_programs_converting_units() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            400.0,
+        );
+        let moves = |program: &Program| -> Result<Vec<String>> {
+            Ok(program
+                .to_gcode()?
+                .lines()
+                .filter(|line| line.starts_with('G'))
+                .map(str::to_string)
+                .collect())
+        };
+
+        let mut imperial = Program::new(Units::Imperial, 0.5, 2.0);
+        let mut context = imperial.context(tool);
+        context.append_cut(Cut::circle(Vector3::new(1.0, 1.0, 0.0), -0.1, 0.5, 0.04));
+        context.append_cut(Cut::path(
+            Vector3::new(0.0, 0.0, 0.0),
+            vec![Segment::line_with_feed(
+                Vector2::new(0.0, 0.0),
+                Vector2::new(2.0, 0.0),
+                10.0,
+            )],
+            -0.05,
+            0.05,
+        ));
+
+        // The merged tool context keeps the converted heights of the imperial program
+        let mut expected = Program::new(Units::Metric, 12.7, 50.8);
+        let mut context = expected.context(tool);
+        context.append_cut(Cut::circle(
+            Vector3::new(25.4, 25.4, 0.0),
+            -2.54,
+            12.7,
+            1.016,
+        ));
+        context.append_cut(Cut::path(
+            Vector3::new(0.0, 0.0, 0.0),
+            vec![Segment::line_with_feed(
+                Vector2::new(0.0, 0.0),
+                Vector2::new(50.8, 0.0),
+                254.0,
+            )],
+            -1.27,
+            1.27,
+        ));
+
+        let mut program = Program::new(Units::Metric, 10.0, 40.0);
+        assert!(program.merge(&imperial).is_err());
+        program.merge_converting(&imperial)?;
+
+        assert_eq!(program.units(), Units::Metric);
+        assert_eq!(program.z_safe(), 10.0);
+        assert_eq!(program.z_tool_change(), 40.0);
+        assert_eq!(program.layers()?, expected.layers()?);
+
+        // The final lift clears the tool change height of the merged context, as it is above
+        // the one of the program
+        let (moves, expected_moves) = (moves(&program)?, moves(&expected)?);
+        assert_eq!(moves, expected_moves);
+        assert_eq!(moves.last().unwrap(), "G0 Z50.8");
+        assert!(moves.contains(&"G1 X50.8 Y0 Z-1.27 F254".to_string()));
+
+        Ok(())
+    }
+
     #[test]
     fn test_program_to_gcode() -> Result<()> {
         let mut program = Program::new(Units::Imperial, 10.0, 50.0);
@@ -1415,11 +3933,12 @@ mod tests {
             ";(Created on: MASKED)",
             ";(Created by: MASKED)",
             ";(Generator: MASKED)",
-            ";(Workarea: size_x = 20 \", size_y = 20 \", size_z = 3.1 \", min_x = 0 \", min_y = 0 \", max_z = 3 \", z_safe = 10 \", z_tool_change = 50 \")",
+            ";(Workarea: size_x = 20.5787 \", size_y = 20.5787 \", size_z = 3.1 \", min_x = -0.0787 \", min_y = -0.0787 \", max_z = 3 \", z_safe = 10 \", z_tool_change = 50 \")",
             "",
             "G17",
+            "G94",
             "",
-            ";(Tool change: type = Conical, angle = 45°, diameter = 1\", length = 1.207\", direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400\"/min)",
+            ";(Tool change: type = Conical, angle = 45°, diameter = 1\", length = 1.2071\", direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 400\"/min)",
             "G20",
             "G0 Z50",
             "M5",
@@ -1438,6 +3957,8 @@ mod tests {
             "G1 X20 Y20 Z1",
             "G1 X10 Y20 Z1",
             "G1 X20 Y20 Z0",
+            "G1 X10 Y20 Z0",
+            "G1 X20 Y20 Z-0.1",
             "G1 X10 Y20 Z-0.1",
             "G1 X20 Y20 Z-0.1",
             "G0 Z10",
@@ -1454,13 +3975,15 @@ mod tests {
             ";(Cut path at: x = 0, y = 0)",
             "G0 Z10",
             "G0 X0 Y0",
-            "G1 Z3 F400",
+            "G1 Z3 F15.748",
             "G1 X0 Y0 Z3",
             "G1 X5 Y10 Z2",
             "G1 X0 Y0 Z2",
             "G1 X5 Y10 Z1",
             "G1 X0 Y0 Z1",
             "G1 X5 Y10 Z0",
+            "G1 X0 Y0 Z0",
+            "G1 X5 Y10 Z-0.1",
             "G1 X0 Y0 Z-0.1",
             "G1 X5 Y10 Z-0.1",
             "G0 Z10",
@@ -1474,10 +3997,176 @@ mod tests {
         Ok(())
     }
 
+    fn planing_gcode(flavor: Flavor) -> Result<Vec<String>> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_name("planing");
+        program.set_flavor(flavor);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            10.0,
+            Direction::Clockwise,
+            5000.0,
+            500.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append(Operation::Message(Message {
+            text: "Planing".into(),
+        }));
+        context.append_cut(Cut::plane(
+            Vector3::new(0.0, 0.0, 3.0),
+            Vector2::new(100.0, 100.0),
+            0.0,
+            1.0,
+        ));
+
+        // Skip the meta comments with time and user dependent values
+        Ok(program
+            .to_gcode()?
+            .lines()
+            .skip(4)
+            .map(|line| line.to_string())
+            .collect())
+    }
+
     #[test]
-    fn test_program_bounds() -> Result<()> {
+    fn test_program_to_gcode_flavors() -> Result<()> {
+        let grbl = planing_gcode(Flavor::Grbl)?;
+
+        let expected_start = |flavor| {
+            match flavor {
+            Flavor::Grbl => vec![
+                ";(Workarea: size_x = 120 mm, size_y = 120 mm, size_z = 3 mm, min_x = -10 mm, min_y = -10 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm)",
+                "",
+                "G17",
+                "G94",
+                "",
+                ";(Tool change: type = Cylindrical, diameter = 10 mm, length = 20 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 500 mm/min)",
+                "G21",
+                "G0 Z50",
+                "M5",
+                "T1 M6",
+                "S5000",
+                "M3",
+                "G4 P4",
+                "(MSG,Planing)",
+                "",
+                ";(Do planing at: x = -5, y = -5, size = {x: 110, y: 110})",
+            ],
+            Flavor::LinuxCnc | Flavor::Mach3 => vec![
+                "(Workarea: size_x = 120 mm, size_y = 120 mm, size_z = 3 mm, min_x = -10 mm, min_y = -10 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm)",
+                "",
+                "G17",
+                "G94",
+                "",
+                "(Tool change: type = Cylindrical, diameter = 10 mm, length = 20 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 500 mm/min)",
+                "G21",
+                "G0 Z50",
+                "M5",
+                "T1 M6",
+                "S5000",
+                "M3",
+                "G4 P4",
+                "(MSG,Planing)",
+                "",
+                "(Do planing at: x = -5, y = -5, size = {x: 110, y: 110})",
+            ],
+            Flavor::Marlin => vec![
+                "; Workarea: size_x = 120 mm, size_y = 120 mm, size_z = 3 mm, min_x = -10 mm, min_y = -10 mm, max_z = 3 mm, z_safe = 10 mm, z_tool_change = 50 mm",
+                "",
+                "G17",
+                "G94",
+                "",
+                "; Tool change: type = Cylindrical, diameter = 10 mm, length = 20 mm, direction = clockwise, spindle_speed = 5000 rpm, feed_rate = 500 mm/min",
+                "G21",
+                "G0 Z50",
+                "M5",
+                "M0 Change to tool T1",
+                "M3 S5000",
+                "G4 P4000",
+                "M117 Planing",
+                "",
+                "; Do planing at: x = -5, y = -5, size = {x: 110, y: 110}",
+            ],
+        }
+        };
+
+        let expected_end = |flavor| match flavor {
+            Flavor::Mach3 => vec!["G0 Z10", "G0 Z50", "", "M30"],
+            _ => vec!["G0 Z10", "G0 Z50", "", "M2"],
+        };
+
+        for flavor in [
+            Flavor::Grbl,
+            Flavor::LinuxCnc,
+            Flavor::Mach3,
+            Flavor::Marlin,
+        ] {
+            let gcode = planing_gcode(flavor)?;
+            let start = expected_start(flavor);
+            let end = expected_end(flavor);
+
+            assert_eq!(gcode[..start.len()], start, "{flavor}");
+            assert_eq!(gcode[gcode.len() - end.len()..], end, "{flavor}");
+
+            // The moves are the same for all flavors
+            let grbl_start = expected_start(Flavor::Grbl).len();
+            assert_eq!(
+                gcode[start.len()..gcode.len() - end.len()],
+                grbl[grbl_start..grbl.len() - end.len()],
+                "{flavor}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_comment_style() -> Result<()> {
         let mut program = Program::new(Units::Metric, 10.0, 50.0);
-        program.set_name("program bounds");
+
+        let mut context = program.context(Tool::default());
+        context.append(Operation::Comment(Comment {
+            text: "A comment".into(),
+        }));
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.lines().any(|line| line == ";(A comment)"));
+
+        for (style, expected) in [
+            (CommentStyle::SemicolonParentheses, ";(A comment)"),
+            (CommentStyle::Parentheses, "(A comment)"),
+            (CommentStyle::Semicolon, "; A comment"),
+        ] {
+            program.set_comment_style(style);
+            let gcode = program.to_gcode()?;
+            assert!(gcode.lines().any(|line| line == expected), "{style:?}");
+            assert_eq!(
+                gcode
+                    .lines()
+                    .filter(|line| line.contains("A comment"))
+                    .count(),
+                1
+            );
+        }
+
+        // The comment style overrides the flavor default
+        program.set_flavor(Flavor::Marlin);
+        program.set_comment_style(CommentStyle::Parentheses);
+        assert!(program
+            .to_gcode()?
+            .lines()
+            .any(|line| line == "(A comment)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_dwell_format() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_spindle_spinup(Duration::from_millis(500));
 
         let tool = Tool::cylindrical(
             Units::Metric,
@@ -1489,36 +4178,2174 @@ mod tests {
         );
 
         let mut context = program.context(tool);
+        context.append_cut(Cut::circle(Vector3::new(0.0, 0.0, 0.0), -1.0, 10.0, 1.0));
 
-        context.append_cut(Cut::path(
+        let dwell = |program: &Program| -> Result<Vec<String>> {
+            Ok(program
+                .to_gcode()?
+                .lines()
+                .filter(|line| line.starts_with("G4"))
+                .map(str::to_string)
+                .collect())
+        };
+
+        assert_eq!(program.dwell_format(), DwellFormat::Seconds);
+        assert_eq!(dwell(&program)?, vec!["G4 P0.5"]);
+
+        program.set_dwell_format(DwellFormat::Milliseconds);
+        assert_eq!(dwell(&program)?, vec!["G4 P500"]);
+
+        program.set_dwell_format(DwellFormat::SecondsWord);
+        assert_eq!(dwell(&program)?, vec!["G4 S0.5"]);
+
+        // Marlin reads the P word in milliseconds unless overridden
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_flavor(Flavor::Marlin);
+        assert_eq!(program.dwell_format(), DwellFormat::Milliseconds);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_json_round_trip() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_name("json");
+        program.set_flavor(Flavor::LinuxCnc);
+        program.set_feed_rate_scale(0.8);
+
+        let tool1 = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+        let tool2 = Tool::ballnose(
+            Units::Metric,
+            20.0,
+            2.0,
+            Direction::Counterclockwise,
+            10_000.0,
+            200.0,
+        );
+
+        let mut tool1_context = program.context(tool1);
+        tool1_context.append_cut(Cut::plane(
             Vector3::new(0.0, 0.0, 3.0),
-            vec![Segment::line(
-                Vector2::default(),
-                Vector2::new(-28.0, -30.0),
-            )],
-            -0.1,
+            Vector2::new(40.0, 40.0),
+            0.0,
+            1.0,
+        ));
+        tool1_context.append(Operation::Comment(Comment {
+            text: "Holes".into(),
+        }));
+        tool1_context.append_cut(Cut::circle_inner(
+            Vector3::new(20.0, 20.0, 0.0),
+            -5.0,
+            6.0,
             1.0,
         ));
 
-        context.append_cut(Cut::path(
-            Vector3::new(0.0, 0.0, 3.0),
+        let mut tool2_context = program.context(tool2);
+        tool2_context.append_cut(Cut::path(
+            Vector3::new(5.0, 5.0, 0.0),
             vec![
-                Segment::line(Vector2::new(23.0, 12.0), Vector2::new(5.0, 10.0)),
-                Segment::line(Vector2::new(5.0, 10.0), Vector2::new(67.0, 102.0)),
-                Segment::line(Vector2::new(67.0, 102.0), Vector2::new(23.0, 12.0)),
+                Segment::line(Vector2::new(5.0, 5.0), Vector2::new(15.0, 5.0)),
+                Segment::arc(
+                    Vector2::new(15.0, 5.0),
+                    Vector2::new(15.0, 15.0),
+                    Vector2::new(15.0, 10.0),
+                    Direction::Counterclockwise,
+                ),
             ],
-            -0.1,
-            1.0,
+            -1.0,
+            0.5,
+        ));
+        tool2_context.append(Operation::Message(Message {
+            text: "Done".into(),
+        }));
+        tool2_context.append_cut(Cut::line(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 10.0, -1.0),
         ));
+        program.set_tool_ordering(&tool2, 1);
 
-        let bounds = program.bounds();
+        let json = program.to_json()?;
+        let restored = Program::from_json(&json)?;
+
+        assert_eq!(restored.tools(), program.tools());
+        assert_eq!(restored.to_gcode()?, program.to_gcode()?);
+        assert_eq!(restored.to_json()?, json);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_append_raw() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
 
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+        context.append_raw("G38.2 Z-10 F100");
+        context.append_raw("G38.2 Z-10 F100");
+        context.append_cut(Cut::drill(Vector3::new(10.0, 0.0, 0.0), -1.0));
+
+        let gcode = program.to_gcode()?;
+        let lines = gcode.lines().collect::<Vec<_>>();
+        let index = lines
+            .iter()
+            .position(|line| *line == "G38.2 Z-10 F100")
+            .unwrap();
+
+        assert_eq!(lines[index + 1], "G38.2 Z-10 F100");
         assert_eq!(
-            bounds,
-            Bounds {
-                min: Vector3::new(-28.0, -30.0, -0.1),
-                max: Vector3::new(67.0, 102.0, 3.0),
+            lines
+                .iter()
+                .filter(|line| **line == "G38.2 Z-10 F100")
+                .count(),
+            2
+        );
+        assert!(lines[..index].contains(&";(Drill hole at: x = 0, y = 0)"));
+        assert!(lines[index..].contains(&";(Drill hole at: x = 10, y = 0)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_append_pause() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+        context.append_pause("Flip the workpiece");
+        context.append_pause("Flip the workpiece");
+        context.append_cut(Cut::drill(Vector3::new(10.0, 0.0, 0.0), -1.0));
+
+        let gcode = program.to_gcode()?;
+        let lines = gcode.lines().collect::<Vec<_>>();
+        let index = lines
+            .iter()
+            .position(|line| *line == "(MSG,Flip the workpiece)")
+            .unwrap();
+
+        assert_eq!(
+            lines[index..index + 4],
+            [
+                "(MSG,Flip the workpiece)",
+                "M0",
+                "(MSG,Flip the workpiece)",
+                "M0"
+            ]
+        );
+        assert!(lines[..index].contains(&";(Drill hole at: x = 0, y = 0)"));
+        assert!(lines[index..].contains(&";(Drill hole at: x = 10, y = 0)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_operations_without_cloning() {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::default();
+        let mut context = program.context(tool);
+        assert!(context.is_empty());
+
+        context.append(Operation::Comment(Comment {
+            text: "Holes".to_string(),
+        }));
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+        context.append_cut(Cut::drill(Vector3::new(10.0, 0.0, 0.0), -1.0));
+
+        assert_eq!(context.len(), 3);
+        assert!(!context.is_empty());
+
+        let mut cuts = 0;
+        context.for_each_operation(|operation| {
+            if matches!(operation, Operation::Cut(_)) {
+                cuts += 1;
+            }
+        });
+        assert_eq!(cuts, 2);
+
+        let contexts = program.contexts.borrow();
+        let inner = &contexts[&tool];
+        assert_eq!(inner.len(), 3);
+        assert_eq!(
+            inner
+                .operations_iter()
+                .filter(|operation| matches!(operation, Operation::Comment(_)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_context_insert_and_move_operations() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::default();
+        let mut context = program.context(tool);
+
+        let comment = |text: &str| {
+            Operation::Comment(Comment {
+                text: text.to_string(),
+            })
+        };
+        let texts = |context: &Context| {
+            let mut texts = vec![];
+            context.for_each_operation(|operation| match operation {
+                Operation::Comment(comment) => texts.push(comment.text.clone()),
+                Operation::Cut(_) => texts.push("cut".to_string()),
+                _ => {}
+            });
+            texts
+        };
+
+        context.append(comment("a"));
+        context.append(comment("b"));
+        context.append(comment("c"));
+
+        context.insert_cut(1, Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0))?;
+        assert_eq!(texts(&context), vec!["a", "cut", "b", "c"]);
+
+        context.insert(4, comment("d"))?;
+        assert_eq!(texts(&context), vec!["a", "cut", "b", "c", "d"]);
+        assert!(context.insert(6, comment("e")).is_err());
+
+        context.move_operation(1, 3)?;
+        assert_eq!(texts(&context), vec!["a", "b", "c", "cut", "d"]);
+        context.move_operation(4, 0)?;
+        assert_eq!(texts(&context), vec!["d", "a", "b", "c", "cut"]);
+        assert!(context.move_operation(0, 5).is_err());
+        assert!(context.move_operation(5, 0).is_err());
+
+        let removed = context.remove_operation(0)?;
+        assert!(matches!(removed, Operation::Comment(comment) if comment.text == "d"));
+        assert_eq!(texts(&context), vec!["a", "b", "c", "cut"]);
+        assert!(context.remove_operation(4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_append_optional_stop() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+        context.append_optional_stop("Inspect the hole");
+        context.append_optional_stop("");
+        context.append_optional_stop("");
+        context.append_cut(Cut::drill(Vector3::new(10.0, 0.0, 0.0), -1.0));
+
+        let gcode = program.to_gcode()?;
+        let lines = gcode.lines().collect::<Vec<_>>();
+        let index = lines
+            .iter()
+            .position(|line| *line == "(MSG,Inspect the hole)")
+            .unwrap();
+
+        assert_eq!(
+            lines[index..index + 4],
+            ["(MSG,Inspect the hole)", "M1", "M1", "M1"]
+        );
+        assert!(!gcode.contains("M0"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_append_comment_and_empty() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_comment("Holes");
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+        context.append_empty();
+        context.append_comment("Repeated");
+        context.append_comment("Repeated");
+        context.append_empty();
+        context.append_empty();
+        context.append_cut(Cut::drill(Vector3::new(10.0, 0.0, 0.0), -1.0));
+
+        assert!(matches!(
+            context.operations()[0],
+            Operation::Comment(Comment { ref text }) if text == "Holes"
+        ));
+
+        let gcode = program.to_gcode()?;
+        let lines = gcode.lines().collect::<Vec<_>>();
+        let index = lines
+            .iter()
+            .position(|line| *line == ";(Repeated)")
+            .unwrap();
+
+        assert!(lines.contains(&";(Holes)"));
+        assert_eq!(
+            lines[index - 2..index + 4],
+            [
+                "G0 Z10",
+                "",
+                ";(Repeated)",
+                ";(Repeated)",
+                "",
+                ";(Drill hole at: x = 10, y = 0)"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_append_rectangle_pocket() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::default();
+        let mut context = program.context(tool);
+        context.append_rectangle_pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(40.0, 20.0),
+            -3.0,
+            1.0,
+            0.5,
+        )?;
+
+        let operations = program.contexts.borrow()[&tool].operations();
+        assert_eq!(operations.len(), 2);
+
+        match &operations[0] {
+            Operation::Cut(Cut::Area(area)) => {
+                assert_eq!(area.start, Vector3::new(0.5, 0.5, 0.0));
+                assert_eq!(area.size, Vector2::new(39.0, 19.0));
+                assert_eq!(area.end_z, -3.0);
+            }
+            operation => panic!("Expected pocket cut, got {:?}", operation),
+        }
+
+        match &operations[1] {
+            Operation::Cut(Cut::Frame(frame)) => {
+                assert_eq!(frame.start, Vector3::new(0.0, 0.0, 0.0));
+                assert_eq!(frame.size, Vector2::new(40.0, 20.0));
+                assert_eq!(frame.end_z, -3.0);
+                assert_eq!(frame.max_step_z, 3.0);
+                assert!(matches!(frame.compensation, ToolPathCompensation::Inner));
             }
+            operation => panic!("Expected frame cut, got {:?}", operation),
+        }
+
+        program.to_gcode()?;
+
+        // The finish on both sides must leave room for the pocket
+        let error = program
+            .context(tool)
+            .append_rectangle_pocket(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector2::new(40.0, 20.0),
+                -3.0,
+                1.0,
+                10.0,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::FinishTooLarge { finish, size }
+                if finish == 10.0 && size == Vector2::new(40.0, 20.0)
+        ));
+
+        // A pocket without depth has nothing to cut
+        program.context(tool).append_rectangle_pocket(
+            Vector3::new(0.0, 0.0, -3.0),
+            Vector2::new(40.0, 20.0),
+            -3.0,
+            1.0,
+            0.5,
+        )?;
+        assert_eq!(program.contexts.borrow()[&tool].operations().len(), 2);
+        program.to_gcode()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_context_for() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let tool = Tool::default();
+
+        let error = program.context_for(tool).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Program has no context for tool"));
+        assert!(program.tools().is_empty());
+
+        program.context(tool);
+
+        let mut context = program.context_for(tool)?;
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+        assert_eq!(context.operations().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_builder() {
+        let program = Program::builder()
+            .units(Units::Imperial)
+            .z_safe(0.5)
+            .z_tool_change(2.0)
+            .name("builder")
+            .description("First line")
+            .description("Second line")
+            .flavor(Flavor::LinuxCnc)
+            .build();
+
+        assert_eq!(program.units(), Units::Imperial);
+        assert_eq!(program.z_safe(), 0.5);
+        assert_eq!(program.z_tool_change(), 2.0);
+        assert_eq!(program.name(), "builder");
+        assert_eq!(program.description(), &["First line", "Second line"]);
+        assert_eq!(program.flavor(), Flavor::LinuxCnc);
+
+        let program = ProgramBuilder::default().build();
+        let default = Program::default();
+        assert_eq!(program.units(), default.units());
+        assert_eq!(program.z_safe(), default.z_safe());
+        assert_eq!(program.z_tool_change(), default.z_tool_change());
+        assert_eq!(program.rapid_rate(), default.rapid_rate());
+        assert_eq!(program.flavor(), default.flavor());
+    }
+
+    #[test]
+    fn test_program_bounds_per_tool() {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let flat = Tool::preset_6mm_flat();
+        let mut flat_context = program.context(flat);
+        flat_context.append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 10.0),
+            -2.0,
+            1.0,
+        ));
+
+        let vbit = Tool::preset_90deg_vbit();
+        let mut vbit_context = program.context(vbit);
+        vbit_context.append_cut(Cut::line(
+            Vector3::new(30.0, 5.0, 0.0),
+            Vector3::new(50.0, 5.0, -1.0),
+        ));
+
+        let bounds = program.bounds_per_tool();
+        assert_eq!(bounds.len(), 2);
+
+        // The pocket is cut with inner compensation, so the tool sweeps the pocket itself
+        assert_eq!(bounds[0].0, flat);
+        assert_eq!(bounds[0].1.min, Vector3::new(0.0, 0.0, -2.0));
+        assert_eq!(bounds[0].1.max, Vector3::new(20.0, 10.0, 0.0));
+
+        assert_eq!(bounds[1].0, vbit);
+        assert_eq!(bounds[1].1.min, Vector3::new(24.0, -1.0, -1.0));
+        assert_eq!(bounds[1].1.max, Vector3::new(56.0, 11.0, 0.0));
+
+        assert_eq!(
+            bounds[0].1.union(&bounds[1].1),
+            program.bounds(),
+            "the program bounds cover the bounds of all tools"
+        );
+    }
+
+    #[test]
+    fn test_program_stock_top() -> Result<()> {
+        let mut relative = Program::new(Units::Metric, 10.0, 50.0);
+        relative.set_name("stock top");
+
+        // Relative cuts can not be placed before the stock top is known
+        let cut = Cut::pocket_depth(Vector2::new(0.0, 0.0), Vector2::new(20.0, 20.0), 2.0, 1.0);
+        assert!(relative
+            .context(Tool::default())
+            .append_relative_cut(cut)
+            .is_err());
+        assert!(relative.context(Tool::default()).is_empty());
+
+        relative.set_stock_top(5.0);
+        assert_eq!(relative.stock_top(), Some(5.0));
+
+        let mut context = relative.context(Tool::default());
+        context.append_relative_cut(Cut::pocket_depth(
+            Vector2::new(0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            2.0,
+            1.0,
+        ))?;
+
+        let mut absolute = Program::new(Units::Metric, 10.0, 50.0);
+        absolute.set_name("stock top");
+        let mut context = absolute.context(Tool::default());
+        context.append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 5.0),
+            Vector2::new(20.0, 20.0),
+            3.0,
+            1.0,
+        ));
+
+        assert_eq!(relative.bounds(), absolute.bounds());
+        assert_eq!(
+            mask_non_pure_comments(&relative.to_gcode()?),
+            mask_non_pure_comments(&absolute.to_gcode()?)
+        );
+
+        // z_safe must be above the stock top, even when the cuts are below it
+        relative.set_stock_top(12.0);
+        assert!(matches!(
+            relative.to_gcode(),
+            Err(CncError::ZSafeBelowWorkpiece { tool: None, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_trims_repeated_retracts() -> Result<()> {
+        let mut context = InnerContext::new(Units::Metric, &Tool::default(), 5.0, 50.0);
+        let first = Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+        );
+        let second = Cut::pocket(
+            Vector3::new(30.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+        );
+
+        let is_retract = |instruction: &Instruction| {
+            *instruction
+                == Instruction::G0(G0 {
+                    x: None,
+                    y: None,
+                    z: Some(5.0),
+                })
+        };
+        let retracts = |instructions: Vec<Instruction>| {
+            instructions
+                .iter()
+                .filter(|instruction| is_retract(instruction))
+                .count()
+        };
+
+        let separate = retracts(first.to_instructions(&context)?)
+            + retracts(second.to_instructions(&context)?);
+
+        context.append(Operation::Cut(first));
+        context.append(Operation::Comment(Comment {
+            text: "Second pocket".to_string(),
+        }));
+        context.append(Operation::Cut(second));
+        let instructions = context.to_instructions()?;
+        assert_eq!(retracts(instructions.clone()), separate - 1);
+
+        // The retract is kept before the rapid move to the second pocket
+        let rapid = instructions
+            .iter()
+            .position(|instruction| {
+                *instruction
+                    == Instruction::G0(G0 {
+                        x: Some(33.0),
+                        y: Some(3.0),
+                        z: None,
+                    })
+            })
+            .unwrap();
+        assert!(instructions[..rapid]
+            .iter()
+            .rev()
+            .find(|instruction| !matches!(
+                instruction,
+                Instruction::Empty(_) | Instruction::Comment(_)
+            ))
+            .is_some_and(is_retract));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_minimal_spindle_commands() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        for diameter in [2.0, 4.0] {
+            let tool = Tool::cylindrical(
+                Units::Metric,
+                20.0,
+                diameter,
+                Direction::Clockwise,
+                12_000.0,
+                400.0,
+            );
+            program
+                .context(tool)
+                .append_cut(Cut::drill(Vector3::ZERO, -1.0));
+        }
+
+        assert!(!program.minimal_spindle_commands());
+        assert_eq!(program.to_gcode()?.matches("\nS12000\n").count(), 2);
+
+        program.set_minimal_spindle_commands(true);
+        let gcode = program.to_gcode()?;
+        assert_eq!(gcode.matches("\nS12000\n").count(), 1);
+        assert_eq!(gcode.matches("\nM3\n").count(), 2);
+
+        // Marlin sets the speed with each M3, also when the S is left out
+        program.set_flavor(Flavor::Marlin);
+        assert_eq!(program.to_gcode()?.matches("\nM3 S12000\n").count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_tool_change_position() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program
+            .context(Tool::default())
+            .append_cut(Cut::drill(Vector3::new(20.0, 20.0, 0.0), -1.0));
+
+        assert_eq!(program.tool_change_position(), None);
+        assert!(program.to_gcode()?.contains("\nG0 Z50\nM5\nT1 M6\n"));
+
+        program.set_tool_change_position(Some(Vector2::new(0.0, -10.0)));
+        assert_eq!(
+            program.tool_change_position(),
+            Some(Vector2::new(0.0, -10.0))
+        );
+        assert!(program
+            .to_gcode()?
+            .contains("\nG0 Z50\nG0 X0 Y-10\nM5\nT1 M6\n"));
+
+        let program = Program::from_json(&program.to_json()?)?;
+        assert_eq!(
+            program.tool_change_position(),
+            Some(Vector2::new(0.0, -10.0))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_tool_probing() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let tool1 = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            400.0,
+        );
+        let tool2 = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            2.0,
+            Direction::Clockwise,
+            10_000.0,
+            400.0,
+        );
+        program
+            .context(tool1)
+            .append_cut(Cut::drill(Vector3::ZERO, -1.0));
+        program
+            .context(tool2)
+            .append_cut(Cut::drill(Vector3::ZERO, -1.0));
+
+        assert_eq!(program.tool_probe(), None);
+        assert!(!program.to_gcode()?.contains("G38.2"));
+
+        program.enable_tool_probing(Vector3::new(-20.0, 5.0, 2.0), 100.0);
+        let gcode = program.to_gcode()?;
+        assert_eq!(gcode.matches("G38.2 Z-46 F100").count(), 2);
+        assert_eq!(gcode.matches("G10 L20 P0 Z2").count(), 2);
+
+        // The probing follows each tool change, before the spindle is started
+        let instructions = program.to_instructions()?;
+        for (index, instruction) in instructions.iter().enumerate() {
+            if let Instruction::M6(_) = instruction {
+                assert_eq!(
+                    instructions[index + 2],
+                    Instruction::G0(G0 {
+                        x: Some(-20.0),
+                        y: Some(5.0),
+                        z: None,
+                    })
+                );
+                assert!(matches!(instructions[index + 3], Instruction::G38_2(_)));
+                assert!(matches!(instructions[index + 6], Instruction::S(_)));
+            }
+        }
+
+        program.set_dry_run(true);
+        assert!(!program.to_gcode()?.contains("G38.2"));
+        program.set_dry_run(false);
+
+        program.enable_tool_probing(Vector3::new(-20.0, 5.0, 60.0), 100.0);
+        assert!(matches!(
+            program.to_gcode(),
+            Err(CncError::ProbeAboveToolChange { .. })
+        ));
+
+        program.enable_tool_probing(Vector3::new(-20.0, 5.0, 2.0), 0.0);
+        assert!(matches!(
+            program.validate(),
+            Err(CncError::InvalidProbeFeedRate { .. })
+        ));
+
+        program.disable_tool_probing();
+        assert!(!program.to_gcode()?.contains("G38.2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_omit_unchanged_coordinates() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::line(
+            Vector3::new(0.0, 0.0, -0.0001),
+            Vector3::new(20.0, 0.0, -0.0001),
+        ));
+        context.append_cut(Cut::line(
+            Vector3::new(20.0, 0.0, -1.0),
+            Vector3::new(20.0, 10.0, -1.0),
+        ));
+
+        let moves = |program: &Program| -> Result<Vec<String>> {
+            Ok(program
+                .to_gcode_minified()?
+                .lines()
+                .skip_while(|line| !line.starts_with("T1"))
+                .filter(|line| line.starts_with("G0") || line.starts_with("G1"))
+                .map(String::from)
+                .collect())
+        };
+
+        // Depths that round to zero are written as Z0 and not Z-0
+        assert_eq!(
+            moves(&program)?,
+            vec![
+                "G0 Z10",
+                "G0 X0 Y0",
+                "G1 Z0 F500",
+                "G1 X20 Y0 Z0",
+                "G0 Z10",
+                "G0 X20 Y0",
+                "G1 Z-1 F500",
+                "G1 X20 Y10 Z-1",
+                "G0 Z10",
+                "G0 Z50",
+            ]
+        );
+
+        // The position is unknown after the tool change, so the first words are kept
+        program.set_omit_unchanged_coordinates(true);
+        assert_eq!(
+            moves(&program)?,
+            vec![
+                "G0 Z10",
+                "G0 X0 Y0",
+                "G1 Z0 F500",
+                "G1 X20",
+                "G0 Z10",
+                "G1 Z-1 F500",
+                "G1 Y10",
+                "G0 Z10",
+                "G0 Z50",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_verbose_cut_headers() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10000.0,
+            400.0,
+        );
+        let mut context = program.context(tool);
+        context.append_cut(Cut::circle(Vector3::new(20.0, 20.0, 0.0), -3.0, 10.0, 1.0));
+        context.append_comment("Done");
+
+        assert!(!program.verbose_cut_headers());
+        assert!(!program.to_gcode()?.contains("Cut details"));
+
+        program.set_verbose_cut_headers(true);
+        let gcode = program.to_gcode()?;
+        let lines = gcode
+            .lines()
+            .skip_while(|line| !line.starts_with(";(Cut hole"))
+            .take(3)
+            .collect::<Vec<_>>();
+
+        // Three helix revolutions followed by a flat pass at the end depth
+        assert_eq!(
+            lines[1],
+            ";(Cut details: start_z = 0 mm, end_z = -3 mm, max_step_z = 1 mm, passes = 4)"
+        );
+        assert!(lines[2].starts_with(
+            ";(Cut details: tool_diameter = 4 mm, feed_rate = 400 mm/min, estimated_time = "
+        ));
+
+        // Other operations have no header
+        assert_eq!(gcode.matches("Cut details").count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_precision() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::line(
+            Vector3::new(0.0, 0.0, -0.12345),
+            Vector3::new(20.123456, 0.0, -0.12345),
+        ));
+
+        let line = |program: &Program| -> Result<String> {
+            Ok(program
+                .to_gcode()?
+                .lines()
+                .find(|line| line.starts_with("G1 X"))
+                .unwrap_or_default()
+                .to_string())
+        };
+
+        assert_eq!(program.precision(), 3);
+        assert_eq!(line(&program)?, "G1 X20.123 Y0 Z-0.123");
+
+        program.set_precision(2);
+        assert_eq!(line(&program)?, "G1 X20.12 Y0 Z-0.12");
+
+        program.set_precision(4);
+        assert_eq!(line(&program)?, "G1 X20.1235 Y0 Z-0.1235");
+
+        // The precision is only used while writing the program, and is kept in the JSON
+        assert_eq!(round_precision(0.12345), 0.123);
+        assert_eq!(Program::from_json(&program.to_json()?)?.precision(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_imperial_precision() -> Result<()> {
+        let mut program = Program::new(Units::Imperial, 0.5, 2.0);
+        assert_eq!(program.precision(), 4);
+
+        // A 1/8" metric tool cutting a 1/4" wide slot, 1/16" deep
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            3.175,
+            Direction::Clockwise,
+            18000.0,
+            500.0,
+        );
+        let mut context = program.context(tool);
+        context.append_cut(Cut::frame_inner(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(0.25, 1.0),
+            -0.0625,
+            0.0625,
+        ));
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.contains("G0 X0.0625 Y0.0625"), "{}", gcode);
+        assert!(gcode.contains("G1 X0.1875 Z-0.0625"), "{}", gcode);
+        assert!(!gcode.contains("0.188"), "{}", gcode);
+
+        program.set_precision(3);
+        assert!(program.to_gcode()?.contains("G1 X0.188 Z-0.063"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_feed_mode() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let mut context = program.context(Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            5000.0,
+            400.0,
+        ));
+        context.append_cut(Cut::line(
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(20.0, 0.0, -1.0),
+        ));
+
+        let lines = |program: &Program| -> Result<Vec<String>> {
+            Ok(program
+                .to_gcode_minified()?
+                .lines()
+                .filter(|line| line.starts_with("G9") || line.starts_with("G1 "))
+                .map(String::from)
+                .collect())
+        };
+
+        assert_eq!(program.feed_mode(), FeedMode::UnitsPerMinute);
+        assert_eq!(
+            lines(&program)?,
+            vec!["G94", "G1 Z-1 F400", "G1 X20 Y0 Z-1"]
+        );
+
+        // The 11 mm plunge from z = 10 takes 1/36.364 minutes and the 20 mm line 1/20 minutes
+        program.set_feed_mode(FeedMode::InverseTime);
+        assert_eq!(
+            lines(&program)?,
+            vec!["G93", "G1 Z-1 F36.364", "G1 X20 Y0 Z-1 F20"]
+        );
+
+        program.set_feed_mode(FeedMode::UnitsPerMinute);
+        let duration = program.estimated_duration()?;
+        program.set_feed_mode(FeedMode::InverseTime);
+        assert_eq!(program.estimated_duration()?, duration);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_to_gcode_minified() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.add_description("Minified");
+
+        let mut context = program.context(Tool::default());
+        context.append_comment("A comment");
+        context.append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+        ));
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.contains(';'));
+        assert!(gcode.lines().any(|line| line.is_empty()));
+
+        let minified = program.to_gcode_minified()?;
+        assert!(!minified.contains(';'));
+        assert!(minified.lines().all(|line| !line.trim().is_empty()));
+        assert_eq!(
+            minified.lines().collect::<Vec<_>>(),
+            gcode
+                .lines()
+                .filter(|line| !line.is_empty() && !line.starts_with(';'))
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_overlapping_cuts() {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::default();
+        let mut context = program.context(tool);
+        context.append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+        ));
+        context.append_comment("Separate pocket");
+        context.append_cut(Cut::pocket(
+            Vector3::new(20.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+        ));
+        context.append_cut(Cut::pocket(
+            Vector3::new(10.0, 10.0, -1.0),
+            Vector2::new(20.0, 20.0),
+            -3.0,
+            1.0,
+        ));
+
+        // Cuts with other tools are not compared with the cuts above
+        let mut other_context = program.context(Tool::preset_6mm_flat());
+        other_context.append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(20.0, 20.0),
+            -2.0,
+            1.0,
+        ));
+
+        // The pockets that only touch at x = 20 are not reported
+        assert_eq!(program.overlapping_cuts(), vec![(0, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn test_program_layers() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let segments = vec![Segment::line(
+            Vector2::new(0.0, 0.0),
+            Vector2::new(20.0, 0.0),
+        )];
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::path(start, segments.clone(), -3.05, 1.0));
+        context.append_cut(Cut::Path(
+            Path::new(start, segments.clone(), -3.05, 1.0).with_depth_mode(DepthMode::Constant),
+        ));
+        context.append_comment("Not a cut");
+        context.append_cut(Cut::pocket(start, Vector2::new(20.0, 20.0), -3.05, 1.0));
+        context.append_cut(Cut::frame(start, Vector2::new(20.0, 20.0), -3.05, 1.0));
+        context.append_cut(Cut::circle(start, -3.05, 10.0, 1.0));
+        context.append_cut(Cut::drill(start, -3.05));
+        context.append_cut(Cut::circular_pocket(start, 10.0, -3.05, 1.0, 1.0));
+        context.append_cut(Cut::bore(start, 10.0, -3.0, 2.0));
+        context.append_cut(Cut::polygon(
+            start,
+            10.0,
+            6,
+            0.0,
+            -3.05,
+            1.0,
+            ToolPathCompensation::Inner,
+        ));
+        context.append_cut(Cut::line(start, Vector3::new(20.0, 0.0, -1.0)));
+
+        let mut other_context = program.context(Tool::preset_6mm_flat());
+        other_context.append_cut(Cut::path(start, segments, -3.0, 1.0));
+
+        assert_eq!(
+            program.layers()?,
+            vec![
+                (0, vec![-1.0, -2.0, -3.0, -3.05, -3.05]),
+                (1, vec![-1.0, -2.0, -3.0, -3.05]),
+                (3, vec![-1.0, -2.0, -3.0, -3.05]),
+                (4, vec![-1.0, -2.0, -3.0, -3.05, -3.05]),
+                (5, vec![-1.0, -2.0, -3.0, -3.05, -3.05]),
+                (6, vec![-3.05]),
+                (7, vec![-1.0, -2.0, -3.0, -3.05]),
+                (8, vec![-1.5, -3.0]),
+                (9, vec![-1.0, -2.0, -3.0, -3.05, -3.05]),
+                (10, vec![]),
+                (11, vec![-1.0, -2.0, -3.0, -3.0]),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_append_cut_array() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::default();
+        let mut context = program.context(tool);
+        context.append_cut_array(
+            Cut::drill(Vector3::new(5.0, 5.0, 0.0), -2.0),
+            2,
+            3,
+            Vector2::new(10.0, 20.0),
+        )?;
+
+        let starts = program.contexts.borrow()[&tool]
+            .operations()
+            .iter()
+            .map(|operation| match operation {
+                Operation::Cut(Cut::Circle(circle)) => circle.start,
+                operation => panic!("Expected drill cut, got {:?}", operation),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![
+                Vector3::new(5.0, 5.0, 0.0),
+                Vector3::new(15.0, 5.0, 0.0),
+                Vector3::new(25.0, 5.0, 0.0),
+                Vector3::new(5.0, 25.0, 0.0),
+                Vector3::new(15.0, 25.0, 0.0),
+                Vector3::new(25.0, 25.0, 0.0),
+            ]
+        );
+
+        let bounds = program.bounds();
+        assert_eq!(bounds.min, Vector3::new(2.0, 2.0, -2.0));
+        assert_eq!(bounds.max, Vector3::new(28.0, 28.0, 0.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_write_gcode_with_progress() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool1 = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+        let tool2 = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            2.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut context = program.context(tool1);
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+        context.append_cut(Cut::drill(Vector3::new(10.0, 0.0, 0.0), -1.0));
+        context.append_cut(Cut::drill(Vector3::new(20.0, 0.0, 0.0), -1.0));
+
+        let mut context = program.context(tool2);
+        context.append_cut(Cut::drill(Vector3::new(0.0, 10.0, 0.0), -1.0));
+
+        let mut gcode = vec![];
+        let mut fractions = vec![];
+        program.write_gcode_with_progress(&mut gcode, |fraction| fractions.push(fraction))?;
+
+        assert_eq!(String::from_utf8(gcode)?, program.to_gcode()?);
+        assert_eq!(fractions, vec![0.25, 0.5, 0.75, 1.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_mirror_x() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::path(
+            Vector3::new(10.0, 5.0, 0.0),
+            vec![
+                Segment::line(Vector2::new(0.0, 0.0), Vector2::new(20.0, 0.0)),
+                Segment::arc(
+                    Vector2::new(20.0, 0.0),
+                    Vector2::new(20.0, 10.0),
+                    Vector2::new(20.0, 5.0),
+                    Direction::Counterclockwise,
+                ),
+            ],
+            -1.0,
+            1.0,
+        ));
+        context.append_cut(Cut::frame(
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector2::new(20.0, 10.0),
+            -1.0,
+            1.0,
+        ));
+
+        program.mirror_x()?;
+
+        let operations = program.context(tool).operations();
+
+        match &operations[0] {
+            Operation::Cut(Cut::Path(path)) => {
+                assert_eq!(path.start, Vector3::new(-10.0, 5.0, 0.0));
+                match &path.segments[0] {
+                    Segment::Line(line) => {
+                        assert_eq!(line.from, Vector2::new(0.0, 0.0));
+                        assert_eq!(line.to, Vector2::new(-20.0, 0.0));
+                    }
+                    segment => panic!("Unexpected segment {:?}", segment),
+                }
+                match &path.segments[1] {
+                    Segment::Arc(arc) => {
+                        assert_eq!(arc.from, Vector2::new(-20.0, 0.0));
+                        assert_eq!(arc.to, Vector2::new(-20.0, 10.0));
+                        assert_eq!(arc.center, Vector2::new(-20.0, 5.0));
+                        assert_eq!(arc.direction, Direction::Clockwise);
+                    }
+                    segment => panic!("Unexpected segment {:?}", segment),
+                }
+            }
+            operation => panic!("Unexpected operation {:?}", operation),
+        }
+
+        match &operations[1] {
+            Operation::Cut(Cut::Frame(frame)) => {
+                assert_eq!(frame.start, Vector3::new(-30.0, 0.0, 0.0));
+                assert_eq!(frame.size, Vector2::new(20.0, 10.0));
+            }
+            operation => panic!("Unexpected operation {:?}", operation),
+        }
+
+        let bounds = program.bounds();
+        assert_eq!(bounds.min, Vector3::new(-32.0, -2.0, -1.0));
+        assert_eq!(bounds.max.x, -8.0);
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.contains("G2 X-30 Y15"));
+        assert!(!gcode.contains("G3"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_rotate_z_frame_error() {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::frame(
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector2::new(20.0, 10.0),
+            -1.0,
+            1.0,
+        ));
+
+        assert!(program
+            .rotate_z(std::f64::consts::FRAC_PI_4, Vector2::ZERO)
+            .is_err());
+
+        assert!(program
+            .rotate_z(std::f64::consts::FRAC_PI_2, Vector2::ZERO)
+            .is_ok());
+
+        let bounds = program.bounds();
+        assert!(bounds.min.distance_to(Vector3::new(-13.0, 7.0, -1.0)) < 1e-9);
+        assert!(bounds.max.distance_to(Vector3::new(3.0, 33.0, 0.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_program_estimated_duration() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_rapid_rate(6000.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            600.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::line(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(60.0, 0.0, 0.0),
+        ));
+
+        // Rapids: 50 + 40 + 10 + 40 mm at 100 mm/s = 1.4 s
+        // Cuts: 10 + 60 mm at 10 mm/s = 7 s
+        // Spindle spin up dwell: 4 s
+        // Tool change: 60 s
+        let duration = program.estimated_duration()?;
+        assert!((duration.as_secs_f64() - 72.4).abs() < 1e-6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_simulate() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Imperial,
+            2.0,
+            0.25,
+            Direction::Clockwise,
+            5_000.0,
+            20.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::line(
+            Vector3::new(10.0, 5.0, 0.0),
+            Vector3::new(30.0, 5.0, -1.0),
+        ));
+
+        let states = program.simulate()?;
+        assert_eq!(states.len(), program.to_instructions()?.len());
+
+        // The machine starts in the units of the program
+        let (_, state) = &states[0];
+        assert_eq!(state.units, Units::Metric);
+        assert_eq!(state.position, Vector3::ZERO);
+
+        let (instruction, state) = states
+            .iter()
+            .rev()
+            .find(|(instruction, _)| matches!(instruction, Instruction::G1(_)))
+            .unwrap();
+        assert_eq!(
+            *instruction,
+            Instruction::G1(G1 {
+                x: Some(30.0),
+                y: Some(5.0),
+                z: Some(-1.0),
+                f: None,
+            })
+        );
+        assert_eq!(state.position, Vector3::new(30.0, 5.0, -1.0));
+        assert_eq!(state.units, Units::Metric);
+        assert!(matches!(state.plane, Axis::Z));
+        assert_eq!(state.feed_rate, Some(508.0));
+
+        let (instruction, state) = states.last().unwrap();
+        assert_eq!(*instruction, Instruction::M2(M2 {}));
+        assert_eq!(state.position, Vector3::new(30.0, 5.0, 50.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_travel_stats() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            600.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::line(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(60.0, 0.0, 0.0),
+        ));
+        context.append_cut(Cut::arc(
+            Vector3::new(60.0, 0.0, 0.0),
+            Vector3::new(60.0, 20.0, 0.0),
+            Vector3::new(60.0, 10.0, 0.0),
+            Axis::Z,
+            Direction::Clockwise,
+        ));
+
+        let stats = program.travel_stats()?;
+
+        assert_eq!(stats.g0_count, 7);
+        assert_eq!(stats.g1_count, 3);
+        assert_eq!(stats.g2_count, 1);
+        assert_eq!(stats.g3_count, 0);
+        assert_eq!(stats.tool_change_count, 1);
+        assert!((stats.rapid_distance - 150.0).abs() < 1e-9);
+        assert!((stats.cut_distance - (80.0 + 10.0 * std::f64::consts::PI)).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_feed_rate_and_spindle_speed_scale() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::line(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, -1.0),
+        ));
+        context.append_cut(Cut::drill(Vector3::new(5.0, 5.0, 0.0), -1.0));
+        context.append_cut(Cut::arc(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(5.0, 0.0, 0.0),
+            Axis::Z,
+            Direction::Clockwise,
+        ));
+
+        let nominal = program.to_instructions()?;
+
+        program.set_feed_rate_scale(0.5);
+        program.set_spindle_speed_scale(0.5);
+        let scaled = program.to_instructions()?;
+
+        assert_eq!(nominal.len(), scaled.len());
+
+        let mut scaled_count = 0;
+        for (nominal, scaled) in nominal.iter().zip(scaled.iter()) {
+            let (nominal_value, scaled_value) = match (nominal, scaled) {
+                (Instruction::G1(a), Instruction::G1(b)) => (a.f, b.f),
+                (Instruction::G2(a), Instruction::G2(b)) => (a.f, b.f),
+                (Instruction::G3(a), Instruction::G3(b)) => (a.f, b.f),
+                (Instruction::F(a), Instruction::F(b)) => (Some(a.x), Some(b.x)),
+                (Instruction::S(a), Instruction::S(b)) => (Some(a.x), Some(b.x)),
+                _ => {
+                    assert_eq!(nominal, scaled);
+                    continue;
+                }
+            };
+
+            assert_eq!(nominal_value.map(|value| value * 0.5), scaled_value);
+            if scaled_value.is_some() {
+                scaled_count += 1;
+            }
+        }
+        assert!(scaled_count > 2);
+
+        // The tool change comment still shows the nominal values
+        assert!(program
+            .to_gcode()?
+            .contains("spindle_speed = 5000 rpm, feed_rate = 400 mm/min"));
+        assert_eq!(tool.feed_rate(), 400.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_spindle_spinup() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::drill(Vector3::new(5.0, 5.0, 0.0), -1.0));
+
+        assert_eq!(program.spindle_spinup(), None);
+        assert!(program.to_gcode()?.contains("\nG4 P4\n"));
+
+        program.set_spindle_spinup(Duration::from_millis(8_500));
+        assert_eq!(program.spindle_spinup(), Some(Duration::from_millis(8_500)));
+        assert!(program.to_gcode()?.contains("\nG4 P8.5\n"));
+
+        program.set_spindle_spinup(Duration::ZERO);
+        assert!(program.to_gcode()?.contains("\nG4 P0\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_dry_run() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::circle(Vector3::new(0.0, 0.0, 0.0), -2.0, 10.0, 1.0));
+        context.append_cut(Cut::arc(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(5.0, 0.0, 0.0),
+            Axis::Y,
+            Direction::Clockwise,
+        ));
+
+        let z_values = |program: &Program| -> Result<Vec<f64>> {
+            Ok(program
+                .to_instructions()?
+                .into_iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::G0(g) => g.z,
+                    Instruction::G1(g) => g.z,
+                    Instruction::G2(g) => g.z,
+                    Instruction::G3(g) => g.z,
+                    _ => None,
+                })
+                .collect())
+        };
+
+        assert!(z_values(&program)?.iter().any(|z| *z < 10.0));
+
+        program.set_dry_run(true);
+        assert!(program.dry_run());
+        assert!(z_values(&program)?.iter().all(|z| *z >= 10.0));
+
+        let instructions = program.to_instructions()?;
+        // The xy motion of the circle is kept, while the arc in the xz plane becomes a line
+        assert!(instructions.iter().any(|instruction| matches!(
+            instruction,
+            Instruction::G2(G2 {
+                i: Some(_),
+                k: None,
+                ..
+            })
+        )));
+        assert!(!instructions
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::G2(G2 { k: Some(_), .. }))));
+
+        // The cuts are not changed
+        program.set_dry_run(false);
+        assert!(z_values(&program)?.iter().any(|z| *z < 10.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_tool_number() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool1 = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            6.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+        let tool2 = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            2.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+        let tool3 = Tool::ballnose(
+            Units::Metric,
+            50.0,
+            1.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        for (index, tool) in [tool1, tool2, tool3].iter().enumerate() {
+            let mut context = program.context(*tool);
+            context.append_cut(Cut::drill(Vector3::new(index as f64, 0.0, 0.0), -1.0));
+        }
+
+        program.set_tool_number(&tool1, 4);
+        program.set_tool_ordering(&tool3, 1);
+
+        assert_eq!(program.tools(), vec![tool3, tool1, tool2]);
+        assert_eq!(program.tool_number(&tool1), Some(4));
+        assert_eq!(program.tool_number(&tool2), Some(3));
+        assert_eq!(program.tool_number(&tool3), Some(1));
+
+        let tool_changes = program
+            .to_instructions()?
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::M6(m6) => Some(m6.t),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tool_changes, vec![1, 4, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_z_overrides() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let drill = Tool::cylindrical(
+            Units::Metric,
+            60.0,
+            3.0,
+            Direction::Clockwise,
+            5_000.0,
+            200.0,
+        );
+        let surfacing = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            20.0,
+            Direction::Clockwise,
+            10_000.0,
+            800.0,
+        );
+
+        {
+            let mut context = program.context(drill);
+            context.set_z_safe(30.0);
+            context.set_z_tool_change(80.0);
+            context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -10.0));
+            assert_eq!(context.z_safe(), 30.0);
+            assert_eq!(context.z_tool_change(), 80.0);
+        }
+
+        {
+            let mut context = program.context(surfacing);
+            context.append_cut(Cut::drill(Vector3::new(20.0, 0.0, 0.0), -1.0));
+            assert_eq!(context.z_safe(), 10.0);
+            assert_eq!(context.z_tool_change(), 50.0);
+        }
+
+        let instructions = program.to_instructions()?;
+        let z_values = |tool_number: u8| {
+            instructions
+                .iter()
+                .skip_while(|instruction| *instruction != &Instruction::M6(M6 { t: tool_number }))
+                .skip(1)
+                .take_while(|instruction| !matches!(instruction, Instruction::M6(_)))
+                .filter_map(|instruction| match instruction {
+                    Instruction::G0(G0 { z: Some(z), .. }) => Some(*z),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(z_values(1)[0], 30.0);
+        assert_eq!(z_values(2)[0], 10.0);
+        assert!(instructions.contains(&Instruction::G0(G0 {
+            x: None,
+            y: None,
+            z: Some(80.0),
+        })));
+
+        program.context(drill).set_z_tool_change(20.0);
+        assert!(matches!(
+            program.to_instructions(),
+            Err(CncError::ZToolChangeBelowZSafe { tool: Some(tool), .. }) if tool == drill
+        ));
+
+        program.context(drill).set_z_tool_change(80.0);
+        program.context(surfacing).set_z_safe(-5.0);
+        assert!(matches!(
+            program.to_instructions(),
+            Err(CncError::ZSafeBelowWorkpiece { tool: Some(tool), .. }) if tool == surfacing
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_end_retract_above_all_tool_changes() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let drill = Tool::cylindrical(
+            Units::Metric,
+            60.0,
+            3.0,
+            Direction::Clockwise,
+            5_000.0,
+            200.0,
+        );
+        let surfacing = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            20.0,
+            Direction::Clockwise,
+            10_000.0,
+            800.0,
+        );
+
+        let mut context = program.context(drill);
+        context.set_z_tool_change(80.0);
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -10.0));
+
+        let mut context = program.context(surfacing);
+        context.set_z_tool_change(60.0);
+        context.append_cut(Cut::drill(Vector3::new(20.0, 0.0, 0.0), -1.0));
+
+        let last_retract = |program: &Program| -> Result<Option<f64>> {
+            Ok(program
+                .to_instructions()?
+                .into_iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::G0(G0 { z: Some(z), .. }) => Some(z),
+                    _ => None,
+                })
+                .next_back())
+        };
+
+        // The drill is changed first, but the end retract still clears its tool change height
+        assert_eq!(program.tools(), vec![drill, surfacing]);
+        assert_eq!(last_retract(&program)?, Some(80.0));
+
+        program.context(drill).set_z_tool_change(55.0);
+        assert_eq!(last_retract(&program)?, Some(60.0));
+
+        program.context(drill).set_z_tool_change(50.0);
+        program.context(surfacing).set_z_tool_change(50.0);
+        assert_eq!(last_retract(&program)?, Some(50.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_arc_turns_by_flavor() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::bore(Vector3::new(0.0, 0.0, 0.0), 5.0, -7.0, 2.0));
+
+        program.set_flavor(Flavor::LinuxCnc);
+        assert!(program
+            .to_gcode()?
+            .lines()
+            .any(|line| line == "G2 X-3 Y0 Z-7 I3 J0 P4"));
+
+        // Controllers without support for the P word get one arc per revolution
+        program.set_flavor(Flavor::Grbl);
+        let arcs = program
+            .to_instructions()?
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::G2(g2) => Some(g2),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(arcs.len(), 5);
+        for (index, arc) in arcs[..4].iter().enumerate() {
+            assert_eq!(arc.x, Some(-3.0));
+            assert_eq!(arc.p, None);
+            assert!((arc.z.unwrap() + 1.75 * (index + 1) as f64).abs() < 1e-9);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_end_sequence() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.ends_with("G0 Z50\n\nM2"));
+
+        program.set_end_sequence(
+            Some(Vector3::new(0.0, 200.0, 40.0)),
+            Some(Duration::from_secs(5)),
+        );
+        assert_eq!(program.park(), Some(Vector3::new(0.0, 200.0, 40.0)));
+        assert_eq!(program.spindle_off_dwell(), Some(Duration::from_secs(5)));
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.ends_with("G0 Z50\nM5\nG4 P5\nG0 X0 Y200\nG0 Z40\n\nM2"));
+
+        let program = Program::from_json(&program.to_json()?)?;
+        assert_eq!(program.park(), Some(Vector3::new(0.0, 200.0, 40.0)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_tool_in_other_units() -> Result<()> {
+        let mut program = Program::new(Units::Imperial, 0.5, 2.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            6.35,
+            Direction::Clockwise,
+            10_000.0,
+            254.0,
+        );
+        let mut context = program.context(tool);
+        context.append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(1.0, 1.0),
+            -0.1,
+            0.1,
+        ));
+
+        // The tool is 0.25" wide and feeds 10"/min in the imperial program
+        let bounds = context.bounds();
+        assert!(bounds.min.distance_to(Vector3::new(0.0, 0.0, -0.1)) < 1e-9);
+
+        let gcode = program.to_gcode()?;
+        assert!(gcode.contains("\nG0 X0.125 Y0.125\n"), "{}", gcode);
+        assert!(gcode.contains("\nG1 Z0 F10\n"), "{}", gcode);
+
+        // An imperial tool is too wide for a 5 mm pocket in a metric program
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let tool = tool.to_units(Units::Imperial);
+        assert!((tool.diameter() - 0.25).abs() < 1e-9);
+
+        program.context(tool).append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector2::new(5.0, 5.0),
+            -1.0,
+            1.0,
+        ));
+        let error = program.to_gcode().unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::ToolWiderThanFeature {
+                units: Units::Metric,
+                ..
+            }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_tool_length_offsets() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+
+        assert!(!program.tool_length_offsets());
+        assert!(!program.to_gcode()?.contains("G43"));
+
+        program.set_tool_length_offsets(true);
+        assert!(program.tool_length_offsets());
+
+        let instructions = program.to_instructions()?;
+        let m6 = instructions
+            .iter()
+            .position(|instruction| instruction == &Instruction::M6(M6 { t: 1 }))
+            .unwrap();
+        assert_eq!(instructions[m6 + 1], Instruction::G43(G43 { h: 1 }));
+        assert!(program.to_gcode()?.contains("\nG43 H1\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_validate_tool_length() -> Result<()> {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            10.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let mut context = program.context(tool);
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -10.0));
+        context.append_cut(Cut::frame(
+            Vector3::new(0.0, 0.0, 3.0),
+            Vector2::new(20.0, 20.0),
+            -5.0,
+            1.0,
+        ));
+
+        // Only the 5 mm below the stock surface counts, the rest of the cut is in the air
+        context.append_cut(Cut::drill(Vector3::new(10.0, 10.0, 8.0), -5.0));
+
+        assert!(program.validate().is_ok());
+        assert!(program.to_gcode().is_ok());
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::circle(Vector3::new(20.0, 30.0, 0.0), -12.5, 5.0, 1.0));
+
+        let error = program.validate().unwrap_err();
+        assert!(matches!(
+            error,
+            CncError::CutTooDeep { index: 3, tool: error_tool, .. } if error_tool == tool
+        ));
+        assert_eq!(
+            error.to_string(),
+            format!("Cut 4 at x = 15, y = 25 is 12.5 mm deep which exceeds the tool length of 10 mm for tool {tool}")
+        );
+        assert!(program.to_gcode().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_validate_tool_length_imperial() {
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.8,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut program = Program::new(Units::Imperial, 1.0, 2.0);
+        let mut context = program.context(tool);
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.9));
+        assert!(program.validate().is_ok());
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -2.1));
+        assert!(program.validate().is_err());
+    }
+
+    #[test]
+    fn test_context_optimize_travel() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::drill(Vector3::new(50.0, 50.0, 0.0), -1.0));
+        context.append_cut(Cut::drill(Vector3::new(0.0, 10.0, 0.0), -1.0));
+        context.append_cut(Cut::drill(Vector3::new(40.0, 40.0, 0.0), -1.0));
+        context.append_cut(Cut::drill(Vector3::new(10.0, 0.0, 0.0), -1.0));
+        context.append(Operation::Comment(Comment {
+            text: "Barrier".into(),
+        }));
+        context.append_cut(Cut::drill(Vector3::new(100.0, 100.0, 0.0), -1.0));
+        context.append_cut(Cut::drill(Vector3::new(20.0, 0.0, 0.0), -1.0));
+
+        context.optimize_travel();
+
+        let positions = context
+            .operations()
+            .iter()
+            .map(|operation| match operation {
+                Operation::Cut(Cut::Circle(circle)) => Some(circle.start.xy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            positions,
+            vec![
+                Some(Vector2::new(0.0, 10.0)),
+                Some(Vector2::new(10.0, 0.0)),
+                Some(Vector2::new(40.0, 40.0)),
+                Some(Vector2::new(50.0, 50.0)),
+                None,
+                Some(Vector2::new(20.0, 0.0)),
+                Some(Vector2::new(100.0, 100.0)),
+            ]
+        );
+
+        assert!(program.to_gcode().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_bounds() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        program.set_name("program bounds");
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            50.0,
+            4.0,
+            Direction::Clockwise,
+            5_000.0,
+            400.0,
+        );
+
+        let mut context = program.context(tool);
+
+        context.append_cut(Cut::path(
+            Vector3::new(0.0, 0.0, 3.0),
+            vec![Segment::line(
+                Vector2::default(),
+                Vector2::new(-28.0, -30.0),
+            )],
+            -0.1,
+            1.0,
+        ));
+
+        context.append_cut(Cut::path(
+            Vector3::new(0.0, 0.0, 3.0),
+            vec![
+                Segment::line(Vector2::new(23.0, 12.0), Vector2::new(5.0, 10.0)),
+                Segment::line(Vector2::new(5.0, 10.0), Vector2::new(67.0, 102.0)),
+                Segment::line(Vector2::new(67.0, 102.0), Vector2::new(23.0, 12.0)),
+            ],
+            -0.1,
+            1.0,
+        ));
+
+        let bounds = program.bounds();
+
+        assert_eq!(
+            bounds,
+            Bounds {
+                min: Vector3::new(-30.0, -32.0, -0.1),
+                max: Vector3::new(69.0, 104.0, 3.0),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_required_stock() {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            4.0,
+            Direction::Clockwise,
+            10_000.0,
+            1_000.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::frame(
+            Vector3::new(10.0, 20.0, 0.0),
+            Vector2::new(100.0, 50.0),
+            -12.0,
+            2.0,
+        ));
+
+        let stock = program.required_stock(Vector3::new(5.0, 5.0, 5.0));
+
+        assert_eq!(stock.min, Vector3::new(3.0, 13.0, -17.0));
+        assert_eq!(stock.max, Vector3::new(117.0, 77.0, 0.0));
+        assert_eq!(stock.size(), Vector3::new(114.0, 64.0, 17.0));
+    }
+
+    #[test]
+    fn test_program_bounds_swept_by_tools() {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let start = Vector3::new(10.0, 20.0, 0.0);
+
+        let mut context = program.context(Tool::default());
+        context.append_cut(Cut::line(start, start.add_x(30.0).with_z(-1.0)));
+
+        // The 6 mm tool sweeps 3 mm on each side of the line
+        assert_eq!(
+            program.bounds(),
+            Bounds {
+                min: Vector3::new(7.0, 17.0, -1.0),
+                max: Vector3::new(43.0, 23.0, 0.0),
+            }
+        );
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            10.0,
+            Direction::Clockwise,
+            10_000.0,
+            3_000.0,
+        );
+        let mut context = program.context(tool);
+        context.append_cut(Cut::drill(Vector3::new(60.0, 20.0, 0.0), -2.0));
+
+        // Each cut grows by the radius of its own tool
+        assert_eq!(
+            program.bounds(),
+            Bounds {
+                min: Vector3::new(7.0, 15.0, -2.0),
+                max: Vector3::new(65.0, 25.0, 0.0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_program_bounds_planing_compensation() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            10.0,
+            Direction::Clockwise,
+            10_000.0,
+            3_000.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::plane(
+            Vector3::new(0.0, 0.0, 3.0),
+            Vector2::new(100.0, 100.0),
+            0.0,
+            1.0,
+        ));
+
+        assert_eq!(
+            program.bounds(),
+            Bounds {
+                min: Vector3::new(-10.0, -10.0, 0.0),
+                max: Vector3::new(110.0, 110.0, 3.0),
+            }
+        );
+
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+        let mut context = program.context(tool);
+        context.append_cut(Cut::pocket(
+            Vector3::new(0.0, 0.0, 3.0),
+            Vector2::new(100.0, 100.0),
+            0.0,
+            1.0,
+        ));
+
+        assert_eq!(
+            program.bounds(),
+            Bounds {
+                min: Vector3::new(0.0, 0.0, 0.0),
+                max: Vector3::new(100.0, 100.0, 3.0),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_meta_setters() -> Result<()> {
+        let program = || -> Result<Program> {
+            let mut program = Program::new(Units::Metric, 10.0, 50.0).with_deterministic_meta();
+            program.set_name("meta");
+
+            let mut context = program.context(Tool::default());
+            context.append_cut(Cut::drill(Vector3::new(0.0, 0.0, 0.0), -1.0));
+
+            Ok(program)
+        };
+
+        let gcode = program()?.to_gcode()?;
+        assert_eq!(gcode, program()?.to_gcode()?);
+        assert!(gcode.contains(";(Created on: 1970-01-01 0:00:00.0 +00:00:00)"));
+
+        let mut program = program()?;
+        program.set_created_by("operator");
+        program.set_generator("cnccoder tests");
+        program.set_created_on(OffsetDateTime::UNIX_EPOCH + Duration::from_secs(86_400));
+
+        assert_eq!(program.created_by(), "operator");
+        assert_eq!(program.generator(), "cnccoder tests");
+
+        let lines = program.to_gcode()?;
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..4],
+            [
+                ";(Name: meta)",
+                ";(Created on: 1970-01-02 0:00:00.0 +00:00:00)",
+                ";(Created by: operator)",
+                ";(Generator: cnccoder tests)",
+            ]
         );
 
         Ok(())
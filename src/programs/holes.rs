@@ -0,0 +1,280 @@
+use std::f64::consts::TAU;
+
+use crate::prelude::*;
+
+/// Measurements required by the bolt circle program.
+pub struct BoltCircleMeasurements {
+    /// The center of the bolt circle.
+    pub center: Vector2,
+    /// The radius of the bolt circle, measured to the hole centers.
+    pub radius: f64,
+    /// The number of evenly spaced holes.
+    pub hole_count: u32,
+    /// The angle in radians of the first hole, counterclockwise from the x axis.
+    pub start_angle: f64,
+    /// The height of where to start drilling on the z axis.
+    pub z_start: f64,
+    /// The depth of the holes on the z axis.
+    pub z_end: f64,
+    /// The maximum depth to drill before retracting to clear chips, the holes are drilled in
+    /// one go when set to 0.0.
+    pub z_peck_step: f64,
+    /// The units used for the measurements.
+    pub units: Units,
+}
+
+impl Default for BoltCircleMeasurements {
+    fn default() -> Self {
+        let units = Units::default();
+
+        Self {
+            center: Vector2::default(),
+            radius: units.measurement_from_mm(20.0),
+            hole_count: 4,
+            start_angle: 0.0,
+            z_start: units.measurement_from_mm(0.0),
+            z_end: units.measurement_from_mm(-5.0),
+            z_peck_step: units.measurement_from_mm(0.0),
+            units,
+        }
+    }
+}
+
+impl BoltCircleMeasurements {
+    /// Returns the positions of the hole centers.
+    #[must_use]
+    pub fn hole_positions(&self) -> Vec<Vector2> {
+        (0..self.hole_count)
+            .map(|index| {
+                let angle = self.start_angle + TAU * f64::from(index) / f64::from(self.hole_count);
+                self.center + Vector2::from_polar(self.radius, angle)
+            })
+            .collect()
+    }
+}
+
+/// A program for drilling evenly spaced holes around a circle, for example for bolt
+/// patterns.
+pub fn bolt_circle(tool: Tool, measurements: BoltCircleMeasurements) -> Program {
+    drilling(
+        tool,
+        measurements.units,
+        measurements.hole_positions(),
+        measurements.z_start,
+        measurements.z_end,
+        measurements.z_peck_step,
+    )
+}
+
+/// Measurements required by the hole grid program.
+pub struct HoleGridMeasurements {
+    /// The position of the first hole, the grid extends towards positive x and y.
+    pub start: Vector2,
+    /// The number of rows of holes along the y axis.
+    pub rows: u32,
+    /// The number of columns of holes along the x axis.
+    pub columns: u32,
+    /// The distance between the hole centers on the x and y axis.
+    pub spacing: Vector2,
+    /// The height of where to start drilling on the z axis.
+    pub z_start: f64,
+    /// The depth of the holes on the z axis.
+    pub z_end: f64,
+    /// The maximum depth to drill before retracting to clear chips, the holes are drilled in
+    /// one go when set to 0.0.
+    pub z_peck_step: f64,
+    /// The units used for the measurements.
+    pub units: Units,
+}
+
+impl Default for HoleGridMeasurements {
+    fn default() -> Self {
+        let units = Units::default();
+
+        Self {
+            start: Vector2::default(),
+            rows: 2,
+            columns: 2,
+            spacing: Vector2::new(
+                units.measurement_from_mm(10.0),
+                units.measurement_from_mm(10.0),
+            ),
+            z_start: units.measurement_from_mm(0.0),
+            z_end: units.measurement_from_mm(-5.0),
+            z_peck_step: units.measurement_from_mm(0.0),
+            units,
+        }
+    }
+}
+
+impl HoleGridMeasurements {
+    /// Returns the positions of the hole centers, row by row, with every other row reversed
+    /// to reduce the travel between the holes.
+    #[must_use]
+    pub fn hole_positions(&self) -> Vec<Vector2> {
+        let mut positions = vec![];
+
+        for row in 0..self.rows {
+            let mut columns = (0..self.columns).collect::<Vec<_>>();
+            if row % 2 == 1 {
+                columns.reverse();
+            }
+
+            for column in columns {
+                positions.push(Vector2::new(
+                    self.start.x + self.spacing.x * f64::from(column),
+                    self.start.y + self.spacing.y * f64::from(row),
+                ));
+            }
+        }
+
+        positions
+    }
+}
+
+/// A program for drilling holes in a rectangular grid.
+pub fn hole_grid(tool: Tool, measurements: HoleGridMeasurements) -> Program {
+    drilling(
+        tool,
+        measurements.units,
+        measurements.hole_positions(),
+        measurements.z_start,
+        measurements.z_end,
+        measurements.z_peck_step,
+    )
+}
+
+fn drilling(
+    tool: Tool,
+    units: Units,
+    positions: Vec<Vector2>,
+    z_start: f64,
+    z_end: f64,
+    z_peck_step: f64,
+) -> Program {
+    let mut program = Program::new(
+        units,
+        z_start + units.measurement_from_mm(2.0),
+        z_start + units.measurement_from_mm(50.0),
+    );
+
+    let mut context = program.context(tool);
+
+    for position in positions {
+        let start = Vector3::new(position.x, position.y, z_start);
+
+        // Peck by drilling progressively deeper, retracting in between
+        if z_peck_step > 0.0 {
+            let mut z = z_start - z_peck_step;
+            while z > z_end {
+                context.append_cut(Cut::drill(start, z));
+                z -= z_peck_step;
+            }
+        }
+
+        context.append_cut(Cut::drill(start, z_end));
+    }
+
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool() -> Tool {
+        Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            5.0,
+            Direction::Clockwise,
+            10_000.0,
+            300.0,
+        )
+    }
+
+    fn drill_positions(program: &mut Program) -> Vec<Vector3> {
+        let tool = program.tools()[0];
+        program
+            .context(tool)
+            .operations()
+            .iter()
+            .map(|operation| match operation {
+                Operation::Cut(Cut::Circle(circle)) => circle.start.with_z(circle.end_z),
+                _ => panic!("Expected drill cut"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bolt_circle() {
+        let mut program = bolt_circle(
+            tool(),
+            BoltCircleMeasurements {
+                center: Vector2::new(100.0, 100.0),
+                radius: 50.0,
+                hole_count: 6,
+                z_end: -8.0,
+                ..Default::default()
+            },
+        );
+
+        let positions = drill_positions(&mut program);
+        let half_height = 50.0 * 3f64.sqrt() / 2.0;
+        let expected = [
+            Vector2::new(150.0, 100.0),
+            Vector2::new(125.0, 100.0 + half_height),
+            Vector2::new(75.0, 100.0 + half_height),
+            Vector2::new(50.0, 100.0),
+            Vector2::new(75.0, 100.0 - half_height),
+            Vector2::new(125.0, 100.0 - half_height),
+        ];
+
+        assert_eq!(positions.len(), 6);
+        for (position, expected) in positions.iter().zip(expected) {
+            assert!(position.xy().distance_to(expected) < 1e-9);
+            assert_eq!(position.z, -8.0);
+        }
+
+        assert!(program.to_gcode().is_ok());
+    }
+
+    #[test]
+    fn test_hole_grid_peck() {
+        let mut program = hole_grid(
+            tool(),
+            HoleGridMeasurements {
+                rows: 2,
+                columns: 3,
+                spacing: Vector2::new(10.0, 20.0),
+                z_end: -5.0,
+                z_peck_step: 2.0,
+                ..Default::default()
+            },
+        );
+
+        let positions = drill_positions(&mut program);
+        let holes = positions
+            .chunks(3)
+            .map(|pecks| {
+                assert_eq!(
+                    pecks.iter().map(|peck| peck.z).collect::<Vec<_>>(),
+                    vec![-2.0, -4.0, -5.0]
+                );
+                pecks[0].xy()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            holes,
+            vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(10.0, 0.0),
+                Vector2::new(20.0, 0.0),
+                Vector2::new(20.0, 20.0),
+                Vector2::new(10.0, 20.0),
+                Vector2::new(0.0, 20.0),
+            ]
+        );
+    }
+}
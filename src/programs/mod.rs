@@ -48,5 +48,9 @@
 //! }
 //! ```
 
+mod holes;
 mod planing;
+mod text;
+pub use holes::*;
 pub use planing::*;
+pub use text::*;
@@ -0,0 +1,380 @@
+use crate::prelude::*;
+
+/// The width of the glyphs in font units.
+const GLYPH_WIDTH: f64 = 4.0;
+/// The height of the glyphs in font units.
+const GLYPH_HEIGHT: f64 = 6.0;
+
+type Stroke = &'static [(f64, f64)];
+
+/// Returns the strokes of a glyph in the built in single stroke font, on a grid that is
+/// `GLYPH_WIDTH` wide and `GLYPH_HEIGHT` high, or `None` if the character is not supported.
+fn glyph(character: char) -> Option<&'static [Stroke]> {
+    let strokes: &'static [Stroke] = match character.to_ascii_uppercase() {
+        ' ' => &[],
+        'A' => &[
+            &[(0.0, 0.0), (0.0, 4.0), (2.0, 6.0), (4.0, 4.0), (4.0, 0.0)],
+            &[(0.0, 3.0), (4.0, 3.0)],
+        ],
+        'B' => &[
+            &[
+                (0.0, 0.0),
+                (0.0, 6.0),
+                (3.0, 6.0),
+                (4.0, 5.0),
+                (4.0, 4.0),
+                (3.0, 3.0),
+                (0.0, 3.0),
+            ],
+            &[(3.0, 3.0), (4.0, 2.0), (4.0, 1.0), (3.0, 0.0), (0.0, 0.0)],
+        ],
+        'C' => &[&[
+            (4.0, 5.0),
+            (3.0, 6.0),
+            (1.0, 6.0),
+            (0.0, 5.0),
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (3.0, 0.0),
+            (4.0, 1.0),
+        ]],
+        'D' => &[&[
+            (0.0, 0.0),
+            (0.0, 6.0),
+            (2.0, 6.0),
+            (4.0, 4.0),
+            (4.0, 2.0),
+            (2.0, 0.0),
+            (0.0, 0.0),
+        ]],
+        'E' => &[
+            &[(4.0, 6.0), (0.0, 6.0), (0.0, 0.0), (4.0, 0.0)],
+            &[(0.0, 3.0), (3.0, 3.0)],
+        ],
+        'F' => &[
+            &[(4.0, 6.0), (0.0, 6.0), (0.0, 0.0)],
+            &[(0.0, 3.0), (3.0, 3.0)],
+        ],
+        'G' => &[&[
+            (4.0, 5.0),
+            (3.0, 6.0),
+            (1.0, 6.0),
+            (0.0, 5.0),
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (3.0, 0.0),
+            (4.0, 1.0),
+            (4.0, 3.0),
+            (2.0, 3.0),
+        ]],
+        'H' => &[
+            &[(0.0, 0.0), (0.0, 6.0)],
+            &[(4.0, 0.0), (4.0, 6.0)],
+            &[(0.0, 3.0), (4.0, 3.0)],
+        ],
+        'I' => &[
+            &[(1.0, 6.0), (3.0, 6.0)],
+            &[(2.0, 6.0), (2.0, 0.0)],
+            &[(1.0, 0.0), (3.0, 0.0)],
+        ],
+        'J' => &[&[(4.0, 6.0), (4.0, 1.0), (3.0, 0.0), (1.0, 0.0), (0.0, 1.0)]],
+        'K' => &[
+            &[(0.0, 0.0), (0.0, 6.0)],
+            &[(4.0, 6.0), (0.0, 2.0)],
+            &[(1.0, 3.0), (4.0, 0.0)],
+        ],
+        'L' => &[&[(0.0, 6.0), (0.0, 0.0), (4.0, 0.0)]],
+        'M' => &[&[(0.0, 0.0), (0.0, 6.0), (2.0, 3.0), (4.0, 6.0), (4.0, 0.0)]],
+        'N' => &[&[(0.0, 0.0), (0.0, 6.0), (4.0, 0.0), (4.0, 6.0)]],
+        'O' => &[&[
+            (1.0, 0.0),
+            (0.0, 1.0),
+            (0.0, 5.0),
+            (1.0, 6.0),
+            (3.0, 6.0),
+            (4.0, 5.0),
+            (4.0, 1.0),
+            (3.0, 0.0),
+            (1.0, 0.0),
+        ]],
+        'P' => &[&[
+            (0.0, 0.0),
+            (0.0, 6.0),
+            (3.0, 6.0),
+            (4.0, 5.0),
+            (4.0, 4.0),
+            (3.0, 3.0),
+            (0.0, 3.0),
+        ]],
+        'Q' => &[
+            &[
+                (1.0, 0.0),
+                (0.0, 1.0),
+                (0.0, 5.0),
+                (1.0, 6.0),
+                (3.0, 6.0),
+                (4.0, 5.0),
+                (4.0, 1.0),
+                (3.0, 0.0),
+                (1.0, 0.0),
+            ],
+            &[(2.0, 2.0), (4.0, 0.0)],
+        ],
+        'R' => &[
+            &[
+                (0.0, 0.0),
+                (0.0, 6.0),
+                (3.0, 6.0),
+                (4.0, 5.0),
+                (4.0, 4.0),
+                (3.0, 3.0),
+                (0.0, 3.0),
+            ],
+            &[(2.0, 3.0), (4.0, 0.0)],
+        ],
+        'S' => &[&[
+            (4.0, 5.0),
+            (3.0, 6.0),
+            (1.0, 6.0),
+            (0.0, 5.0),
+            (0.0, 4.0),
+            (1.0, 3.0),
+            (3.0, 3.0),
+            (4.0, 2.0),
+            (4.0, 1.0),
+            (3.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 1.0),
+        ]],
+        'T' => &[&[(0.0, 6.0), (4.0, 6.0)], &[(2.0, 6.0), (2.0, 0.0)]],
+        'U' => &[&[
+            (0.0, 6.0),
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (3.0, 0.0),
+            (4.0, 1.0),
+            (4.0, 6.0),
+        ]],
+        'V' => &[&[(0.0, 6.0), (2.0, 0.0), (4.0, 6.0)]],
+        'W' => &[&[(0.0, 6.0), (1.0, 0.0), (2.0, 4.0), (3.0, 0.0), (4.0, 6.0)]],
+        'X' => &[&[(0.0, 6.0), (4.0, 0.0)], &[(0.0, 0.0), (4.0, 6.0)]],
+        'Y' => &[
+            &[(0.0, 6.0), (2.0, 3.0), (4.0, 6.0)],
+            &[(2.0, 3.0), (2.0, 0.0)],
+        ],
+        'Z' => &[&[(0.0, 6.0), (4.0, 6.0), (0.0, 0.0), (4.0, 0.0)]],
+        '0' => &[
+            &[
+                (1.0, 0.0),
+                (0.0, 1.0),
+                (0.0, 5.0),
+                (1.0, 6.0),
+                (3.0, 6.0),
+                (4.0, 5.0),
+                (4.0, 1.0),
+                (3.0, 0.0),
+                (1.0, 0.0),
+            ],
+            &[(0.0, 1.0), (4.0, 5.0)],
+        ],
+        '1' => &[
+            &[(1.0, 5.0), (2.0, 6.0), (2.0, 0.0)],
+            &[(1.0, 0.0), (3.0, 0.0)],
+        ],
+        '2' => &[&[
+            (0.0, 5.0),
+            (1.0, 6.0),
+            (3.0, 6.0),
+            (4.0, 5.0),
+            (4.0, 4.0),
+            (0.0, 0.0),
+            (4.0, 0.0),
+        ]],
+        '3' => &[
+            &[
+                (0.0, 5.0),
+                (1.0, 6.0),
+                (3.0, 6.0),
+                (4.0, 5.0),
+                (4.0, 4.0),
+                (3.0, 3.0),
+                (4.0, 2.0),
+                (4.0, 1.0),
+                (3.0, 0.0),
+                (1.0, 0.0),
+                (0.0, 1.0),
+            ],
+            &[(1.0, 3.0), (3.0, 3.0)],
+        ],
+        '4' => &[&[(3.0, 0.0), (3.0, 6.0), (0.0, 2.0), (4.0, 2.0)]],
+        '5' => &[&[
+            (4.0, 6.0),
+            (0.0, 6.0),
+            (0.0, 3.0),
+            (3.0, 3.0),
+            (4.0, 2.0),
+            (4.0, 1.0),
+            (3.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 1.0),
+        ]],
+        '6' => &[&[
+            (4.0, 5.0),
+            (3.0, 6.0),
+            (1.0, 6.0),
+            (0.0, 5.0),
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (3.0, 0.0),
+            (4.0, 1.0),
+            (4.0, 2.0),
+            (3.0, 3.0),
+            (0.0, 3.0),
+        ]],
+        '7' => &[&[(0.0, 6.0), (4.0, 6.0), (1.0, 0.0)]],
+        '8' => &[&[
+            (1.0, 3.0),
+            (0.0, 4.0),
+            (0.0, 5.0),
+            (1.0, 6.0),
+            (3.0, 6.0),
+            (4.0, 5.0),
+            (4.0, 4.0),
+            (3.0, 3.0),
+            (1.0, 3.0),
+            (0.0, 2.0),
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (3.0, 0.0),
+            (4.0, 1.0),
+            (4.0, 2.0),
+            (3.0, 3.0),
+        ]],
+        '9' => &[&[
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (3.0, 0.0),
+            (4.0, 1.0),
+            (4.0, 5.0),
+            (3.0, 6.0),
+            (1.0, 6.0),
+            (0.0, 5.0),
+            (0.0, 4.0),
+            (1.0, 3.0),
+            (4.0, 3.0),
+        ]],
+        _ => return None,
+    };
+
+    Some(strokes)
+}
+
+/// Converts a glyph stroke to path segments scaled to the given size. Strokes that are not
+/// closed are traced back to their start so that the path can be cut in several passes
+/// without cutting straight across the glyph.
+fn stroke_to_segments(stroke: Stroke, scale: f64) -> Vec<Segment> {
+    let mut points = stroke
+        .iter()
+        .map(|(x, y)| Vector2::new(x * scale, y * scale))
+        .collect::<Vec<_>>();
+
+    if points.first() != points.last() {
+        let mut reversed = points.iter().rev().skip(1).copied().collect::<Vec<_>>();
+        points.append(&mut reversed);
+    }
+
+    points
+        .windows(2)
+        .map(|pair| Segment::line(pair[0], pair[1]))
+        .collect()
+}
+
+/// A program for engraving text using a built in single stroke font.
+///
+/// The text is written along the x axis from `position`, where the z value is the surface
+/// to engrave into. The `height` is the height of the capital letters and `spacing` is the
+/// gap between the characters, the glyphs are two thirds as wide as they are high.
+///
+/// Uppercase ASCII letters, digits, and spaces are supported, lowercase letters are engraved
+/// as uppercase and other characters are left blank.
+pub fn engrave_text(
+    tool: Tool,
+    text: &str,
+    position: Vector3,
+    height: f64,
+    depth: f64,
+    spacing: f64,
+) -> Program {
+    let units = tool.units();
+    let mut program = Program::new(
+        units,
+        position.z + units.measurement_from_mm(2.0),
+        position.z + units.measurement_from_mm(50.0),
+    );
+
+    let mut context = program.context(tool);
+
+    let scale = height / GLYPH_HEIGHT;
+    let advance = GLYPH_WIDTH * scale + spacing;
+
+    for (index, character) in text.chars().enumerate() {
+        let start = position.add_x(advance * index as f64);
+
+        for stroke in glyph(character).unwrap_or_default() {
+            context.append_cut(Cut::path(
+                start,
+                stroke_to_segments(stroke, scale),
+                position.z - depth,
+                depth,
+            ));
+        }
+    }
+
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engrave_text() -> anyhow::Result<()> {
+        let tool = Tool::conical(
+            Units::Metric,
+            45.0,
+            15.0,
+            Direction::Clockwise,
+            20_000.0,
+            500.0,
+        );
+
+        let mut program =
+            engrave_text(tool, "Hi 10", Vector3::new(10.0, 20.0, 0.0), 12.0, 0.5, 2.0);
+
+        // H and I have three strokes each, 1 has two, and 0 has two
+        assert_eq!(program.context(tool).operations().len(), 10);
+
+        let bounds = program.bounds();
+        // The bounds include the radius of the tool around the strokes
+        assert_eq!(bounds.min, Vector3::new(2.5, 12.5, -0.5));
+        assert_eq!(
+            bounds.max,
+            Vector3::new(10.0 + 4.0 * 10.0 + 8.0 + 7.5, 32.0 + 7.5, 0.0)
+        );
+
+        assert!(program.to_gcode().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_strokes_are_traced_back() {
+        let segments = stroke_to_segments(&[(0.0, 0.0), (0.0, 6.0), (4.0, 6.0)], 0.5);
+
+        assert_eq!(segments.len(), 4);
+        match segments.last() {
+            Some(Segment::Line(line)) => assert_eq!(line.to, Vector2::new(0.0, 0.0)),
+            _ => panic!("Expected line segment"),
+        }
+    }
+}
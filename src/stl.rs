@@ -0,0 +1,327 @@
+//! Exports a coarse 3D model of the machined workpiece as a binary STL file.
+
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+use anyhow::{anyhow, Result};
+
+use crate::instructions::*;
+use crate::motion::*;
+use crate::program::*;
+use crate::tools::*;
+use crate::types::*;
+
+// A height map of the workpiece top surface, sampled on a regular grid.
+struct HeightMap {
+    origin: Vector2,
+    resolution: f64,
+    columns: usize,
+    rows: usize,
+    heights: Vec<f64>,
+}
+
+impl HeightMap {
+    fn new(bounds: &Bounds, resolution: f64) -> Self {
+        let size = bounds.size();
+        let columns = (size.x / resolution).ceil() as usize + 1;
+        let rows = (size.y / resolution).ceil() as usize + 1;
+
+        Self {
+            origin: bounds.min.xy(),
+            resolution,
+            columns,
+            rows,
+            heights: vec![bounds.max.z; columns * rows],
+        }
+    }
+
+    fn point(&self, column: usize, row: usize) -> Vector3 {
+        Vector3::new(
+            self.origin.x + column as f64 * self.resolution,
+            self.origin.y + row as f64 * self.resolution,
+            self.heights[row * self.columns + column],
+        )
+    }
+
+    // Lowers the surface where the tool, with its tip at the position, reaches below it.
+    fn cut(&mut self, tool: &Tool, position: Vector3) {
+        let radius = tool.radius();
+        let first_column = ((position.x - radius - self.origin.x) / self.resolution)
+            .floor()
+            .max(0.0) as usize;
+        let last_column = (((position.x + radius - self.origin.x) / self.resolution).ceil()
+            as usize)
+            .min(self.columns - 1);
+        let first_row = ((position.y - radius - self.origin.y) / self.resolution)
+            .floor()
+            .max(0.0) as usize;
+        let last_row = (((position.y + radius - self.origin.y) / self.resolution).ceil() as usize)
+            .min(self.rows - 1);
+
+        for row in first_row..=last_row {
+            for column in first_column..=last_column {
+                let point = self.point(column, row);
+                let distance = point.xy().distance_to(position.xy());
+                if distance > radius {
+                    continue;
+                }
+
+                let z = position.z
+                    + match tool {
+                        Tool::Cylindrical(_) => 0.0,
+                        Tool::Ballnose(_) => {
+                            radius - (radius * radius - distance * distance).sqrt()
+                        }
+                        Tool::Conical(t) => distance / (t.angle / 2.0).to_radians().tan(),
+                        Tool::Engraving(t) => {
+                            (distance - t.tip_diameter / 2.0).max(0.0)
+                                / (t.angle / 2.0).to_radians().tan()
+                        }
+                    };
+
+                let height = &mut self.heights[row * self.columns + column];
+                *height = height.min(z);
+            }
+        }
+    }
+}
+
+// Returns the points along a cutting move, spaced at most `spacing` apart. Arcs in the xy
+// plane are followed, other moves are treated as straight lines.
+fn move_points(
+    from: Vector3,
+    to: Vector3,
+    instruction: &Instruction,
+    xy_plane: bool,
+    spacing: f64,
+) -> Vec<Vector3> {
+    let arc = match instruction {
+        Instruction::G2(g2) if xy_plane => Some((g2.i, g2.j, g2.p, -1.0)),
+        Instruction::G3(g3) if xy_plane => Some((g3.i, g3.j, g3.p, 1.0)),
+        _ => None,
+    };
+
+    if let Some((i, j, turns, direction)) = arc.filter(|(i, j, ..)| i.is_some() || j.is_some()) {
+        let center = from.xy() + Vector2::new(i.unwrap_or(0.0), j.unwrap_or(0.0));
+        let radius = from.xy().distance_to(center);
+        let from_angle = (from.y - center.y).atan2(from.x - center.x);
+        let to_angle = (to.y - center.y).atan2(to.x - center.x);
+        let mut sweep = ((to_angle - from_angle) * direction).rem_euclid(TAU);
+        if sweep < 1e-9 {
+            sweep = TAU;
+        }
+        sweep += f64::from(turns.unwrap_or(1).max(1) - 1) * TAU;
+
+        let steps = ((radius * sweep / spacing).ceil() as usize).max(1);
+        return (0..=steps)
+            .map(|step| {
+                let t = step as f64 / steps as f64;
+                let angle = from_angle + sweep * t * direction;
+                Vector3::new(
+                    center.x + radius * angle.cos(),
+                    center.y + radius * angle.sin(),
+                    from.z + (to.z - from.z) * t,
+                )
+            })
+            .collect();
+    }
+
+    let steps = ((from.distance_to(to) / spacing).ceil() as usize).max(1);
+    (0..=steps)
+        .map(|step| {
+            let t = step as f64 / steps as f64;
+            from + (to - from) * Vector3::splat(t)
+        })
+        .collect()
+}
+
+fn write_triangle(stl: &mut Vec<u8>, a: Vector3, b: Vector3, c: Vector3) {
+    let normal = (b - a).cross(c - a).normalized();
+
+    for vector in [normal, a, b, c] {
+        for value in [vector.x, vector.y, vector.z] {
+            stl.extend_from_slice(&(value as f32).to_le_bytes());
+        }
+    }
+
+    stl.extend_from_slice(&0u16.to_le_bytes());
+}
+
+impl Program {
+    /// Exports a coarse 3D model of the machined workpiece as a binary STL, useful for
+    /// documentation and quick 3D checks outside of Camotics.
+    ///
+    /// The workpiece is a block covering the program bounds, and the material removed by the
+    /// tools is found by sweeping the tool shapes along the cutting moves on a height map with
+    /// a grid spacing of `resolution`. The height map can not represent undercuts, and a lower
+    /// resolution value gives more detail but takes longer to compute.
+    ///
+    /// Returns error if the resolution is not larger than 0 or if the program instructions can
+    /// not be generated.
+    pub fn to_stl(&self, resolution: f64) -> Result<Vec<u8>> {
+        if resolution <= 0.0 || !resolution.is_finite() {
+            return Err(anyhow!(
+                "Unable to export STL, resolution must be larger than 0, got {}",
+                resolution
+            ));
+        }
+
+        let tools = self
+            .tools()
+            .into_iter()
+            .filter_map(|tool| Some((self.tool_number(&tool)?, tool)))
+            .collect::<HashMap<_, _>>();
+
+        // The program bounds already cover the area swept by the tools
+        let mut bounds = self.bounds();
+        bounds.min = bounds.min.add_z(-resolution);
+
+        let mut height_map = HeightMap::new(&bounds, resolution);
+        let mut state = MachineState::default();
+        let mut tool = None;
+
+        for instruction in self.to_instructions()? {
+            let from = state.position;
+            let xy_plane = matches!(state.plane, Axis::Z);
+
+            if let Instruction::M6(m6) = &instruction {
+                tool = tools.get(&m6.t);
+            }
+
+            let Some(motion) = state.apply(&instruction) else {
+                continue;
+            };
+
+            if let (Some(tool), MoveKind::Linear | MoveKind::Arc) = (tool, motion.kind) {
+                for point in move_points(
+                    from,
+                    state.position,
+                    &instruction,
+                    xy_plane,
+                    resolution / 2.0,
+                ) {
+                    height_map.cut(tool, point);
+                }
+            }
+        }
+
+        let columns = height_map.columns;
+        let rows = height_map.rows;
+        let bottom = |point: Vector3| point.with_z(bounds.min.z);
+
+        let mut stl = vec![0; 80];
+        stl.extend_from_slice(&0u32.to_le_bytes());
+        let mut triangles = 0u32;
+        let mut add = |stl: &mut Vec<u8>, a, b, c| {
+            write_triangle(stl, a, b, c);
+            triangles += 1;
+        };
+
+        // Top surface and bottom
+        for row in 0..rows - 1 {
+            for column in 0..columns - 1 {
+                let a = height_map.point(column, row);
+                let b = height_map.point(column + 1, row);
+                let c = height_map.point(column + 1, row + 1);
+                let d = height_map.point(column, row + 1);
+
+                add(&mut stl, a, b, c);
+                add(&mut stl, a, c, d);
+                add(&mut stl, bottom(a), bottom(c), bottom(b));
+                add(&mut stl, bottom(a), bottom(d), bottom(c));
+            }
+        }
+
+        // Sides, walking counterclockwise around the edge as seen from above
+        let edge = (0..columns - 1)
+            .map(|column| (column, 0))
+            .chain((0..rows - 1).map(|row| (columns - 1, row)))
+            .chain((1..columns).rev().map(|column| (column, rows - 1)))
+            .chain((1..rows).rev().map(|row| (0, row)))
+            .collect::<Vec<_>>();
+
+        for (index, (column, row)) in edge.iter().enumerate() {
+            let (next_column, next_row) = edge[(index + 1) % edge.len()];
+            let a = height_map.point(*column, *row);
+            let b = height_map.point(next_column, next_row);
+
+            add(&mut stl, bottom(a), bottom(b), b);
+            add(&mut stl, bottom(a), b, a);
+        }
+
+        stl[80..84].copy_from_slice(&triangles.to_le_bytes());
+
+        Ok(stl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuts::*;
+
+    fn triangles(stl: &[u8]) -> Vec<[Vector3; 4]> {
+        stl[84..]
+            .chunks(50)
+            .map(|chunk| {
+                let value = |index: usize| {
+                    f64::from(f32::from_le_bytes(
+                        chunk[index * 4..index * 4 + 4].try_into().unwrap(),
+                    ))
+                };
+                let vector = |index: usize| {
+                    Vector3::new(value(index * 3), value(index * 3 + 1), value(index * 3 + 2))
+                };
+                [vector(0), vector(1), vector(2), vector(3)]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_program_to_stl() -> Result<()> {
+        let mut program = Program::new(Units::Metric, 10.0, 50.0);
+
+        let tool = Tool::cylindrical(
+            Units::Metric,
+            20.0,
+            2.0,
+            Direction::Clockwise,
+            10_000.0,
+            500.0,
+        );
+
+        let mut context = program.context(tool);
+        context.append_cut(Cut::circle(Vector3::new(0.0, 0.0, 0.0), -2.0, 5.0, 1.0));
+
+        let stl = program.to_stl(0.5)?;
+        let count = u32::from_le_bytes(stl[80..84].try_into()?) as usize;
+        assert_eq!(stl.len(), 84 + count * 50);
+
+        // The bounds are 10 x 10 mm with a 1 mm margin, giving a 25 x 25 point grid
+        assert_eq!(count, 24 * 24 * 4 + 24 * 4 * 2);
+
+        let triangles = triangles(&stl);
+        let lowest = triangles
+            .iter()
+            .flat_map(|triangle| triangle[1..].to_vec())
+            .filter(|point| point.z > -2.5)
+            .map(|point| point.z)
+            .fold(f64::MAX, f64::min);
+        assert_eq!(lowest, -2.0);
+
+        // The center of the circle is not cut
+        assert!(triangles
+            .iter()
+            .flat_map(|triangle| triangle[1..].to_vec())
+            .any(|point| point == Vector3::new(0.0, 0.0, 0.0)));
+
+        // All surface normals of the top surface point upwards
+        assert!(triangles[..24 * 24 * 4]
+            .chunks(4)
+            .all(|quad| quad[0][0].z > 0.0 && quad[1][0].z > 0.0));
+
+        assert!(program.to_stl(0.0).is_err());
+
+        Ok(())
+    }
+}
@@ -10,7 +10,8 @@ doc = ::embed_doc_image::embed_image!("cylindrical-tool", "doc-assets/cylindrica
            to enable."
 )]
 
-//! Module containing tool configurations for ballnose, conical, and cylindrical cutting tools.
+//! Module containing tool configurations for ballnose, conical, cylindrical, and engraving
+//! cutting tools.
 //!
 //! |Tool type |Example image |Uses |
 //! |--- |---  |--- |
@@ -39,6 +40,7 @@ use std::{
     hash::{Hash, Hasher},
 };
 
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
 use crate::types::*;
@@ -54,6 +56,8 @@ pub enum Tool {
     Conical(Conical),
     /// Cylindrical tool configuration.
     Cylindrical(Cylindrical),
+    /// Engraving tool configuration, a conical tool with a flat tip.
+    Engraving(Engraving),
 }
 
 impl Tool {
@@ -117,6 +121,93 @@ impl Tool {
         ))
     }
 
+    /// Helper for creating an engraving tool configuration.
+    #[must_use]
+    pub fn engraving(
+        units: Units,
+        angle: f64,
+        tip_diameter: f64,
+        length: f64,
+        direction: Direction,
+        spindle_speed: f64,
+        feed_rate: f64,
+    ) -> Tool {
+        Tool::Engraving(Engraving::new(
+            units,
+            angle,
+            tip_diameter,
+            length,
+            direction,
+            spindle_speed,
+            feed_rate,
+        ))
+    }
+
+    /// Preset for a metric 3 mm flat end mill.
+    #[must_use]
+    pub fn preset_3mm_flat() -> Tool {
+        Tool::cylindrical(
+            Units::Metric,
+            12.0,
+            3.0,
+            Direction::Clockwise,
+            18000.0,
+            800.0,
+        )
+    }
+
+    /// Preset for a metric 6 mm flat end mill.
+    #[must_use]
+    pub fn preset_6mm_flat() -> Tool {
+        Tool::cylindrical(
+            Units::Metric,
+            22.0,
+            6.0,
+            Direction::Clockwise,
+            18000.0,
+            1200.0,
+        )
+    }
+
+    /// Preset for a metric 3 mm ballnose end mill.
+    #[must_use]
+    pub fn preset_3mm_ballnose() -> Tool {
+        Tool::ballnose(
+            Units::Metric,
+            12.0,
+            3.0,
+            Direction::Clockwise,
+            18000.0,
+            800.0,
+        )
+    }
+
+    /// Preset for a metric 90° v-bit with a 12 mm diameter.
+    #[must_use]
+    pub fn preset_90deg_vbit() -> Tool {
+        Tool::conical(
+            Units::Metric,
+            90.0,
+            12.0,
+            Direction::Clockwise,
+            16000.0,
+            600.0,
+        )
+    }
+
+    /// Preset for an imperial 1/4 inch flat end mill.
+    #[must_use]
+    pub fn preset_quarter_inch_flat() -> Tool {
+        Tool::cylindrical(
+            Units::Imperial,
+            0.75,
+            0.25,
+            Direction::Clockwise,
+            18000.0,
+            40.0,
+        )
+    }
+
     /// Returns the units used for the tool measurements (mm for metric, and inches for imperial).
     #[must_use]
     pub fn units(&self) -> Units {
@@ -124,6 +215,18 @@ impl Tool {
             Self::Cylindrical(t) => t.units,
             Self::Ballnose(t) => t.units,
             Self::Conical(t) => t.units,
+            Self::Engraving(t) => t.units,
+        }
+    }
+
+    /// Returns the usable cutting length of the tool cutter.
+    #[must_use]
+    pub fn length(&self) -> f64 {
+        match self {
+            Self::Cylindrical(t) => t.length,
+            Self::Ballnose(t) => t.length,
+            Self::Conical(t) => t.length,
+            Self::Engraving(t) => t.length,
         }
     }
 
@@ -134,6 +237,7 @@ impl Tool {
             Self::Cylindrical(t) => t.diameter,
             Self::Ballnose(t) => t.diameter,
             Self::Conical(t) => t.diameter,
+            Self::Engraving(t) => t.diameter,
         }
     }
 
@@ -144,6 +248,7 @@ impl Tool {
             Self::Cylindrical(t) => t.diameter / 2.0,
             Self::Ballnose(t) => t.diameter / 2.0,
             Self::Conical(t) => t.diameter / 2.0,
+            Self::Engraving(t) => t.diameter / 2.0,
         }
     }
 
@@ -154,6 +259,7 @@ impl Tool {
             Self::Cylindrical(t) => t.direction,
             Self::Ballnose(t) => t.direction,
             Self::Conical(t) => t.direction,
+            Self::Engraving(t) => t.direction,
         }
     }
 
@@ -164,6 +270,7 @@ impl Tool {
             Self::Cylindrical(t) => t.spindle_speed,
             Self::Ballnose(t) => t.spindle_speed,
             Self::Conical(t) => t.spindle_speed,
+            Self::Engraving(t) => t.spindle_speed,
         }
     }
 
@@ -174,6 +281,47 @@ impl Tool {
             Self::Cylindrical(t) => t.feed_rate,
             Self::Ballnose(t) => t.feed_rate,
             Self::Conical(t) => t.feed_rate,
+            Self::Engraving(t) => t.feed_rate,
+        }
+    }
+
+    /// Returns a copy of the tool with the measurements and feed rate converted to the given
+    /// units. Angles and the spindle speed are not affected.
+    #[must_use]
+    pub fn to_units(&self, units: Units) -> Tool {
+        let from = self.units();
+        let convert = |value: f64| from.convert(value, units);
+
+        match *self {
+            Self::Cylindrical(t) => Self::Cylindrical(Cylindrical {
+                units,
+                length: convert(t.length),
+                diameter: convert(t.diameter),
+                feed_rate: convert(t.feed_rate),
+                ..t
+            }),
+            Self::Ballnose(t) => Self::Ballnose(Ballnose {
+                units,
+                length: convert(t.length),
+                diameter: convert(t.diameter),
+                feed_rate: convert(t.feed_rate),
+                ..t
+            }),
+            Self::Conical(t) => Self::Conical(Conical {
+                units,
+                length: convert(t.length),
+                diameter: convert(t.diameter),
+                feed_rate: convert(t.feed_rate),
+                ..t
+            }),
+            Self::Engraving(t) => Self::Engraving(Engraving {
+                units,
+                length: convert(t.length),
+                tip_diameter: convert(t.tip_diameter),
+                diameter: convert(t.diameter),
+                feed_rate: convert(t.feed_rate),
+                ..t
+            }),
         }
     }
 }
@@ -190,12 +338,273 @@ impl fmt::Display for Tool {
             Self::Cylindrical(t) => t.to_string(),
             Self::Ballnose(t) => t.to_string(),
             Self::Conical(t) => t.to_string(),
+            Self::Engraving(t) => t.to_string(),
         };
 
         write!(formatter, "{}", description)
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum ToolKind {
+    Ballnose,
+    Conical,
+    Cylindrical,
+    Engraving,
+}
+
+/// A builder for [Tool] with named setters, as an alternative to the positional helpers
+/// like [Tool::cylindrical] where the arguments are easily mixed up. Measurements that are not
+/// set use the defaults of the tool type, converted to the selected units.
+///
+/// Example:
+/// ```
+/// use cnccoder::prelude::*;
+///
+/// let tool = ToolBuilder::cylindrical()
+///     .units(Units::Metric)
+///     .length(20.0)
+///     .diameter(10.0)
+///     .direction(Direction::Clockwise)
+///     .spindle_speed(20000.0)
+///     .feed_rate(5000.0)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(tool.diameter(), 10.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ToolBuilder {
+    kind: ToolKind,
+    units: Units,
+    length: Option<f64>,
+    diameter: Option<f64>,
+    angle: Option<f64>,
+    tip_diameter: Option<f64>,
+    direction: Direction,
+    spindle_speed: Option<f64>,
+    feed_rate: Option<f64>,
+}
+
+impl ToolBuilder {
+    fn new(kind: ToolKind) -> Self {
+        Self {
+            kind,
+            units: Units::Metric,
+            length: None,
+            diameter: None,
+            angle: None,
+            tip_diameter: None,
+            direction: Direction::Clockwise,
+            spindle_speed: None,
+            feed_rate: None,
+        }
+    }
+
+    /// Creates a builder for a ballnose tool.
+    #[must_use]
+    pub fn ballnose() -> Self {
+        Self::new(ToolKind::Ballnose)
+    }
+
+    /// Creates a builder for a conical tool, its length is given by the angle and diameter.
+    #[must_use]
+    pub fn conical() -> Self {
+        Self::new(ToolKind::Conical)
+    }
+
+    /// Creates a builder for a cylindrical tool.
+    #[must_use]
+    pub fn cylindrical() -> Self {
+        Self::new(ToolKind::Cylindrical)
+    }
+
+    /// Creates a builder for an engraving tool, its diameter is given by the angle, tip
+    /// diameter and length.
+    #[must_use]
+    pub fn engraving() -> Self {
+        Self::new(ToolKind::Engraving)
+    }
+
+    /// Sets the units used for the tool measurements, defaults to metric.
+    #[must_use]
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Sets the length of the tool cutter.
+    #[must_use]
+    pub fn length(mut self, length: f64) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    /// Sets the diameter of the tool cutter.
+    #[must_use]
+    pub fn diameter(mut self, diameter: f64) -> Self {
+        self.diameter = Some(diameter);
+        self
+    }
+
+    /// Sets the angle of a conical or engraving tool cutter.
+    #[must_use]
+    pub fn angle(mut self, angle: f64) -> Self {
+        self.angle = Some(angle);
+        self
+    }
+
+    /// Sets the diameter of the flat at the tip of an engraving tool cutter.
+    #[must_use]
+    pub fn tip_diameter(mut self, tip_diameter: f64) -> Self {
+        self.tip_diameter = Some(tip_diameter);
+        self
+    }
+
+    /// Sets the spin direction for the tool, defaults to clockwise.
+    #[must_use]
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Sets the spindle/tool rotation speed (rpm) for the tool.
+    #[must_use]
+    pub fn spindle_speed(mut self, spindle_speed: f64) -> Self {
+        self.spindle_speed = Some(spindle_speed);
+        self
+    }
+
+    /// Sets the feed rate (mm/min for metric and inches/min for imperial) for the tool.
+    #[must_use]
+    pub fn feed_rate(mut self, feed_rate: f64) -> Self {
+        self.feed_rate = Some(feed_rate);
+        self
+    }
+
+    /// Builds the `Tool`, returns an error if a measurement is invalid or does not apply to
+    /// the tool type.
+    pub fn build(&self) -> Result<Tool> {
+        let defaults = match self.kind {
+            ToolKind::Ballnose => Tool::Ballnose(Ballnose::default()),
+            ToolKind::Conical => Tool::Conical(Conical::default()),
+            ToolKind::Cylindrical => Tool::Cylindrical(Cylindrical::default()),
+            ToolKind::Engraving => Tool::Engraving(Engraving::default()),
+        }
+        .to_units(self.units);
+
+        let (default_angle, default_tip_diameter) = match defaults {
+            Tool::Conical(t) => (Some(t.angle), None),
+            Tool::Engraving(t) => (Some(t.angle), Some(t.tip_diameter)),
+            _ => (None, None),
+        };
+
+        let has_angle = default_angle.is_some();
+        let has_tip_diameter = default_tip_diameter.is_some();
+        let has_length = !matches!(self.kind, ToolKind::Conical);
+        let has_diameter = !matches!(self.kind, ToolKind::Engraving);
+
+        if (self.angle.is_some() && !has_angle)
+            || (self.tip_diameter.is_some() && !has_tip_diameter)
+            || (self.length.is_some() && !has_length)
+            || (self.diameter.is_some() && !has_diameter)
+        {
+            return Err(anyhow!(
+                "Unable to build {:?} tool, a measurement was set that does not apply to the tool type",
+                self.kind
+            ));
+        }
+
+        let length = self.length.unwrap_or(defaults.length());
+        let diameter = self.diameter.unwrap_or(defaults.diameter());
+        let angle = self.angle.or(default_angle).unwrap_or_default();
+        let tip_diameter = self
+            .tip_diameter
+            .or(default_tip_diameter)
+            .unwrap_or_default();
+        let spindle_speed = self.spindle_speed.unwrap_or(defaults.spindle_speed());
+        let feed_rate = self.feed_rate.unwrap_or(defaults.feed_rate());
+
+        if !(diameter.is_finite() && diameter > 0.0) {
+            return Err(anyhow!(
+                "Unable to build tool, the diameter must be positive, got {}",
+                diameter
+            ));
+        }
+
+        if !(length.is_finite() && length > 0.0) {
+            return Err(anyhow!(
+                "Unable to build tool, the length must be positive, got {}",
+                length
+            ));
+        }
+
+        if has_angle && !(angle > 0.0 && angle < 180.0) {
+            return Err(anyhow!(
+                "Unable to build tool, the angle must be between 0 and 180 degrees, got {}",
+                angle
+            ));
+        }
+
+        if !(tip_diameter.is_finite() && tip_diameter >= 0.0) {
+            return Err(anyhow!(
+                "Unable to build tool, the tip diameter must not be negative, got {}",
+                tip_diameter
+            ));
+        }
+
+        if !spindle_speed.is_finite() || spindle_speed == 0.0 {
+            return Err(anyhow!(
+                "Unable to build tool, the spindle speed must be a finite value other than 0, got {}",
+                spindle_speed
+            ));
+        }
+
+        if !(feed_rate.is_finite() && feed_rate > 0.0) {
+            return Err(anyhow!(
+                "Unable to build tool, the feed rate must be positive, got {}",
+                feed_rate
+            ));
+        }
+
+        Ok(match self.kind {
+            ToolKind::Ballnose => Tool::ballnose(
+                self.units,
+                length,
+                diameter,
+                self.direction,
+                spindle_speed,
+                feed_rate,
+            ),
+            ToolKind::Conical => Tool::conical(
+                self.units,
+                angle,
+                diameter,
+                self.direction,
+                spindle_speed,
+                feed_rate,
+            ),
+            ToolKind::Cylindrical => Tool::cylindrical(
+                self.units,
+                length,
+                diameter,
+                self.direction,
+                spindle_speed,
+                feed_rate,
+            ),
+            ToolKind::Engraving => Tool::engraving(
+                self.units,
+                angle,
+                tip_diameter,
+                length,
+                self.direction,
+                spindle_speed,
+                feed_rate,
+            ),
+        })
+    }
+}
+
 /// Ballnose tool configuration.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct Ballnose {
@@ -239,6 +648,21 @@ impl Ballnose {
     pub fn radius(&self) -> f64 {
         self.diameter / 2.0
     }
+
+    /// Returns the diameter of the tool engaged with the material when the tip is at the
+    /// given depth below the surface, from the spherical tip. The full diameter is engaged
+    /// at depths of the tool radius or more.
+    #[must_use]
+    pub fn effective_diameter_at_depth(&self, depth: f64) -> f64 {
+        let radius = self.radius();
+        let depth = depth.abs();
+
+        if depth >= radius {
+            return self.diameter;
+        }
+
+        2.0 * (radius.powi(2) - (radius - depth).powi(2)).sqrt()
+    }
 }
 
 impl Default for Ballnose {
@@ -320,7 +744,9 @@ pub struct Conical {
 }
 
 impl Conical {
-    /// Creates a new `Conical` tool struct
+    /// Creates a new `Conical` tool struct, the length is calculated from the angle and
+    /// diameter. An angle that is not between 0° and 180° gives a length of 0, use
+    /// [try_new](#method.try_new) to get an error instead.
     #[must_use]
     pub fn new(
         units: Units,
@@ -330,9 +756,16 @@ impl Conical {
         spindle_speed: f64,
         feed_rate: f64,
     ) -> Conical {
+        let length = (diameter / 2.0) / (angle / 2.0).to_radians().tan();
+
         Conical {
             units,
-            length: (diameter / 2.0) / (angle / 2.0).to_radians().tan(),
+            // Rounded to hide floating point noise, such as 2.9999999999999996 for 90°
+            length: if angle > 0.0 && angle < 180.0 && length.is_finite() {
+                (length * 1e9).round() / 1e9
+            } else {
+                0.0
+            },
             angle,
             diameter,
             direction,
@@ -341,11 +774,61 @@ impl Conical {
         }
     }
 
+    /// Creates a new `Conical` tool struct, see [new](#method.new).
+    ///
+    /// Returns error if the angle is not between 0° and 180°, or if the diameter is not
+    /// positive.
+    pub fn try_new(
+        units: Units,
+        angle: f64,
+        diameter: f64,
+        direction: Direction,
+        spindle_speed: f64,
+        feed_rate: f64,
+    ) -> Result<Conical> {
+        if !(angle > 0.0 && angle < 180.0) {
+            return Err(anyhow!(
+                "Unable to create conical tool, the angle must be between 0 and 180 degrees, got {}",
+                angle
+            ));
+        }
+
+        if !(diameter.is_finite() && diameter > 0.0) {
+            return Err(anyhow!(
+                "Unable to create conical tool, the diameter must be positive, got {}",
+                diameter
+            ));
+        }
+
+        Ok(Conical::new(
+            units,
+            angle,
+            diameter,
+            direction,
+            spindle_speed,
+            feed_rate,
+        ))
+    }
+
     /// Returns the radius of the tool cutter.
     #[must_use]
     pub fn radius(&self) -> f64 {
         self.diameter / 2.0
     }
+
+    /// Returns the width of the groove cut at the surface when the tool tip is at the given
+    /// depth below it. The width is not limited to the tool diameter.
+    #[must_use]
+    pub fn width_at_depth(&self, depth: f64) -> f64 {
+        2.0 * depth.abs() * (self.angle / 2.0).to_radians().tan()
+    }
+
+    /// Returns the depth below the surface that the tool tip needs to reach to cut a groove
+    /// of the given width at the surface. The depth is not limited to the tool length.
+    #[must_use]
+    pub fn depth_for_width(&self, width: f64) -> f64 {
+        width.abs() / 2.0 / (self.angle / 2.0).to_radians().tan()
+    }
 }
 
 impl Default for Conical {
@@ -515,12 +998,190 @@ impl Hash for Cylindrical {
     }
 }
 
+/// Engraving tool configuration, a V-bit with a small flat at the tip.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Engraving {
+    /// The units used for the tool measurements (mm for metric, and inches for imperial).
+    pub units: Units,
+    /// The length of the tool cutter.
+    pub length: f64,
+    /// The angle of the tool cutter.
+    pub angle: f64,
+    /// The diameter of the flat at the tip of the tool cutter.
+    pub tip_diameter: f64,
+    /// The diameter of the tool cutter.
+    pub diameter: f64,
+    /// The spin direction for the tool.
+    pub direction: Direction,
+    /// The selected spindle/tool rotation speed (rpm) for this tool.
+    pub spindle_speed: f64,
+    /// The selected feed rate (mm/min for metric and inches/min for imperial) for this tool.
+    pub feed_rate: f64,
+}
+
+impl Engraving {
+    /// Creates a new `Engraving` tool struct, the diameter is given by the width of the
+    /// cutter at its full length.
+    #[must_use]
+    pub fn new(
+        units: Units,
+        angle: f64,
+        tip_diameter: f64,
+        length: f64,
+        direction: Direction,
+        spindle_speed: f64,
+        feed_rate: f64,
+    ) -> Engraving {
+        Engraving {
+            units,
+            length,
+            angle,
+            tip_diameter,
+            diameter: tip_diameter + 2.0 * length * (angle / 2.0).to_radians().tan(),
+            direction,
+            spindle_speed,
+            feed_rate,
+        }
+    }
+
+    /// Returns the radius of the tool cutter.
+    #[must_use]
+    pub fn radius(&self) -> f64 {
+        self.diameter / 2.0
+    }
+
+    /// Returns the width of the groove cut at the surface when the tool tip is at the given
+    /// depth below it. The width is not limited to the tool diameter.
+    #[must_use]
+    pub fn width_at_depth(&self, depth: f64) -> f64 {
+        self.tip_diameter + 2.0 * depth.abs() * (self.angle / 2.0).to_radians().tan()
+    }
+
+    /// Returns the depth below the surface that the tool tip needs to reach to cut a groove
+    /// of the given width at the surface, grooves narrower than the tip give a depth of 0.
+    /// The depth is not limited to the tool length.
+    #[must_use]
+    pub fn depth_for_width(&self, width: f64) -> f64 {
+        (width.abs() - self.tip_diameter).max(0.0) / 2.0 / (self.angle / 2.0).to_radians().tan()
+    }
+}
+
+impl Default for Engraving {
+    fn default() -> Self {
+        Self::new(
+            Units::Metric,
+            30.0,
+            0.1,
+            5.0,
+            Direction::Clockwise,
+            15000.0,
+            300.0,
+        )
+    }
+}
+
+impl fmt::Display for Engraving {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let units = match self.units {
+            Units::Imperial => self.units.to_string(),
+            Units::Metric => format!(" {}", self.units),
+        };
+
+        write!(
+            formatter,
+            "type = Engraving, angle = {}°, tip_diameter = {}{}, diameter = {}{}, length = {}{}, direction = {}, spindle_speed = {} rpm, feed_rate = {}{}/min",
+            round_precision(self.angle),
+            round_precision(self.tip_diameter),
+            units,
+            round_precision(self.diameter),
+            units,
+            round_precision(self.length),
+            units,
+            self.direction,
+            round_precision(self.spindle_speed),
+            round_precision(self.feed_rate),
+            units,
+        )
+    }
+}
+
+impl PartialEq for Engraving {
+    fn eq(&self, other: &Engraving) -> bool {
+        self.units == other.units
+            && self.angle == other.angle
+            && self.tip_diameter == other.tip_diameter
+            && self.length == other.length
+            && self.diameter == other.diameter
+            && self.direction == other.direction
+            && self.spindle_speed == other.spindle_speed
+            && self.feed_rate == other.feed_rate
+    }
+}
+
+impl Eq for Engraving {}
+
+impl Hash for Engraving {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.units.hash(state);
+        self.angle.to_bits().hash(state);
+        self.tip_diameter.to_bits().hash(state);
+        self.length.to_bits().hash(state);
+        self.diameter.to_bits().hash(state);
+        self.direction.hash(state);
+        self.spindle_speed.to_bits().hash(state);
+        self.feed_rate.to_bits().hash(state);
+    }
+}
+
+/// The key to sort tools by with
+/// [ToolOrdering::order_by](struct.ToolOrdering.html#method.order_by).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ToolSortKey {
+    /// Largest tool diameter first, for example to rough out with the larger tools first.
+    DiameterDesc,
+    /// Smallest tool diameter first.
+    DiameterAsc,
+    /// Alphabetical order of the tool descriptions.
+    Name,
+}
+
 /// Keeps a list of tools and their order. It also allows for manipulating the order of the tools to ease choosing which cuts that should be made first.
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct ToolOrdering {
     tools: Vec<Tool>,
+    #[serde(with = "tool_map")]
     ordering: HashMap<Tool, u8>,
+    #[serde(with = "tool_map")]
     explicit_ordering: HashMap<Tool, u8>,
+    #[serde(with = "tool_map", default)]
+    numbers: HashMap<Tool, u8>,
+}
+
+// Serializes tool maps as lists of tool and order pairs, since formats like JSON only allows
+// strings as map keys.
+mod tool_map {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Tool;
+
+    pub fn serialize<S>(map: &HashMap<Tool, u8>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut pairs = map.iter().collect::<Vec<_>>();
+        pairs.sort_by_key(|(_, order)| **order);
+        pairs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<Tool, u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pairs = Vec::<(Tool, u8)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
 }
 
 impl ToolOrdering {
@@ -576,6 +1237,85 @@ impl ToolOrdering {
         self.ordering.get(tool).copied()
     }
 
+    /// Pins the tool number used in the G-code T commands for a tool, independent of its order.
+    /// The minimum tool number is 1.
+    pub fn set_number(&mut self, tool: &Tool, number: u8) {
+        let number = number.max(1);
+
+        self.numbers.retain(|t, n| *n != number || t == tool);
+        self.numbers.insert(*tool, number);
+    }
+
+    /// Returns the tool number used in the G-code T commands for a tool, returns None if the
+    /// tool has not been added.
+    ///
+    /// Tools without a pinned number use their order as tool number, unless that number is
+    /// pinned by another tool, in which case the first free number is used.
+    pub fn number(&self, tool: &Tool) -> Option<u8> {
+        self.ordering(tool)?;
+
+        if let Some(number) = self.numbers.get(tool) {
+            return Some(*number);
+        }
+
+        let mut used = self
+            .tools_ordered()
+            .iter()
+            .filter_map(|tool| self.numbers.get(tool).copied())
+            .collect::<Vec<_>>();
+
+        for current in self.tools_ordered() {
+            if self.numbers.contains_key(&current) {
+                continue;
+            }
+
+            let mut number = self.ordering[&current];
+            if used.contains(&number) {
+                number = 1;
+                while used.contains(&number) {
+                    number += 1;
+                }
+            }
+
+            if current == *tool {
+                return Some(number);
+            }
+
+            used.push(number);
+        }
+
+        None
+    }
+
+    /// Reorders all added tools by the sort key, replacing any previously set ordering. Tools
+    /// that are equal by the key keep their current order, and the diameters are compared in
+    /// the same units.
+    pub fn order_by(&mut self, key: ToolSortKey) {
+        let mut tools = self.tools_ordered();
+        let diameter = |tool: &Tool| tool.to_units(Units::Metric).diameter();
+
+        match key {
+            ToolSortKey::DiameterDesc => {
+                tools.sort_by(|a, b| diameter(b).total_cmp(&diameter(a)));
+            }
+            ToolSortKey::DiameterAsc => {
+                tools.sort_by(|a, b| diameter(a).total_cmp(&diameter(b)));
+            }
+            ToolSortKey::Name => tools.sort_by_key(Tool::to_string),
+        }
+
+        self.ordering.clear();
+        self.explicit_ordering.clear();
+
+        for (index, tool) in tools.iter().enumerate() {
+            let order = u8::try_from(index + 1).unwrap_or(u8::MAX);
+            self.ordering.insert(*tool, order);
+            self.explicit_ordering.insert(*tool, order);
+        }
+
+        self.tools = tools;
+    }
+
     /// Returns an ordered list of the tools added.
     pub fn tools_ordered(&self) -> Vec<Tool> {
         let mut tools = vec![];
@@ -596,6 +1336,250 @@ impl ToolOrdering {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_conical_length() -> Result<()> {
+        let tool = Conical::try_new(
+            Units::Metric,
+            90.0,
+            6.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        )?;
+        assert_eq!(tool.length, 3.0);
+
+        let tool = Conical::try_new(
+            Units::Metric,
+            60.0,
+            6.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        )?;
+        assert!((tool.length - 3.0 * 3.0_f64.sqrt()).abs() < 1e-9);
+
+        for angle in [0.0, -10.0, 180.0, f64::NAN] {
+            let error = Conical::try_new(
+                Units::Metric,
+                angle,
+                6.0,
+                Direction::Clockwise,
+                10000.0,
+                500.0,
+            )
+            .unwrap_err();
+            assert!(error.to_string().contains("angle"), "{}", error);
+
+            let tool = Conical::new(
+                Units::Metric,
+                angle,
+                6.0,
+                Direction::Clockwise,
+                10000.0,
+                500.0,
+            );
+            assert_eq!(tool.length, 0.0);
+        }
+
+        assert!(Conical::try_new(
+            Units::Metric,
+            90.0,
+            0.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ballnose_effective_diameter() {
+        let tool = Ballnose::new(
+            Units::Metric,
+            20.0,
+            6.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        );
+
+        assert_eq!(tool.effective_diameter_at_depth(0.0), 0.0);
+        assert!((tool.effective_diameter_at_depth(1.5) - 27.0_f64.sqrt()).abs() < 1e-9);
+        assert!((tool.effective_diameter_at_depth(-1.5) - 27.0_f64.sqrt()).abs() < 1e-9);
+        assert_eq!(tool.effective_diameter_at_depth(3.0), 6.0);
+        assert_eq!(tool.effective_diameter_at_depth(10.0), 6.0);
+    }
+
+    #[test]
+    fn test_conical_width_and_depth() {
+        let tool = Conical::new(
+            Units::Metric,
+            90.0,
+            6.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        );
+
+        assert!((tool.width_at_depth(1.0) - 2.0).abs() < 1e-9);
+        assert!((tool.width_at_depth(-1.5) - 3.0).abs() < 1e-9);
+        assert!((tool.depth_for_width(4.0) - 2.0).abs() < 1e-9);
+
+        let tool = Conical::new(
+            Units::Metric,
+            60.0,
+            6.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        );
+
+        // A 60° bit cuts a groove as wide as the sides of an equilateral triangle
+        assert!((tool.width_at_depth(3.0_f64.sqrt()) - 2.0).abs() < 1e-9);
+        assert!((tool.depth_for_width(2.0) - 3.0_f64.sqrt()).abs() < 1e-9);
+        assert!((tool.depth_for_width(tool.width_at_depth(0.7)) - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_engraving_width_and_depth() {
+        let tool = Tool::engraving(
+            Units::Metric,
+            90.0,
+            0.2,
+            3.0,
+            Direction::Clockwise,
+            15000.0,
+            300.0,
+        );
+        assert!((tool.diameter() - 6.2).abs() < 1e-9);
+        assert!(tool.to_string().contains("type = Engraving"));
+
+        let Tool::Engraving(tool) = tool else {
+            panic!("Expected an engraving tool");
+        };
+
+        assert!((tool.width_at_depth(0.0) - 0.2).abs() < 1e-9);
+        assert!((tool.width_at_depth(1.0) - 2.2).abs() < 1e-9);
+        assert!((tool.depth_for_width(2.2) - 1.0).abs() < 1e-9);
+        assert_eq!(tool.depth_for_width(0.1), 0.0);
+
+        assert_ne!(
+            Tool::Engraving(tool),
+            Tool::conical(
+                Units::Metric,
+                90.0,
+                6.2,
+                Direction::Clockwise,
+                15000.0,
+                300.0
+            )
+        );
+    }
+
+    #[test]
+    fn test_tool_builder() -> Result<()> {
+        let tool = ToolBuilder::cylindrical()
+            .units(Units::Imperial)
+            .length(1.0)
+            .diameter(0.25)
+            .direction(Direction::Counterclockwise)
+            .spindle_speed(12000.0)
+            .feed_rate(30.0)
+            .build()?;
+        assert_eq!(
+            tool,
+            Tool::cylindrical(
+                Units::Imperial,
+                1.0,
+                0.25,
+                Direction::Counterclockwise,
+                12000.0,
+                30.0
+            )
+        );
+
+        let tool = ToolBuilder::ballnose()
+            .length(12.0)
+            .diameter(3.0)
+            .spindle_speed(18000.0)
+            .feed_rate(800.0)
+            .build()?;
+        assert_eq!(tool, Tool::preset_3mm_ballnose());
+
+        let tool = ToolBuilder::conical()
+            .angle(90.0)
+            .diameter(12.0)
+            .spindle_speed(16000.0)
+            .feed_rate(600.0)
+            .build()?;
+        assert_eq!(tool, Tool::preset_90deg_vbit());
+
+        let tool = ToolBuilder::engraving()
+            .angle(30.0)
+            .tip_diameter(0.2)
+            .length(4.0)
+            .spindle_speed(15000.0)
+            .feed_rate(300.0)
+            .build()?;
+        assert_eq!(
+            tool,
+            Tool::engraving(
+                Units::Metric,
+                30.0,
+                0.2,
+                4.0,
+                Direction::Clockwise,
+                15000.0,
+                300.0
+            )
+        );
+
+        // Unset measurements use the tool type defaults
+        assert_eq!(ToolBuilder::cylindrical().build()?, Tool::default());
+        assert_eq!(
+            ToolBuilder::cylindrical().units(Units::Imperial).build()?,
+            Tool::default().to_units(Units::Imperial)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tool_builder_validation() {
+        assert!(ToolBuilder::cylindrical().diameter(0.0).build().is_err());
+        assert!(ToolBuilder::cylindrical().diameter(-6.0).build().is_err());
+        assert!(ToolBuilder::cylindrical().length(0.0).build().is_err());
+        assert!(ToolBuilder::cylindrical()
+            .spindle_speed(0.0)
+            .build()
+            .is_err());
+        assert!(ToolBuilder::cylindrical().feed_rate(0.0).build().is_err());
+        assert!(ToolBuilder::conical().angle(180.0).build().is_err());
+        assert!(ToolBuilder::engraving().tip_diameter(-0.1).build().is_err());
+
+        // Measurements that are derived or do not apply to the tool type are rejected
+        assert!(ToolBuilder::cylindrical().angle(90.0).build().is_err());
+        assert!(ToolBuilder::conical().length(10.0).build().is_err());
+        assert!(ToolBuilder::engraving().diameter(10.0).build().is_err());
+
+        assert!(ToolBuilder::cylindrical()
+            .direction(Direction::Counterclockwise)
+            .spindle_speed(-12000.0)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_tool_presets() {
+        assert_eq!(Tool::preset_6mm_flat().diameter(), 6.0);
+        assert_eq!(Tool::preset_3mm_flat().diameter(), 3.0);
+        assert_eq!(Tool::preset_quarter_inch_flat().units(), Units::Imperial);
+        assert!(matches!(Tool::preset_3mm_ballnose(), Tool::Ballnose(_)));
+        assert!(matches!(Tool::preset_90deg_vbit(), Tool::Conical(_)));
+    }
+
     #[test]
     fn test_auto_ordering() {
         let mut tool_ordering = ToolOrdering::default();
@@ -711,4 +1695,63 @@ mod tests {
             tool_ordering.ordering(&tool3)
         );
     }
+
+    #[test]
+    fn test_mix_pinned_numbers_and_ordering() {
+        let mut tool_ordering = ToolOrdering::default();
+
+        let tool1 = Tool::cylindrical(
+            Units::Metric,
+            30.0,
+            6.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        );
+        let tool2 = Tool::cylindrical(
+            Units::Metric,
+            30.0,
+            2.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        );
+        let tool3 = Tool::ballnose(
+            Units::Metric,
+            20.0,
+            1.0,
+            Direction::Clockwise,
+            10000.0,
+            500.0,
+        );
+
+        assert_eq!(tool_ordering.number(&tool1), None);
+
+        tool_ordering.auto_ordering(&tool1);
+        tool_ordering.auto_ordering(&tool2);
+        tool_ordering.auto_ordering(&tool3);
+
+        assert_eq!(tool_ordering.number(&tool1), Some(1));
+        assert_eq!(tool_ordering.number(&tool2), Some(2));
+        assert_eq!(tool_ordering.number(&tool3), Some(3));
+
+        tool_ordering.set_number(&tool1, 4);
+        tool_ordering.set_number(&tool3, 2);
+
+        // The pinned numbers do not affect the order the tools are used in
+        assert_eq!(tool_ordering.ordering(&tool1), Some(1));
+        assert_eq!(tool_ordering.ordering(&tool2), Some(2));
+        assert_eq!(tool_ordering.ordering(&tool3), Some(3));
+
+        assert_eq!(tool_ordering.number(&tool1), Some(4));
+        assert_eq!(tool_ordering.number(&tool2), Some(1));
+        assert_eq!(tool_ordering.number(&tool3), Some(2));
+
+        // Pinning a number that is already taken moves the other tool back to auto numbering
+        tool_ordering.set_number(&tool2, 4);
+
+        assert_eq!(tool_ordering.number(&tool1), Some(1));
+        assert_eq!(tool_ordering.number(&tool2), Some(4));
+        assert_eq!(tool_ordering.number(&tool3), Some(2));
+    }
 }
@@ -1,12 +1,21 @@
-//! Shared types used by cnccoder, such as Vector2, Vector3, Units, Direction, Axis and Bounds.
+//! Shared types used by cnccoder, such as Vector2, Vector3, Units, Direction, Axis, Bounds,
+//! Transform, Flavor, CommentStyle and DwellFormat.
 
-use std::fmt;
+use std::{fmt, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
+mod transform;
+pub use transform::*;
+
 mod vector;
 pub use vector::*;
 
+/// The default largest difference between distances or coordinates that are considered equal,
+/// used when validating the geometry of cuts, see
+/// [Vector2::approx_eq](struct.Vector2.html#method.approx_eq).
+pub const DEFAULT_EPSILON: f64 = 0.0001;
+
 /// Represents an area in 3D space from one min and one max [Vector3](struct.Vector3.html) point.
 #[derive(Default, Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 pub struct Bounds {
@@ -44,6 +53,75 @@ impl Bounds {
             self.max.z - self.min.z,
         )
     }
+
+    /// Return the center point of the bounds area as a [Vector3](struct.Vector3.html).
+    #[must_use]
+    pub fn center(&self) -> Vector3 {
+        Vector3::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+
+    /// Returns true if the point is inside the bounds, points on the edges are considered inside.
+    #[must_use]
+    pub fn contains(&self, point: Vector3) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+            && point.z >= self.min.z
+            && point.z <= self.max.z
+    }
+
+    /// Returns true if the other bounds are completely inside these bounds.
+    #[must_use]
+    pub fn contains_bounds(&self, other: &Bounds) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    /// Returns the smallest bounds that covers both these and the other bounds.
+    #[must_use]
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    /// Returns the overlapping area of these and the other bounds, or `None` if the bounds
+    /// do not overlap. Bounds that only touch at an edge results in a zero sized intersection.
+    #[must_use]
+    pub fn intersection(&self, other: &Bounds) -> Option<Bounds> {
+        let bounds = Bounds {
+            min: Vector3::new(
+                self.min.x.max(other.min.x),
+                self.min.y.max(other.min.y),
+                self.min.z.max(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.min(other.max.x),
+                self.max.y.min(other.max.y),
+                self.max.z.min(other.max.z),
+            ),
+        };
+
+        if bounds.min.x > bounds.max.x || bounds.min.y > bounds.max.y || bounds.min.z > bounds.max.z
+        {
+            return None;
+        }
+
+        Some(bounds)
+    }
 }
 
 /// Indicates if metric or imperial units should be used. This is used as a setting both for a
@@ -61,7 +139,22 @@ pub enum Units {
 impl Units {
     /// Converts from millimeters to inches
     pub fn mm_to_inch(mm: f64) -> f64 {
-        mm * 25.4
+        mm / 25.4
+    }
+
+    /// Converts from inches to millimeters
+    pub fn inch_to_mm(inch: f64) -> f64 {
+        inch * 25.4
+    }
+
+    /// Converts a measurement from these units to the given units, for example a length or a
+    /// feed rate per minute.
+    pub fn convert(self, value: f64, to: Units) -> f64 {
+        match (self, to) {
+            (Self::Metric, Self::Imperial) => Self::mm_to_inch(value),
+            (Self::Imperial, Self::Metric) => Self::inch_to_mm(value),
+            _ => value,
+        }
     }
 
     /// Converts a measurement from the selected unit to millimeters
@@ -105,6 +198,139 @@ impl fmt::Display for Units {
     }
 }
 
+/// The G-code flavor to generate. The controllers mostly understand the same G-code, but differ
+/// in how comments, dwells, spindle speeds, and tool changes are written.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    /// [Grbl](https://github.com/gnea/grbl), comments are written as `;(text)`. This is the
+    /// default flavor.
+    #[default]
+    Grbl,
+    /// [LinuxCNC](https://linuxcnc.org/), comments are written as `(text)`.
+    LinuxCnc,
+    /// [Mach3](https://www.machsupport.com/), comments are written as `(text)` and the
+    /// program ends with M30.
+    Mach3,
+    /// [Marlin](https://marlinfw.org/), comments are written as `; text`, dwells are given in
+    /// milliseconds, the spindle speed is set on the M3/M4 command, messages are shown with
+    /// M117, and tool changes are made as an M0 pause.
+    Marlin,
+}
+
+impl Flavor {
+    /// Returns the default comment style for the flavor.
+    #[must_use]
+    pub fn comment_style(&self) -> CommentStyle {
+        match self {
+            Flavor::Grbl => CommentStyle::SemicolonParentheses,
+            Flavor::LinuxCnc | Flavor::Mach3 => CommentStyle::Parentheses,
+            Flavor::Marlin => CommentStyle::Semicolon,
+        }
+    }
+
+    /// Returns true if the controller supports the P word on G2/G3 arcs, for repeating full
+    /// revolutions.
+    #[must_use]
+    pub fn supports_arc_turns(&self) -> bool {
+        matches!(self, Flavor::LinuxCnc)
+    }
+
+    /// Returns the default format of the G4 dwell duration for the flavor.
+    #[must_use]
+    pub fn dwell_format(&self) -> DwellFormat {
+        match self {
+            Flavor::Grbl | Flavor::LinuxCnc | Flavor::Mach3 => DwellFormat::Seconds,
+            Flavor::Marlin => DwellFormat::Milliseconds,
+        }
+    }
+}
+
+impl fmt::Display for Flavor {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            match self {
+                Flavor::Grbl => "Grbl",
+                Flavor::LinuxCnc => "LinuxCNC",
+                Flavor::Mach3 => "Mach3",
+                Flavor::Marlin => "Marlin",
+            }
+        )
+    }
+}
+
+/// Indicates how comments are written in the generated G-code.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentStyle {
+    /// Comments are written as `;(text)`. This is the default style.
+    #[default]
+    SemicolonParentheses,
+    /// Comments are written as `(text)`.
+    Parentheses,
+    /// Comments are written as `; text`.
+    Semicolon,
+}
+
+impl CommentStyle {
+    /// Formats a comment text in the style, empty comments results in an empty line.
+    #[must_use]
+    pub fn format(&self, text: &str) -> String {
+        if text.is_empty() {
+            return String::new();
+        }
+
+        match self {
+            CommentStyle::SemicolonParentheses => format!(";({text})"),
+            CommentStyle::Parentheses => format!("({text})"),
+            CommentStyle::Semicolon => format!("; {text}"),
+        }
+    }
+}
+
+/// Indicates how the duration of G4 dwells is given in the generated G-code. The duration is
+/// written with millisecond precision in all formats.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DwellFormat {
+    /// The duration is given in seconds with the P word, as `G4 P0.5`. This is the default
+    /// format.
+    #[default]
+    Seconds,
+    /// The duration is given in milliseconds with the P word, as `G4 P500`.
+    Milliseconds,
+    /// The duration is given in seconds with the S word, as `G4 S0.5`.
+    SecondsWord,
+}
+
+impl DwellFormat {
+    /// Formats a G4 dwell of the given duration in the format.
+    #[must_use]
+    pub fn format(&self, duration: Duration) -> String {
+        let milliseconds = duration.as_millis();
+
+        match self {
+            DwellFormat::Seconds => format!("G4 P{}", milliseconds as f64 / 1000.0),
+            DwellFormat::Milliseconds => format!("G4 P{}", milliseconds),
+            DwellFormat::SecondsWord => format!("G4 S{}", milliseconds as f64 / 1000.0),
+        }
+    }
+}
+
+/// Indicates how feed rates are given in the generated G-code.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedMode {
+    /// Feed rates are given in units per minute with G94. This is the default mode.
+    #[default]
+    UnitsPerMinute,
+    /// Feed rates are given in inverse time with G93, where each cutting move gets an F value
+    /// of moves per minute, calculated from the move length and the tool feed rate.
+    InverseTime,
+}
+
 /// Indicates a rotation direction, this is used by the [tools](../tools/), but also when cutting [arcs](../cuts/struct.Arc.html).
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
@@ -116,6 +342,29 @@ pub enum Direction {
     Counterclockwise,
 }
 
+impl Direction {
+    /// Returns the opposite rotation direction.
+    #[must_use]
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Clockwise => Direction::Counterclockwise,
+            Direction::Counterclockwise => Direction::Clockwise,
+        }
+    }
+
+    /// Returns the direction of a rotation around the axis after the transform is applied,
+    /// mirroring the plane of the rotation gives the opposite direction, while mirroring along
+    /// the rotation axis itself keeps it.
+    #[must_use]
+    pub fn transformed(self, transform: &Transform, axis: &Axis) -> Self {
+        match (transform, axis) {
+            (Transform::MirrorX, Axis::X) | (Transform::MirrorY, Axis::Y) => self,
+            _ if transform.is_mirror() => self.opposite(),
+            _ => self,
+        }
+    }
+}
+
 impl fmt::Display for Direction {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -130,7 +379,8 @@ impl fmt::Display for Direction {
 }
 
 /// Indicates one specific axis, mainy when cutting [arcs](../cuts/struct.Arc.html).
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
 pub enum Axis {
     /// Indicates X axis.
     X,
@@ -155,7 +405,8 @@ impl fmt::Display for Axis {
 }
 
 /// Indicates how a path should be compensated by the radius of the tool.
-#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum ToolPathCompensation {
     /// The tool will cut at the specified path, without compensating for the radius. This is the default value.
     #[default]
@@ -179,3 +430,122 @@ impl fmt::Display for ToolPathCompensation {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direction_opposite() {
+        assert_eq!(Direction::Clockwise.opposite(), Direction::Counterclockwise);
+        assert_eq!(Direction::Counterclockwise.opposite(), Direction::Clockwise);
+        assert_eq!(
+            Direction::Clockwise.transformed(&Transform::MirrorX, &Axis::Z),
+            Direction::Counterclockwise
+        );
+        assert_eq!(
+            Direction::Clockwise.transformed(&Transform::MirrorX, &Axis::X),
+            Direction::Clockwise
+        );
+        assert_eq!(
+            Direction::Counterclockwise.transformed(&Transform::Scale(2.0), &Axis::Z),
+            Direction::Counterclockwise
+        );
+    }
+
+    #[test]
+    fn test_dwell_format() {
+        let dwell = Duration::from_millis(500);
+        assert_eq!(DwellFormat::Seconds.format(dwell), "G4 P0.5");
+        assert_eq!(DwellFormat::Milliseconds.format(dwell), "G4 P500");
+        assert_eq!(DwellFormat::SecondsWord.format(dwell), "G4 S0.5");
+
+        assert_eq!(
+            DwellFormat::Seconds.format(Duration::from_micros(8_504_900)),
+            "G4 P8.504"
+        );
+        assert_eq!(Flavor::Marlin.dwell_format(), DwellFormat::Milliseconds);
+        assert_eq!(Flavor::Grbl.dwell_format(), DwellFormat::Seconds);
+    }
+
+    #[test]
+    fn test_bounds_center() {
+        let bounds = Bounds {
+            min: Vector3::new(-10.0, 0.0, -2.0),
+            max: Vector3::new(10.0, 20.0, 4.0),
+        };
+        assert_eq!(bounds.center(), Vector3::new(0.0, 10.0, 1.0));
+    }
+
+    #[test]
+    fn test_bounds_contains() {
+        let bounds = Bounds::new(10.0, 10.0, 10.0);
+        assert!(bounds.contains(Vector3::new(5.0, 5.0, 5.0)));
+        assert!(bounds.contains(Vector3::new(0.0, 10.0, 0.0)));
+        assert!(!bounds.contains(Vector3::new(5.0, 5.0, 10.1)));
+        assert!(!bounds.contains(Vector3::new(-0.1, 5.0, 5.0)));
+    }
+
+    #[test]
+    fn test_bounds_nested() {
+        let outer = Bounds::new(10.0, 10.0, 10.0);
+        let inner = Bounds {
+            min: Vector3::new(2.0, 2.0, 2.0),
+            max: Vector3::new(8.0, 8.0, 8.0),
+        };
+
+        assert!(outer.contains_bounds(&inner));
+        assert!(!inner.contains_bounds(&outer));
+        assert_eq!(outer.union(&inner), outer);
+        assert_eq!(outer.intersection(&inner), Some(inner));
+    }
+
+    #[test]
+    fn test_bounds_overlapping() {
+        let a = Bounds::new(10.0, 10.0, 10.0);
+        let b = Bounds {
+            min: Vector3::new(5.0, 5.0, -5.0),
+            max: Vector3::new(15.0, 15.0, 5.0),
+        };
+
+        assert!(!a.contains_bounds(&b));
+        assert_eq!(
+            a.union(&b),
+            Bounds {
+                min: Vector3::new(0.0, 0.0, -5.0),
+                max: Vector3::new(15.0, 15.0, 10.0),
+            }
+        );
+        assert_eq!(
+            a.intersection(&b),
+            Some(Bounds {
+                min: Vector3::new(5.0, 5.0, 0.0),
+                max: Vector3::new(10.0, 10.0, 5.0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_bounds_disjoint() {
+        let a = Bounds::new(10.0, 10.0, 10.0);
+        let b = Bounds {
+            min: Vector3::new(20.0, 0.0, 0.0),
+            max: Vector3::new(30.0, 10.0, 10.0),
+        };
+
+        assert_eq!(a.intersection(&b), None);
+        assert_eq!(
+            a.union(&b),
+            Bounds {
+                min: Vector3::new(0.0, 0.0, 0.0),
+                max: Vector3::new(30.0, 10.0, 10.0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_bounds_union_minmax() {
+        let bounds = Bounds::new(10.0, 10.0, 10.0);
+        assert_eq!(Bounds::minmax().union(&bounds), bounds);
+    }
+}
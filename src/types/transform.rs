@@ -0,0 +1,167 @@
+use std::f64::consts::FRAC_PI_2;
+
+use crate::types::{Vector2, Vector3};
+
+/// A geometric transformation that can be applied to cuts, tool contexts and whole programs,
+/// for example to stamp the same motif at several positions or orientations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    /// Moves all coordinates by the given offset.
+    Translate(Vector3),
+    /// Rotates all coordinates counterclockwise around the z axis by `angle` radians,
+    /// pivoting around the `pivot` xy coordinate.
+    RotateZ {
+        /// Angle in radians.
+        angle: f64,
+        /// The xy coordinate to rotate around.
+        pivot: Vector2,
+    },
+    /// Scales all coordinates, including z, by the given factor relative to origo.
+    Scale(f64),
+    /// Mirrors the x coordinates around x = 0 (x becomes -x).
+    MirrorX,
+    /// Mirrors the y coordinates around y = 0 (y becomes -y).
+    MirrorY,
+}
+
+impl Transform {
+    /// Applies the transform to an absolute point in 3D space.
+    #[must_use]
+    pub fn apply(&self, point: Vector3) -> Vector3 {
+        match self {
+            Self::Translate(offset) => point + *offset,
+            Self::RotateZ { angle, pivot } => {
+                let xy = (point.xy() - *pivot).rotate(*angle) + *pivot;
+                Vector3::new(xy.x, xy.y, point.z)
+            }
+            Self::Scale(factor) => {
+                Vector3::new(point.x * factor, point.y * factor, point.z * factor)
+            }
+            Self::MirrorX => point.with_x(-point.x),
+            Self::MirrorY => point.with_y(-point.y),
+        }
+    }
+
+    /// Applies the transform to an absolute z coordinate, such as the `end_z` value of a cut.
+    #[must_use]
+    pub fn apply_z(&self, z: f64) -> f64 {
+        match self {
+            Self::Translate(offset) => z + offset.z,
+            Self::Scale(factor) => z * factor,
+            Self::RotateZ { .. } | Self::MirrorX | Self::MirrorY => z,
+        }
+    }
+
+    /// Applies the transform to a relative 2D offset, such as the segments of a
+    /// [Path](../cuts/struct.Path.html). Translations does not affect offsets.
+    #[must_use]
+    pub fn apply_offset(&self, offset: Vector2) -> Vector2 {
+        match self {
+            Self::Translate(_) => offset,
+            Self::RotateZ { angle, .. } => offset.rotate(*angle),
+            Self::Scale(factor) => Vector2::new(offset.x * factor, offset.y * factor),
+            Self::MirrorX => offset.with_x(-offset.x),
+            Self::MirrorY => offset.with_y(-offset.y),
+        }
+    }
+
+    /// Applies the transform to a length, such as a circle radius.
+    #[must_use]
+    pub fn apply_length(&self, length: f64) -> f64 {
+        match self {
+            Self::Scale(factor) => length * factor.abs(),
+            _ => length,
+        }
+    }
+
+    /// Returns true if the transform mirrors the geometry, in which case arc directions
+    /// needs to be flipped for the arcs to stay valid.
+    #[must_use]
+    pub fn is_mirror(&self) -> bool {
+        matches!(self, Self::MirrorX | Self::MirrorY)
+    }
+
+    /// Returns true if the transform keeps axis aligned rectangles axis aligned, which is
+    /// true for all transforms except rotations that are not a multiple of 90°.
+    #[must_use]
+    pub fn is_axis_aligned(&self) -> bool {
+        match self {
+            Self::RotateZ { angle, .. } => {
+                let quarter_turns = angle / FRAC_PI_2;
+                (quarter_turns - quarter_turns.round()).abs() < 1e-9
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn test_transform_apply() {
+        let point = Vector3::new(2.0, 3.0, 4.0);
+
+        assert_eq!(
+            Transform::Translate(Vector3::new(1.0, -1.0, 2.0)).apply(point),
+            Vector3::new(3.0, 2.0, 6.0)
+        );
+        assert_eq!(
+            Transform::Scale(2.0).apply(point),
+            Vector3::new(4.0, 6.0, 8.0)
+        );
+        assert_eq!(
+            Transform::MirrorX.apply(point),
+            Vector3::new(-2.0, 3.0, 4.0)
+        );
+        assert_eq!(
+            Transform::MirrorY.apply(point),
+            Vector3::new(2.0, -3.0, 4.0)
+        );
+
+        let rotated = Transform::RotateZ {
+            angle: PI / 2.0,
+            pivot: Vector2::new(2.0, 0.0),
+        }
+        .apply(point);
+        assert!(rotated.distance_to(Vector3::new(-1.0, 0.0, 4.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_transform_apply_offset() {
+        let offset = Vector2::new(1.0, 2.0);
+
+        assert_eq!(
+            Transform::Translate(Vector3::new(5.0, 5.0, 5.0)).apply_offset(offset),
+            offset
+        );
+        assert_eq!(
+            Transform::MirrorX.apply_offset(offset),
+            Vector2::new(-1.0, 2.0)
+        );
+
+        let rotated = Transform::RotateZ {
+            angle: PI,
+            pivot: Vector2::new(100.0, 100.0),
+        }
+        .apply_offset(offset);
+        assert!(rotated.distance_to(Vector2::new(-1.0, -2.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_transform_is_axis_aligned() {
+        let rotate = |angle| Transform::RotateZ {
+            angle,
+            pivot: Vector2::ZERO,
+        };
+
+        assert!(rotate(0.0).is_axis_aligned());
+        assert!(rotate(PI / 2.0).is_axis_aligned());
+        assert!(rotate(-PI * 1.5).is_axis_aligned());
+        assert!(!rotate(PI / 4.0).is_axis_aligned());
+        assert!(Transform::MirrorY.is_axis_aligned());
+    }
+}
@@ -6,6 +6,7 @@ use std::{
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::types::Axis;
 use crate::utils::round_precision;
 
 // Used to deserialize a struct as a tuple.
@@ -84,6 +85,16 @@ impl Vector2 {
         Self { x: value, y: value }
     }
 
+    /// Create a 2D point struct from polar coordinates, where `angle` is in radians
+    /// counterclockwise from the positive x axis.
+    #[must_use]
+    pub fn from_polar(radius: f64, angle: f64) -> Self {
+        Self {
+            x: radius * angle.cos(),
+            y: radius * angle.sin(),
+        }
+    }
+
     /// Create a 2D point struct using `f64::MIN` for x and y coordinates.
     #[must_use]
     #[deprecated]
@@ -110,6 +121,13 @@ impl Vector2 {
         ((self.x - to.x) * (self.x - to.x) + (self.y - to.y) * (self.y - to.y)).sqrt()
     }
 
+    /// Returns true if the distance to another `Vector2` struct is at most epsilon, see
+    /// [DEFAULT_EPSILON](constant.DEFAULT_EPSILON.html).
+    #[must_use]
+    pub fn approx_eq(&self, other: Self, epsilon: f64) -> bool {
+        self.distance_to(other) <= epsilon
+    }
+
     /// Computes the angle in radians with respect to the positive x-axis.
     #[must_use]
     pub fn angle(&self) -> f64 {
@@ -122,6 +140,39 @@ impl Vector2 {
         self.angle().to_degrees()
     }
 
+    /// Returns the length (magnitude) of the vector.
+    #[must_use]
+    pub fn length(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    /// Calculate the dot product with another `Vector2` struct.
+    #[must_use]
+    pub fn dot(&self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// Returns a new `Vector2` with the same direction and a length of 1.0. A zero length
+    /// vector is returned unchanged.
+    #[must_use]
+    pub fn normalized(&self) -> Self {
+        let length = self.length();
+
+        if length == 0.0 {
+            return *self;
+        }
+
+        Self::new(self.x / length, self.y / length)
+    }
+
+    /// Returns a new `Vector2` rotated counterclockwise around origo by the given angle in
+    /// radians.
+    #[must_use]
+    pub fn rotate(&self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
     /// Returns a new `Vector2` incrementing the x coordinate by the given value.
     #[must_use]
     pub fn add_x(&self, value: f64) -> Self {
@@ -210,6 +261,30 @@ impl fmt::Display for Vector2 {
     }
 }
 
+impl From<(f64, f64)> for Vector2 {
+    fn from((x, y): (f64, f64)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vector2> for (f64, f64) {
+    fn from(value: Vector2) -> Self {
+        (value.x, value.y)
+    }
+}
+
+impl From<[f64; 2]> for Vector2 {
+    fn from([x, y]: [f64; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vector2> for [f64; 2] {
+    fn from(value: Vector2) -> Self {
+        [value.x, value.y]
+    }
+}
+
 #[cfg(feature = "glam")]
 impl From<glam::Vec2> for Vector2 {
     fn from(value: glam::Vec2) -> Self {
@@ -333,6 +408,17 @@ impl Vector3 {
         }
     }
 
+    /// Create a 3D point struct from cylindrical coordinates, where `angle` is in radians
+    /// counterclockwise from the positive x axis around the z axis.
+    #[must_use]
+    pub fn from_cylindrical(radius: f64, angle: f64, z: f64) -> Self {
+        Self {
+            x: radius * angle.cos(),
+            y: radius * angle.sin(),
+            z,
+        }
+    }
+
     /// Create a 3D point struct using `f64::MIN` for x, y and z coordinates.
     #[must_use]
     #[deprecated]
@@ -364,6 +450,68 @@ impl Vector3 {
             .sqrt()
     }
 
+    /// Returns true if the distance to another `Vector3` struct is at most epsilon, see
+    /// [DEFAULT_EPSILON](constant.DEFAULT_EPSILON.html).
+    #[must_use]
+    pub fn approx_eq(&self, other: Self, epsilon: f64) -> bool {
+        self.distance_to(other) <= epsilon
+    }
+
+    /// Returns the length (magnitude) of the vector.
+    #[must_use]
+    pub fn length(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    /// Calculate the dot product with another `Vector3` struct.
+    #[must_use]
+    pub fn dot(&self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Calculate the cross product with another `Vector3` struct.
+    #[must_use]
+    pub fn cross(&self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Returns a new `Vector3` with the same direction and a length of 1.0. A zero length
+    /// vector is returned unchanged.
+    #[must_use]
+    pub fn normalized(&self) -> Self {
+        let length = self.length();
+
+        if length == 0.0 {
+            return *self;
+        }
+
+        Self::new(self.x / length, self.y / length, self.z / length)
+    }
+
+    /// Returns a new `Vector3` rotated around the given axis (through origo) by the given
+    /// angle in radians, using the right hand rule.
+    #[must_use]
+    pub fn rotate_around(&self, axis: Axis, angle: f64) -> Self {
+        match axis {
+            Axis::X => {
+                let yz = self.yz().rotate(angle);
+                Self::new(self.x, yz.x, yz.y)
+            }
+            Axis::Y => {
+                let zx = Vector2::new(self.z, self.x).rotate(angle);
+                Self::new(zx.y, self.y, zx.x)
+            }
+            Axis::Z => {
+                let xy = self.xy().rotate(angle);
+                Self::new(xy.x, xy.y, self.z)
+            }
+        }
+    }
+
     /// Returns a `Vector2` struct using the x and y coordinates from this `Vector3` struct.
     #[must_use]
     pub fn xy(&self) -> Vector2 {
@@ -491,6 +639,30 @@ impl fmt::Display for Vector3 {
     }
 }
 
+impl From<(f64, f64, f64)> for Vector3 {
+    fn from((x, y, z): (f64, f64, f64)) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vector3> for (f64, f64, f64) {
+    fn from(value: Vector3) -> Self {
+        (value.x, value.y, value.z)
+    }
+}
+
+impl From<[f64; 3]> for Vector3 {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<Vector3> for [f64; 3] {
+    fn from(value: Vector3) -> Self {
+        [value.x, value.y, value.z]
+    }
+}
+
 #[cfg(feature = "glam")]
 impl From<glam::Vec3> for Vector3 {
     fn from(value: glam::Vec3) -> Self {
@@ -574,6 +746,7 @@ impl From<Vector3> for nalgebra::Point3<f64> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::DEFAULT_EPSILON;
 
     #[test]
     fn test_vector3_min() {
@@ -653,6 +826,25 @@ mod tests {
         assert!(vector_a.distance_to(vector_b) == 20.0);
     }
 
+    #[test]
+    fn test_vector2_approx_eq() {
+        let vector = Vector2::new(20.0, 40.0);
+        assert!(vector.approx_eq(vector, 0.0));
+        assert!(vector.approx_eq(Vector2::new(20.00005, 40.0), DEFAULT_EPSILON));
+        assert!(vector.approx_eq(Vector2::new(20.0, 39.99991), DEFAULT_EPSILON));
+        assert!(!vector.approx_eq(Vector2::new(20.0, 40.00011), DEFAULT_EPSILON));
+        assert!(!vector.approx_eq(Vector2::new(20.00008, 40.00008), DEFAULT_EPSILON));
+        assert!(vector.approx_eq(Vector2::new(20.00008, 40.00008), 0.001));
+    }
+
+    #[test]
+    fn test_vector3_approx_eq() {
+        let vector = Vector3::new(20.0, 40.0, -2.0);
+        assert!(vector.approx_eq(Vector3::new(20.0, 40.0, -2.00009), DEFAULT_EPSILON));
+        assert!(!vector.approx_eq(Vector3::new(20.0, 40.0, -2.00011), DEFAULT_EPSILON));
+        assert!(!vector.approx_eq(Vector3::new(20.00006, 40.00006, -2.00006), DEFAULT_EPSILON));
+    }
+
     #[test]
     fn test_vector2_angle() {
         let vector = Vector2::new(20.0, 0.0);
@@ -665,6 +857,103 @@ mod tests {
         assert!(vector.angle_degrees() == 45.0);
     }
 
+    #[test]
+    fn test_vector2_length_dot_normalized() {
+        let vector = Vector2::new(3.0, 4.0);
+        assert!(vector.length() == 5.0);
+        assert!(vector.dot(Vector2::new(2.0, -1.0)) == 2.0);
+        assert!(vector.normalized() == Vector2::new(0.6, 0.8));
+        assert!(Vector2::ZERO.normalized() == Vector2::ZERO);
+    }
+
+    #[test]
+    fn test_vector2_rotate() {
+        let vector = Vector2::new(10.0, 0.0).rotate(PI / 2.0);
+        assert!(vector.x.abs() < 1e-9);
+        assert!((vector.y - 10.0).abs() < 1e-9);
+
+        let vector = Vector2::new(1.0, 1.0).rotate(PI);
+        assert!((vector.x + 1.0).abs() < 1e-9);
+        assert!((vector.y + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_vector2_from_polar() {
+        let vector = Vector2::from_polar(10.0, 0.0);
+        assert!(vector == Vector2::new(10.0, 0.0));
+
+        let vector = Vector2::from_polar(10.0, PI / 2.0);
+        assert!(vector.distance_to(Vector2::new(0.0, 10.0)) < 1e-9);
+
+        let vector = Vector2::from_polar(10.0, PI);
+        assert!(vector.distance_to(Vector2::new(-10.0, 0.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_vector3_from_cylindrical() {
+        let vector = Vector3::from_cylindrical(5.0, 0.0, -2.0);
+        assert!(vector == Vector3::new(5.0, 0.0, -2.0));
+
+        let vector = Vector3::from_cylindrical(5.0, PI / 2.0, -2.0);
+        assert!(vector.distance_to(Vector3::new(0.0, 5.0, -2.0)) < 1e-9);
+
+        let vector = Vector3::from_cylindrical(5.0, PI, 3.0);
+        assert!(vector.distance_to(Vector3::new(-5.0, 0.0, 3.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_vector3_length_dot_cross_normalized() {
+        let vector = Vector3::new(2.0, 3.0, 6.0);
+        assert!(vector.length() == 7.0);
+        assert!(vector.dot(Vector3::new(1.0, 1.0, 1.0)) == 11.0);
+        assert!((vector.normalized().length() - 1.0).abs() < 1e-9);
+        assert!(Vector3::ZERO.normalized() == Vector3::ZERO);
+
+        let x = Vector3::new(1.0, 0.0, 0.0);
+        let y = Vector3::new(0.0, 1.0, 0.0);
+        assert!(x.cross(y) == Vector3::new(0.0, 0.0, 1.0));
+        assert!(y.cross(x) == Vector3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_vector3_rotate_around() {
+        let vector = Vector3::new(1.0, 2.0, 3.0);
+        let quarter = PI / 2.0;
+
+        let rotated = vector.rotate_around(Axis::X, quarter);
+        assert!(rotated.distance_to(Vector3::new(1.0, -3.0, 2.0)) < 1e-9);
+
+        let rotated = vector.rotate_around(Axis::Y, quarter);
+        assert!(rotated.distance_to(Vector3::new(3.0, 2.0, -1.0)) < 1e-9);
+
+        let rotated = vector.rotate_around(Axis::Z, quarter);
+        assert!(rotated.distance_to(Vector3::new(-2.0, 1.0, 3.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_tuple_and_array_from_into() {
+        let v = Vector2::from((23.1, 5.0));
+        assert_eq!(v, Vector2::new(23.1, 5.0));
+        assert_eq!(Vector2::from([23.1, 5.0]), v);
+
+        let tuple: (f64, f64) = v.into();
+        assert_eq!(tuple, (23.1, 5.0));
+        let array: [f64; 2] = v.into();
+        assert_eq!(array, [23.1, 5.0]);
+
+        let v = Vector3::from((23.1, 5.0, -1.0));
+        assert_eq!(v, Vector3::new(23.1, 5.0, -1.0));
+        assert_eq!(Vector3::from([23.1, 5.0, -1.0]), v);
+
+        let tuple: (f64, f64, f64) = v.into();
+        assert_eq!(Vector3::from(tuple), v);
+        let array: [f64; 3] = v.into();
+        assert_eq!(Vector3::from(array), v);
+
+        // The serialized representation is still a tuple
+        assert_eq!(serde_json::to_string(&v).unwrap(), "[23.1,5.0,-1.0]");
+    }
+
     #[cfg(feature = "glam")]
     #[test]
     fn test_glam_from_into() {
@@ -1,5 +1,17 @@
 //! Small utility functions used in cnccoder.
 
+use std::cell::Cell;
+
+use crate::types::DEFAULT_EPSILON;
+
+/// The default number of decimals that values are rounded to by
+/// [round_precision](fn.round_precision.html).
+pub const DEFAULT_PRECISION: u8 = 3;
+
+thread_local! {
+    static PRECISION: Cell<u8> = const { Cell::new(DEFAULT_PRECISION) };
+}
+
 /// Scale a f64 value from one range to another.
 #[must_use]
 pub fn scale(x: f64, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> f64 {
@@ -7,9 +19,61 @@ pub fn scale(x: f64, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> f6
 }
 
 /// Rounds an f64 value to 3 decimal digits, for example used to reduce clutter in G-code.
+/// Values that round to zero are returned as positive zero, so that they are not printed as `-0`.
+///
+/// While a program is converted to G-code the values are rounded to the
+/// [precision](../program/struct.Program.html#method.set_precision) of the program instead.
 #[must_use]
 pub fn round_precision(value: f64) -> f64 {
-    (value * 1000.0).round() / 1000.0
+    let factor = 10.0_f64.powi(i32::from(PRECISION.with(Cell::get)));
+    let rounded = (value * factor).round() / factor;
+    if rounded == 0.0 {
+        0.0
+    } else {
+        rounded
+    }
+}
+
+// Runs the function with round_precision rounding to the given number of decimals on this
+// thread, the previous precision is restored afterwards.
+pub(crate) fn with_precision<T>(precision: u8, function: impl FnOnce() -> T) -> T {
+    struct Restore(u8);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            PRECISION.with(|cell| cell.set(self.0));
+        }
+    }
+
+    let _restore = Restore(PRECISION.with(|cell| cell.replace(precision)));
+    function()
+}
+
+/// Returns the z depths of the passes needed to cut from `start_z` down to `end_z` without
+/// going deeper than `max_step_z` in each pass. Each pass is `max_step_z` deeper than the
+/// previous one and the last pass is always exactly at `end_z`, so a depth that is not a
+/// multiple of the step gets a shallower last pass.
+///
+/// Depths within [DEFAULT_EPSILON](../types/constant.DEFAULT_EPSILON.html) of a multiple of
+/// the step do not get an extra pass. A cut that does not go down, or an invalid step, gives a
+/// single pass at `end_z`.
+#[must_use]
+pub fn depth_layers(start_z: f64, end_z: f64, max_step_z: f64) -> Vec<f64> {
+    let max_step_z = max_step_z.abs();
+    let depth = start_z - end_z;
+
+    if depth <= DEFAULT_EPSILON || max_step_z == 0.0 || !max_step_z.is_finite() {
+        return vec![end_z];
+    }
+
+    let layers = ((depth - DEFAULT_EPSILON) / max_step_z).ceil() as u32;
+
+    let mut depths = (1..layers)
+        .map(|layer| start_z - f64::from(layer) * max_step_z)
+        .collect::<Vec<_>>();
+    depths.push(end_z);
+
+    depths
 }
 
 #[cfg(test)]
@@ -35,5 +99,26 @@ mod tests {
     fn test_round_precision() {
         let rounded = round_precision(1.235567774);
         assert!(rounded == 1.236);
+
+        assert_eq!(round_precision(-0.0).to_string(), "0");
+        assert_eq!(round_precision(-0.0004).to_string(), "0");
+        assert_eq!(round_precision(-0.0006).to_string(), "-0.001");
+    }
+
+    #[test]
+    fn test_depth_layers() {
+        assert_eq!(depth_layers(0.0, -3.05, 1.0), vec![-1.0, -2.0, -3.0, -3.05]);
+        assert_eq!(depth_layers(0.0, -3.0, 1.0), vec![-1.0, -2.0, -3.0]);
+        assert_eq!(depth_layers(0.0, -3.00005, 1.0), vec![-1.0, -2.0, -3.00005]);
+        assert_eq!(
+            depth_layers(0.0, -3.0002, 1.0),
+            vec![-1.0, -2.0, -3.0, -3.0002]
+        );
+        assert_eq!(depth_layers(1.0, -2.05, -1.0), vec![0.0, -1.0, -2.0, -2.05]);
+        assert_eq!(depth_layers(0.0, -0.5, 1.0), vec![-0.5]);
+        assert_eq!(depth_layers(0.0, 0.0, 1.0), vec![0.0]);
+        assert_eq!(depth_layers(0.0, 1.0, 1.0), vec![1.0]);
+        assert_eq!(depth_layers(0.0, -3.0, 0.0), vec![-3.0]);
+        assert_eq!(depth_layers(0.0, -3.0, f64::NAN), vec![-3.0]);
     }
 }